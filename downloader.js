@@ -72,7 +72,44 @@ async function download_splat2() {
 */
 
 
+// Posts each chunk as soon as it arrives (for formats decoded incrementally, e.g. .splatv)
+async function download_chunks(url_param) {
+    const url = new URL(url_param);
+
+    const req = await fetch(url, {
+        mode: "cors",
+        credentials: "omit",
+    });
+    console.log(req);
+    if (req.status != 200)
+        throw new Error("download_chunks(): HTTP status: " + req.status + ", failed to load " + req.url);
+
+    const reader = req.body.getReader();
+    const total = parseInt(req.headers.get("content-length")) || 0;
+    let bytesRead = 0;
+    while (true) {
+        const { done, value } = await reader.read();
+        if (done) break;
+
+        bytesRead += value.length;
+
+        postMessage({
+            bytes: bytesRead,
+            total: total,
+            chunk: value,
+        });
+    }
+}
+
+
 self.onmessage = async function(event) {
     console.log("downloader.js: Received message from Rust:", event.data);
-    download_splat(event.data);
+    if (typeof event.data === "object" && event.data.chunked) {
+        // an uncaught rejection would never reach Rust, leaving "Loading..." up forever
+        download_chunks(event.data.url).catch((e) => {
+            postMessage({ error: String(e && e.message ? e.message : e) });
+        });
+    } else {
+        download_splat(event.data);
+    }
 };
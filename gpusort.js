@@ -0,0 +1,234 @@
+// Depth sort in WebGPU compute shaders for the webgpu_sort feature (cf. src/gpusort.rs)
+// WebGPU buffers can't be bound to the WebGL context, so the sorted indices are read back
+// and uploaded as a_index like the ones of the sorter thread.
+
+const WORKGROUP_SIZE = 256;
+const MAX_WORKGROUPS = 65535; // maxComputeWorkgroupsPerDimension
+const STAGE_STRIDE = 256; // minUniformBufferOffsetAlignment
+
+// one thread per (padded) splat: the depth along depth_row as a sortable key
+const DEPTH_WGSL = `
+struct View {
+    depth_row: vec4<f32>,
+    count: u32,
+    padded: u32,
+    descending: u32,
+    pad: u32,
+};
+@group(0) @binding(0) var<storage, read> positions: array<f32>;
+@group(0) @binding(1) var<storage, read_write> keys: array<u32>;
+@group(0) @binding(2) var<storage, read_write> values: array<u32>;
+@group(0) @binding(3) var<uniform> view: View;
+
+@compute @workgroup_size(${WORKGROUP_SIZE})
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= view.padded) {
+        return;
+    }
+    values[i] = i;
+    if (i >= view.count) {
+        keys[i] = 0xffffffffu; // the padding sorts last
+        return;
+    }
+    let p = vec3<f32>(positions[3u*i], positions[3u*i + 1u], positions[3u*i + 2u]);
+    let bits = bitcast<u32>(dot(view.depth_row.xyz, p));
+    // flip the sign bit of positive floats and all bits of negative ones so that they compare as u32
+    var key = select(bits | 0x80000000u, ~bits, (bits & 0x80000000u) != 0u);
+    if (view.descending != 0u) {
+        key = ~key;
+    }
+    keys[i] = key;
+}
+`;
+
+// one compare-and-swap stage (k, j) of a bitonic sort of the (key, value) pairs
+const BITONIC_WGSL = `
+struct Stage {
+    k: u32,
+    j: u32,
+    padded: u32,
+    pad: u32,
+};
+@group(0) @binding(0) var<storage, read_write> keys: array<u32>;
+@group(0) @binding(1) var<storage, read_write> values: array<u32>;
+@group(1) @binding(0) var<uniform> stage: Stage;
+
+@compute @workgroup_size(${WORKGROUP_SIZE})
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    let l = i ^ stage.j;
+    if (i >= stage.padded || l <= i) {
+        return;
+    }
+    let ascending = (i & stage.k) == 0u;
+    let a = keys[i];
+    let b = keys[l];
+    if ((a > b) == ascending && a != b) {
+        keys[i] = b;
+        keys[l] = a;
+        let v = values[i];
+        values[i] = values[l];
+        values[l] = v;
+    }
+}
+`;
+
+
+let gpu = null; // device and pipelines, set once gpu_sort_init() succeeded
+let buffers = null; // per-scene buffers, set by gpu_sort_set_positions()
+
+
+function storage_entry(binding, type) {
+    return { binding, visibility: GPUShaderStage.COMPUTE, buffer: { type } };
+}
+
+
+export async function gpu_sort_init() {
+    try {
+        if (!navigator.gpu) {
+            return false;
+        }
+        const adapter = await navigator.gpu.requestAdapter();
+        if (!adapter) {
+            return false;
+        }
+        const device = await adapter.requestDevice();
+        device.lost.then((info) => {
+            console.log("gpu_sort: device lost: " + info.message);
+            gpu = null;
+            buffers = null;
+        });
+
+        const depth_layout = device.createBindGroupLayout({ entries: [
+            storage_entry(0, "read-only-storage"),
+            storage_entry(1, "storage"),
+            storage_entry(2, "storage"),
+            storage_entry(3, "uniform"),
+        ]});
+        const pairs_layout = device.createBindGroupLayout({ entries: [
+            storage_entry(0, "storage"),
+            storage_entry(1, "storage"),
+        ]});
+        const stage_layout = device.createBindGroupLayout({ entries: [
+            { binding: 0, visibility: GPUShaderStage.COMPUTE, buffer: { type: "uniform", hasDynamicOffset: true } },
+        ]});
+
+        const depth_pipeline = device.createComputePipeline({
+            layout: device.createPipelineLayout({ bindGroupLayouts: [depth_layout] }),
+            compute: { module: device.createShaderModule({ code: DEPTH_WGSL }), entryPoint: "main" },
+        });
+        const bitonic_pipeline = device.createComputePipeline({
+            layout: device.createPipelineLayout({ bindGroupLayouts: [pairs_layout, stage_layout] }),
+            compute: { module: device.createShaderModule({ code: BITONIC_WGSL }), entryPoint: "main" },
+        });
+
+        gpu = { device, depth_layout, pairs_layout, stage_layout, depth_pipeline, bitonic_pipeline };
+        return true;
+    } catch (e) {
+        console.log("gpu_sort_init(): " + e);
+        return false;
+    }
+}
+
+
+// positions: x, y, z of every splat
+export function gpu_sort_set_positions(positions) {
+    if (!gpu) {
+        return;
+    }
+    const device = gpu.device;
+    if (buffers) {
+        for (const b of [buffers.positions, buffers.keys, buffers.values, buffers.readback, buffers.view, buffers.stages]) {
+            b.destroy();
+        }
+        buffers = null;
+    }
+
+    const count = Math.floor(positions.length / 3);
+    let padded = 1;
+    while (padded < count) {
+        padded *= 2;
+    }
+    if (count == 0 || padded / WORKGROUP_SIZE > MAX_WORKGROUPS) {
+        return;
+    }
+
+    // the (k, j) pairs of every stage, at the dynamic offsets of the stage uniform
+    const stage_pairs = [];
+    for (let k = 2; k <= padded; k *= 2) {
+        for (let j = k / 2; j > 0; j = Math.floor(j / 2)) {
+            stage_pairs.push([k, j]);
+        }
+    }
+    const stage_data = new Uint32Array(Math.max(stage_pairs.length, 1) * STAGE_STRIDE / 4);
+    stage_pairs.forEach(([k, j], s) => stage_data.set([k, j, padded, 0], s * STAGE_STRIDE / 4));
+
+    const usage = GPUBufferUsage;
+    const b = {
+        count,
+        padded,
+        n_stages: stage_pairs.length,
+        positions: device.createBuffer({ size: 12 * count, usage: usage.STORAGE | usage.COPY_DST }),
+        keys: device.createBuffer({ size: 4 * padded, usage: usage.STORAGE }),
+        values: device.createBuffer({ size: 4 * padded, usage: usage.STORAGE | usage.COPY_SRC }),
+        readback: device.createBuffer({ size: 4 * count, usage: usage.MAP_READ | usage.COPY_DST }),
+        view: device.createBuffer({ size: 32, usage: usage.UNIFORM | usage.COPY_DST }),
+        stages: device.createBuffer({ size: stage_data.byteLength, usage: usage.UNIFORM | usage.COPY_DST }),
+    };
+    // copied out of the (shared) wasm memory
+    device.queue.writeBuffer(b.positions, 0, positions.slice(0, 3 * count));
+    device.queue.writeBuffer(b.stages, 0, stage_data);
+
+    b.depth_group = device.createBindGroup({ layout: gpu.depth_layout, entries: [
+        { binding: 0, resource: { buffer: b.positions } },
+        { binding: 1, resource: { buffer: b.keys } },
+        { binding: 2, resource: { buffer: b.values } },
+        { binding: 3, resource: { buffer: b.view } },
+    ]});
+    b.pairs_group = device.createBindGroup({ layout: gpu.pairs_layout, entries: [
+        { binding: 0, resource: { buffer: b.keys } },
+        { binding: 1, resource: { buffer: b.values } },
+    ]});
+    b.stage_group = device.createBindGroup({ layout: gpu.stage_layout, entries: [
+        { binding: 0, resource: { buffer: b.stages, size: 16 } },
+    ]});
+    buffers = b;
+}
+
+
+// depth_row: x, y, z of the depth direction, and 1 to sort the farthest splats first (0: nearest first)
+// resolves to the sorted splat indices
+export async function gpu_sort(depth_row) {
+    if (!gpu || !buffers) {
+        throw new Error("gpu_sort(): no device or no positions");
+    }
+    const device = gpu.device;
+    const b = buffers;
+
+    const view = new ArrayBuffer(32);
+    new Float32Array(view, 0, 4).set([depth_row[0], depth_row[1], depth_row[2], 0]);
+    new Uint32Array(view, 16, 4).set([b.count, b.padded, depth_row[3] > 0 ? 1 : 0, 0]);
+    device.queue.writeBuffer(b.view, 0, view);
+
+    const workgroups = Math.ceil(b.padded / WORKGROUP_SIZE);
+    const encoder = device.createCommandEncoder();
+    const pass = encoder.beginComputePass();
+    pass.setPipeline(gpu.depth_pipeline);
+    pass.setBindGroup(0, b.depth_group);
+    pass.dispatchWorkgroups(workgroups);
+    pass.setPipeline(gpu.bitonic_pipeline);
+    pass.setBindGroup(0, b.pairs_group);
+    for (let s = 0; s < b.n_stages; s++) {
+        pass.setBindGroup(1, b.stage_group, [s * STAGE_STRIDE]);
+        pass.dispatchWorkgroups(workgroups);
+    }
+    pass.end();
+    encoder.copyBufferToBuffer(b.values, 0, b.readback, 0, 4 * b.count);
+    device.queue.submit([encoder.finish()]);
+
+    await b.readback.mapAsync(GPUMapMode.READ);
+    const indices = new Uint32Array(b.readback.getMappedRange().slice(0));
+    b.readback.unmap();
+    return indices;
+}
@@ -34,20 +34,46 @@ export function get_webgl2_version() {
 }
 
 
+function resolve_url(url) {
+    if (url.startsWith("data:")) {
+        return url.replace(/ /g, "+"); // an unescaped '+' in the query string decodes to a space
+    }
+    if (!url.toLowerCase().includes("http")) {
+        url = "https://huggingface.co/datasets/satyoshi/gauzilla-data/resolve/main/" + url;
+    }
+    return url;
+}
+
+
 export function get_url_param() {
     const params = new URLSearchParams(location.search);
     if (params.has("url")) {
-        let url = params.get("url");
-        if (!url.toLowerCase().includes("http")) {
-            url = "https://huggingface.co/datasets/satyoshi/gauzilla-data/resolve/main/" + url;
-        }
-        return url;
+        return resolve_url(params.get("url"));
     } else {
         return "";
     }
 }
 
 
+// scenes to switch between (?scene=a.splat&scene=b.splat...)
+export function get_scene_params() {
+    const params = new URLSearchParams(location.search);
+    return params.getAll("scene").filter((url) => url.length > 0).map(resolve_url);
+}
+
+
+// Downloads url in the background with the same request mode as the downloader worker,
+// so that a later switch to the scene can be served from the HTTP cache (if the server allows caching)
+export async function prefetch_url(url) {
+    try {
+        const res = await fetch(url, { mode: "cors", credentials: "omit", priority: "low" });
+        await res.arrayBuffer();
+    } catch (e) {
+        console.log("prefetch_url(): " + e);
+    }
+}
+
+
 function getVectorParam(paramName, defaultValue) {
     const params = new URLSearchParams(window.location.search);
     const param = params.get(paramName);
@@ -72,11 +98,225 @@ export function get_target_param() {
 }
 
 
+export function has_position_param() {
+    const params = new URLSearchParams(window.location.search);
+    return params.has('position');
+}
+
+
+export function has_target_param() {
+    const params = new URLSearchParams(window.location.search);
+    return params.has('target');
+}
+
+
 export function get_up_param() {
     return getVectorParam('up', [0.0, 1.0, 0.0]);
 }
 
 
+export function get_mirror_param() {
+    const params = new URLSearchParams(window.location.search);
+    return params.get('mirror') || "";
+}
+
+
+export function is_drawing_buffer_preserved() {
+    const canvas = document.getElementById("render_canvas");
+    const gl = canvas.getContext("webgl2"); // returns the existing context
+    return gl != null && gl.getContextAttributes().preserveDrawingBuffer;
+}
+
+
+export function get_format_param() {
+    const params = new URLSearchParams(window.location.search);
+    return params.get('format') || "";
+}
+
+
+export function get_sort_param() {
+    const params = new URLSearchParams(window.location.search);
+    return params.get('sort') || "";
+}
+
+
 export async function sleep_js(ms) {
     await new Promise(resolve => setTimeout(resolve, ms));
 }
+
+
+let webgl_context_lost = false;
+let webgl_context_restored = false;
+
+
+function show_overlay_message(msg) {
+    let div = document.getElementById("gauzilla_overlay");
+    if (!div) {
+        div = document.createElement("div");
+        div.id = "gauzilla_overlay";
+        div.style.cssText = "position:fixed;top:50%;left:50%;transform:translate(-50%,-50%);" +
+            "padding:12px 20px;background:rgba(0,0,0,0.8);color:#ff6060;font-family:sans-serif;z-index:1000;";
+        document.body.appendChild(div);
+    }
+    div.textContent = msg;
+}
+
+
+function hide_overlay_message() {
+    const div = document.getElementById("gauzilla_overlay");
+    if (div) {
+        div.remove();
+    }
+}
+
+
+// Shows a message over the canvas for ms milliseconds
+export function show_warning(msg, ms) {
+    show_overlay_message(msg);
+    setTimeout(() => {
+        if (!webgl_context_lost) {
+            hide_overlay_message();
+        }
+    }, ms);
+}
+
+
+export function register_webgl_context_handlers() {
+    const canvas = document.getElementById("render_canvas");
+    canvas.addEventListener("webglcontextlost", (event) => {
+        event.preventDefault(); // required for the browser to restore the context later
+        console.log("helper.js: webglcontextlost");
+        webgl_context_lost = true;
+        show_overlay_message("WebGL context lost. Waiting for the browser to restore it...");
+    }, false);
+    canvas.addEventListener("webglcontextrestored", () => {
+        console.log("helper.js: webglcontextrestored");
+        webgl_context_lost = false;
+        webgl_context_restored = true;
+        hide_overlay_message();
+    }, false);
+}
+
+
+export function is_webgl_context_lost() {
+    return webgl_context_lost;
+}
+
+
+// Returns true only once after each restoration
+export function take_webgl_context_restored() {
+    const restored = webgl_context_restored;
+    webgl_context_restored = false;
+    return restored;
+}
+
+
+export function copy_to_clipboard(text) {
+    if (!navigator.clipboard) {
+        console.log("helper.js: Clipboard API not available");
+        return;
+    }
+    navigator.clipboard.writeText(text)
+        .then(() => console.log("helper.js: copied " + text.length + " chars to clipboard"))
+        .catch((err) => console.log("helper.js: failed to copy to clipboard: " + err));
+}
+
+
+export async function read_from_clipboard() {
+    if (!navigator.clipboard || !navigator.clipboard.readText) {
+        throw new Error("Clipboard API not available");
+    }
+    return await navigator.clipboard.readText();
+}
+
+
+// Encodes RGBA pixels (top row first) as PNG bytes
+export async function encode_png(pixels, width, height) {
+    const canvas = document.createElement("canvas");
+    canvas.width = width;
+    canvas.height = height;
+    const ctx = canvas.getContext("2d");
+    ctx.putImageData(new ImageData(new Uint8ClampedArray(pixels), width, height), 0, 0);
+    const blob = await new Promise(resolve => canvas.toBlob(resolve, "image/png"));
+    return new Uint8Array(await blob.arrayBuffer());
+}
+
+
+export async function gzip_bytes(bytes) {
+    const stream = new Blob([bytes]).stream().pipeThrough(new CompressionStream("gzip"));
+    return new Uint8Array(await new Response(stream).arrayBuffer());
+}
+
+
+export function download_bytes(bytes, filename) {
+    const url = URL.createObjectURL(new Blob([bytes], { type: "application/octet-stream" }));
+    const a = document.createElement("a");
+    a.href = url;
+    a.download = filename;
+    a.click();
+    setTimeout(() => URL.revokeObjectURL(url), 1000);
+}
+
+
+export function load_image_rgba(url) {
+    return new Promise((resolve, reject) => {
+        const img = new Image();
+        img.crossOrigin = "anonymous";
+        img.onload = () => {
+            const canvas = document.createElement("canvas");
+            canvas.width = img.naturalWidth;
+            canvas.height = img.naturalHeight;
+            const ctx = canvas.getContext("2d");
+            ctx.drawImage(img, 0, 0);
+            const data = ctx.getImageData(0, 0, canvas.width, canvas.height);
+            resolve({ width: canvas.width, height: canvas.height, pixels: new Uint8Array(data.data.buffer) });
+        };
+        img.onerror = () => reject(new Error("could not load the image " + url));
+        img.src = url;
+    });
+}
+
+
+let recorder = null;
+let recorded_chunks = [];
+
+export function start_recording(fps) {
+    const canvas = document.getElementById("render_canvas");
+    if (recorder != null || !canvas.captureStream || typeof MediaRecorder === "undefined") {
+        return false;
+    }
+    const types = ["video/webm;codecs=vp9", "video/webm;codecs=vp8", "video/webm"];
+    const mimeType = types.find((t) => MediaRecorder.isTypeSupported(t));
+    if (mimeType === undefined) {
+        return false;
+    }
+    recorded_chunks = [];
+    recorder = new MediaRecorder(canvas.captureStream(fps), { mimeType });
+    recorder.ondataavailable = (e) => {
+        if (e.data.size > 0) {
+            recorded_chunks.push(e.data);
+        }
+    };
+    recorder.start(1000); // hand over a chunk every second
+    return true;
+}
+
+
+export function stop_recording(filename) {
+    if (recorder == null) {
+        return;
+    }
+    const r = recorder;
+    recorder = null;
+    r.onstop = () => {
+        const url = URL.createObjectURL(new Blob(recorded_chunks, { type: "video/webm" }));
+        recorded_chunks = [];
+        const a = document.createElement("a");
+        a.href = url;
+        a.download = filename;
+        a.click();
+        setTimeout(() => URL.revokeObjectURL(url), 1000);
+    };
+    r.stop();
+    r.stream.getTracks().forEach((t) => t.stop());
+}
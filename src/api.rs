@@ -0,0 +1,356 @@
+use std::{
+    sync::{Mutex, atomic::{AtomicBool, AtomicU32, Ordering}},
+    cell::RefCell,
+};
+use wasm_bindgen::prelude::*;
+use js_sys::{Function, Object, Reflect, JSON};
+use serde::{Serialize, Deserialize};
+
+use crate::log; // macro import
+use crate::utils::is_drawing_buffer_preserved;
+use crate::error::GauzillaError;
+
+
+// f32 stored as its bit pattern so that it can be shared without a lock
+static SPLAT_SCALE: AtomicU32 = AtomicU32::new(1.0_f32.to_bits());
+static GLOBAL_ALPHA: AtomicU32 = AtomicU32::new(1.0_f32.to_bits());
+
+// RGBA (straight alpha) bit patterns, opaque black by default
+static CLEAR_COLOR: [AtomicU32; 4] = [
+    AtomicU32::new(0.0_f32.to_bits()),
+    AtomicU32::new(0.0_f32.to_bits()),
+    AtomicU32::new(0.0_f32.to_bits()),
+    AtomicU32::new(1.0_f32.to_bits()),
+];
+static PRESERVE_CANVAS: AtomicBool = AtomicBool::new(false);
+static CAMERA_ROLL: AtomicU32 = AtomicU32::new(0.0_f32.to_bits()); // degrees
+
+// (position, target, up) of the camera in the last frame, and one requested by the host (taken by the render loop)
+static CAMERA_POSE: Mutex<[[f32; 3]; 3]> = Mutex::new([[0.0; 3]; 3]);
+static CAMERA_POSE_REQUEST: Mutex<Option<[[f32; 3]; 3]>> = Mutex::new(None);
+
+// indices into BLEND_MODES and CONTROL_MODES
+static BLEND_MODE: AtomicU32 = AtomicU32::new(0);
+static CONTROL_MODE: AtomicU32 = AtomicU32::new(0);
+static LOD_THRESHOLD: AtomicU32 = AtomicU32::new(0.0_f32.to_bits()); // 0 = LOD off
+static FLY_SPEED: AtomicU32 = AtomicU32::new(0.0_f32.to_bits()); // 0 = scaled to the scene
+
+/// Names of the blend modes in the settings object, in the order of renderer::BlendMode::ALL
+pub(crate) const BLEND_MODES: [&str; 4] = ["over", "additive", "premultiplied", "under"];
+/// Names of the camera controls in the settings object
+pub(crate) const CONTROL_MODES: [&str; 2] = ["orbit", "fly"];
+
+/// Version of the settings object, bumped on incompatible changes
+pub const SETTINGS_VERSION: u32 = 1;
+
+
+/// Sets the splat scale used by the render loop from the next frame on (clamped to [0.1, 1.0])
+#[wasm_bindgen]
+pub fn set_splat_scale(scale: f32) {
+    SPLAT_SCALE.store(scale.clamp(0.1, 1.0).to_bits(), Ordering::Relaxed);
+}
+
+
+/// Returns the splat scale currently used by the render loop
+#[wasm_bindgen]
+pub fn get_splat_scale() -> f32 {
+    f32::from_bits(SPLAT_SCALE.load(Ordering::Relaxed))
+}
+
+
+/// Sets the opacity multiplier of the whole cloud from the next frame on (clamped to [0, 1])
+/// Calling this every frame fades the scene in or out (e.g. for crossfades between scenes).
+#[wasm_bindgen]
+pub fn set_global_alpha(alpha: f32) {
+    GLOBAL_ALPHA.store(alpha.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+}
+
+
+/// Returns the opacity multiplier of the whole cloud
+#[wasm_bindgen]
+pub fn get_global_alpha() -> f32 {
+    f32::from_bits(GLOBAL_ALPHA.load(Ordering::Relaxed))
+}
+
+
+/// Sets the color the canvas is cleared to before each frame (straight alpha, clamped to [0, 1])
+/// An alpha below 1 lets the HTML content behind a transparent canvas show through.
+#[wasm_bindgen]
+pub fn set_clear_color(r: f32, g: f32, b: f32, a: f32) {
+    for (c, v) in CLEAR_COLOR.iter().zip([r, g, b, a]) {
+        c.store(v.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+}
+
+
+/// Returns the color the canvas is cleared to as [r, g, b, a]
+#[wasm_bindgen]
+pub fn get_clear_color() -> Vec<f32> {
+    CLEAR_COLOR.iter().map(|c| f32::from_bits(c.load(Ordering::Relaxed))).collect()
+}
+
+
+/// Keeps the previous canvas content and composites each frame over it instead of clearing
+/// Only has an effect if the WebGL2 context was created with `preserveDrawingBuffer: true`
+/// (the host page can call `canvas.getContext("webgl2", {preserveDrawingBuffer: true})` before starting Gauzilla).
+#[wasm_bindgen]
+pub fn set_preserve_canvas(preserve: bool) {
+    if preserve && !is_drawing_buffer_preserved() {
+        log!("set_preserve_canvas(): WARNING: the WebGL2 context doesn't preserve the drawing buffer");
+    }
+    PRESERVE_CANVAS.store(preserve, Ordering::Relaxed);
+}
+
+
+/// Returns true if the canvas is composited over instead of cleared
+#[wasm_bindgen]
+pub fn get_preserve_canvas() -> bool {
+    PRESERVE_CANVAS.load(Ordering::Relaxed)
+}
+
+
+/// Sets the camera roll in degrees from the next frame on (clamped to [-180, 180])
+#[wasm_bindgen]
+pub fn set_camera_roll(degrees: f32) {
+    CAMERA_ROLL.store(degrees.clamp(-180.0, 180.0).to_bits(), Ordering::Relaxed);
+}
+
+
+/// Returns the camera roll in degrees
+#[wasm_bindgen]
+pub fn get_camera_roll() -> f32 {
+    f32::from_bits(CAMERA_ROLL.load(Ordering::Relaxed))
+}
+
+
+/// Moves the camera to position, looking at target with the given up vector, on the next frame
+/// The up vector defines the whole orientation, so the camera roll is reset to 0.
+/// The orbit control orbits around target from then on.
+#[wasm_bindgen]
+pub fn set_camera_pose(position: &[f32], target: &[f32], up: &[f32]) -> Result<(), JsValue> {
+    let vector = |v: &[f32], what: &str| -> Result<[f32; 3], GauzillaError> {
+        let v: [f32; 3] = v.try_into()
+            .map_err(|_| GauzillaError::DecodeFailed(format!("set_camera_pose(): {} needs 3 values, got {}", what, v.len())))?;
+        if v.iter().all(|c| c.is_finite()) {
+            Ok(v)
+        } else {
+            Err(GauzillaError::DecodeFailed(format!("set_camera_pose(): {} {:?} isn't finite", what, v)))
+        }
+    };
+    let pose = [vector(position, "position")?, vector(target, "target")?, vector(up, "up")?];
+    let length = |v: [f32; 3]| (v[0]*v[0] + v[1]*v[1] + v[2]*v[2]).sqrt();
+    let [p, t, u] = pose;
+    if length([t[0] - p[0], t[1] - p[1], t[2] - p[2]]) < 1e-6 || length(u) < 1e-6 {
+        return Err(GauzillaError::DecodeFailed("set_camera_pose(): target must differ from position, up must be nonzero".to_string()).into());
+    }
+    *CAMERA_POSE_REQUEST.lock().unwrap() = Some(pose);
+    Ok(())
+}
+
+
+/// Returns the camera pose of the last frame as [px, py, pz, tx, ty, tz, ux, uy, uz]
+#[wasm_bindgen]
+pub fn get_camera_pose() -> Vec<f32> {
+    CAMERA_POSE.lock().unwrap().concat()
+}
+
+
+/// Pose requested by set_camera_pose() since the last call, if any
+pub(crate) fn take_camera_pose() -> Option<[[f32; 3]; 3]> {
+    CAMERA_POSE_REQUEST.lock().unwrap().take()
+}
+
+
+pub(crate) fn set_current_camera_pose(position: [f32; 3], target: [f32; 3], up: [f32; 3]) {
+    *CAMERA_POSE.lock().unwrap() = [position, target, up];
+}
+
+
+/// Index of the blend mode used by the render loop (cf. BLEND_MODES)
+pub(crate) fn get_blend_mode() -> usize {
+    BLEND_MODE.load(Ordering::Relaxed) as usize
+}
+
+
+pub(crate) fn set_blend_mode(index: usize) {
+    BLEND_MODE.store(index.min(BLEND_MODES.len() - 1) as u32, Ordering::Relaxed);
+}
+
+
+/// Index of the camera control used by the render loop (cf. CONTROL_MODES)
+pub(crate) fn get_control_mode() -> usize {
+    CONTROL_MODE.load(Ordering::Relaxed) as usize
+}
+
+
+pub(crate) fn set_control_mode(index: usize) {
+    CONTROL_MODE.store(index.min(CONTROL_MODES.len() - 1) as u32, Ordering::Relaxed);
+}
+
+
+/// Octree LOD threshold used by the render loop (None = LOD off)
+pub(crate) fn get_lod() -> Option<f32> {
+    let threshold = f32::from_bits(LOD_THRESHOLD.load(Ordering::Relaxed));
+    (threshold > 0.0).then_some(threshold)
+}
+
+
+pub(crate) fn set_lod(lod: Option<f32>) {
+    LOD_THRESHOLD.store(lod.map_or(0.0, |t| t.clamp(0.01, 1.0)).to_bits(), Ordering::Relaxed);
+}
+
+
+/// Speed of the fly control used by the render loop (None = scaled to the scene)
+pub(crate) fn get_fly_speed() -> Option<f32> {
+    let speed = f32::from_bits(FLY_SPEED.load(Ordering::Relaxed));
+    (speed > 0.0).then_some(speed)
+}
+
+
+pub(crate) fn set_fly_speed(speed: Option<f32>) {
+    FLY_SPEED.store(speed.map_or(0.0, |s| s.max(0.0)).to_bits(), Ordering::Relaxed);
+}
+
+
+/// All host-facing settings in one object, e.g.
+/// `{version: 1, splat_scale: 1, clear_color: [0, 0, 0, 1], blend_mode: "over", control_mode: "orbit", fly_speed: 0, lod: 0}`
+/// Every field is optional in apply_settings(), so hosts can change a subset.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(default)]
+pub(crate) struct Settings {
+    pub version: Option<u32>,
+    pub splat_scale: Option<f32>,
+    pub clear_color: Option<[f32; 4]>, // straight alpha
+    pub blend_mode: Option<String>, // one of BLEND_MODES
+    pub control_mode: Option<String>, // one of CONTROL_MODES
+    pub fly_speed: Option<f32>, // movement speed of the fly control, 0 = scaled to the scene
+    pub lod: Option<f32>, // octree LOD refinement threshold (share of the viewport height), 0 = off
+}
+impl Settings {
+    /// Current values of all settings
+    pub fn current() -> Self {
+        Self {
+            version: Some(SETTINGS_VERSION),
+            splat_scale: Some(get_splat_scale()),
+            clear_color: get_clear_color().try_into().ok(),
+            blend_mode: Some(BLEND_MODES[get_blend_mode()].to_string()),
+            control_mode: Some(CONTROL_MODES[get_control_mode()].to_string()),
+            fly_speed: Some(get_fly_speed().unwrap_or(0.0)),
+            lod: Some(get_lod().unwrap_or(0.0)),
+        }
+    }
+
+
+    /// Applies the given fields, after validating all of them (nothing is changed on error)
+    pub fn apply(&self) -> Result<(), GauzillaError> {
+        if let Some(version) = self.version {
+            if version > SETTINGS_VERSION {
+                return Err(GauzillaError::UnsupportedFormat(format!("settings version {}", version)));
+            }
+        }
+        let index_of = |names: &[&str], name: &Option<String>, what: &str| match name {
+            Some(name) => names.iter()
+                .position(|n| n.eq_ignore_ascii_case(name))
+                .map(Some)
+                .ok_or_else(|| GauzillaError::DecodeFailed(format!("unknown {} {:?}, expected one of {:?}", what, name, names))),
+            None => Ok(None),
+        };
+        let blend_mode = index_of(&BLEND_MODES, &self.blend_mode, "blend_mode")?;
+        let control_mode = index_of(&CONTROL_MODES, &self.control_mode, "control_mode")?;
+
+        if let Some(scale) = self.splat_scale {
+            set_splat_scale(scale);
+        }
+        if let Some([r, g, b, a]) = self.clear_color {
+            set_clear_color(r, g, b, a);
+        }
+        if let Some(index) = blend_mode {
+            set_blend_mode(index);
+        }
+        if let Some(index) = control_mode {
+            set_control_mode(index);
+        }
+        if let Some(speed) = self.fly_speed {
+            set_fly_speed((speed > 0.0).then_some(speed));
+        }
+        if let Some(lod) = self.lod {
+            set_lod((lod > 0.0).then_some(lod));
+        }
+        Ok(())
+    }
+}
+
+
+/// Sets splat scale, background color, blend mode, camera control (and fly speed), and LOD in one call
+/// from a settings object (cf. get_settings()), e.g. one persisted by the host page.
+/// Omitted fields are left unchanged. Takes effect from the next frame on.
+#[wasm_bindgen]
+pub fn apply_settings(settings: JsValue) -> Result<(), JsValue> {
+    let json: String = JSON::stringify(&settings)?.into();
+    let settings: Settings = serde_json::from_str(&json)
+        .map_err(|e| GauzillaError::DecodeFailed(format!("apply_settings(): {}", e)))?;
+    settings.apply()?;
+    log!("apply_settings(): {:?}", settings);
+    Ok(())
+}
+
+
+/// Returns the current settings as an object accepted by apply_settings()
+#[wasm_bindgen]
+pub fn get_settings() -> Result<JsValue, JsValue> {
+    let json = serde_json::to_string(&Settings::current())
+        .map_err(|e| GauzillaError::DecodeFailed(format!("get_settings(): {}", e)))?;
+    JSON::parse(&json)
+}
+
+
+struct StatsCallback {
+    function: Function,
+    interval_ms: f64,
+    last_call_ms: f64,
+}
+
+thread_local! {
+    // JS functions can't be shared across threads, and the render loop runs on the main thread
+    static STATS_CALLBACK: RefCell<Option<StatsCallback>> = RefCell::new(None);
+}
+
+
+/// Registers a callback invoked with `{fps, sort_time_ms, splat_count, cpu_cores}`
+/// at most once every interval_ms (0 = every frame). Passing null/undefined unregisters it.
+/// No telemetry is collected unless a callback is registered.
+#[wasm_bindgen]
+pub fn set_stats_callback(callback: Option<Function>, interval_ms: f64) {
+    STATS_CALLBACK.with(|cb| {
+        *cb.borrow_mut() = callback.map(|function| StatsCallback {
+            function,
+            interval_ms: interval_ms.max(0.0),
+            last_call_ms: f64::MIN,
+        });
+    });
+}
+
+
+/// Invokes the registered stats callback (if any) with the already computed moving averages
+pub(crate) fn report_stats(now_ms: f64, fps: f64, sort_time_ms: f64, splat_count: usize, cpu_cores: usize) {
+    STATS_CALLBACK.with(|cb| {
+        let mut cb = cb.borrow_mut();
+        let cb = match cb.as_mut() {
+            Some(cb) => cb,
+            None => return,
+        };
+        if now_ms - cb.last_call_ms < cb.interval_ms {
+            return;
+        }
+        cb.last_call_ms = now_ms;
+
+        let stats = Object::new();
+        let _ = Reflect::set(&stats, &"fps".into(), &JsValue::from_f64(fps));
+        let _ = Reflect::set(&stats, &"sort_time_ms".into(), &JsValue::from_f64(sort_time_ms));
+        let _ = Reflect::set(&stats, &"splat_count".into(), &JsValue::from_f64(splat_count as f64));
+        let _ = Reflect::set(&stats, &"cpu_cores".into(), &JsValue::from_f64(cpu_cores as f64));
+        if let Err(e) = cb.function.call1(&JsValue::NULL, &stats) {
+            log!("report_stats(): ERROR: stats callback failed: {:?}", e);
+        }
+    });
+}
@@ -0,0 +1,20 @@
+#version 300 es
+precision highp float;
+precision highp int;
+
+uniform mat4 projection, view;
+uniform vec3 box_min, box_max;
+
+// the 12 edges of the box as pairs of corners (bit k of a corner picks box_max along axis k)
+const int EDGES[24] = int[24](
+    0, 1, 2, 3, 4, 5, 6, 7, // along x
+    0, 2, 1, 3, 4, 6, 5, 7, // along y
+    0, 4, 1, 5, 2, 6, 3, 7  // along z
+);
+
+// one line vertex per gl_VertexID (no vertex attributes)
+void main () {
+    int corner = EDGES[gl_VertexID];
+    vec3 pick = vec3(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+    gl_Position = projection * view * vec4(mix(box_min, box_max, pick), 1.0);
+}
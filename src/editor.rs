@@ -0,0 +1,192 @@
+use three_d::*;
+
+use crate::log; // macro import
+use crate::utils::*;
+use crate::scene::Scene;
+
+
+/// Brush tool that lowers the opacity of splats whose projected centers fall under the cursor
+pub struct OpacityBrush {
+    pub enabled: bool,
+    pub radius: f32, // brush radius in physical pixels
+    pub strength: f32, // fraction of the alpha removed per brush step
+    alpha: Vec<u8>, // per-splat alpha as currently uploaded to the texture
+    stroke: Vec<(u32, u8)>, // (splat index, previous alpha) of the stroke in progress
+    undo_stack: Vec<Vec<(u32, u8)>>,
+}
+impl OpacityBrush {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            radius: 30.0,
+            strength: 0.25,
+            alpha: Vec::<u8>::new(),
+            stroke: Vec::<(u32, u8)>::new(),
+            undo_stack: Vec::<Vec<(u32, u8)>>::new(),
+        }
+    }
+
+
+    /// Discards all edits (e.g. when a new scene has been loaded)
+    pub fn reset(&mut self) {
+        self.alpha.clear();
+        self.stroke.clear();
+        self.undo_stack.clear();
+    }
+
+
+    /// Returns true if any splat has been painted since the scene was loaded
+    pub fn has_edits(&self) -> bool {
+        !self.alpha.is_empty()
+    }
+
+
+    /// Returns true if there is at least one stroke that can be undone
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+
+    /// Copies the alpha of each splat out of the texture data on first use
+    fn ensure_alpha(&mut self, scene: &Scene) {
+        if self.alpha.len() == scene.splat_count {
+            return;
+        }
+        self.alpha = (0..scene.splat_count)
+            .map(|i| (scene.tex_data[scene.color_offset(i)] >> 24) as u8) // RGBA packed in a single u32
+            .collect();
+    }
+
+
+    pub fn begin_stroke(&mut self) {
+        self.stroke.clear();
+    }
+
+
+    pub fn end_stroke(&mut self) {
+        if !self.stroke.is_empty() {
+            let stroke = std::mem::take(&mut self.stroke);
+            log!("OpacityBrush::end_stroke(): {} splat edits", stroke.len());
+            self.undo_stack.push(stroke);
+        }
+    }
+
+
+    /// Lowers the alpha of the splats under the cursor
+    /// Returns the (sorted, unique) texture rows that need to be re-uploaded
+    pub fn paint(&mut self, scene: &Scene, view_proj: &Mat4, viewport: Viewport, cursor: (f32, f32)) -> Vec<usize> {
+        if scene.buffer.is_empty() || scene.tex_data.is_empty() {
+            return Vec::<usize>::new();
+        }
+        self.ensure_alpha(scene);
+
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(scene.buffer.as_slice());
+        let w = viewport.width as f32;
+        let h = viewport.height as f32;
+        let r2 = self.radius*self.radius;
+        let row_len = scene.tex_width*4; // 4 components per pixel (RGBA)
+
+        let mut rows = Vec::<usize>::new();
+        for i in 0..scene.splat_count {
+            let index_f = 8*i;
+            let p = view_proj * vec4(f_buffer[index_f + 0], f_buffer[index_f + 1], f_buffer[index_f + 2], 1.0);
+            if p.w <= 0.0 {
+                continue; // behind the camera
+            }
+
+            // NDC -> physical pixels (origin at the bottom-left corner like the mouse events)
+            let sx = (0.5*(p.x/p.w) + 0.5)*w + viewport.x as f32;
+            let sy = (0.5*(p.y/p.w) + 0.5)*h + viewport.y as f32;
+            let dx = sx - cursor.0;
+            let dy = sy - cursor.1;
+            if dx*dx + dy*dy > r2 {
+                continue;
+            }
+
+            let old = self.alpha[i];
+            if old == 0 {
+                continue;
+            }
+            let new = ((old as f32)*(1.0 - self.strength)) as u8;
+            self.stroke.push((i as u32, old));
+            self.alpha[i] = new;
+            rows.push(scene.color_offset(i) / row_len);
+        }
+
+        rows.dedup(); // indices are visited in ascending order
+        rows
+    }
+
+
+    /// Reverts the last stroke
+    /// Returns the texture rows that need to be re-uploaded
+    pub fn undo(&mut self, scene: &Scene) -> Vec<usize> {
+        let mut rows = Vec::<usize>::new();
+        if let Some(stroke) = self.undo_stack.pop() {
+            self.ensure_alpha(scene);
+            let row_len = scene.tex_width*4;
+            for &(i, old) in stroke.iter().rev() {
+                self.alpha[i as usize] = old;
+                rows.push(scene.color_offset(i as usize) / row_len);
+            }
+            rows.sort_unstable();
+            rows.dedup();
+        }
+        rows
+    }
+
+
+    /// Returns a copy of one texture row with the edited alphas patched in
+    pub fn row_data(&self, scene: &Scene, row: usize) -> Vec<u32> {
+        let row_len = scene.tex_width*4; // 4 components per pixel (RGBA)
+        let row_start = row*row_len;
+        let mut data = scene.tex_data[row_start..row_start + row_len].to_vec();
+        if self.alpha.len() == scene.splat_count {
+            for i in scene.splats_in_row(row) {
+                let k = scene.color_offset(i);
+                if k < row_start || k >= row_start + row_len {
+                    continue;
+                }
+                let j = k - row_start;
+                data[j] = (data[j] & 0x00ffffff) | ((self.alpha[i] as u32) << 24);
+            }
+        }
+        data
+    }
+}
+
+
+/// Returns the index of the front-most visible splat whose projected center lies within radius
+/// physical pixels of the cursor (e.g. for highlighting the splat under the mouse)
+pub fn pick_splat(scene: &Scene, view_proj: &Mat4, viewport: Viewport, cursor: (f32, f32), radius: f32) -> Option<u32> {
+    if scene.buffer.is_empty() {
+        return None;
+    }
+    let f_buffer: &[f32] = transmute_slice::<_, f32>(scene.buffer.as_slice());
+    let w = viewport.width as f32;
+    let h = viewport.height as f32;
+    let r2 = radius*radius;
+
+    let mut picked: Option<(u32, f32)> = None; // (splat index, clip-space w)
+    for i in 0..scene.splat_count {
+        if scene.buffer[32*i + 3*4 + 3*4 + 3] == 0 {
+            continue; // fully transparent
+        }
+        let index_f = 8*i;
+        let p = view_proj * vec4(f_buffer[index_f + 0], f_buffer[index_f + 1], f_buffer[index_f + 2], 1.0);
+        if p.w <= 0.0 || picked.map_or(false, |(_, d)| p.w >= d) {
+            continue; // behind the camera or behind the current pick
+        }
+
+        // NDC -> physical pixels (origin at the bottom-left corner like the mouse events)
+        let sx = (0.5*(p.x/p.w) + 0.5)*w + viewport.x as f32;
+        let sy = (0.5*(p.y/p.w) + 0.5)*h + viewport.y as f32;
+        let dx = sx - cursor.0;
+        let dy = sy - cursor.1;
+        if dx*dx + dy*dy <= r2 {
+            picked = Some((i as u32, p.w));
+        }
+    }
+
+    picked.map(|(i, _)| i)
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use js_sys::Reflect;
+
+
+/// Errors returned by the scene loaders
+///
+/// Converts into a JavaScript `Error` whose `name` is "GauzillaError" and whose `kind` property
+/// is the variant name (plus `status` for [GauzillaError::Network]), so host pages can
+/// react to specific failures without matching on the message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GauzillaError {
+    BadHeader(String), // malformed or missing file header
+    Truncated(String), // fewer bytes than the header announced
+    UnsupportedFormat(String), // unknown file extension, magic number, or frame type
+    Network(u16), // HTTP status other than 200
+    DecodeFailed(String), // the payload could not be decoded
+    Js(String), // error raised by a browser API
+    TooLarge(String), // the scene exceeds a GPU limit (e.g. the 2D texture size)
+}
+impl GauzillaError {
+    /// Name of the variant (also exposed to JavaScript as `error.kind`)
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GauzillaError::BadHeader(_) => "BadHeader",
+            GauzillaError::Truncated(_) => "Truncated",
+            GauzillaError::UnsupportedFormat(_) => "UnsupportedFormat",
+            GauzillaError::Network(_) => "Network",
+            GauzillaError::DecodeFailed(_) => "DecodeFailed",
+            GauzillaError::Js(_) => "Js",
+            GauzillaError::TooLarge(_) => "TooLarge",
+        }
+    }
+}
+impl fmt::Display for GauzillaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GauzillaError::BadHeader(s) => write!(f, "bad header: {}", s),
+            GauzillaError::Truncated(s) => write!(f, "truncated data: {}", s),
+            GauzillaError::UnsupportedFormat(s) => write!(f, "unsupported format: {}", s),
+            GauzillaError::Network(status) => write!(f, "network error: HTTP status={}", status),
+            GauzillaError::DecodeFailed(s) => write!(f, "decode failed: {}", s),
+            GauzillaError::Js(s) => write!(f, "JavaScript error: {}", s),
+            GauzillaError::TooLarge(s) => write!(f, "too large: {}", s),
+        }
+    }
+}
+impl std::error::Error for GauzillaError {}
+impl From<GauzillaError> for JsValue {
+    fn from(e: GauzillaError) -> Self {
+        let error = js_sys::Error::new(&e.to_string());
+        error.set_name("GauzillaError");
+        let _ = Reflect::set(&error, &"kind".into(), &JsValue::from_str(e.kind()));
+        if let GauzillaError::Network(status) = e {
+            let _ = Reflect::set(&error, &"status".into(), &JsValue::from(status));
+        }
+        error.into()
+    }
+}
+impl From<JsValue> for GauzillaError {
+    fn from(v: JsValue) -> Self {
+        GauzillaError::Js(v.as_string().unwrap_or_else(|| format!("{:?}", v)))
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    rc::Rc,
+    cell::{Cell, RefCell},
+    sync::{Arc, Weak},
+};
+use wasm_bindgen::prelude::*;
+use js_sys::Uint32Array;
+
+use crate::log; // macro import
+use crate::utils::*;
+use crate::scene::{Scene, SortSettings, DrawOrder};
+
+
+const MAX_SPLATS: usize = 1 << 23; // the padded count has to fit into 65535 workgroups of 256 threads
+
+
+#[wasm_bindgen(module = "/gpusort.js")]
+extern "C" {
+    async fn gpu_sort_init() -> JsValue;
+    fn gpu_sort_set_positions(positions: &[f32]);
+    #[wasm_bindgen(catch)]
+    async fn gpu_sort(depth_row: &[f32]) -> Result<JsValue, JsValue>;
+}
+
+
+/// Returns true if the GPU sort gives the same order as Scene::sort() for settings,
+/// i.e. a plain depth sort of every splat (the filters, LOD, and explode offsets only exist on the CPU)
+fn gpu_sortable(scene: &Scene, settings: &SortSettings) -> bool {
+    scene.splat_count > 0
+        && scene.splat_count <= MAX_SPLATS
+        && settings.axis.is_none()
+        && settings.lod.is_none()
+        && settings.explode.is_none()
+        && settings.crop.is_none()
+        && !settings.cull
+        && settings.min_alpha() == 0
+        && settings.limited_count(scene.splat_count) == scene.splat_count
+}
+
+
+/// Depth sort in WebGPU compute shaders (webgpu_sort feature), driven from the main thread
+/// The sorted indices are read back asynchronously and uploaded as a_index like the ones of the sorter thread,
+/// which still gets the views the GPU sort can't handle (or all of them while WebGPU is unavailable).
+pub struct GpuSorter {
+    available: Rc<Cell<Option<bool>>>, // None while the device is requested
+    busy: Rc<Cell<bool>>, // a sort is running (one at a time, they share the readback buffer)
+    pending: Rc<Cell<bool>>, // a view came in while busy, or the sort failed: sort again
+    generation: Rc<Cell<u32>>, // bumped when a view goes to the sorter thread, so that a running sort's result is dropped
+    result: Rc<RefCell<Option<Vec<u32>>>>,
+    sort_time: Rc<Cell<Option<f64>>>, // milliseconds including the readback
+    uploaded: Weak<Scene>, // the scene whose positions are in the GPU buffer
+}
+impl GpuSorter {
+    pub fn new() -> Self {
+        let available = Rc::new(Cell::new(None));
+        execute_future({
+            let available = available.clone();
+            async move {
+                let ok = gpu_sort_init().await.as_bool().unwrap_or(false);
+                log!("GpuSorter::new(): {}", if ok { "sorting on the GPU" } else { "WebGPU not available, sorting on the CPU" });
+                available.set(Some(ok));
+            }
+        });
+        Self {
+            available,
+            busy: Rc::new(Cell::new(false)),
+            pending: Rc::new(Cell::new(false)),
+            generation: Rc::new(Cell::new(0)),
+            result: Rc::new(RefCell::new(None)),
+            sort_time: Rc::new(Cell::new(None)),
+            uploaded: Weak::new(),
+        }
+    }
+
+
+    /// Starts sorting scene for view_proj on the GPU (or queues the view behind the running sort)
+    /// Returns false if the view has to go to the sorter thread instead
+    pub fn sort(&mut self, scene: &Arc<Scene>, view_proj: &[f32; 16], settings: &SortSettings) -> bool {
+        if self.available.get() != Some(true) || !gpu_sortable(scene, settings) {
+            self.generation.set(self.generation.get().wrapping_add(1));
+            self.pending.set(false);
+            return false;
+        }
+        if self.busy.get() {
+            self.pending.set(true);
+            return true;
+        }
+
+        // the weak reference keeps a scene patched in place (splatv frames) from looking like the uploaded one
+        let weak = Arc::downgrade(scene);
+        if !self.uploaded.ptr_eq(&weak) {
+            let f_buffer: &[f32] = transmute_slice::<_, f32>(scene.buffer.as_slice());
+            let positions: Vec<f32> = f_buffer
+                .chunks_exact(8)
+                .take(scene.splat_count)
+                .flat_map(|f| [f[0], f[1], f[2]])
+                .collect();
+            gpu_sort_set_positions(&positions);
+            self.uploaded = weak;
+        }
+
+        // same depth row as Scene::sort(), sorted nearest first unless drawn back to front
+        let descending = if settings.order == DrawOrder::BackToFront { 1.0 } else { 0.0 };
+        let depth_row = [view_proj[2], view_proj[6], view_proj[10], descending];
+
+        self.busy.set(true);
+        let available = self.available.clone();
+        let busy = self.busy.clone();
+        let pending = self.pending.clone();
+        let generation = self.generation.clone();
+        let sort_generation = generation.get();
+        let result = self.result.clone();
+        let sort_time = self.sort_time.clone();
+        execute_future(async move {
+            let start = get_time_milliseconds();
+            match gpu_sort(&depth_row).await {
+                Ok(_) if generation.get() != sort_generation => (),
+                Ok(indices) => {
+                    *result.borrow_mut() = Some(Uint32Array::new(&indices).to_vec());
+                    sort_time.set(Some(get_time_milliseconds() - start));
+                },
+                Err(e) => {
+                    log!("GpuSorter::sort(): ERROR: {:?}, sorting on the CPU from now on", e);
+                    available.set(Some(false));
+                    pending.set(true); // the view still needs a sort
+                },
+            }
+            busy.set(false);
+        });
+        true
+    }
+
+
+    /// Returns true once if the current view has to be sent again (it came in while busy, or the GPU sort failed)
+    pub fn take_pending(&mut self) -> bool {
+        !self.busy.get() && self.pending.replace(false)
+    }
+
+
+    /// Indices of the last finished sort
+    pub fn take_result(&mut self) -> Option<Vec<u32>> {
+        self.result.borrow_mut().take()
+    }
+
+
+    /// Duration of the last finished sort
+    pub fn take_sort_time(&mut self) -> Option<f64> {
+        self.sort_time.take()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gpu_sortable() {
+        let scene = Scene::test_scene(2);
+        let settings = SortSettings::default();
+        assert!(gpu_sortable(&scene, &settings));
+        assert!(!gpu_sortable(&Scene::new(), &settings));
+        assert!(!gpu_sortable(&scene, &SortSettings { cull: true, ..settings.clone() }));
+        assert!(!gpu_sortable(&scene, &SortSettings { splat_percent: 50.0, ..settings.clone() }));
+        assert!(!gpu_sortable(&scene, &SortSettings { lod: Some(0.1), ..settings.clone() }));
+        assert!(!gpu_sortable(&scene, &SortSettings { crop: Some(([0.0; 3], [1.0; 3])), ..settings }));
+    }
+}
@@ -1,14 +1,100 @@
 #version 300 es
 precision highp float;
+precision highp int;
+
+uniform bool premultiply;
+uniform bool srgb_to_linear; // decode the splat colors to linear before blending
+uniform highp sampler2D u_sh_texture;
+uniform int sh_degree; // degree of the SH texture (0 = DC only, no texture)
+uniform vec2 near_fade; // (z_near, fade distance), no fade if the distance is 0
+uniform int hovered; // index of the splat under the cursor (-1 = none)
+uniform float u_global_alpha; // opacity multiplier for the whole cloud
+uniform float depth_focus; // in-focus view-space distance, fragDepth is encoded around it
 
 in vec4 vColor;
 in vec2 vPosition;
+flat in int vIndex;
+flat in vec3 vDir;
+flat in float vFade;
+flat in float vDepth;
+
+layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec4 fragDepth; // depth/(depth + depth_focus) in r, blended like the color (cf. quad.frag)
+
+// sRGB transfer function (IEC 61966-2-1) inverse
+vec3 srgb_decode(vec3 c) {
+    return mix(c/12.92, pow((c + 0.055)/1.055, vec3(2.4)), step(0.04045, c));
+}
+
+const vec3 HIGHLIGHT = vec3(1.0, 0.75, 0.2);
 
-out vec4 fragColor;
+const float SH_C1 = 0.4886025119029199;
+const float SH_C2[5] = float[5](
+    1.0925484305920792, -1.0925484305920792, 0.31539156525252005, -1.0925484305920792, 0.5462742152960396
+);
+const float SH_C3[7] = float[7](
+    -0.5900435899266435, 2.890611442640554, -0.4570457994644658, 0.3731763325901154,
+    -0.4570457994644658, 1.445305721320277, -0.5900435899266435
+);
+
+// k-th higher-order coefficient (1-based, as in the SH basis) of the current splat
+vec3 sh(int k) {
+    int n = (sh_degree + 1)*(sh_degree + 1) - 1;
+    int t = vIndex*n + k - 1;
+    int w = textureSize(u_sh_texture, 0).x;
+    return texelFetch(u_sh_texture, ivec2(t % w, t / w), 0).rgb;
+}
+
+// view-dependent part of the color, cf. eval_sh() of the reference implementation
+vec3 eval_sh(vec3 d) {
+    float x = d.x, y = d.y, z = d.z;
+    vec3 c = SH_C1*(-y*sh(1) + z*sh(2) - x*sh(3));
+    if (sh_degree > 1) {
+        float xx = x*x, yy = y*y, zz = z*z;
+        c += SH_C2[0]*x*y*sh(4) +
+            SH_C2[1]*y*z*sh(5) +
+            SH_C2[2]*(2.0*zz - xx - yy)*sh(6) +
+            SH_C2[3]*x*z*sh(7) +
+            SH_C2[4]*(xx - yy)*sh(8);
+        if (sh_degree > 2) {
+            c += SH_C3[0]*y*(3.0*xx - yy)*sh(9) +
+                SH_C3[1]*x*y*z*sh(10) +
+                SH_C3[2]*y*(4.0*zz - xx - yy)*sh(11) +
+                SH_C3[3]*z*(2.0*zz - 3.0*xx - 3.0*yy)*sh(12) +
+                SH_C3[4]*x*(4.0*zz - xx - yy)*sh(13) +
+                SH_C3[5]*z*(xx - yy)*sh(14) +
+                SH_C3[6]*x*(xx - 3.0*yy)*sh(15);
+        }
+    }
+    return c;
+}
 
 void main () {
     float A = -dot(vPosition, vPosition);
     if (A < -4.0) discard;
+    vec3 rgb = vColor.rgb;
+    if (sh_degree > 0) {
+        rgb = max(rgb + vFade*eval_sh(vDir), 0.0);
+    }
+    if (srgb_to_linear) {
+        rgb = srgb_decode(rgb);
+    }
     float B = exp(A) * vColor.a;
-    fragColor = vec4(B * vColor.rgb, B);
+    if (near_fade.y > 0.0) {
+        // splats closer than z_near + distance fade out towards the near plane
+        B *= smoothstep(near_fade.x, near_fade.x + near_fade.y, vDepth);
+    }
+    if (vIndex == hovered) {
+        // tint the hovered splat and outline its footprint
+        rgb = mix(rgb, HIGHLIGHT, 0.4);
+        if (A < -3.0) {
+            rgb = HIGHLIGHT;
+            B = max(B, 0.8);
+        }
+    }
+    B *= u_global_alpha;
+    fragColor = premultiply ? vec4(B * rgb, B) : vec4(rgb, B);
+    // 0.5 at the focal plane, so that the 8-bit target is most precise around it
+    float d = vDepth/(vDepth + depth_focus);
+    fragDepth = premultiply ? vec4(B * d, 0.0, 0.0, B) : vec4(d, 0.0, 0.0, B);
 }
@@ -9,20 +9,106 @@ uniform vec2 viewport;
 uniform vec2 htan_fov;
 uniform vec3 cam_pos;
 uniform float splat_scale;
+uniform bool half_positions; // positions packed as half-floats, 3 texels per 2 splats
+uniform bool full_covariance; // covariance as f32 in 2 texels per splat instead of half-floats in 1
+uniform bool oriented_quads; // quads span the 3D principal axes instead of the 2D screen-space ones
+uniform float aa_dilation; // screen-space low-pass filter variance in pixels^2 (0 = off)
+uniform vec3 u_explode_center; // splats are pushed away from this point
+uniform float u_explode; // by this distance (0 = off)
+uniform bool aa_compensate; // scale the opacity to keep the dilated splat's energy (Mip-Splatting)
 
 in vec2 position;
 in int index;
 
 out vec4 vColor;
 out vec2 vPosition;
+flat out int vIndex; // for the SH lookup in gsplat.frag
+flat out vec3 vDir; // view direction to the splat center in world space
+flat out float vFade;
+flat out float vDepth; // view-space depth of the splat center
+
+// any unit vector perpendicular to v
+vec3 perpendicular(vec3 v) {
+    return normalize(abs(v.x) > 0.9 ? cross(v, vec3(0.0, 1.0, 0.0)) : cross(v, vec3(1.0, 0.0, 0.0)));
+}
 
-void main () {
-    // 0x3ffu (1023 in decimal) masks the lower 10 bits of index
-    uint u = (uint(index) & 0x3ffu) << 1;
-    uint v = uint(index) >> 10;
+// unit eigenvector of the symmetric matrix A for a simple eigenvalue l
+// (the null space of A - l*I is perpendicular to its rows, i.e. parallel to the cross product of any two of them)
+vec3 eigenvector(mat3 A, float l) {
+    mat3 M = A - l*mat3(1.0);
+    vec3 c0 = cross(M[0], M[1]);
+    vec3 c1 = cross(M[0], M[2]);
+    vec3 c2 = cross(M[1], M[2]);
+    float d0 = dot(c0, c0), d1 = dot(c1, c1), d2 = dot(c2, c2);
+    vec3 c = d0 > d1 ? (d0 > d2 ? c0 : c2) : (d1 > d2 ? c1 : c2);
+    return normalize(c);
+}
+
+// two largest eigenvalues (descending) and their eigenvectors e1, e2 of the symmetric matrix A
+// cf. Smith, "Eigenvalues of a symmetric 3x3 matrix", Communications of the ACM, 1961
+vec2 principal_axes(mat3 A, out vec3 e1, out vec3 e2) {
+    float q = (A[0][0] + A[1][1] + A[2][2])/3.0;
+    float p1 = A[0][1]*A[0][1] + A[0][2]*A[0][2] + A[1][2]*A[1][2];
+    float p2 = (A[0][0] - q)*(A[0][0] - q) + (A[1][1] - q)*(A[1][1] - q) + (A[2][2] - q)*(A[2][2] - q) + 2.0*p1;
+    float p = sqrt(p2/6.0);
+    if (p <= 1e-6*abs(q)) { // isotropic, any basis will do
+        e1 = vec3(1.0, 0.0, 0.0);
+        e2 = vec3(0.0, 1.0, 0.0);
+        return vec2(q);
+    }
+    mat3 B = (A - q*mat3(1.0))/p;
+    float phi = acos(clamp(0.5*determinant(B), -1.0, 1.0))/3.0;
+    float l1 = q + 2.0*p*cos(phi);
+    float l3 = q + 2.0*p*cos(phi + 2.0943951023931953); // + 2*pi/3
+    float l2 = 3.0*q - l1 - l3;
+
+    float eps = 1e-4*l1;
+    if (l1 - l2 > eps) {
+        e1 = eigenvector(A, l1);
+        // l2 == l3: any direction perpendicular to e1
+        e2 = l2 - l3 > eps ? eigenvector(A, l2) : perpendicular(e1);
+    } else {
+        // l1 == l2: the plane perpendicular to e3
+        e1 = perpendicular(eigenvector(A, l3));
+        e2 = cross(eigenvector(A, l3), e1);
+    }
+    e2 = normalize(e2 - dot(e2, e1)*e1);
+    return max(vec2(l1, l2), 0.0);
+}
 
-    uvec3 pos = texelFetch(u_splat_texture, ivec2(u, v), 0).rgb;
-    vec3 center = uintBitsToFloat(pos); // splat pos in world space
+void main () {
+    uint i = uint(index);
+    vec3 center; // splat pos in world space
+    uint w = uint(textureSize(u_splat_texture, 0).x);
+    uint c = full_covariance ? 2u : 1u; // covariance texels per splat
+    uint t_cov; // first covariance texel
+    if (half_positions) {
+        // texel 3k:   half2(x0, y0), half2(z0, x1), half2(y1, z1)
+        // texel 3k+1: covariance and color of splat 2k, texel 3k+2: same for splat 2k+1
+        // (5 texels per 2 splats with full_covariance, each covariance taking 2)
+        uint t_pos = (1u + 2u*c)*(i >> 1);
+        t_cov = t_pos + 1u + c*(i & 1u);
+        uvec3 pos = texelFetch(u_splat_texture, ivec2(t_pos % w, t_pos / w), 0).rgb;
+        if ((i & 1u) == 0u) {
+            center = vec3(unpackHalf2x16(pos.x), unpackHalf2x16(pos.y).x);
+        } else {
+            center = vec3(unpackHalf2x16(pos.y).y, unpackHalf2x16(pos.z));
+        }
+    } else {
+        // position texel followed by the covariance texel(s)
+        uint t_pos = (1u + c)*i;
+        uvec3 pos = texelFetch(u_splat_texture, ivec2(t_pos % w, t_pos / w), 0).rgb;
+        center = uintBitsToFloat(pos);
+        t_cov = t_pos + 1u;
+    }
+    ivec2 cov_uv = ivec2(t_cov % w, t_cov / w);
+    if (u_explode > 0.0) {
+        vec3 d = center - u_explode_center;
+        float len = length(d);
+        if (len > 1e-6) {
+            center += u_explode*d/len;
+        }
+    }
     vec4 cam = view * vec4(center, 1.0);
     vec4 pos2d = projection * cam;
 
@@ -32,17 +118,50 @@ void main () {
         return;
     }
 
-    uvec4 cov = texelFetch(u_splat_texture, ivec2(u | 1u, v), 0);
+    uvec4 cov = texelFetch(u_splat_texture, cov_uv, 0);
     // cf. Eq.29 of https://www.cs.umd.edu/~zwicker/publications/EWASplatting-TVCG02.pdf
-    vec2 u1 = unpackHalf2x16(cov.x); // a, b
-    vec2 u2 = unpackHalf2x16(cov.y); // c, d
-    vec2 u3 = unpackHalf2x16(cov.z); // e, f
     // eq.24, symmetric matrix, R * S * S^T * R^T
-    mat3 Vrk = mat3(
-        u1.x, u1.y, u2.x,
-        u1.y, u2.y, u3.x,
-        u2.x, u3.x, u3.y
-    );
+    mat3 Vrk;
+    if (full_covariance) {
+        uint t = t_cov + 1u;
+        vec3 abc = uintBitsToFloat(cov.xyz);
+        vec3 def = uintBitsToFloat(texelFetch(u_splat_texture, ivec2(t % w, t / w), 0).xyz);
+        Vrk = mat3(
+            abc.x, abc.y, abc.z,
+            abc.y, def.x, def.y,
+            abc.z, def.y, def.z
+        );
+    } else {
+        vec2 u1 = unpackHalf2x16(cov.x); // a, b
+        vec2 u2 = unpackHalf2x16(cov.y); // c, d
+        vec2 u3 = unpackHalf2x16(cov.z); // e, f
+        Vrk = mat3(
+            u1.x, u1.y, u2.x,
+            u1.y, u2.y, u3.x,
+            u2.x, u3.x, u3.y
+        );
+    }
+
+    vFade = clamp(pos2d.z/pos2d.w+1.0, 0.0, 1.0);
+    vColor = vFade * vec4(
+        (cov.w) & 0xffu, // 0xffu == 255 in decimal, masks the lowest 8 bits (value in [0, 255])
+        (cov.w >> 8) & 0xffu,
+        (cov.w >> 16) & 0xffu,
+        (cov.w >> 24) & 0xffu
+    ) / 255.0;
+    vPosition = position;
+    vIndex = index;
+    vDepth = pos2d.w;
+    vDir = normalize(center - cam_pos);
+
+    if (oriented_quads) {
+        // the quad lies in the plane of the two largest principal axes (an oriented disc)
+        vec3 e1, e2;
+        vec2 l = principal_axes(Vrk, e1, e2);
+        vec3 corner = center + splat_scale*(position.x*sqrt(2.0*l.x)*e1 + position.y*sqrt(2.0*l.y)*e2);
+        gl_Position = projection * view * vec4(corner, 1.0);
+        return;
+    }
 
     mat3 view3 = mat3(
         view[0].xyz,
@@ -84,6 +203,17 @@ void main () {
     // covariance matrix in ray space
     mat3 cov2d = transpose(T) * Vrk * T;
 
+    // low-pass filter against aliasing of sub-pixel splats, cf. Eq.9 of https://arxiv.org/abs/2311.16493
+    if (aa_dilation > 0.0) {
+        float det0 = cov2d[0][0]*cov2d[1][1] - cov2d[0][1]*cov2d[0][1];
+        cov2d[0][0] += aa_dilation;
+        cov2d[1][1] += aa_dilation;
+        if (aa_compensate) {
+            float det1 = cov2d[0][0]*cov2d[1][1] - cov2d[0][1]*cov2d[0][1];
+            vColor.a *= sqrt(max(det0, 0.0)/det1);
+        }
+    }
+
     float mid = 0.5*(cov2d[0][0] + cov2d[1][1]);
     float radius = length(vec2(0.5*(cov2d[0][0] - cov2d[1][1]), cov2d[0][1]));
     float lambda1 = mid + radius, lambda2 = mid - radius;
@@ -93,14 +223,6 @@ void main () {
     vec2 majorAxis = min(sqrt(2.0*lambda1), 1024.0) * diagonalVector;
     vec2 minorAxis = min(sqrt(2.0*lambda2), 1024.0) * vec2(diagonalVector.y, -diagonalVector.x);
 
-    vColor = clamp(pos2d.z/pos2d.w+1.0, 0.0, 1.0) * vec4(
-        (cov.w) & 0xffu, // 0xffu == 255 in decimal, masks the lowest 8 bits (value in [0, 255])
-        (cov.w >> 8) & 0xffu,
-        (cov.w >> 16) & 0xffu,
-        (cov.w >> 24) & 0xffu
-    ) / 255.0;
-    vPosition = position;
-
     vec2 vCenter = vec2(pos2d) / pos2d.w;
 
     vec2 major = (position.x*majorAxis) / viewport;
@@ -0,0 +1,21 @@
+#version 300 es
+precision highp float;
+precision highp int;
+
+// picking variant of gsplat.frag: writes the splat index instead of the color (cf. SplatIdGLSL)
+uniform float min_alpha; // fainter fragments don't count as a hit
+
+in vec4 vColor;
+in vec2 vPosition;
+flat in int vIndex;
+flat in float vDepth;
+
+out uint fragId;
+
+void main () {
+    float A = -dot(vPosition, vPosition);
+    if (A < -4.0 || exp(A) * vColor.a < min_alpha) discard;
+    // the nearest splat wins regardless of the draw order (view-space depth mapped to [0, 1))
+    gl_FragDepth = vDepth/(vDepth + 1.0);
+    fragId = uint(vIndex) + 1u; // 0 = no splat
+}
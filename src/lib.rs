@@ -3,14 +3,22 @@
     unused_variables,
     dead_code,
 )]
-#![feature(slice_as_array)]
 
 use wasm_bindgen::prelude::*;
 
 mod utils;
+mod error;
 mod scene;
 mod renderer;
 mod spz;
+mod editor;
+mod api;
+mod splatv;
+mod thumbnail;
+mod lod;
+mod pool;
+#[cfg(feature = "webgpu_sort")]
+mod gpusort;
 
 
 #[wasm_bindgen(start)]
@@ -0,0 +1,550 @@
+use std::{
+    rc::Rc,
+    cell::{Cell, RefCell},
+    sync::{Arc, Mutex, atomic::AtomicBool},
+};
+use bus::Bus;
+
+use crate::log; // macro import
+use crate::utils::*;
+use crate::scene::{Scene, DepthSink, MirrorAxes, PackSettings, aabb_in_frustum, fetch_bytes, fetch_range};
+use crate::error::GauzillaError;
+
+
+const MAX_DEPTH: usize = 16;
+pub const NODE_SPLATS: usize = 4096; // splats kept by each interior node
+const NO_CHILD: u32 = u32::MAX;
+const LOD_MAGIC: &[u8; 4] = b"GZLD";
+const LOD_VERSION: u32 = 1;
+const LOD_HEADER_BYTES: usize = 16; // magic, version, node count, splat count
+const LOD_NODE_BYTES: usize = 64; // min, max, children, start, count
+const MAX_IN_FLIGHT: usize = 4; // node downloads at a time
+
+
+/// A cell of the octree
+/// Holds the most important splats of its subtree that none of its ancestors hold,
+/// so drawing a node together with its ancestors gives a coarse version of its region
+#[derive(Clone, Debug, PartialEq)]
+pub struct OctreeNode {
+    pub min: [f32; 3], // bounds of the cell
+    pub max: [f32; 3],
+    pub children: [u32; 8], // node indices (NO_CHILD if empty), octant bits: x=1, y=2, z=4
+    pub start: u32, // first entry of the node's splats in Octree::indices
+    pub count: u32,
+}
+
+
+/// Octree over the splat centers for view-dependent level of detail
+/// Built over a loaded scene, it only limits what gets sorted and drawn; a .lod scene is streamed
+/// node by node instead (see [LodStream]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Octree {
+    pub nodes: Vec<OctreeNode>, // nodes[0] is the root
+    pub indices: Vec<u32>, // splat indices grouped by node
+}
+impl Octree {
+    /// Builds the octree over the splat centers of the scene
+    /// node_splats: number of splats kept by each interior node
+    pub fn build(scene: &Scene, node_splats: usize) -> Self {
+        let mut octree = Self {
+            nodes: Vec::<OctreeNode>::new(),
+            indices: Vec::<u32>::with_capacity(scene.splat_count),
+        };
+        let (min, max) = match scene.bbox {
+            Some(bbox) if scene.splat_count > 0 => bbox,
+            _ => return octree,
+        };
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(scene.buffer.as_slice());
+
+        // most important first, so each node keeps the head of its list
+        let mut splats: Vec<u32> = (0..scene.splat_count as u32).collect();
+        if scene.importance_rank.len() == scene.splat_count {
+            splats.sort_by_key(|&i| scene.importance_rank[i as usize]);
+        }
+
+        octree.build_node(f_buffer, splats, [min.x, min.y, min.z], [max.x, max.y, max.z], node_splats.max(1), 0);
+        log!("Octree::build(): {} nodes over {} splats", octree.nodes.len(), octree.indices.len());
+
+        octree
+    }
+
+
+    fn build_node(&mut self, f_buffer: &[f32], splats: Vec<u32>, min: [f32; 3], max: [f32; 3], node_splats: usize, depth: usize) -> u32 {
+        let node = self.nodes.len();
+        let own = if depth + 1 >= MAX_DEPTH { splats.len() } else { splats.len().min(node_splats) };
+        self.nodes.push(OctreeNode {
+            min,
+            max,
+            children: [NO_CHILD; 8],
+            start: self.indices.len() as u32,
+            count: own as u32,
+        });
+        self.indices.extend_from_slice(&splats[..own]);
+
+        // the rest goes down to the octants, keeping the importance order
+        let center = [0.5*(min[0] + max[0]), 0.5*(min[1] + max[1]), 0.5*(min[2] + max[2])];
+        let mut octants = vec![Vec::<u32>::new(); 8];
+        for &i in &splats[own..] {
+            let index_f = 8*i as usize;
+            let octant =
+                (f_buffer[index_f + 0] >= center[0]) as usize |
+                ((f_buffer[index_f + 1] >= center[1]) as usize) << 1 |
+                ((f_buffer[index_f + 2] >= center[2]) as usize) << 2;
+            octants[octant].push(i);
+        }
+        for (octant, splats) in octants.into_iter().enumerate() {
+            if splats.is_empty() {
+                continue;
+            }
+            let mut child_min = min;
+            let mut child_max = max;
+            for axis in 0..3 {
+                if octant & (1 << axis) == 0 {
+                    child_max[axis] = center[axis];
+                } else {
+                    child_min[axis] = center[axis];
+                }
+            }
+            let child = self.build_node(f_buffer, splats, child_min, child_max, node_splats, depth + 1);
+            self.nodes[node].children[octant] = child;
+        }
+
+        node as u32
+    }
+
+
+    /// Selects the nodes to draw for view_proj
+    /// Nodes outside of the frustum are skipped with their subtrees, and a node's children are only
+    /// visited while the node covers more than threshold of the viewport height
+    /// Returns a per-splat mask of the selected splats
+    pub fn select(&self, view_proj: &[f32], threshold: f32, splat_count: usize) -> Vec<bool> {
+        let mut mask = vec![false; splat_count];
+        for node in self.select_nodes(view_proj, threshold) {
+            let node = &self.nodes[node as usize];
+            for &i in &self.indices[node.start as usize..(node.start + node.count) as usize] {
+                mask[i as usize] = true;
+            }
+        }
+        mask
+    }
+
+
+    /// Same as select(), returning the selected nodes (parents before their children) instead
+    pub fn select_nodes(&self, view_proj: &[f32], threshold: f32) -> Vec<u32> {
+        let mut selected = Vec::<u32>::new();
+        if self.nodes.is_empty() {
+            return selected;
+        }
+
+        // world units -> NDC along y (the rows of the view matrix are unit vectors)
+        let scale = (view_proj[1]*view_proj[1] + view_proj[5]*view_proj[5] + view_proj[9]*view_proj[9]).sqrt();
+
+        let mut stack = vec![0_u32];
+        while let Some(k) = stack.pop() {
+            let node = &self.nodes[k as usize];
+            if !aabb_in_frustum(node.min, node.max, view_proj) {
+                continue;
+            }
+            selected.push(k);
+
+            let c = [0.5*(node.min[0] + node.max[0]), 0.5*(node.min[1] + node.max[1]), 0.5*(node.min[2] + node.max[2])];
+            let d = [node.max[0] - c[0], node.max[1] - c[1], node.max[2] - c[2]];
+            let radius = (d[0]*d[0] + d[1]*d[1] + d[2]*d[2]).sqrt();
+            let w = view_proj[3]*c[0] + view_proj[7]*c[1] + view_proj[11]*c[2] + view_proj[15];
+            // the NDC height is 2, so the share of the viewport covered by the diameter is radius*scale/w
+            let descend = w <= radius || radius*scale/w > threshold;
+            if descend {
+                stack.extend(node.children.iter().filter(|&&child| child != NO_CHILD));
+            }
+        }
+
+        selected
+    }
+
+
+    /// Serializes the nodes into a .lod file (little-endian):
+    /// "GZLD", version, node count, and splat count (u32 each), then per node min and max (3 f32 each),
+    /// children (8 u32), start, and count (u32 each)
+    /// start and count refer to the node-ordered .splat file written along with it (see [export_lod])
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::<u8>::with_capacity(LOD_HEADER_BYTES + LOD_NODE_BYTES*self.nodes.len());
+        bytes.extend_from_slice(LOD_MAGIC);
+        for v in [LOD_VERSION, self.nodes.len() as u32, self.indices.len() as u32] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        for node in self.nodes.iter() {
+            for v in node.min.iter().chain(node.max.iter()) {
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+            for v in node.children.iter().chain([node.start, node.count].iter()) {
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+
+    /// Parses a .lod file written by to_bytes()
+    /// The splats of the node-ordered .splat file are their own indices.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GauzillaError> {
+        if bytes.len() < LOD_HEADER_BYTES || &bytes[0..4] != LOD_MAGIC {
+            return Err(GauzillaError::UnsupportedFormat("not a .lod file".to_string()));
+        }
+        let u32_at = |o: usize| u32::from_le_bytes(bytes[o..o + 4].try_into().unwrap());
+        let f32_at = |o: usize| f32::from_le_bytes(bytes[o..o + 4].try_into().unwrap());
+        if u32_at(4) != LOD_VERSION {
+            return Err(GauzillaError::UnsupportedFormat(format!(".lod version {}", u32_at(4))));
+        }
+        let (node_count, splat_count) = (u32_at(8) as usize, u32_at(12));
+        if bytes.len() < LOD_HEADER_BYTES + LOD_NODE_BYTES*node_count {
+            return Err(GauzillaError::Truncated(format!("expected {} nodes", node_count)));
+        }
+
+        let mut nodes = Vec::<OctreeNode>::with_capacity(node_count);
+        for k in 0..node_count {
+            let o = LOD_HEADER_BYTES + LOD_NODE_BYTES*k;
+            let node = OctreeNode {
+                min: [0, 1, 2].map(|a| f32_at(o + 4*a)),
+                max: [3, 4, 5].map(|a| f32_at(o + 4*a)),
+                children: std::array::from_fn(|c| u32_at(o + 24 + 4*c)),
+                start: u32_at(o + 56),
+                count: u32_at(o + 60),
+            };
+            let bad_child = node.children.iter().any(|&c| c != NO_CHILD && c as usize >= node_count);
+            if bad_child || node.start.checked_add(node.count).map_or(true, |end| end > splat_count) {
+                return Err(GauzillaError::DecodeFailed(format!("node {} is out of range", k)));
+            }
+            nodes.push(node);
+        }
+
+        Ok(Self {
+            nodes,
+            indices: (0..splat_count).collect(),
+        })
+    }
+}
+
+
+/// Builds the octree over a scene (as displayed, i.e., mirrored and scaled) and returns the .lod file
+/// and the .splat file with the splats of each node stored contiguously, so that a node can be
+/// streamed with one range request (see [LodStream])
+/// node_splats: number of splats kept by each interior node
+pub fn export_lod(scene: &Scene, node_splats: usize) -> (Vec<u8>, Vec<u8>) {
+    let octree = Octree::build(scene, node_splats);
+    // nodes[k].start already indexes octree.indices, which becomes the order of the .splat file
+    let mut splat = Vec::<u8>::with_capacity(32*octree.indices.len());
+    for &i in octree.indices.iter() {
+        splat.extend_from_slice(&scene.buffer[32*i as usize..32*(i as usize + 1)]);
+    }
+    log!("export_lod(): {} nodes, {} splats", octree.nodes.len(), octree.indices.len());
+    (octree.to_bytes(), splat)
+}
+
+
+/// Returns view_proj applied after the mirror and world scale of a scene,
+/// i.e., the view_proj for positions in source file coordinates
+pub fn source_view_proj(view_proj: &[f32], mirror: MirrorAxes, world_scale: f32) -> [f32; 16] {
+    let mut m: [f32; 16] = view_proj.try_into().unwrap();
+    for (axis, mirrored) in [mirror.x, mirror.y, mirror.z].into_iter().enumerate() {
+        let s = if mirrored { -world_scale } else { world_scale };
+        m[4*axis..4*axis + 4].iter_mut().for_each(|v| *v *= s); // column-major: column axis
+    }
+    m
+}
+
+
+/// Streams a .lod scene: the octree comes from the .lod file, and the splats of the nodes selected for
+/// the current view are downloaded from the node-ordered .splat file next to it with range requests,
+/// coarse nodes first, and appended to the scene as they arrive
+/// Nodes stay resident once downloaded.
+pub struct LodStream {
+    splat_url: String,
+    octree: Octree, // over the whole file, in source file coordinates
+    slots: Vec<Option<u32>>, // per node: first splat in the streamed scene (None while not downloaded)
+    requested: Vec<bool>,
+    in_flight: Rc<Cell<usize>>,
+    arrived: Rc<RefCell<Vec<(u32, Vec<u8>)>>>, // downloaded nodes not yet appended to the scene
+    error: Rc<RefCell<Option<String>>>,
+}
+impl LodStream {
+    pub fn new(url: &str, octree: Octree) -> Self {
+        let n = octree.nodes.len();
+        Self {
+            splat_url: Self::splat_url(url),
+            octree,
+            slots: vec![None; n],
+            requested: vec![false; n],
+            in_flight: Rc::new(Cell::new(0)),
+            arrived: Rc::new(RefCell::new(Vec::<(u32, Vec<u8>)>::new())),
+            error: Rc::new(RefCell::new(None)),
+        }
+    }
+
+
+    pub fn is_lod_url(url: &str) -> bool {
+        url.split('?').next().unwrap_or("").to_lowercase().ends_with(".lod")
+    }
+
+
+    /// URL of the .splat file next to a .lod file (the query is kept)
+    fn splat_url(url: &str) -> String {
+        let (path, query) = url.split_once('?').map_or((url, None), |(p, q)| (p, Some(q)));
+        let path = format!("{}.splat", &path[..path.len() - ".lod".len().min(path.len())]);
+        match query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        }
+    }
+
+
+    /// Returns the scene to stream into (no splats yet) in the given formats, mirror, and world scale
+    pub fn empty_scene(&self, pack: &PackSettings) -> Scene {
+        let mut scene = Scene::new();
+        scene.half_positions = pack.half_positions;
+        scene.full_covariance = pack.full_covariance;
+        scene.max_sh_degree = pack.max_sh_degree;
+        scene.scale_clamp = pack.scale_clamp;
+        scene.source_format = "lod";
+        scene.set_mirror(pack.mirror);
+        scene.set_world_scale(pack.world_scale);
+        scene.octree = Some(Arc::new(self.resident_octree()));
+        scene
+    }
+
+
+    /// The octree over the splats appended to the scene so far
+    /// Nodes that haven't been downloaded are kept (without splats) so that their resident children are still reached.
+    fn resident_octree(&self) -> Octree {
+        let mut nodes = self.octree.nodes.clone();
+        let mut splat_count = 0;
+        for (node, slot) in nodes.iter_mut().zip(self.slots.iter()) {
+            match slot {
+                Some(slot) => {
+                    node.start = *slot;
+                    splat_count = splat_count.max(slot + node.count);
+                },
+                None => {
+                    node.start = 0;
+                    node.count = 0;
+                },
+            }
+        }
+        Octree {
+            nodes,
+            indices: (0..splat_count).collect(),
+        }
+    }
+
+
+    /// Starts downloading the nodes selected for view_proj (see [Octree::select_nodes]) that aren't resident yet
+    /// mirror and world_scale are the ones of the scene the nodes are appended to.
+    pub fn request(&mut self, view_proj: &[f32], threshold: f32, mirror: MirrorAxes, world_scale: f32) {
+        let view_proj = source_view_proj(view_proj, mirror, world_scale);
+        for node in self.octree.select_nodes(&view_proj, threshold) {
+            if self.in_flight.get() >= MAX_IN_FLIGHT {
+                break;
+            }
+            let k = node as usize;
+            if self.requested[k] {
+                continue;
+            }
+            self.requested[k] = true;
+            self.in_flight.set(self.in_flight.get() + 1);
+
+            let (start, end) = (32*self.octree.nodes[k].start as usize, 32*(self.octree.nodes[k].start + self.octree.nodes[k].count) as usize);
+            let url = self.splat_url.clone();
+            let in_flight = self.in_flight.clone();
+            let arrived = self.arrived.clone();
+            let error = self.error.clone();
+            execute_future(async move {
+                match fetch_range(&url, start, end).await {
+                    Ok(bytes) => arrived.borrow_mut().push((node, bytes)),
+                    Err(e) => {
+                        log!("LodStream::request(): ERROR: node {}: {}", node, e);
+                        *error.borrow_mut() = Some(format!("node {}: {}", node, e));
+                    },
+                }
+                in_flight.set(in_flight.get() - 1);
+            });
+        }
+    }
+
+
+    /// Returns the error of a failed node download (once)
+    pub fn take_error(&self) -> Option<String> {
+        self.error.borrow_mut().take()
+    }
+
+
+    /// Appends the nodes downloaded since the last call to (a copy of) scene
+    /// Returns None if no node has arrived, otherwise the new scene and the texture rows to re-upload
+    /// (None: the whole texture)
+    pub fn append_arrived(&mut self, scene: &Scene) -> Option<(Scene, Option<Vec<usize>>)> {
+        let arrived = std::mem::take(&mut *self.arrived.borrow_mut());
+        if arrived.is_empty() {
+            return None;
+        }
+        let mut rows = Vec::<u8>::new();
+        let mut slot = scene.splat_count as u32;
+        for (node, bytes) in arrived.iter() {
+            self.slots[*node as usize] = Some(slot);
+            slot += (bytes.len() / 32) as u32;
+            rows.extend_from_slice(bytes);
+        }
+        let (mut next, tex_rows) = scene.appended(&rows);
+        next.octree = Some(Arc::new(self.resident_octree()));
+        log!("LodStream::append_arrived(): {} nodes, {} splats resident", arrived.len(), next.splat_count);
+        Some((next, tex_rows))
+    }
+}
+
+
+/// Downloads the octree of a .lod URL in the background and hands it over via bus_lod
+/// A failure is reported via the error window.
+pub fn open_lod_in_background(
+    url: String,
+    bus_lod: Rc<RefCell<Bus<Octree>>>,
+    error_flag: Arc<AtomicBool>,
+    error_msg: Arc<Mutex<String>>,
+) {
+    execute_future(async move {
+        let result = match fetch_bytes(&url).await {
+            Ok(bytes) => Octree::from_bytes(&bytes),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(octree) => {
+                log!("open_lod_in_background(): {} nodes over {} splats", octree.nodes.len(), octree.indices.len());
+                let _ = bus_lod.borrow_mut().try_broadcast(octree);
+            },
+            Err(e) => {
+                log!("open_lod_in_background(): ERROR: {}", e);
+                set_error_for_egui(&error_flag, &error_msg, format!("ERROR: could not open the .lod file: {}", e));
+            },
+        }
+    });
+}
+
+
+/// Drops the splats that are not selected by the LOD from the sort result before passing it on
+pub struct LodFilter<'a, S: DepthSink> {
+    pub mask: &'a [bool],
+    pub sink: &'a mut S,
+}
+impl<'a, S: DepthSink> DepthSink for LodFilter<'a, S> {
+    fn submit(&mut self, mut depth_index: Vec<u32>) {
+        depth_index.retain(|&i| self.mask.get(i as usize).copied().unwrap_or(true));
+        self.sink.submit(depth_index);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_octree_lod() {
+        let n = 100;
+        let scene = Scene::test_scene(n);
+        let octree = Octree::build(&scene, 4);
+        let mut indices = octree.indices.clone();
+        indices.sort();
+        assert_eq!(indices, (0..n as u32).collect::<Vec<u32>>());
+        assert_eq!(octree.nodes[0].count, 4);
+
+        // orthographic view of the whole scene (w = 1)
+        let mut view_proj = [0_f32; 16];
+        view_proj[0] = 1.0/100.0;
+        view_proj[5] = 1.0/200.0;
+        view_proj[10] = 1.0/300.0;
+        view_proj[15] = 1.0;
+        assert!(octree.select(&view_proj, 0.0, n).iter().all(|&b| b));
+        // the root covers less than the threshold, so only its splats are selected
+        let coarse = octree.select(&view_proj, 10.0, n);
+        assert_eq!(coarse.iter().filter(|&&b| b).count(), 4);
+    }
+
+    #[test]
+    fn test_lod_file() {
+        let n = 100;
+        let scene = Scene::test_scene(n);
+        let (lod, splat) = export_lod(&scene, 4);
+        let built = Octree::build(&scene, 4);
+        let octree = Octree::from_bytes(&lod).unwrap();
+        assert_eq!(octree.nodes, built.nodes);
+        assert_eq!(octree.indices, (0..n as u32).collect::<Vec<u32>>());
+
+        // the splats of each node are contiguous in the .splat file
+        assert_eq!(splat.len(), 32*n);
+        for node in built.nodes.iter() {
+            let start = node.start as usize;
+            for (k, &i) in built.indices[start..start + node.count as usize].iter().enumerate() {
+                assert_eq!(splat[32*(start + k)..32*(start + k + 1)], scene.buffer[32*i as usize..32*(i as usize + 1)]);
+            }
+        }
+
+        assert!(matches!(Octree::from_bytes(&lod[..lod.len() - 1]), Err(GauzillaError::Truncated(_))));
+        assert!(matches!(Octree::from_bytes(&splat), Err(GauzillaError::UnsupportedFormat(_))));
+        let mut bad = lod.clone();
+        bad[LOD_HEADER_BYTES + 60..LOD_HEADER_BYTES + 64].copy_from_slice(&1000_u32.to_le_bytes()); // root count
+        assert!(matches!(Octree::from_bytes(&bad), Err(GauzillaError::DecodeFailed(_))));
+    }
+
+    #[test]
+    fn test_lod_stream() {
+        let n = 100;
+        let (lod, splat) = export_lod(&Scene::test_scene(n), 4);
+        let mut stream = LodStream::new("https://a.com/s.lod?v=1", Octree::from_bytes(&lod).unwrap());
+        assert!(LodStream::is_lod_url("https://a.com/s.LOD?v=1"));
+        assert_eq!(stream.splat_url, "https://a.com/s.splat?v=1");
+        let nodes = stream.octree.nodes.clone();
+        let node_bytes = |k: u32| {
+            let node = &nodes[k as usize];
+            splat[32*node.start as usize..32*(node.start + node.count) as usize].to_vec()
+        };
+
+        // a child arrives before the root
+        let child = *nodes[0].children.iter().find(|&&c| c != NO_CHILD).unwrap();
+        let (root_count, child_count) = (nodes[0].count, nodes[child as usize].count);
+        let arrived = vec![(child, node_bytes(child)), (0, node_bytes(0))];
+        *stream.arrived.borrow_mut() = arrived;
+        let empty = stream.empty_scene(&PackSettings::default());
+        assert_eq!(empty.splat_count, 0);
+        let (scene, tex_rows) = stream.append_arrived(&empty).unwrap();
+        assert!(tex_rows.is_none()); // the first texture
+        assert_eq!(scene.splat_count, (root_count + child_count) as usize);
+        let resident = scene.octree.clone().unwrap();
+        assert_eq!((resident.nodes[child as usize].start, resident.nodes[0].start), (0, child_count));
+        assert!(stream.append_arrived(&scene).is_none());
+
+        // orthographic view of the whole scene (w = 1): everything resident is selected
+        let mut view_proj = [0_f32; 16];
+        view_proj[0] = 1.0/100.0;
+        view_proj[5] = 1.0/200.0;
+        view_proj[10] = 1.0/300.0;
+        view_proj[15] = 1.0;
+        assert!(resident.select(&view_proj, 0.0, scene.splat_count).iter().all(|&b| b));
+
+        // the next node goes into the spare rows of the texture
+        let other = *nodes[0].children.iter().filter(|&&c| c != NO_CHILD).nth(1).unwrap();
+        stream.arrived.borrow_mut().push((other, node_bytes(other)));
+        let (next, tex_rows) = stream.append_arrived(&scene).unwrap();
+        assert!(!tex_rows.unwrap().is_empty());
+        assert_eq!(next.tex_height, scene.tex_height);
+        assert_eq!(next.buffer[..32*scene.splat_count], scene.buffer[..]);
+    }
+
+    #[test]
+    fn test_source_view_proj() {
+        let mut view_proj = [0_f32; 16];
+        for k in 0..4 {
+            view_proj[5*k] = 1.0;
+        }
+        let m = source_view_proj(&view_proj, MirrorAxes { x: true, y: false, z: false }, 2.0);
+        // source (1, 2, 3) is displayed at (-2, 4, 6)
+        let p = [1.0_f32, 2.0, 3.0, 1.0];
+        let q: Vec<f32> = (0..4).map(|r| (0..4).map(|c| m[4*c + r]*p[c]).sum()).collect();
+        assert_eq!(q, vec![-2.0, 4.0, 6.0, 1.0]);
+    }
+}
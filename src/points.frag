@@ -0,0 +1,18 @@
+#version 300 es
+precision highp float;
+
+uniform bool srgb_to_linear; // decode the splat colors to linear (cf. gsplat.frag)
+
+in vec4 vColor;
+
+out vec4 fragColor;
+
+vec3 srgb_decode(vec3 c) {
+    return mix(c/12.92, pow((c + 0.055)/1.055, vec3(2.4)), step(0.04045, c));
+}
+
+void main () {
+    // opaque regardless of the splat opacity, so that every center stays visible
+    vec3 rgb = srgb_to_linear ? srgb_decode(vColor.rgb) : vColor.rgb;
+    fragColor = vec4(rgb, 1.0);
+}
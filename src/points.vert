@@ -0,0 +1,48 @@
+#version 300 es
+precision highp float;
+precision highp int;
+
+uniform highp usampler2D u_splat_texture;
+uniform mat4 projection, view;
+uniform bool half_positions; // same texture layouts as in gsplat.vert
+uniform bool full_covariance;
+uniform float point_size; // in pixels
+uniform vec3 u_explode_center;
+uniform float u_explode;
+
+out vec4 vColor;
+
+// one point per splat, indexed by gl_VertexID (no vertex attributes)
+void main () {
+    uint i = uint(gl_VertexID);
+    vec3 center;
+    uint w = uint(textureSize(u_splat_texture, 0).x);
+    uint c = full_covariance ? 2u : 1u;
+    uint t_cov;
+    if (half_positions) {
+        uint t_pos = (1u + 2u*c)*(i >> 1);
+        t_cov = t_pos + 1u + c*(i & 1u);
+        uvec3 pos = texelFetch(u_splat_texture, ivec2(t_pos % w, t_pos / w), 0).rgb;
+        if ((i & 1u) == 0u) {
+            center = vec3(unpackHalf2x16(pos.x), unpackHalf2x16(pos.y).x);
+        } else {
+            center = vec3(unpackHalf2x16(pos.y).y, unpackHalf2x16(pos.z));
+        }
+    } else {
+        uint t_pos = (1u + c)*i;
+        center = uintBitsToFloat(texelFetch(u_splat_texture, ivec2(t_pos % w, t_pos / w), 0).rgb);
+        t_cov = t_pos + 1u;
+    }
+    if (u_explode > 0.0) {
+        vec3 d = center - u_explode_center;
+        float len = length(d);
+        if (len > 1e-6) {
+            center += u_explode*d/len;
+        }
+    }
+
+    uint rgba = texelFetch(u_splat_texture, ivec2(t_cov % w, t_cov / w), 0).w;
+    vColor = vec4(rgba & 0xffu, (rgba >> 8) & 0xffu, (rgba >> 16) & 0xffu, (rgba >> 24) & 0xffu) / 255.0;
+    gl_Position = projection * view * vec4(center, 1.0);
+    gl_PointSize = point_size;
+}
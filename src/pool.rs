@@ -0,0 +1,126 @@
+use std::{
+    sync::mpsc::{channel, Sender},
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+};
+use wasm_thread as thread;
+
+use crate::log; // macro import
+
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+
+/// Threads started once and reused for the chunks of every depth sort
+/// wasm_thread starts a new Web Worker (and instantiates the module in it) per spawn,
+/// which costs more than the sort of a mid-sized scene, so the workers are kept alive instead.
+pub struct WorkerPool {
+    jobs: Vec<Sender<Job>>, // one queue per worker
+}
+impl WorkerPool {
+    pub fn new(n_threads: usize) -> Self {
+        log!("WorkerPool::new(): n_threads={}", n_threads);
+        let jobs = (0..n_threads)
+            .map(|_| {
+                let (tx, rx) = channel::<Job>();
+                thread::spawn(move || {
+                    while let Ok(job) = rx.recv() {
+                        job();
+                    }
+                });
+                tx
+            })
+            .collect();
+        Self { jobs }
+    }
+
+
+    /// Number of worker threads
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+
+    /// True if map() runs on the calling thread
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+
+    /// Runs f on each item on the workers (item k on worker k % len()) and returns the results in item order
+    /// Blocks until all items are done, so call it from a worker (e.g. the sorter thread) only.
+    pub fn map<T, R, F>(&self, items: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> R + Sync,
+    {
+        if self.jobs.is_empty() {
+            return items.into_iter().map(f).collect();
+        }
+
+        let n = items.len();
+        let (tx, rx) = channel::<(usize, std::thread::Result<R>)>();
+        let f = &f;
+        for (k, item) in items.into_iter().enumerate() {
+            let tx = tx.clone();
+            let job: Box<dyn FnOnce() + Send + '_> = Box::new(move || {
+                let result = catch_unwind(AssertUnwindSafe(|| f(item)));
+                let _ = tx.send((k, result));
+            });
+            // SAFETY: map() doesn't return before it has the results of all queued jobs, so the borrowed
+            // items and f outlive them. The jobs catch their panics, and a lost worker aborts instead of
+            // unwinding, as that would return while queued jobs still borrow from this frame.
+            let job: Job = unsafe { std::mem::transmute(job) };
+            if self.jobs[k % self.jobs.len()].send(job).is_err() {
+                Self::abort("worker has exited");
+            }
+        }
+        drop(tx);
+
+        let mut results: Vec<Option<std::thread::Result<R>>> = (0..n).map(|_| None).collect();
+        for _ in 0..n {
+            let (k, result) = match rx.recv() {
+                Ok(received) => received,
+                Err(_) => Self::abort("worker has exited with a job pending"),
+            };
+            results[k] = Some(result);
+        }
+        results.into_iter()
+            .map(|result| match result.unwrap() {
+                Ok(r) => r,
+                Err(e) => resume_unwind(e),
+            })
+            .collect()
+    }
+
+
+    /// Ends the process, as map() can't unwind while its jobs may still borrow from its frame
+    fn abort(reason: &str) -> ! {
+        log!("WorkerPool::map(): ERROR: {}", reason);
+        std::process::abort()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_pool_map() {
+        let pool = WorkerPool::new(3);
+        let mut data: Vec<u32> = (0..1000).collect();
+        let offset = 7; // borrowed by the jobs
+        let sums = pool.map(data.chunks_mut(64).collect(), |chunk| {
+            chunk.iter_mut().for_each(|v| *v += offset);
+            chunk.iter().sum::<u32>()
+        });
+        assert_eq!(sums.len(), 16);
+        assert_eq!(sums.iter().sum::<u32>(), (0..1000).map(|v| v + offset).sum::<u32>());
+        assert_eq!(data[999], 1006);
+
+        // reused, and without workers on the calling thread
+        assert_eq!(pool.map(vec![1, 2, 3], |v| 2*v), vec![2, 4, 6]);
+        assert_eq!(WorkerPool::new(0).map(vec![1, 2, 3], |v| 2*v), vec![2, 4, 6]);
+    }
+}
@@ -2,11 +2,171 @@
 precision highp float;
 
 uniform sampler2D u_screen_texture;
+uniform float u_dither; // ordered dither amplitude in 8-bit steps (0 = off)
+uniform bool u_linear_to_srgb; // encode the (linear, premultiplied) texture to sRGB for display
+uniform int u_background_mode; // 0: none (already in the texture), 1: vertical gradient, 2: image
+uniform vec4 u_gradient_top; // straight alpha, sRGB
+uniform vec4 u_gradient_bottom;
+uniform sampler2D u_background_texture; // top row first
+uniform float u_background_aspect; // image aspect ratio / viewport aspect ratio
+uniform sampler2D u_depth_texture; // premultiplied encoded splat depth (cf. gsplat.frag)
+uniform float u_dof_blur; // largest depth-of-field blur radius in texels (0 = off)
+uniform vec2 u_uv_scale; // same as in quad.vert
+uniform int u_tone_map; // 0: linear, 1: Reinhard, 2: ACES (cf. ToneMap)
+uniform float u_exposure; // in stops
+uniform bool u_fxaa;
 
 in vec2 texcoords;
+in vec2 screen_uv;
 
 out vec4 fragColor;
 
+// 4x4 Bayer matrix threshold in [-0.5, 0.5)
+float bayer4(ivec2 p) {
+    const int m[16] = int[16](
+         0,  8,  2, 10,
+        12,  4, 14,  6,
+         3, 11,  1,  9,
+        15,  7, 13,  5
+    );
+    return (float(m[(p.y & 3)*4 + (p.x & 3)]) + 0.5) / 16.0 - 0.5;
+}
+
+// sRGB transfer function (IEC 61966-2-1)
+vec3 srgb_encode(vec3 c) {
+    return mix(12.92*c, 1.055*pow(c, vec3(1.0/2.4)) - 0.055, step(0.0031308, c));
+}
+
+vec3 srgb_decode(vec3 c) {
+    return mix(c/12.92, pow((c + 0.055)/1.055, vec3(2.4)), step(0.04045, c));
+}
+
+// blur amount in [0, 1] at uv: 0 at the focal plane, 1 infinitely far away (or on the background)
+float circle_of_confusion(vec2 uv) {
+    vec4 d = texture(u_depth_texture, uv);
+    if (d.a <= 0.0) {
+        return 1.0;
+    }
+    // d = depth/(depth + focus) -> |depth - focus|/depth
+    float t = clamp(d.r/d.a, 1e-3, 1.0);
+    return min(abs(2.0*t - 1.0)/t, 1.0);
+}
+
+const int DOF_TAPS = 24;
+
+// gathers the screen texture over a disc (golden angle spiral) whose radius follows the depth,
+// samples only count where their own blur reaches the center, so sharp splats don't bleed into the blur behind them
+vec4 depth_of_field(vec2 uv) {
+    vec2 texel = 1.0/vec2(textureSize(u_screen_texture, 0));
+    float radius = u_dof_blur*circle_of_confusion(uv);
+    vec4 sum = texture(u_screen_texture, uv);
+    float weight = 1.0;
+    for (int i = 1; i < DOF_TAPS; i++) {
+        float r = radius*sqrt(float(i)/float(DOF_TAPS));
+        float angle = 2.39996323*float(i);
+        // stay within the rendered part of the texture
+        vec2 p = clamp(uv + r*vec2(cos(angle), sin(angle))*texel, 0.5*texel, u_uv_scale - 0.5*texel);
+        float w = clamp(u_dof_blur*circle_of_confusion(p) - r + 1.0, 0.0, 1.0);
+        sum += w*texture(u_screen_texture, p);
+        weight += w;
+    }
+    return sum/weight;
+}
+
+const float FXAA_SPAN_MAX = 8.0;
+const float FXAA_REDUCE_MUL = 1.0/8.0;
+const float FXAA_REDUCE_MIN = 1.0/128.0;
+
+float luma(vec4 c) {
+    return dot(c.rgb, vec3(0.299, 0.587, 0.114));
+}
+
+// screen texture within its rendered part
+vec4 screen(vec2 uv, vec2 texel) {
+    return texture(u_screen_texture, clamp(uv, 0.5*texel, u_uv_scale - 0.5*texel));
+}
+
+// cf. Lottes, "FXAA", NVIDIA white paper, 2009 (the console variant: blur along the edge direction from 4 diagonal taps)
+vec4 fxaa(vec2 uv) {
+    vec2 texel = 1.0/vec2(textureSize(u_screen_texture, 0));
+    vec4 center = screen(uv, texel);
+    float nw = luma(screen(uv + vec2(-1.0, -1.0)*texel, texel));
+    float ne = luma(screen(uv + vec2(1.0, -1.0)*texel, texel));
+    float sw = luma(screen(uv + vec2(-1.0, 1.0)*texel, texel));
+    float se = luma(screen(uv + vec2(1.0, 1.0)*texel, texel));
+    float m = luma(center);
+    float luma_min = min(m, min(min(nw, ne), min(sw, se)));
+    float luma_max = max(m, max(max(nw, ne), max(sw, se)));
+
+    vec2 dir = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    float reduce = max(0.25*FXAA_REDUCE_MUL*(nw + ne + sw + se), FXAA_REDUCE_MIN);
+    float scale = 1.0/(min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir*scale, -FXAA_SPAN_MAX, FXAA_SPAN_MAX)*texel;
+
+    vec4 a = 0.5*(screen(uv - dir/6.0, texel) + screen(uv + dir/6.0, texel));
+    vec4 b = 0.5*a + 0.25*(screen(uv - 0.5*dir, texel) + screen(uv + 0.5*dir, texel));
+    // the wider blur overshoots the local contrast on thin features
+    float luma_b = luma(b);
+    return (luma_b < luma_min || luma_b > luma_max) ? a : b;
+}
+
+// exposure and tone curve on linear color
+vec3 tone_map(vec3 c) {
+    c *= exp2(u_exposure);
+    if (u_tone_map == 1) {
+        return c/(1.0 + c);
+    }
+    if (u_tone_map == 2) {
+        // cf. https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+        return clamp((c*(2.51*c + 0.03))/(c*(2.43*c + 0.59) + 0.14), 0.0, 1.0);
+    }
+    return c;
+}
+
+// straight-alpha sRGB background at the current pixel
+vec4 background() {
+    if (u_background_mode == 1) {
+        return mix(u_gradient_bottom, u_gradient_top, screen_uv.y);
+    }
+    // scale to cover the viewport, cropping the overhanging sides
+    vec2 uv = screen_uv - 0.5;
+    if (u_background_aspect > 1.0) {
+        uv.x /= u_background_aspect;
+    } else {
+        uv.y *= u_background_aspect;
+    }
+    uv += 0.5;
+    return texture(u_background_texture, vec2(uv.x, 1.0 - uv.y));
+}
+
 void main() {
-    fragColor = texture(u_screen_texture, texcoords);
+    vec4 color;
+    if (u_dof_blur > 0.0) {
+        color = depth_of_field(texcoords);
+    } else if (u_fxaa) {
+        color = fxaa(texcoords);
+    } else {
+        color = texture(u_screen_texture, texcoords);
+    }
+    if ((u_tone_map > 0 || u_exposure != 0.0) && color.a > 0.0) {
+        // the texture is premultiplied, and sRGB unless it is encoded below
+        vec3 c = color.rgb/color.a;
+        c = tone_map(u_linear_to_srgb ? c : srgb_decode(c));
+        color.rgb = (u_linear_to_srgb ? c : srgb_encode(c))*color.a;
+    }
+    if (u_background_mode > 0) {
+        // splats (premultiplied) over the background
+        vec4 bg = background();
+        if (u_linear_to_srgb) {
+            bg.rgb = srgb_decode(bg.rgb);
+        }
+        color += (1.0 - color.a)*vec4(bg.rgb*bg.a, bg.a);
+    }
+    if (u_linear_to_srgb && color.a > 0.0) {
+        color.rgb = srgb_encode(color.rgb/color.a)*color.a;
+    }
+    if (u_dither > 0.0) {
+        color.rgb += u_dither * bayer4(ivec2(gl_FragCoord.xy)) / 255.0;
+    }
+    fragColor = color;
 }
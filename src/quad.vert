@@ -1,10 +1,14 @@
 #version 300 es
 precision highp float;
 
+uniform vec2 u_uv_scale; // part of the texture that has been rendered to (render scale < 1)
+
 in vec3 position;
 out vec2 texcoords;
+out vec2 screen_uv; // [0, 1] over the viewport, origin at the bottom-left
 
 void main() {
     gl_Position = vec4(position, 1.0);
-    texcoords = 0.5*position.xy + 0.5;
+    screen_uv = 0.5*position.xy + 0.5;
+    texcoords = screen_uv*u_uv_scale;
 }
@@ -2,7 +2,8 @@
 use std::{
     sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
     rc::Rc,
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    collections::VecDeque,
 };
 
 //use parking_lot::Mutex;
@@ -10,16 +11,672 @@ use three_d::*;
 use wasm_thread as thread;
 use bus::{Bus, BusReader};
 use num_format::{Locale, ToFormattedString};
+use serde::{Serialize, Deserialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use js_sys::{Reflect, Uint8Array};
 
 use crate::log; // macro import
 use crate::utils::*;
 use crate::scene::*;
+use crate::editor::*;
+use crate::api;
+use crate::spz;
+use crate::splatv::*;
+use crate::error::GauzillaError;
+use crate::lod::{Octree, LodFilter, LodStream, NODE_SPLATS, export_lod, open_lod_in_background, source_view_proj};
+use crate::pool::WorkerPool;
+#[cfg(feature = "webgpu_sort")]
+use crate::gpusort::GpuSorter;
 
 
-#[derive(PartialEq)]
+const EXPLODE_ANIM_MS: f64 = 2000.0; // duration of the explode animation (out and back in)
+const FADE_IN_MS: f64 = 500.0; // duration of the opacity ramp after the first sort of a new scene
+const FLY_SPEED_PER_DIAGONAL: f32 = 0.0005; // default fly speed relative to the scene size (0.005 for a 10-unit scene)
+
+
+#[derive(PartialEq, Debug)]
 enum TdCameraControl { Orbit, Fly }
 
 
+/// Compositing of the splats onto the framebuffer
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum BlendMode {
+    Over, // straight alpha, back-to-front
+    Additive, // order-independent, for emissive captures (fireworks, lights)
+    Premultiplied, // premultiplied alpha, back-to-front
+    Under, // premultiplied alpha, front-to-back (onto a transparent target)
+}
+impl BlendMode {
+    /// In the order of api::BLEND_MODES
+    pub const ALL: [BlendMode; 4] = [BlendMode::Over, BlendMode::Additive, BlendMode::Premultiplied, BlendMode::Under];
+
+
+    /// Sort direction the blend func needs
+    fn draw_order(&self) -> DrawOrder {
+        match self {
+            BlendMode::Under => DrawOrder::FrontToBack,
+            _ => DrawOrder::BackToFront, // additive doesn't care
+        }
+    }
+
+
+    /// Sets the GL blend state for the mode (explicitly every frame, nothing is inherited from other passes)
+    /// Returns true if the fragment shader has to output premultiplied color
+    /// The target starts out cleared, so dst always holds premultiplied color
+    fn apply(&self, gl: &Context) -> bool {
+        unsafe {
+            gl.enable(context::BLEND);
+            gl.blend_equation(context::FUNC_ADD);
+            match self {
+                // rgb = a*src + (1 - a)*dst, alpha = a + (1 - a)*dst_alpha:
+                // the same premultiplied result as Premultiplied, from the straight shader output
+                BlendMode::Over => {
+                    gl.blend_func_separate(
+                        context::SRC_ALPHA,
+                        context::ONE_MINUS_SRC_ALPHA,
+                        context::ONE,
+                        context::ONE_MINUS_SRC_ALPHA,
+                    );
+                    false
+                },
+                BlendMode::Additive => {
+                    gl.blend_func(context::ONE, context::ONE);
+                    true
+                },
+                // src is a*rgb already: rgba = src + (1 - a)*dst
+                BlendMode::Premultiplied => {
+                    gl.blend_func(context::ONE, context::ONE_MINUS_SRC_ALPHA);
+                    true
+                },
+                // rgba = (1 - dst_alpha)*src + dst, the nearer splats already in dst occlude the new one
+                BlendMode::Under => {
+                    gl.blend_func(context::ONE_MINUS_DST_ALPHA, context::ONE);
+                    true
+                },
+            }
+        }
+    }
+}
+
+
+/// Screen-space antialiasing filter of the projected splats
+/// Splats trained with the filter (the SPZ antialiased flag) are meant to be rendered with compensation.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Antialias {
+    pub dilation: f32, // variance in pixels^2 added to the 2D covariance (0 = off)
+    pub compensate: bool, // lower the opacity of dilated splats to keep their energy (Mip-Splatting)
+}
+impl Antialias {
+    /// 0.3 as in the reference 3DGS rasterizer (which doesn't compensate)
+    pub const DEFAULT_DILATION: f32 = 0.3;
+}
+impl Default for Antialias {
+    fn default() -> Self {
+        Self {
+            dilation: Self::DEFAULT_DILATION,
+            compensate: false,
+        }
+    }
+}
+
+
+/// Model transform of the scene, folded into the view matrix of the passes and into the view_proj of the sort
+/// The scale is uniform, so that the view directions of the SH evaluation stay valid in model space.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ModelTransform {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 3], // XYZ Euler angles in degrees (applied in this order)
+    pub scale: f32,
+    // Y-down captures (e.g. COLMAP's convention) to Y-up, applied first
+    // Y is flipped together with X (180° about Z), as flipping Y alone would mirror the scene.
+    pub invert_y: bool,
+}
+impl ModelTransform {
+    pub const MIN_SCALE: f32 = 0.001;
+
+    pub fn matrix(&self) -> Mat4 {
+        let [tx, ty, tz] = self.translation;
+        let [rx, ry, rz] = self.rotation;
+        Mat4::from_translation(vec3(tx, ty, tz))
+            * Mat4::from_angle_z(degrees(rz))
+            * Mat4::from_angle_y(degrees(ry))
+            * Mat4::from_angle_x(degrees(rx))
+            * Mat4::from_scale(self.scale.max(Self::MIN_SCALE))
+            * Mat4::from_angle_z(degrees(if self.invert_y { 180.0 } else { 0.0 }))
+    }
+
+
+    /// Maps a point of the scene into world space
+    pub fn apply(&self, p: Vec3) -> Vec3 {
+        (self.matrix() * p.extend(1.0)).truncate()
+    }
+
+
+    /// Maps a camera pose (position, target, up) given relative to the scene (e.g. the ?position=/?target=/?up= URL params)
+    /// into world space, so that it shows the scene from the same viewpoint whatever the model transform
+    /// With invert_y, up is negated as well, as Y used to be inverted by rolling the camera 180° instead.
+    pub fn apply_pose(&self, position: Vec3, target: Vec3, up: Vec3) -> (Vec3, Vec3, Vec3) {
+        let up = if self.invert_y { -up } else { up };
+        (self.apply(position), self.apply(target), (self.matrix() * up.extend(0.0)).truncate())
+    }
+}
+impl Default for ModelTransform {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: 1.0,
+            invert_y: false,
+        }
+    }
+}
+
+
+/// Color space of the splat colors and of the blending
+/// The offscreen texture has 8 bits per channel, so the linear modes may band in dark gradients (cf. the dither option).
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ColorSpace {
+    Srgb, // colors are sRGB and blended as is (as most 3DGS trainers do)
+    LinearBlend, // colors are sRGB, decoded to linear for blending and encoded back for display
+    Linear, // colors are linear, blended as is and encoded to sRGB for display
+}
+impl ColorSpace {
+    /// Returns true if the splat colors have to be decoded from sRGB before blending
+    fn decode_colors(&self) -> bool {
+        *self == ColorSpace::LinearBlend
+    }
+
+    /// Returns true if the blended image is linear and has to be encoded to sRGB for display
+    fn encode_output(&self) -> bool {
+        *self != ColorSpace::Srgb
+    }
+}
+
+
+/// Tone curve the quad pass applies to the splats after the exposure
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ToneMap {
+    Linear, // passthrough (clipped at 1)
+    Reinhard, // c/(1 + c)
+    Aces, // Narkowicz's fit of the ACES filmic curve
+}
+
+
+/// What is drawn behind the splats
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum BackgroundMode {
+    Solid, // the clear color
+    Gradient, // vertical gradient from bottom to top
+    Image, // image loaded from a URL, scaled to cover the canvas
+}
+
+
+/// Background settings, composited under the splats by the quad pass
+pub struct Background {
+    pub mode: BackgroundMode,
+    pub top: [f32; 4], // gradient colors (sRGB, straight alpha)
+    pub bottom: [f32; 4],
+    pub url: String, // image URL as typed in egui
+    image_size: Option<(u32, u32)>, // size of the uploaded image
+    image_pixels: Vec<u8>, // RGBA8 pixels of the uploaded image, kept to upload them again after a WebGL context loss
+}
+impl Background {
+    pub fn new() -> Self {
+        Self {
+            mode: BackgroundMode::Solid,
+            top: [0.25, 0.3, 0.4, 1.0],
+            bottom: [0.0, 0.0, 0.0, 1.0],
+            url: String::new(),
+            image_size: None,
+            image_pixels: Vec::<u8>::new(),
+        }
+    }
+
+
+    /// Plain color composited by the quad pass (e.g. the clear color under front-to-back blending)
+    fn solid(color: [f32; 4]) -> Self {
+        Self {
+            mode: BackgroundMode::Gradient,
+            top: color,
+            bottom: color,
+            ..Self::new()
+        }
+    }
+
+
+    /// Returns true if the quad pass has to composite the splats over the background
+    /// (an image mode without an image falls back to the solid clear color)
+    pub fn is_composited(&self) -> bool {
+        match self.mode {
+            BackgroundMode::Solid => false,
+            BackgroundMode::Gradient => true,
+            BackgroundMode::Image => self.image_size.is_some(),
+        }
+    }
+}
+
+
+/// Loads an image from a URL as RGBA8 pixels (top row first)
+async fn load_background_image(url: &str) -> Result<(u32, u32, Vec<u8>), GauzillaError> {
+    let image = load_image_rgba(url).await?;
+    let get = |key: &str| Reflect::get(&image, &JsValue::from_str(key));
+    let width = get("width")?.as_f64().unwrap_or(0.0) as u32;
+    let height = get("height")?.as_f64().unwrap_or(0.0) as u32;
+    let pixels = Uint8Array::new(&get("pixels")?).to_vec();
+    if width == 0 || height == 0 || pixels.len() != 4*(width*height) as usize {
+        return Err(GauzillaError::DecodeFailed(format!("unexpected image data from {}", url)));
+    }
+    Ok((width, height, pixels))
+}
+
+
+/// Decodes an sRGB color component to linear (IEC 61966-2-1)
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c/12.92 } else { ((c + 0.055)/1.055).powf(2.4) }
+}
+
+
+/// Geometry of the quad drawn for each splat
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum QuadMode {
+    ScreenAligned, // spanned by the axes of the projected 2D covariance (default)
+    Oriented, // spanned by the two largest axes of the 3D covariance, projected as a flat disc
+}
+
+
+/// One-shot snapshot of the current settings and stats, exported as JSON
+#[derive(Serialize)]
+struct RenderSnapshot {
+    camera: CameraSnapshot,
+    camera_control: String,
+    splat_scale: f32,
+    quality_preset: Option<String>,
+    render_scale: f32,
+    blend_mode: String,
+    color_space: String,
+    tone_map: String,
+    exposure: f32,
+    quad_mode: String,
+    near_fade: f32,
+    global_alpha: f32,
+    aa_dilation: f32,
+    fxaa: bool,
+    splat_percent: f32,
+    depth_bits: u32,
+    draw_cap: Option<usize>, // None if disabled
+    half_positions: bool,
+    full_covariance: bool,
+    mirror: [bool; 3],
+    world_scale: f32,
+    sh_degree: usize, // rendered SH degree
+    fps: f64,
+    sort_time_ms: f64,
+    splat_count: usize,
+    source_format: String,
+    url: String,
+}
+
+
+#[derive(Serialize)]
+struct CameraSnapshot {
+    position: [f32; 3],
+    target: [f32; 3],
+    up: [f32; 3],
+    fovy_degrees: f32,
+    z_near: f32,
+    z_far: f32,
+}
+impl CameraSnapshot {
+    fn new(camera: &Camera, fovy: Degrees) -> Self {
+        let p = camera.position();
+        let t = camera.target();
+        let u = camera.up();
+        Self {
+            position: [p.x, p.y, p.z],
+            target: [t.x, t.y, t.z],
+            up: [u.x, u.y, u.z],
+            fovy_degrees: fovy.0,
+            z_near: camera.z_near(),
+            z_far: camera.z_far(),
+        }
+    }
+}
+
+
+/// Camera pose copied to/pasted from the clipboard as JSON for reproducible viewpoints
+/// up already includes the roll, roll_degrees only restores the Camera Roll slider
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct CameraPose {
+    position: [f32; 3],
+    target: [f32; 3],
+    up: [f32; 3],
+    fovy_degrees: f32,
+    roll_degrees: f32,
+}
+impl CameraPose {
+    fn new(camera: &Camera, fovy: Degrees, roll: f32) -> Self {
+        let p = camera.position();
+        let t = camera.target();
+        let u = camera.up();
+        Self {
+            position: [p.x, p.y, p.z],
+            target: [t.x, t.y, t.z],
+            up: [u.x, u.y, u.z],
+            fovy_degrees: fovy.0,
+            roll_degrees: roll,
+        }
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    fn from_json(json: &str) -> Result<Self, GauzillaError> {
+        let pose: Self = serde_json::from_str(json.trim())
+            .map_err(|e| GauzillaError::DecodeFailed(format!("camera pose: {}", e)))?;
+        let finite = pose.position.iter()
+            .chain(pose.target.iter())
+            .chain(pose.up.iter())
+            .chain([pose.fovy_degrees, pose.roll_degrees].iter())
+            .all(|v| v.is_finite());
+        let position = Vec3::from(pose.position);
+        let up = Vec3::from(pose.up);
+        if !finite
+            || (Vec3::from(pose.target) - position).magnitude() < 1e-6
+            || up.magnitude() < 1e-6
+            || !(1.0..180.0).contains(&pose.fovy_degrees)
+        {
+            return Err(GauzillaError::DecodeFailed(format!("camera pose: invalid values in {}", json.trim())));
+        }
+        Ok(pose)
+    }
+}
+
+
+/// Default quality settings picked from the scene size when a scene is loaded
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum QualityPreset {
+    Full,
+    Balanced,
+    Performance,
+}
+impl QualityPreset {
+    /// Picks the preset for splat_count splats, treating fewer cores as a bigger scene
+    pub fn for_scene(splat_count: usize, cpu_cores: usize) -> Self {
+        let load = match cpu_cores {
+            0..=3 => 2.0,
+            4..=7 => 1.33,
+            _ => 1.0,
+        }*splat_count as f64;
+        if load <= 1_500_000.0 {
+            QualityPreset::Full
+        } else if load <= 4_000_000.0 {
+            QualityPreset::Balanced
+        } else {
+            QualityPreset::Performance
+        }
+    }
+
+    pub fn splat_scale(&self) -> f32 {
+        match self {
+            QualityPreset::Full | QualityPreset::Balanced => 1.0,
+            QualityPreset::Performance => 0.9,
+        }
+    }
+
+    /// Share of the most important splats rendered (cf. SortSettings::splat_percent)
+    pub fn splat_percent(&self) -> f32 {
+        match self {
+            QualityPreset::Full => 100.0,
+            QualityPreset::Balanced => 75.0,
+            QualityPreset::Performance => 50.0,
+        }
+    }
+
+    /// Resolution of the splat pass relative to the canvas
+    pub fn render_scale(&self) -> f32 {
+        match self {
+            QualityPreset::Full => 1.0,
+            QualityPreset::Balanced => 0.85,
+            QualityPreset::Performance => 0.7,
+        }
+    }
+}
+
+
+/// Detects when the camera has been still for a while
+pub struct IdleDetector {
+    pub enabled: bool,
+    pub idle_ms: f64, // time without activity before the detector fires
+    last_activity: f64,
+    fired: bool,
+}
+impl IdleDetector {
+    pub fn new(idle_ms: f64) -> Self {
+        Self {
+            enabled: false,
+            idle_ms,
+            last_activity: 0.0,
+            fired: false,
+        }
+    }
+
+
+    /// Records activity (e.g. camera movement) and re-arms the detector
+    pub fn touch(&mut self, now: f64) {
+        self.last_activity = now;
+        self.fired = false;
+    }
+
+
+    /// Returns true once per idle period, idle_ms after the last activity
+    pub fn poll(&mut self, now: f64) -> bool {
+        if !self.enabled || self.fired || now - self.last_activity < self.idle_ms {
+            return false;
+        }
+        self.fired = true;
+        true
+    }
+}
+
+
+/// Detects two presses close together in time and space
+pub struct DoubleClick {
+    pub max_ms: f64, // longest gap between the two presses
+    pub max_pixels: f32, // farthest the cursor may move between the two presses
+    last_press: Option<(f64, (f32, f32))>, // (time, cursor) of the previous unpaired press
+}
+impl DoubleClick {
+    pub fn new(max_ms: f64, max_pixels: f32) -> Self {
+        Self {
+            max_ms,
+            max_pixels,
+            last_press: None,
+        }
+    }
+
+
+    /// Records a press and returns true if it completes a double click
+    pub fn press(&mut self, now: f64, cursor: (f32, f32)) -> bool {
+        let double = self.last_press.map_or(false, |(time, (x, y))| {
+            let dx = cursor.0 - x;
+            let dy = cursor.1 - y;
+            now - time <= self.max_ms && dx*dx + dy*dy <= self.max_pixels*self.max_pixels
+        });
+        // a third press starts a new pair
+        self.last_press = if double { None } else { Some((now, cursor)) };
+        double
+    }
+}
+
+
+/// Caps the number of splats drawn per frame, optionally tuned to hold a target frame rate
+pub struct DrawCap {
+    pub enabled: bool,
+    pub auto: bool, // adjust max_splats from the measured fps
+    pub target_fps: f64,
+    pub max_splats: usize,
+    last_update: f64,
+}
+impl DrawCap {
+    const UPDATE_INTERVAL_MS: f64 = 500.0;
+    const MIN_SPLATS: usize = 10_000;
+
+
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            auto: true,
+            target_fps: 30.0,
+            max_splats: usize::MAX,
+            last_update: 0.0,
+        }
+    }
+
+
+    /// Shrinks the cap by 10% while below 90% of the target fps and grows it by 10% above 110%
+    pub fn update(&mut self, now: f64, fps: f64, splat_count: usize) {
+        if !self.enabled {
+            return;
+        }
+        self.max_splats = self.max_splats.min(splat_count); // also keeps the manual slider in range
+        if !self.auto || splat_count == 0 || now - self.last_update < Self::UPDATE_INTERVAL_MS {
+            return;
+        }
+        self.last_update = now;
+
+        let cap = self.max_splats.min(splat_count);
+        let cap = if fps < 0.9*self.target_fps {
+            (cap as f64*0.9) as usize
+        } else if fps > 1.1*self.target_fps {
+            (cap as f64*1.1).ceil() as usize
+        } else {
+            cap
+        };
+        self.max_splats = cap.clamp(Self::MIN_SPLATS.min(splat_count), splat_count);
+    }
+
+
+    /// Number of splats to draw out of splat_count
+    pub fn count(&self, splat_count: usize) -> usize {
+        if self.enabled {
+            self.max_splats.min(splat_count)
+        } else {
+            splat_count
+        }
+    }
+}
+
+
+/// Render passes timed by [GpuTimer]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GpuPass {
+    Splat, // splats into the offscreen texture
+    Quad, // offscreen texture onto the canvas
+    Gui, // egui overlay
+}
+impl GpuPass {
+    const COUNT: usize = 3;
+}
+
+
+/// Measures the GPU time of each render pass with EXT_disjoint_timer_query_webgl2
+/// Results arrive a few frames late, so queries are kept in flight per pass and polled every frame.
+/// Does nothing if the extension isn't available.
+pub struct GpuTimer {
+    available: bool,
+    free: Vec<context::WebQueryKey>,
+    pending: [VecDeque<context::WebQueryKey>; GpuPass::COUNT],
+    ma: [IncrementalMA; GpuPass::COUNT],
+    ms: [Option<f64>; GpuPass::COUNT], // moving average of the elapsed time
+    active: bool, // only one TIME_ELAPSED query may be active at a time
+}
+impl GpuTimer {
+    const MAX_PENDING: usize = 8; // per pass, stop issuing queries if the results don't come back
+
+
+    pub fn new(gl: &Context) -> Self {
+        let available = gl.supported_extensions().contains("EXT_disjoint_timer_query_webgl2");
+        log!("GpuTimer::new(): available={}", available);
+        Self {
+            available,
+            free: Vec::<context::WebQueryKey>::new(),
+            pending: Default::default(),
+            ma: [IncrementalMA::new(30), IncrementalMA::new(30), IncrementalMA::new(30)],
+            ms: [None; GpuPass::COUNT],
+            active: false,
+        }
+    }
+
+
+    pub fn is_available(&self) -> bool {
+        self.available
+    }
+
+
+    /// Average GPU time of the pass in milliseconds (None until the first result arrives)
+    pub fn ms(&self, pass: GpuPass) -> Option<f64> {
+        self.ms[pass as usize]
+    }
+
+
+    pub fn begin(&mut self, gl: &Context, pass: GpuPass) {
+        if !self.available || self.active || self.pending[pass as usize].len() >= Self::MAX_PENDING {
+            return;
+        }
+        let query = match self.free.pop() {
+            Some(query) => query,
+            None => match unsafe { gl.create_query() } {
+                Ok(query) => query,
+                Err(_) => return,
+            },
+        };
+        unsafe {
+            gl.begin_query(context::TIME_ELAPSED, query);
+        }
+        self.pending[pass as usize].push_back(query);
+        self.active = true;
+    }
+
+
+    pub fn end(&mut self, gl: &Context) {
+        if !self.active {
+            return;
+        }
+        unsafe {
+            gl.end_query(context::TIME_ELAPSED);
+        }
+        self.active = false;
+    }
+
+
+    /// Collects the results of finished queries (call once per frame outside of begin()/end())
+    pub fn poll(&mut self, gl: &Context) {
+        if !self.available {
+            return;
+        }
+        unsafe {
+            // the GPU was interrupted (e.g. power saving), so the results in flight are meaningless
+            let disjoint = gl.get_parameter_i32(context::GPU_DISJOINT_EXT) != 0;
+            for pass in 0..GpuPass::COUNT {
+                while let Some(&query) = self.pending[pass].front() {
+                    if gl.get_query_parameter_u32(query, context::QUERY_RESULT_AVAILABLE) == 0 && !disjoint {
+                        break;
+                    }
+                    self.pending[pass].pop_front();
+                    if !disjoint {
+                        let ns = gl.get_query_parameter_u32(query, context::QUERY_RESULT);
+                        self.ms[pass] = Some(self.ma[pass].add(ns as f64 / 1e6));
+                    }
+                    self.free.push(query);
+                }
+            }
+        }
+    }
+}
+
+
 /// Re-implementation of three_d::OrbitControl to add right mouse button control
 pub struct OrbitControl2 {
     control: CameraControl,
@@ -44,9 +701,34 @@ impl OrbitControl2 {
         }
     }
 
+    /// Moves the pivot of all orbit/zoom actions to the given target
+    pub fn set_target(&mut self, new_target: Vec3) {
+        if let CameraAction::Zoom { target, .. } = &mut self.control.scroll_vertical {
+            *target = new_target;
+        }
+        if let CameraAction::OrbitLeft { target, .. } = &mut self.control.left_drag_horizontal {
+            *target = new_target;
+        }
+        if let CameraAction::OrbitUp { target, .. } = &mut self.control.left_drag_vertical {
+            *target = new_target;
+        }
+    }
+
     /// Handles the events. Must be called each frame.
     pub fn handle_events(&mut self, camera: &mut Camera, events: &mut [Event]) -> bool {
 
+        // pan speed scales with the distance to the target (about 0.01 at the default distance of 5),
+        // set before the change is calculated so that camera and target move by the same amount
+        if let CameraAction::Zoom { target, .. } = &self.control.scroll_vertical {
+            let x = target.distance(*camera.position());
+            if let CameraAction::Left { speed } = &mut self.control.right_drag_horizontal {
+                *speed = 0.002 * x + 0.001;
+            }
+            if let CameraAction::Up { speed } = &mut self.control.right_drag_vertical {
+                *speed = 0.002 * x + 0.001;
+            }
+        }
+
         // need to re-calculate the change so as to translate the target for orbit
         let mut change = Vec3::zero();
         for event in events.iter() {
@@ -96,33 +778,181 @@ impl OrbitControl2 {
 }
 
 
+/// One of several scenes shown together, merged into one for a global depth sort (cf. Scene::merged())
+struct SceneLayer {
+    scene: Arc<Scene>,
+    name: String,
+    offset: [f32; 3], // translation in world space
+    visible: bool,
+}
+impl SceneLayer {
+    fn new(scene: Arc<Scene>, name: String) -> Self {
+        Self {
+            scene,
+            name,
+            offset: [0.0; 3],
+            visible: true,
+        }
+    }
+}
+
+
+/// Points the camera and the orbit control at the centroid of the scene (placed by model)
+fn orbit_around_centroid(camera: &mut Camera, orbit_control: &mut OrbitControl2, scene: &Scene, model: &ModelTransform) {
+    if let Some(centroid) = scene.centroid().map(|c| model.apply(c)) {
+        log!("orbit_around_centroid(): centroid={:?}", centroid);
+        let mut position = *camera.position();
+        if (position - centroid).magnitude() < 1e-3 {
+            // a point-like scene (e.g. a single splat) under the camera: back off along the view direction
+            position = centroid - 2.0*camera.view_direction().normalize();
+        }
+        let up = *camera.up();
+        camera.set_view(position, centroid, up);
+        orbit_control.set_target(centroid);
+    }
+}
+
+
+/// Frames the whole bounding box of the scene (placed by model) around center (in scene coordinates):
+/// the camera keeps its view direction and backs off from center
+/// until the bounding sphere (around center) fits into the vertical field of view
+fn frame_bounding_box(
+    camera: &mut Camera,
+    orbit_control: &mut OrbitControl2,
+    scene: &Scene,
+    model: &ModelTransform,
+    fovy: Degrees,
+    center: Vec3
+) {
+    if scene.splat_count == 0 {
+        return;
+    }
+    let (min, max) = scene.bounding_box();
+    // distance to the farthest corner
+    let radius = (0..3)
+        .map(|k| (center[k] - min[k]).abs().max((max[k] - center[k]).abs()))
+        .map(|d| d*d)
+        .sum::<f32>()
+        .sqrt()
+        .max(0.001)*model.scale.max(ModelTransform::MIN_SCALE);
+    let center = model.apply(center);
+    let distance = radius / (fovy / 2.0).sin();
+    log!("frame_bounding_box(): center={:?}, radius={}, distance={}", center, radius, distance);
+    let position = center - distance*camera.view_direction().normalize();
+    let up = *camera.up();
+    look_at(camera, orbit_control, position, center, up);
+    // the far plane has to reach behind the box
+    camera.set_perspective_projection(fovy, camera.z_near(), camera.z_far().max(distance + radius));
+}
+
+
+/// Points the camera at a newly loaded scene: frames its bounding box if frame_scene, otherwise orbits around its centroid
+fn place_camera(
+    camera: &mut Camera,
+    orbit_control: &mut OrbitControl2,
+    scene: &Scene,
+    model: &ModelTransform,
+    fovy: Degrees,
+    frame_scene: bool
+) {
+    if frame_scene {
+        let (min, max) = scene.bounding_box();
+        frame_bounding_box(camera, orbit_control, scene, model, fovy, 0.5*(min + max));
+    } else {
+        orbit_around_centroid(camera, orbit_control, scene, model);
+    }
+}
+
+
+/// Points the camera from position at target and orbits around target from then on
+fn look_at(camera: &mut Camera, orbit_control: &mut OrbitControl2, position: Vec3, target: Vec3, up: Vec3) {
+    camera.set_view(position, target, up);
+    orbit_control.set_target(target);
+}
+
+
+/// Fly control speed that suits the size of the scene (for scenes without bounds, the one for a 10-unit scene)
+fn default_fly_speed(scene: &Scene) -> f32 {
+    let diagonal = scene.bbox.map_or(10.0, |(min, max)| (max - min).magnitude());
+    (FLY_SPEED_PER_DIAGONAL*diagonal).max(1e-6)
+}
+
+
+/// Plain-text summary of the scene and the GL environment for bug reports
+fn stats_summary(gl: &Context, scene: &Scene, url: &str, cpu_cores: usize) -> String {
+    let glsl_version = unsafe { gl.get_parameter_string(context::SHADING_LANGUAGE_VERSION) };
+    let mut text = format!("Gauzilla v{}\n", env!("CARGO_PKG_VERSION"));
+    text.push_str(&format!("URL: {}\n", url));
+    text.push_str(&scene.stats_summary());
+    text.push_str(&format!("GL Version: {:?}\n", gl.version()));
+    text.push_str(&format!("GLSL Version: {}\n", glsl_version));
+    text.push_str(&format!("CPU Cores: {}\n", cpu_cores));
+    text
+}
+
+
+/// Sorts the splats with the given algorithm, restricted to the octree LOD selection if settings.lod is set
+/// (the octree is built on first use for the scene, unless it was streamed with one)
+fn sort_with_lod(
+    algorithm: SortAlgorithm,
+    scene: &Arc<Scene>,
+    octree: &mut Option<Octree>,
+    view_proj: &[f32],
+    settings: &SortSettings,
+    sink: &mut impl DepthSink,
+    pool: &WorkerPool,
+) {
+    match settings.lod {
+        Some(threshold) => {
+            let mask = match scene.octree.as_ref() {
+                // the resident nodes of a streamed .lod scene
+                Some(streamed) => streamed.select(
+                    &source_view_proj(view_proj, scene.mirror, scene.world_scale), threshold, scene.splat_count
+                ),
+                None => octree.get_or_insert_with(|| Octree::build(scene, NODE_SPLATS))
+                    .select(view_proj, threshold, scene.splat_count),
+            };
+            scene.invalidate_sort(); // the selection also changes when the camera only moves
+            Scene::sort_with(algorithm, scene, view_proj, settings, &mut LodFilter { mask: &mask, sink }, Some(pool));
+        },
+        None => Scene::sort_with(algorithm, scene, view_proj, settings, sink, Some(pool)),
+    }
+}
+
+
 #[allow(unused_mut)]
 fn launch_sorter_thread(
     scene: Arc<Scene>,
-    mut rx_buffer: BusReader<Vec<u8>>,
+    mut rx_scene: BusReader<Arc<Scene>>,
     mut rx_vp: BusReader<Mat4>,
+    mut rx_settings: BusReader<SortSettings>,
+    mut rx_refine: BusReader<Mat4>,
     mut bus_depth: Bus<Vec<u32>>,
     cpu_cores: usize,
     mut bus_time: Bus<f64>,
+    mut bus_refined: Bus<f64>,
+    algorithm: SortAlgorithm,
 ) -> thread::JoinHandle<()> {
+    log!("launch_sorter_thread(): algorithm={:?}", algorithm);
+    // the workers sorting the chunks of large scenes are started once, not per sort
+    let pool = WorkerPool::new(cpu_cores);
     // launch another thread for view-dependent splat sorting
     let thread_handle = thread::spawn({
         let mut scene = scene.clone();
+        let mut settings = SortSettings::default();
+        let mut octree: Option<Octree> = None;
 
         move || loop {
-            // receive splat binary buffer from async JS worker callback
-            #[cfg(feature = "async_splat_stream")]
-            if let Ok(buffer) = rx_buffer.try_recv() {
-                /*
-                FIXME: scene buffer needs to be duplicated here
-                since Arc<Scene> does not have an interior mutability without a mutex
-                (and mutex is not allowed in wasm main thread)
-                */
-                let mut s = Scene::new();
-                s.buffer = buffer;
-                s.splat_count = s.buffer.len() / 32; // 32bytes per splat
-                //s.generate_texture(); // texture is created instead in render loop in main thread
-                scene = Arc::new(s);
+            // receive a newly streamed/reloaded scene from the main thread
+            if let Ok(s) = rx_scene.try_recv() {
+                scene = s;
+                octree = None;
+            }
+
+            // receive changed sort settings from the main thread
+            if let Ok(s) = rx_settings.try_recv() {
+                settings = s;
+                scene.invalidate_sort();
             }
 
             // receive view proj matrix from main thread
@@ -134,13 +964,25 @@ fn launch_sorter_thread(
                     view_proj[3][0], view_proj[3][1], view_proj[3][2], view_proj[3][3]
                 ];
                 let start =  get_time_milliseconds();
-                Scene::sort(&scene, view_proj_slice, &mut bus_depth, cpu_cores);
+                sort_with_lod(algorithm, &scene, &mut octree, view_proj_slice, &settings, &mut bus_depth, &pool);
                 let sort_time = get_time_milliseconds() - start;
                 //////////////////////////////////
                 // non-blocking (i.e., no atomic.wait)
                 let _ = bus_time.try_broadcast(sort_time);
                 //////////////////////////////////
             }
+
+            // one-shot higher-quality sort requested while the camera is idle
+            if let Ok(view_proj) = rx_refine.try_recv() {
+                let start =  get_time_milliseconds();
+                scene.invalidate_sort(); // same view direction as the last sort
+                sort_with_lod(algorithm, &scene, &mut octree, &mat4_to_array(&view_proj), &settings.refined(), &mut bus_depth, &pool);
+                let sort_time = get_time_milliseconds() - start;
+                //////////////////////////////////
+                // non-blocking (i.e., no atomic.wait)
+                let _ = bus_refined.try_broadcast(sort_time);
+                //////////////////////////////////
+            }
         }
     });
 
@@ -253,7 +1095,54 @@ fn create_glsl_program(
 }
 
 
-struct SplatGLSL {
+/// Camera of a splat pass, with the view in scene coordinates (cf. the model transform)
+pub(crate) struct SplatView {
+    pub projection: [f32; 16],
+    pub view: [f32; 16],
+    pub focal: [f32; 2], // in pixels of the viewport
+    pub viewport: [f32; 2],
+    pub htan_fov: [f32; 2], // tangents of the half field of view
+    pub cam_pos: [f32; 3],
+}
+
+
+/// Settings of SplatGLSL::render() (and the picking pass), the defaults draw the scene as loaded
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SplatRenderParams {
+    pub splat_scale: f32,
+    pub near_fade: [f32; 2], // (z_near, fade distance)
+    pub global_alpha: f32, // opacity multiplier for the whole cloud
+    pub depth_focus: f32, // view-space distance the depth output is encoded around (cf. QuadGLSL::draw_depth())
+    pub explode: Option<([f32; 3], f32)>, // (center, offset), cf. SortSettings::explode
+    pub view_dependent: bool, // evaluate the uploaded SH bands (false: flat DC color, e.g. for A/B comparisons)
+    pub antialias: Antialias,
+    pub hovered: Option<u32>, // splat to highlight
+    pub blend_mode: BlendMode,
+    pub color_space: ColorSpace,
+    pub quad_mode: QuadMode,
+    pub max_splats: i32, // draws at most the max_splats nearest splats
+}
+impl Default for SplatRenderParams {
+    fn default() -> Self {
+        Self {
+            splat_scale: 1.0,
+            near_fade: [0.0, 0.0],
+            global_alpha: 1.0,
+            depth_focus: 1.0,
+            explode: None,
+            view_dependent: true,
+            antialias: Antialias::default(),
+            hovered: None,
+            blend_mode: BlendMode::Over,
+            color_space: ColorSpace::Srgb,
+            quad_mode: QuadMode::ScreenAligned,
+            max_splats: i32::MAX,
+        }
+    }
+}
+
+
+pub(crate) struct SplatGLSL {
     program: Option<context::Program>,
     u_projection: Option<context::UniformLocation>,
     u_viewport: Option<context::UniformLocation>,
@@ -262,6 +1151,21 @@ struct SplatGLSL {
     u_view: Option<context::UniformLocation>,
     u_cam_pos: Option<context::UniformLocation>,
     u_splat_scale: Option<context::UniformLocation>,
+    u_premultiply: Option<context::UniformLocation>,
+    u_srgb_to_linear: Option<context::UniformLocation>,
+    u_half_positions: Option<context::UniformLocation>,
+    u_full_covariance: Option<context::UniformLocation>,
+    u_oriented_quads: Option<context::UniformLocation>,
+    u_aa_dilation: Option<context::UniformLocation>,
+    u_aa_compensate: Option<context::UniformLocation>,
+    u_near_fade: Option<context::UniformLocation>,
+    u_global_alpha: Option<context::UniformLocation>,
+    u_explode_center: Option<context::UniformLocation>,
+    u_explode: Option<context::UniformLocation>,
+    u_hovered: Option<context::UniformLocation>,
+    u_depth_focus: Option<context::UniformLocation>,
+    half_positions: bool, // texture layout of the last uploaded scene
+    full_covariance: bool,
 
     vertex_buffer: Option<context::WebBufferKey>,
     a_position: u32,
@@ -269,7 +1173,13 @@ struct SplatGLSL {
     texture: Option<context::WebTextureKey>,
     u_splat_texture: Option<context::UniformLocation>,
 
+    sh_texture: Option<context::WebTextureKey>,
+    u_sh_texture: Option<context::UniformLocation>,
+    u_sh_degree: Option<context::UniformLocation>,
+    sh_degree: usize, // SH degree of the last uploaded scene (0 = no SH texture)
+
     index_buffer: Option<context::WebBufferKey>,
+    index_count: usize, // length of the last uploaded depth_index
     a_index: u32,
 }
 impl SplatGLSL {
@@ -287,6 +1197,21 @@ impl SplatGLSL {
             u_view: None,
             u_cam_pos: None,
             u_splat_scale: None,
+            u_premultiply: None,
+            u_srgb_to_linear: None,
+            u_half_positions: None,
+            u_full_covariance: None,
+            u_oriented_quads: None,
+            u_aa_dilation: None,
+            u_aa_compensate: None,
+            u_near_fade: None,
+            u_global_alpha: None,
+            u_explode_center: None,
+            u_explode: None,
+            u_hovered: None,
+            u_depth_focus: None,
+            half_positions: false,
+            full_covariance: false,
 
             vertex_buffer: None,
             a_position: 0,
@@ -294,7 +1219,13 @@ impl SplatGLSL {
             texture: None,
             u_splat_texture: None,
 
+            sh_texture: None,
+            u_sh_texture: None,
+            u_sh_degree: None,
+            sh_degree: 0,
+
             index_buffer: None,
+            index_count: 0,
             a_index: 0,
         }
     }
@@ -307,178 +1238,764 @@ impl SplatGLSL {
         error_msg: &Arc<Mutex<String>>,
         scene: &Arc<Scene>
     ) {
-        let gsplat_program_id = create_glsl_program(
+        let gsplat_program_id = create_glsl_program(
+            gl,
+            Self::VERT_SHADER,
+            Self::FRAG_SHADER,
+            error_flag,
+            error_msg
+        );
+        self.program = Some(gsplat_program_id);
+        log!("SplatGLSL::init(): self.program={:?}", self.program);
+
+        unsafe {
+            gl.use_program(self.program);
+            {
+                self.u_projection = gl.get_uniform_location(gsplat_program_id, "projection");
+                log!("SplatGLSL::init(): self.u_projection={:?}", self.u_projection);
+                self.u_viewport = gl.get_uniform_location(gsplat_program_id, "viewport");
+                log!("SplatGLSL::init(): self.u_viewport={:?}", self.u_viewport);
+                self.u_focal = gl.get_uniform_location(gsplat_program_id, "focal");
+                log!("SplatGLSL::init(): self.u_focal={:?}", self.u_focal);
+                self.u_view = gl.get_uniform_location(gsplat_program_id, "view");
+                log!("SplatGLSL::init(): self.u_view={:?}", self.u_view);
+                self.u_htan_fov = gl.get_uniform_location(gsplat_program_id, "htan_fov");
+                log!("SplatGLSL::init(): self.u_htan_fov={:?}", self.u_htan_fov);
+                self.u_cam_pos = gl.get_uniform_location(gsplat_program_id, "cam_pos");
+                log!("SplatGLSL::init(): self.u_cam_pos={:?}", self.u_cam_pos);
+                self.u_splat_scale = gl.get_uniform_location(gsplat_program_id, "splat_scale");
+                log!("SplatGLSL::init(): self.u_splat_scale={:?}", self.u_splat_scale);
+                self.u_premultiply = gl.get_uniform_location(gsplat_program_id, "premultiply");
+                log!("SplatGLSL::init(): self.u_premultiply={:?}", self.u_premultiply);
+                self.u_srgb_to_linear = gl.get_uniform_location(gsplat_program_id, "srgb_to_linear");
+                log!("SplatGLSL::init(): self.u_srgb_to_linear={:?}", self.u_srgb_to_linear);
+                self.u_half_positions = gl.get_uniform_location(gsplat_program_id, "half_positions");
+                log!("SplatGLSL::init(): self.u_half_positions={:?}", self.u_half_positions);
+                self.u_full_covariance = gl.get_uniform_location(gsplat_program_id, "full_covariance");
+                log!("SplatGLSL::init(): self.u_full_covariance={:?}", self.u_full_covariance);
+                self.u_oriented_quads = gl.get_uniform_location(gsplat_program_id, "oriented_quads");
+                log!("SplatGLSL::init(): self.u_oriented_quads={:?}", self.u_oriented_quads);
+                self.u_aa_dilation = gl.get_uniform_location(gsplat_program_id, "aa_dilation");
+                log!("SplatGLSL::init(): self.u_aa_dilation={:?}", self.u_aa_dilation);
+                self.u_aa_compensate = gl.get_uniform_location(gsplat_program_id, "aa_compensate");
+                log!("SplatGLSL::init(): self.u_aa_compensate={:?}", self.u_aa_compensate);
+                self.u_near_fade = gl.get_uniform_location(gsplat_program_id, "near_fade");
+                log!("SplatGLSL::init(): self.u_near_fade={:?}", self.u_near_fade);
+                self.u_global_alpha = gl.get_uniform_location(gsplat_program_id, "u_global_alpha");
+                log!("SplatGLSL::init(): self.u_global_alpha={:?}", self.u_global_alpha);
+                self.u_explode_center = gl.get_uniform_location(gsplat_program_id, "u_explode_center");
+                log!("SplatGLSL::init(): self.u_explode_center={:?}", self.u_explode_center);
+                self.u_explode = gl.get_uniform_location(gsplat_program_id, "u_explode");
+                log!("SplatGLSL::init(): self.u_explode={:?}", self.u_explode);
+                self.u_hovered = gl.get_uniform_location(gsplat_program_id, "hovered");
+                log!("SplatGLSL::init(): self.u_hovered={:?}", self.u_hovered);
+                self.u_depth_focus = gl.get_uniform_location(gsplat_program_id, "depth_focus");
+                log!("SplatGLSL::init(): self.u_depth_focus={:?}", self.u_depth_focus);
+
+                let triangle_vertices = &mut [ // quad
+                    -1_f32, -1.0,
+                    1.0, -1.0,
+                    1.0, 1.0,
+                    -1.0, 1.0,
+                ];
+                triangle_vertices.iter_mut().for_each(|v| *v *= 2.0);
+                self.vertex_buffer = Some(gl.create_buffer().unwrap());
+                log!("SplatGLSL::init(): self.vertex_buffer={:?}", self.vertex_buffer);
+                gl.bind_buffer(context::ARRAY_BUFFER, self.vertex_buffer);
+                gl.buffer_data_u8_slice(context::ARRAY_BUFFER, transmute_slice::<_, u8>(triangle_vertices), context::STATIC_DRAW);
+                self.a_position = gl.get_attrib_location(gsplat_program_id, "position").unwrap();
+                log!("SplatGLSL::init(): self.a_position={:?}", self.a_position);
+                gl.enable_vertex_attrib_array(self.a_position);
+                gl.bind_buffer(context::ARRAY_BUFFER, self.vertex_buffer);
+                gl.vertex_attrib_pointer_f32(self.a_position, 2, context::FLOAT, false, 0, 0);
+
+                self.texture = Some(gl.create_texture().unwrap());
+                log!("SplatGLSL::init(): self.texture={:?}", self.texture); // WebTextureKey(1v1)
+                gl.bind_texture(context::TEXTURE_2D, self.texture);
+                self.u_splat_texture = gl.get_uniform_location(gsplat_program_id, "u_splat_texture");
+                log!("SplatGLSL::init(): self.u_splat_texture={:?}", self.u_splat_texture);
+                gl.uniform_1_i32(self.u_splat_texture.as_ref(), 0); // associate the active texture unit with the uniform
+
+                self.sh_texture = Some(gl.create_texture().unwrap());
+                log!("SplatGLSL::init(): self.sh_texture={:?}", self.sh_texture);
+                self.u_sh_texture = gl.get_uniform_location(gsplat_program_id, "u_sh_texture");
+                log!("SplatGLSL::init(): self.u_sh_texture={:?}", self.u_sh_texture);
+                gl.uniform_1_i32(self.u_sh_texture.as_ref(), 1);
+                self.u_sh_degree = gl.get_uniform_location(gsplat_program_id, "sh_degree");
+                log!("SplatGLSL::init(): self.u_sh_degree={:?}", self.u_sh_degree);
+
+                // index buffer for instanced rendering
+                self.index_buffer = Some(gl.create_buffer().unwrap());
+                log!("SplatGLSL::init(): self.index_buffer={:?}", self.index_buffer);
+                //gl.bind_buffer(context::ARRAY_BUFFER, self.index_buffer);
+                self.a_index = gl.get_attrib_location(gsplat_program_id, "index").unwrap();
+                log!("SplatGLSL::init(): self.a_index={:?}", self.a_index);
+                gl.enable_vertex_attrib_array(self.a_index);
+                gl.bind_buffer(context::ARRAY_BUFFER, self.index_buffer);
+                gl.vertex_attrib_pointer_i32(self.a_index, 1, context::INT, 0, 0);
+                gl.vertex_attrib_divisor(self.a_index, 1);
+            }
+            gl.use_program(None);
+
+            gl.bind_texture(context::TEXTURE_2D, self.texture);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_WRAP_S, context::CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_WRAP_T, context::CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MIN_FILTER, context::NEAREST as i32);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MAG_FILTER, context::NEAREST as i32);
+
+            gl.bind_texture(context::TEXTURE_2D, self.sh_texture);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_WRAP_S, context::CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_WRAP_T, context::CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MIN_FILTER, context::NEAREST as i32);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MAG_FILTER, context::NEAREST as i32);
+
+            //gl.active_texture(context::TEXTURE0);
+            //gl.bind_texture(context::TEXTURE_2D, self.texture);
+
+            gl.bind_buffer(context::ARRAY_BUFFER, None);
+            gl.bind_texture(context::TEXTURE_2D, None);
+        }
+    }
+
+
+    /// Uploads the whole splat texture of a scene
+    /// Uploads nothing if the texture exceeds MAX_TEXTURE_SIZE (cf. Scene::check_texture_size())
+    pub fn upload_texture(&mut self, gl: &Context, scene: &Scene) -> Result<(), GauzillaError> {
+        scene.check_texture_size()?;
+        self.half_positions = scene.half_positions;
+        self.full_covariance = scene.full_covariance;
+        unsafe {
+            gl.bind_texture(context::TEXTURE_2D, self.texture);
+            gl.tex_image_2d(
+                context::TEXTURE_2D,
+                0,
+                context::RGBA32UI as i32,
+                scene.tex_width as i32,
+                scene.tex_height as i32,
+                0,
+                context::RGBA_INTEGER,
+                context::UNSIGNED_INT,
+                Some(transmute_slice::<_, u8>(scene.tex_data.as_slice()))
+            );
+            gl.bind_texture(context::TEXTURE_2D, None);
+        }
+        self.upload_sh_texture(gl, scene);
+        Ok(())
+    }
+
+
+    /// Uploads the SH texture of a scene (DC-only scenes upload nothing)
+    fn upload_sh_texture(&mut self, gl: &Context, scene: &Scene) {
+        self.sh_degree = scene.sh_tex_degree;
+        if self.sh_degree == 0 {
+            return;
+        }
+        unsafe {
+            let max_size = gl.get_parameter_i32(context::MAX_TEXTURE_SIZE) as usize;
+            if scene.sh_tex_height > max_size {
+                log!(
+                    "SplatGLSL::upload_sh_texture(): WARNING: {} rows exceed MAX_TEXTURE_SIZE={}, rendering without SH",
+                    scene.sh_tex_height, max_size
+                );
+                self.sh_degree = 0;
+                return;
+            }
+            gl.bind_texture(context::TEXTURE_2D, self.sh_texture);
+            gl.tex_image_2d(
+                context::TEXTURE_2D,
+                0,
+                context::RGB32F as i32,
+                scene.sh_tex_width as i32,
+                scene.sh_tex_height as i32,
+                0,
+                context::RGB,
+                context::FLOAT,
+                Some(transmute_slice::<_, u8>(scene.sh_tex_data.as_slice()))
+            );
+            gl.bind_texture(context::TEXTURE_2D, None);
+        }
+    }
+
+
+    pub fn render(&mut self, gl: &Context, camera: &SplatView, params: &SplatRenderParams, depth: &mut impl DepthSource) {
+        unsafe {
+            gl.use_program(self.program);
+            {
+                gl.disable(context::DEPTH_TEST);
+                gl.disable(context::CULL_FACE);
+                //gl.cull_face(context::FRONT);
+
+                let premultiply = params.blend_mode.apply(gl);
+                gl.uniform_1_i32(self.u_premultiply.as_ref(), premultiply as i32);
+                gl.uniform_1_i32(self.u_srgb_to_linear.as_ref(), params.color_space.decode_colors() as i32);
+
+                gl.uniform_matrix_4_f32_slice(self.u_projection.as_ref(), false, &camera.projection);
+                gl.uniform_matrix_4_f32_slice(self.u_view.as_ref(), false, &camera.view);
+                gl.uniform_1_i32(self.u_splat_texture.as_ref(), 0); // associate the active texture unit with the uniform
+                gl.uniform_2_f32_slice(self.u_focal.as_ref(), &camera.focal);
+                gl.uniform_2_f32_slice(self.u_viewport.as_ref(), &camera.viewport);
+                gl.uniform_2_f32_slice(self.u_htan_fov.as_ref(), &camera.htan_fov);
+                gl.uniform_3_f32_slice(self.u_cam_pos.as_ref(), &camera.cam_pos);
+                gl.uniform_1_f32(self.u_splat_scale.as_ref(), params.splat_scale);
+                gl.uniform_1_i32(self.u_half_positions.as_ref(), self.half_positions as i32);
+                gl.uniform_1_i32(self.u_full_covariance.as_ref(), self.full_covariance as i32);
+                gl.uniform_1_i32(self.u_oriented_quads.as_ref(), (params.quad_mode == QuadMode::Oriented) as i32);
+                gl.uniform_1_f32(self.u_aa_dilation.as_ref(), params.antialias.dilation);
+                gl.uniform_1_i32(self.u_aa_compensate.as_ref(), params.antialias.compensate as i32);
+                gl.uniform_2_f32_slice(self.u_near_fade.as_ref(), &params.near_fade);
+                gl.uniform_1_f32(self.u_global_alpha.as_ref(), params.global_alpha);
+                let (explode_center, explode_offset) = params.explode.unwrap_or(([0.0; 3], 0.0));
+                gl.uniform_3_f32_slice(self.u_explode_center.as_ref(), &explode_center);
+                gl.uniform_1_f32(self.u_explode.as_ref(), explode_offset);
+                gl.uniform_1_i32(self.u_hovered.as_ref(), params.hovered.map_or(-1, |i| i as i32));
+                gl.uniform_1_f32(self.u_depth_focus.as_ref(), params.depth_focus);
+                gl.uniform_1_i32(self.u_sh_texture.as_ref(), 1);
+                let sh_degree = if params.view_dependent { self.sh_degree } else { 0 };
+                gl.uniform_1_i32(self.u_sh_degree.as_ref(), sh_degree as i32);
+
+                gl.active_texture(context::TEXTURE1);
+                gl.bind_texture(context::TEXTURE_2D, self.sh_texture);
+                gl.active_texture(context::TEXTURE0);
+                gl.bind_texture(context::TEXTURE_2D, self.texture);
+
+                gl.enable_vertex_attrib_array(self.a_position);
+                gl.bind_buffer(context::ARRAY_BUFFER, self.vertex_buffer);
+                gl.vertex_attrib_pointer_f32(self.a_position, 2, context::FLOAT, false, 0, 0);
+
+                gl.enable_vertex_attrib_array(self.a_index);
+                gl.bind_buffer(context::ARRAY_BUFFER, self.index_buffer);
+                if let Some(depth_index) = depth.try_take() {
+                    gl.buffer_data_u8_slice(
+                        context::ARRAY_BUFFER,
+                        transmute_slice::<_, u8>(depth_index.as_slice()),
+                        context::DYNAMIC_DRAW
+                    );
+                    self.index_count = depth_index.len();
+                }
+                // the splat count limiter may have shortened depth_index,
+                // and depth_index is back-to-front, so a draw cap skips the farthest splats at its head
+                let instance_count = params.max_splats.max(0).min(self.index_count as i32);
+                let first = self.index_count as i32 - instance_count;
+                gl.vertex_attrib_pointer_i32(self.a_index, 1, context::INT, 0, 4*first);
+                gl.vertex_attrib_divisor(self.a_index, 1);
+
+                gl.draw_arrays_instanced(
+                    context::TRIANGLE_FAN,
+                    0,
+                    4,
+                    instance_count
+                );
+            }
+            gl.use_program(None);
+            gl.bind_buffer(context::ARRAY_BUFFER, None);
+            gl.active_texture(context::TEXTURE1);
+            gl.bind_texture(context::TEXTURE_2D, None);
+            gl.active_texture(context::TEXTURE0);
+            gl.bind_texture(context::TEXTURE_2D, None);
+        }
+    }
+
+
+    /// Re-uploads a single row of the splat texture (one row of `tex_width` texels)
+    pub fn upload_texture_row(&self, gl: &Context, tex_width: usize, row: usize, data: &[u32]) {
+        unsafe {
+            gl.bind_texture(context::TEXTURE_2D, self.texture);
+            gl.tex_sub_image_2d(
+                context::TEXTURE_2D,
+                0,
+                0,
+                row as i32,
+                tex_width as i32,
+                1,
+                context::RGBA_INTEGER,
+                context::UNSIGNED_INT,
+                context::PixelUnpackData::Slice(transmute_slice::<_, u8>(data))
+            );
+            gl.bind_texture(context::TEXTURE_2D, None);
+        }
+    }
+}
+
+
+/// Picking pass: draws the splats of a SplatGLSL with their index + 1 as the color (cf. gsplat_id.frag)
+/// Only the pixel under the cursor is rendered, into a 1x1 R32UI target that is read back
+pub(crate) struct SplatIdGLSL {
+    program: Option<context::Program>,
+    vao: Option<context::VertexArray>, // the instanced arrays of splat_glsl, bound to this program's locations
+    framebuffer: Option<context::Framebuffer>,
+    id_buffer: Option<context::Renderbuffer>,
+    depth_buffer: Option<context::Renderbuffer>,
+    a_position: u32,
+    a_index: u32,
+    u_projection: Option<context::UniformLocation>,
+    u_viewport: Option<context::UniformLocation>,
+    u_focal: Option<context::UniformLocation>,
+    u_htan_fov: Option<context::UniformLocation>,
+    u_view: Option<context::UniformLocation>,
+    u_cam_pos: Option<context::UniformLocation>,
+    u_splat_scale: Option<context::UniformLocation>,
+    u_half_positions: Option<context::UniformLocation>,
+    u_full_covariance: Option<context::UniformLocation>,
+    u_oriented_quads: Option<context::UniformLocation>,
+    u_aa_dilation: Option<context::UniformLocation>,
+    u_aa_compensate: Option<context::UniformLocation>,
+    u_explode_center: Option<context::UniformLocation>,
+    u_explode: Option<context::UniformLocation>,
+    u_splat_texture: Option<context::UniformLocation>,
+    u_min_alpha: Option<context::UniformLocation>,
+}
+impl SplatIdGLSL {
+    const FRAG_SHADER: &'static str = include_str!("gsplat_id.frag");
+    const MIN_ALPHA: f32 = 0.1; // fainter fragments are seen through
+
+
+    pub fn new() -> Self {
+        Self {
+            program: None,
+            vao: None,
+            framebuffer: None,
+            id_buffer: None,
+            depth_buffer: None,
+            a_position: 0,
+            a_index: 0,
+            u_projection: None,
+            u_viewport: None,
+            u_focal: None,
+            u_htan_fov: None,
+            u_view: None,
+            u_cam_pos: None,
+            u_splat_scale: None,
+            u_half_positions: None,
+            u_full_covariance: None,
+            u_oriented_quads: None,
+            u_aa_dilation: None,
+            u_aa_compensate: None,
+            u_explode_center: None,
+            u_explode: None,
+            u_splat_texture: None,
+            u_min_alpha: None,
+        }
+    }
+
+
+    pub fn init(
+        &mut self,
+        gl: &Context,
+        error_flag: &Arc<AtomicBool>,
+        error_msg: &Arc<Mutex<String>>
+    ) {
+        let id_program_id = create_glsl_program(
+            gl,
+            SplatGLSL::VERT_SHADER,
+            Self::FRAG_SHADER,
+            error_flag,
+            error_msg
+        );
+        self.program = Some(id_program_id);
+        log!("SplatIdGLSL::init(): self.program={:?}", self.program);
+
+        unsafe {
+            self.u_projection = gl.get_uniform_location(id_program_id, "projection");
+            log!("SplatIdGLSL::init(): self.u_projection={:?}", self.u_projection);
+            self.u_viewport = gl.get_uniform_location(id_program_id, "viewport");
+            log!("SplatIdGLSL::init(): self.u_viewport={:?}", self.u_viewport);
+            self.u_focal = gl.get_uniform_location(id_program_id, "focal");
+            log!("SplatIdGLSL::init(): self.u_focal={:?}", self.u_focal);
+            self.u_view = gl.get_uniform_location(id_program_id, "view");
+            log!("SplatIdGLSL::init(): self.u_view={:?}", self.u_view);
+            self.u_htan_fov = gl.get_uniform_location(id_program_id, "htan_fov");
+            log!("SplatIdGLSL::init(): self.u_htan_fov={:?}", self.u_htan_fov);
+            self.u_cam_pos = gl.get_uniform_location(id_program_id, "cam_pos");
+            log!("SplatIdGLSL::init(): self.u_cam_pos={:?}", self.u_cam_pos);
+            self.u_splat_scale = gl.get_uniform_location(id_program_id, "splat_scale");
+            log!("SplatIdGLSL::init(): self.u_splat_scale={:?}", self.u_splat_scale);
+            self.u_half_positions = gl.get_uniform_location(id_program_id, "half_positions");
+            log!("SplatIdGLSL::init(): self.u_half_positions={:?}", self.u_half_positions);
+            self.u_full_covariance = gl.get_uniform_location(id_program_id, "full_covariance");
+            log!("SplatIdGLSL::init(): self.u_full_covariance={:?}", self.u_full_covariance);
+            self.u_oriented_quads = gl.get_uniform_location(id_program_id, "oriented_quads");
+            log!("SplatIdGLSL::init(): self.u_oriented_quads={:?}", self.u_oriented_quads);
+            self.u_aa_dilation = gl.get_uniform_location(id_program_id, "aa_dilation");
+            log!("SplatIdGLSL::init(): self.u_aa_dilation={:?}", self.u_aa_dilation);
+            self.u_aa_compensate = gl.get_uniform_location(id_program_id, "aa_compensate");
+            log!("SplatIdGLSL::init(): self.u_aa_compensate={:?}", self.u_aa_compensate);
+            self.u_explode_center = gl.get_uniform_location(id_program_id, "u_explode_center");
+            log!("SplatIdGLSL::init(): self.u_explode_center={:?}", self.u_explode_center);
+            self.u_explode = gl.get_uniform_location(id_program_id, "u_explode");
+            log!("SplatIdGLSL::init(): self.u_explode={:?}", self.u_explode);
+            self.u_splat_texture = gl.get_uniform_location(id_program_id, "u_splat_texture");
+            log!("SplatIdGLSL::init(): self.u_splat_texture={:?}", self.u_splat_texture);
+            self.u_min_alpha = gl.get_uniform_location(id_program_id, "min_alpha");
+            log!("SplatIdGLSL::init(): self.u_min_alpha={:?}", self.u_min_alpha);
+
+            self.vao = Some(gl.create_vertex_array().unwrap());
+            log!("SplatIdGLSL::init(): self.vao={:?}", self.vao);
+            self.a_position = gl.get_attrib_location(id_program_id, "position").unwrap();
+            log!("SplatIdGLSL::init(): self.a_position={:?}", self.a_position);
+            self.a_index = gl.get_attrib_location(id_program_id, "index").unwrap();
+            log!("SplatIdGLSL::init(): self.a_index={:?}", self.a_index);
+
+            self.framebuffer = Some(gl.create_framebuffer().unwrap());
+            log!("SplatIdGLSL::init(): self.framebuffer={:?}", self.framebuffer);
+            gl.bind_framebuffer(context::FRAMEBUFFER, self.framebuffer);
+            {
+                self.id_buffer = Some(gl.create_renderbuffer().unwrap());
+                gl.bind_renderbuffer(context::RENDERBUFFER, self.id_buffer);
+                gl.renderbuffer_storage(context::RENDERBUFFER, context::R32UI, 1, 1);
+                gl.framebuffer_renderbuffer(context::FRAMEBUFFER, context::COLOR_ATTACHMENT0, context::RENDERBUFFER, self.id_buffer);
+
+                self.depth_buffer = Some(gl.create_renderbuffer().unwrap());
+                gl.bind_renderbuffer(context::RENDERBUFFER, self.depth_buffer);
+                gl.renderbuffer_storage(context::RENDERBUFFER, context::DEPTH_COMPONENT24, 1, 1);
+                gl.framebuffer_renderbuffer(context::FRAMEBUFFER, context::DEPTH_ATTACHMENT, context::RENDERBUFFER, self.depth_buffer);
+
+                let status = gl.check_framebuffer_status(context::FRAMEBUFFER);
+                if status != context::FRAMEBUFFER_COMPLETE {
+                    set_error_for_egui(
+                        error_flag, error_msg,
+                        format!("ERROR: gl.check_framebuffer_status(): {}", status)
+                    );
+                }
+            }
+            gl.bind_framebuffer(context::FRAMEBUFFER, None);
+            gl.bind_renderbuffer(context::RENDERBUFFER, None);
+        }
+    }
+
+
+    /// Returns the index of the nearest splat covering pixel (x, y) of the viewport (origin at the bottom-left), if any
+    /// camera and params are the ones the frame was drawn with by SplatGLSL::render()
+    /// Stalls until the GPU has finished, so call it on demand only
+    pub fn pick(
+        &self,
+        gl: &Context,
+        splat_glsl: &SplatGLSL,
+        pixel: (i32, i32),
+        camera: &SplatView,
+        params: &SplatRenderParams // only the geometry settings apply
+    ) -> Option<u32> {
+        let viewport = camera.viewport;
+        let mut id = [0_u8; 16]; // RGBA_INTEGER, the id in R
+        unsafe {
+            gl.bind_framebuffer(context::FRAMEBUFFER, self.framebuffer);
+            // shift the viewport so that the picked pixel lands on the 1x1 target
+            gl.viewport(-pixel.0, -pixel.1, viewport[0] as i32, viewport[1] as i32);
+            gl.clear_buffer_u32_slice(context::COLOR, 0, &[0; 4]);
+            gl.clear_depth_f32(1.0);
+            gl.clear(context::DEPTH_BUFFER_BIT);
+
+            gl.use_program(self.program);
+            {
+                gl.disable(context::BLEND); // integer targets can't be blended
+                gl.disable(context::CULL_FACE);
+                gl.enable(context::DEPTH_TEST);
+                gl.depth_func(context::LESS);
+                gl.depth_mask(true);
+
+                gl.uniform_matrix_4_f32_slice(self.u_projection.as_ref(), false, &camera.projection);
+                gl.uniform_matrix_4_f32_slice(self.u_view.as_ref(), false, &camera.view);
+                gl.uniform_1_i32(self.u_splat_texture.as_ref(), 0);
+                gl.uniform_2_f32_slice(self.u_focal.as_ref(), &camera.focal);
+                gl.uniform_2_f32_slice(self.u_viewport.as_ref(), &camera.viewport);
+                gl.uniform_2_f32_slice(self.u_htan_fov.as_ref(), &camera.htan_fov);
+                gl.uniform_3_f32_slice(self.u_cam_pos.as_ref(), &camera.cam_pos);
+                gl.uniform_1_f32(self.u_splat_scale.as_ref(), params.splat_scale);
+                gl.uniform_1_i32(self.u_half_positions.as_ref(), splat_glsl.half_positions as i32);
+                gl.uniform_1_i32(self.u_full_covariance.as_ref(), splat_glsl.full_covariance as i32);
+                gl.uniform_1_i32(self.u_oriented_quads.as_ref(), (params.quad_mode == QuadMode::Oriented) as i32);
+                gl.uniform_1_f32(self.u_aa_dilation.as_ref(), params.antialias.dilation);
+                gl.uniform_1_i32(self.u_aa_compensate.as_ref(), params.antialias.compensate as i32);
+                let (explode_center, explode_offset) = params.explode.unwrap_or(([0.0; 3], 0.0));
+                gl.uniform_3_f32_slice(self.u_explode_center.as_ref(), &explode_center);
+                gl.uniform_1_f32(self.u_explode.as_ref(), explode_offset);
+                gl.uniform_1_f32(self.u_min_alpha.as_ref(), Self::MIN_ALPHA);
+
+                gl.active_texture(context::TEXTURE0);
+                gl.bind_texture(context::TEXTURE_2D, splat_glsl.texture);
+
+                gl.bind_vertex_array(self.vao);
+                gl.enable_vertex_attrib_array(self.a_position);
+                gl.bind_buffer(context::ARRAY_BUFFER, splat_glsl.vertex_buffer);
+                gl.vertex_attrib_pointer_f32(self.a_position, 2, context::FLOAT, false, 0, 0);
+
+                // same instances as the last SplatGLSL::render()
+                let instance_count = params.max_splats.max(0).min(splat_glsl.index_count as i32);
+                let first = splat_glsl.index_count as i32 - instance_count;
+                gl.enable_vertex_attrib_array(self.a_index);
+                gl.bind_buffer(context::ARRAY_BUFFER, splat_glsl.index_buffer);
+                gl.vertex_attrib_pointer_i32(self.a_index, 1, context::INT, 0, 4*first);
+                gl.vertex_attrib_divisor(self.a_index, 1);
+
+                gl.draw_arrays_instanced(context::TRIANGLE_FAN, 0, 4, instance_count);
+                gl.bind_vertex_array(None);
+
+                gl.disable(context::DEPTH_TEST);
+            }
+            gl.use_program(None);
+            gl.bind_buffer(context::ARRAY_BUFFER, None);
+            gl.bind_texture(context::TEXTURE_2D, None);
+
+            gl.read_pixels(
+                0,
+                0,
+                1,
+                1,
+                context::RGBA_INTEGER,
+                context::UNSIGNED_INT,
+                context::PixelPackData::Slice(&mut id)
+            );
+            gl.bind_framebuffer(context::FRAMEBUFFER, None);
+        }
+        match u32::from_ne_bytes([id[0], id[1], id[2], id[3]]) {
+            0 => None,
+            id => Some(id - 1),
+        }
+    }
+}
+
+
+/// Debug pass that draws each splat center as a point in the splat color (cf. points.vert)
+/// Reads the positions and colors from the splat texture of a SplatGLSL, so nothing extra is uploaded
+struct PointGLSL {
+    program: Option<context::Program>,
+    vao: Option<context::VertexArray>, // empty, the vertices are indexed by gl_VertexID
+    u_projection: Option<context::UniformLocation>,
+    u_view: Option<context::UniformLocation>,
+    u_splat_texture: Option<context::UniformLocation>,
+    u_half_positions: Option<context::UniformLocation>,
+    u_full_covariance: Option<context::UniformLocation>,
+    u_point_size: Option<context::UniformLocation>,
+    u_explode_center: Option<context::UniformLocation>,
+    u_explode: Option<context::UniformLocation>,
+    u_srgb_to_linear: Option<context::UniformLocation>,
+}
+impl PointGLSL {
+    const VERT_SHADER: &'static str = include_str!("points.vert");
+    const FRAG_SHADER: &'static str = include_str!("points.frag");
+
+
+    pub fn new() -> Self {
+        Self {
+            program: None,
+            vao: None,
+            u_projection: None,
+            u_view: None,
+            u_splat_texture: None,
+            u_half_positions: None,
+            u_full_covariance: None,
+            u_point_size: None,
+            u_explode_center: None,
+            u_explode: None,
+            u_srgb_to_linear: None,
+        }
+    }
+
+
+    pub fn init(
+        &mut self,
+        gl: &Context,
+        error_flag: &Arc<AtomicBool>,
+        error_msg: &Arc<Mutex<String>>
+    ) {
+        let point_program_id = create_glsl_program(
+            gl,
+            Self::VERT_SHADER,
+            Self::FRAG_SHADER,
+            error_flag,
+            error_msg
+        );
+        self.program = Some(point_program_id);
+        log!("PointGLSL::init(): self.program={:?}", self.program);
+
+        unsafe {
+            self.vao = Some(gl.create_vertex_array().unwrap());
+            log!("PointGLSL::init(): self.vao={:?}", self.vao);
+            self.u_projection = gl.get_uniform_location(point_program_id, "projection");
+            log!("PointGLSL::init(): self.u_projection={:?}", self.u_projection);
+            self.u_view = gl.get_uniform_location(point_program_id, "view");
+            log!("PointGLSL::init(): self.u_view={:?}", self.u_view);
+            self.u_splat_texture = gl.get_uniform_location(point_program_id, "u_splat_texture");
+            log!("PointGLSL::init(): self.u_splat_texture={:?}", self.u_splat_texture);
+            self.u_half_positions = gl.get_uniform_location(point_program_id, "half_positions");
+            log!("PointGLSL::init(): self.u_half_positions={:?}", self.u_half_positions);
+            self.u_full_covariance = gl.get_uniform_location(point_program_id, "full_covariance");
+            log!("PointGLSL::init(): self.u_full_covariance={:?}", self.u_full_covariance);
+            self.u_point_size = gl.get_uniform_location(point_program_id, "point_size");
+            log!("PointGLSL::init(): self.u_point_size={:?}", self.u_point_size);
+            self.u_explode_center = gl.get_uniform_location(point_program_id, "u_explode_center");
+            log!("PointGLSL::init(): self.u_explode_center={:?}", self.u_explode_center);
+            self.u_explode = gl.get_uniform_location(point_program_id, "u_explode");
+            log!("PointGLSL::init(): self.u_explode={:?}", self.u_explode);
+            self.u_srgb_to_linear = gl.get_uniform_location(point_program_id, "srgb_to_linear");
+            log!("PointGLSL::init(): self.u_srgb_to_linear={:?}", self.u_srgb_to_linear);
+        }
+    }
+
+
+    /// Draws the centers of the first splat_count splats of the texture uploaded to splat_glsl
+    /// point_size: in pixels, explode: same as for SplatGLSL::render() so that the points follow the splats
+    pub fn render(
+        &self,
+        gl: &Context,
+        splat_glsl: &SplatGLSL,
+        projection_slice: &[f32],
+        view_slice: &[f32],
+        point_size: f32,
+        explode: Option<([f32; 3], f32)>,
+        color_space: ColorSpace,
+        splat_count: i32
+    ) {
+        unsafe {
+            gl.use_program(self.program);
+            {
+                gl.disable(context::DEPTH_TEST);
+                gl.disable(context::BLEND);
+
+                gl.uniform_matrix_4_f32_slice(self.u_projection.as_ref(), false, projection_slice);
+                gl.uniform_matrix_4_f32_slice(self.u_view.as_ref(), false, view_slice);
+                gl.uniform_1_i32(self.u_splat_texture.as_ref(), 0);
+                gl.uniform_1_i32(self.u_half_positions.as_ref(), splat_glsl.half_positions as i32);
+                gl.uniform_1_i32(self.u_full_covariance.as_ref(), splat_glsl.full_covariance as i32);
+                gl.uniform_1_f32(self.u_point_size.as_ref(), point_size);
+                let (explode_center, explode_offset) = explode.unwrap_or(([0.0; 3], 0.0));
+                gl.uniform_3_f32_slice(self.u_explode_center.as_ref(), &explode_center);
+                gl.uniform_1_f32(self.u_explode.as_ref(), explode_offset);
+                gl.uniform_1_i32(self.u_srgb_to_linear.as_ref(), color_space.decode_colors() as i32);
+
+                gl.active_texture(context::TEXTURE0);
+                gl.bind_texture(context::TEXTURE_2D, splat_glsl.texture);
+
+                // a VAO without enabled attributes, so the instanced arrays of the splat pass aren't range-checked
+                gl.bind_vertex_array(self.vao);
+                gl.draw_arrays(context::POINTS, 0, splat_count);
+                gl.bind_vertex_array(None);
+            }
+            gl.use_program(None);
+            gl.bind_texture(context::TEXTURE_2D, None);
+        }
+    }
+}
+
+
+/// Debug pass that draws an axis-aligned box as 12 lines (cf. box.vert), e.g. the crop box
+struct BoxGLSL {
+    program: Option<context::Program>,
+    vao: Option<context::VertexArray>, // empty, the vertices are indexed by gl_VertexID
+    u_projection: Option<context::UniformLocation>,
+    u_view: Option<context::UniformLocation>,
+    u_box_min: Option<context::UniformLocation>,
+    u_box_max: Option<context::UniformLocation>,
+    u_color: Option<context::UniformLocation>,
+}
+impl BoxGLSL {
+    const VERT_SHADER: &'static str = include_str!("box.vert");
+    const FRAG_SHADER: &'static str = include_str!("box.frag");
+
+
+    pub fn new() -> Self {
+        Self {
+            program: None,
+            vao: None,
+            u_projection: None,
+            u_view: None,
+            u_box_min: None,
+            u_box_max: None,
+            u_color: None,
+        }
+    }
+
+
+    pub fn init(
+        &mut self,
+        gl: &Context,
+        error_flag: &Arc<AtomicBool>,
+        error_msg: &Arc<Mutex<String>>
+    ) {
+        let box_program_id = create_glsl_program(
             gl,
             Self::VERT_SHADER,
             Self::FRAG_SHADER,
             error_flag,
             error_msg
         );
-        self.program = Some(gsplat_program_id);
-        log!("SplatGLSL::init(): self.program={:?}", self.program);
+        self.program = Some(box_program_id);
+        log!("BoxGLSL::init(): self.program={:?}", self.program);
 
         unsafe {
-            gl.use_program(self.program);
-            {
-                self.u_projection = gl.get_uniform_location(gsplat_program_id, "projection");
-                log!("SplatGLSL::init(): self.u_projection={:?}", self.u_projection);
-                self.u_viewport = gl.get_uniform_location(gsplat_program_id, "viewport");
-                log!("SplatGLSL::init(): self.u_viewport={:?}", self.u_viewport);
-                self.u_focal = gl.get_uniform_location(gsplat_program_id, "focal");
-                log!("SplatGLSL::init(): self.u_focal={:?}", self.u_focal);
-                self.u_view = gl.get_uniform_location(gsplat_program_id, "view");
-                log!("SplatGLSL::init(): self.u_view={:?}", self.u_view);
-                self.u_htan_fov = gl.get_uniform_location(gsplat_program_id, "htan_fov");
-                log!("SplatGLSL::init(): self.u_htan_fov={:?}", self.u_htan_fov);
-                self.u_cam_pos = gl.get_uniform_location(gsplat_program_id, "cam_pos");
-                log!("SplatGLSL::init(): self.u_cam_pos={:?}", self.u_cam_pos);
-                self.u_splat_scale = gl.get_uniform_location(gsplat_program_id, "splat_scale");
-                log!("SplatGLSL::init(): self.u_splat_scale={:?}", self.u_splat_scale);
-
-                let triangle_vertices = &mut [ // quad
-                    -1_f32, -1.0,
-                    1.0, -1.0,
-                    1.0, 1.0,
-                    -1.0, 1.0,
-                ];
-                triangle_vertices.iter_mut().for_each(|v| *v *= 2.0);
-                self.vertex_buffer = Some(gl.create_buffer().unwrap());
-                log!("SplatGLSL::init(): self.vertex_buffer={:?}", self.vertex_buffer);
-                gl.bind_buffer(context::ARRAY_BUFFER, self.vertex_buffer);
-                gl.buffer_data_u8_slice(context::ARRAY_BUFFER, transmute_slice::<_, u8>(triangle_vertices), context::STATIC_DRAW);
-                self.a_position = gl.get_attrib_location(gsplat_program_id, "position").unwrap();
-                log!("SplatGLSL::init(): self.a_position={:?}", self.a_position);
-                gl.enable_vertex_attrib_array(self.a_position);
-                gl.bind_buffer(context::ARRAY_BUFFER, self.vertex_buffer);
-                gl.vertex_attrib_pointer_f32(self.a_position, 2, context::FLOAT, false, 0, 0);
-
-                self.texture = Some(gl.create_texture().unwrap());
-                log!("SplatGLSL::init(): self.texture={:?}", self.texture); // WebTextureKey(1v1)
-                gl.bind_texture(context::TEXTURE_2D, self.texture);
-                self.u_splat_texture = gl.get_uniform_location(gsplat_program_id, "u_splat_texture");
-                log!("SplatGLSL::init(): self.u_splat_texture={:?}", self.u_splat_texture);
-                gl.uniform_1_i32(self.u_splat_texture.as_ref(), 0); // associate the active texture unit with the uniform
-
-                // index buffer for instanced rendering
-                self.index_buffer = Some(gl.create_buffer().unwrap());
-                log!("SplatGLSL::init(): self.index_buffer={:?}", self.index_buffer);
-                //gl.bind_buffer(context::ARRAY_BUFFER, self.index_buffer);
-                self.a_index = gl.get_attrib_location(gsplat_program_id, "index").unwrap();
-                log!("SplatGLSL::init(): self.a_index={:?}", self.a_index);
-                gl.enable_vertex_attrib_array(self.a_index);
-                gl.bind_buffer(context::ARRAY_BUFFER, self.index_buffer);
-                gl.vertex_attrib_pointer_i32(self.a_index, 1, context::INT, 0, 0);
-                gl.vertex_attrib_divisor(self.a_index, 1);
-            }
-            gl.use_program(None);
-
-            gl.bind_texture(context::TEXTURE_2D, self.texture);
-            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_WRAP_S, context::CLAMP_TO_EDGE as i32);
-            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_WRAP_T, context::CLAMP_TO_EDGE as i32);
-            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MIN_FILTER, context::NEAREST as i32);
-            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MAG_FILTER, context::NEAREST as i32);
-
-            #[cfg(not(feature = "async_splat_stream"))]
-            gl.tex_image_2d(
-                context::TEXTURE_2D,
-                0,
-                context::RGBA32UI as i32,
-                scene.tex_width as i32,
-                scene.tex_height as i32,
-                0,
-                context::RGBA_INTEGER,
-                context::UNSIGNED_INT,
-                Some(transmute_slice::<_, u8>(scene.tex_data.as_slice()))
-            );
-
-            //gl.active_texture(context::TEXTURE0);
-            //gl.bind_texture(context::TEXTURE_2D, self.texture);
-
-            gl.bind_buffer(context::ARRAY_BUFFER, None);
-            gl.bind_texture(context::TEXTURE_2D, None);
+            self.vao = Some(gl.create_vertex_array().unwrap());
+            log!("BoxGLSL::init(): self.vao={:?}", self.vao);
+            self.u_projection = gl.get_uniform_location(box_program_id, "projection");
+            log!("BoxGLSL::init(): self.u_projection={:?}", self.u_projection);
+            self.u_view = gl.get_uniform_location(box_program_id, "view");
+            log!("BoxGLSL::init(): self.u_view={:?}", self.u_view);
+            self.u_box_min = gl.get_uniform_location(box_program_id, "box_min");
+            log!("BoxGLSL::init(): self.u_box_min={:?}", self.u_box_min);
+            self.u_box_max = gl.get_uniform_location(box_program_id, "box_max");
+            log!("BoxGLSL::init(): self.u_box_max={:?}", self.u_box_max);
+            self.u_color = gl.get_uniform_location(box_program_id, "color");
+            log!("BoxGLSL::init(): self.u_color={:?}", self.u_color);
         }
     }
 
 
+    /// Draws the edges of the box (min, max) in world space
+    /// color: premultiplied RGBA
     pub fn render(
         &self,
         gl: &Context,
         projection_slice: &[f32],
         view_slice: &[f32],
-        focal: &[f32],
-        viewport: &[f32],
-        htan_fov: &[f32],
-        cam_pos: &[f32],
-        splat_scale: f32,
-        rx_depth: &mut BusReader<Vec<u32>>,
-        splat_count: i32
+        min: &[f32; 3],
+        max: &[f32; 3],
+        color: &[f32; 4]
     ) {
         unsafe {
             gl.use_program(self.program);
             {
                 gl.disable(context::DEPTH_TEST);
-                gl.disable(context::CULL_FACE);
-                //gl.cull_face(context::FRONT);
-
-                // FIXME
-                gl.enable(context::BLEND);
-                /*
-                gl.clear_color(0.0, 0.0, 0.0, 1.0);
-                gl.blend_func(context::SRC_ALPHA, context::ONE_MINUS_SRC_ALPHA);
-                //gl.blend_func(context::ONE_MINUS_SRC_ALPHA, context::SRC_ALPHA);
-                */
-                /*
-                //gl.clear_color(0.0, 0.0, 0.0, 0.0);
-                gl.blend_func_separate(
-                    context::ONE_MINUS_DST_ALPHA,
-                    context::ONE,
-                    context::ONE_MINUS_DST_ALPHA,
-                    context::ONE,
-                );
-                gl.blend_equation_separate(context::FUNC_ADD, context::FUNC_ADD);
-                */
+                gl.disable(context::BLEND);
 
                 gl.uniform_matrix_4_f32_slice(self.u_projection.as_ref(), false, projection_slice);
                 gl.uniform_matrix_4_f32_slice(self.u_view.as_ref(), false, view_slice);
-                gl.uniform_1_i32(self.u_splat_texture.as_ref(), 0); // associate the active texture unit with the uniform
-                gl.uniform_2_f32_slice(self.u_focal.as_ref(), focal);
-                gl.uniform_2_f32_slice(self.u_viewport.as_ref(), viewport);
-                gl.uniform_2_f32_slice(self.u_htan_fov.as_ref(), htan_fov);
-                gl.uniform_3_f32_slice(self.u_cam_pos.as_ref(), cam_pos);
-                gl.uniform_1_f32(self.u_splat_scale.as_ref(), splat_scale);
-
-                gl.active_texture(context::TEXTURE0);
-                gl.bind_texture(context::TEXTURE_2D, self.texture);
-
-                gl.enable_vertex_attrib_array(self.a_position);
-                gl.bind_buffer(context::ARRAY_BUFFER, self.vertex_buffer);
-                gl.vertex_attrib_pointer_f32(self.a_position, 2, context::FLOAT, false, 0, 0);
-
-                gl.enable_vertex_attrib_array(self.a_index);
-                gl.bind_buffer(context::ARRAY_BUFFER, self.index_buffer);
-                //////////////////////////////////
-                // non-blocking (i.e., no atomic.wait)
-                if let Ok(depth_index) = rx_depth.try_recv() {
-                    gl.buffer_data_u8_slice(
-                        context::ARRAY_BUFFER,
-                        transmute_slice::<_, u8>(depth_index.as_slice()),
-                        context::DYNAMIC_DRAW
-                    );
-                }
-                //////////////////////////////////
-                gl.vertex_attrib_pointer_i32(self.a_index, 1, context::INT, 0, 0);
-                gl.vertex_attrib_divisor(self.a_index, 1);
+                gl.uniform_3_f32_slice(self.u_box_min.as_ref(), min);
+                gl.uniform_3_f32_slice(self.u_box_max.as_ref(), max);
+                gl.uniform_4_f32_slice(self.u_color.as_ref(), color);
 
-                gl.draw_arrays_instanced(
-                    context::TRIANGLE_FAN,
-                    0,
-                    4,
-                    splat_count
-                );
+                gl.bind_vertex_array(self.vao);
+                gl.draw_arrays(context::LINES, 0, 24);
+                gl.bind_vertex_array(None);
             }
             gl.use_program(None);
-            gl.bind_buffer(context::ARRAY_BUFFER, None);
-            gl.bind_texture(context::TEXTURE_2D, None);
+        }
+    }
+}
+
+
+/// Settings of QuadGLSL::render(), the defaults copy the texture to the framebuffer as is
+#[derive(Clone, Copy, Debug)]
+struct QuadRenderParams {
+    dither: f32, // ordered dither amplitude in 8-bit color steps (0 to disable)
+    blend: bool, // composites the (premultiplied) texture over the framebuffer instead of overwriting it
+    linear_to_srgb: bool, // the texture holds linear color that has to be encoded to sRGB
+    uv_scale: [f32; 2], // size of the rendered area relative to the texture (stretched over the whole viewport)
+    aspect: f32, // of the viewport, for the background image
+    dof_blur: f32, // largest depth-of-field blur radius in texels (0 to disable), needs the depth from draw_depth()
+    tone_map: ToneMap,
+    exposure: f32, // in stops, applied with tone_map to the splats (Linear with 0 leaves them untouched)
+    fxaa: bool, // smooths the edges of the rendered image (ignored under the depth of field, which blurs anyway)
+}
+impl Default for QuadRenderParams {
+    fn default() -> Self {
+        Self {
+            dither: 0.0,
+            blend: false,
+            linear_to_srgb: false,
+            uv_scale: [1.0, 1.0],
+            aspect: 1.0,
+            dof_blur: 0.0,
+            tone_map: ToneMap::Linear,
+            exposure: 0.0,
+            fxaa: false,
         }
     }
 }
@@ -488,6 +2005,9 @@ struct QuadGLSL {
     // render to texture
     pub(crate) framebuffer: Option<context::Framebuffer>,
     texture: Option<context::WebTextureKey>,
+    depth_texture: Option<context::WebTextureKey>, // encoded splat depth for the depth of field (cf. gsplat.frag)
+    width: i32, // size of the textures, follows the viewport
+    height: i32,
 
     // textured quad
     program: Option<context::Program>,
@@ -495,6 +2015,22 @@ struct QuadGLSL {
     vbo: Option<context::WebBufferKey>,
     a_position: u32,
     u_screen_texture: Option<context::UniformLocation>,
+    u_dither: Option<context::UniformLocation>,
+    u_linear_to_srgb: Option<context::UniformLocation>,
+    u_uv_scale: Option<context::UniformLocation>,
+    u_depth_texture: Option<context::UniformLocation>,
+    u_dof_blur: Option<context::UniformLocation>,
+    u_tone_map: Option<context::UniformLocation>,
+    u_exposure: Option<context::UniformLocation>,
+    u_fxaa: Option<context::UniformLocation>,
+
+    // background behind the splats
+    background_texture: Option<context::WebTextureKey>,
+    u_background_mode: Option<context::UniformLocation>,
+    u_gradient_top: Option<context::UniformLocation>,
+    u_gradient_bottom: Option<context::UniformLocation>,
+    u_background_texture: Option<context::UniformLocation>,
+    u_background_aspect: Option<context::UniformLocation>,
 }
 impl QuadGLSL {
     const VERT_SHADER: &'static str = include_str!("quad.vert");
@@ -515,12 +2051,30 @@ impl QuadGLSL {
         Self {
             framebuffer: None,
             texture: None,
+            depth_texture: None,
+            width: 0,
+            height: 0,
 
             program: None,
             vao: None,
             vbo: None,
             a_position: 0,
             u_screen_texture: None,
+            u_dither: None,
+            u_linear_to_srgb: None,
+            u_uv_scale: None,
+            u_depth_texture: None,
+            u_dof_blur: None,
+            u_tone_map: None,
+            u_exposure: None,
+            u_fxaa: None,
+
+            background_texture: None,
+            u_background_mode: None,
+            u_gradient_top: None,
+            u_gradient_bottom: None,
+            u_background_texture: None,
+            u_background_aspect: None,
         }
     }
 
@@ -551,17 +2105,7 @@ impl QuadGLSL {
                 self.texture = Some(gl.create_texture().unwrap());
                 log!("QuadGLSL::init(): self.texture={:?}", self.texture);
                 gl.bind_texture(context::TEXTURE_2D, self.texture);
-                gl.tex_image_2d(
-                    context::TEXTURE_2D,
-                    0,
-                    context::RGB as i32,
-                    width,
-                    height,
-                    0,
-                    context::RGB,
-                    context::UNSIGNED_BYTE,
-                    None
-                );
+                self.allocate_texture(gl, width, height);
                 gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MIN_FILTER, context::LINEAR as i32);
                 gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MAG_FILTER, context::LINEAR as i32);
 
@@ -573,6 +2117,22 @@ impl QuadGLSL {
                     0
                 );
 
+                self.depth_texture = Some(gl.create_texture().unwrap());
+                log!("QuadGLSL::init(): self.depth_texture={:?}", self.depth_texture);
+                gl.bind_texture(context::TEXTURE_2D, self.depth_texture);
+                self.allocate_texture(gl, width, height);
+                gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MIN_FILTER, context::NEAREST as i32);
+                gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MAG_FILTER, context::NEAREST as i32);
+                gl.framebuffer_texture_2d(
+                    context::FRAMEBUFFER,
+                    context::COLOR_ATTACHMENT1,
+                    context::TEXTURE_2D,
+                    self.depth_texture,
+                    0
+                );
+                // only the splat pass writes the depth, cf. draw_depth()
+                gl.draw_buffers(&[context::COLOR_ATTACHMENT0]);
+
                 let status = gl.check_framebuffer_status(context::FRAMEBUFFER);
                 if status != context::FRAMEBUFFER_COMPLETE {
                     set_error_for_egui(
@@ -610,6 +2170,41 @@ impl QuadGLSL {
                 self.u_screen_texture = gl.get_uniform_location(quad_program_id, "u_screen_texture");
                 log!("QuadGLSL::init(): self.u_screen_texture={:?}", self.u_screen_texture);
                 gl.uniform_1_i32(self.u_screen_texture.as_ref(), 0); // associate the active texture unit with the uniform
+
+                self.u_dither = gl.get_uniform_location(quad_program_id, "u_dither");
+                log!("QuadGLSL::init(): self.u_dither={:?}", self.u_dither);
+
+                self.u_linear_to_srgb = gl.get_uniform_location(quad_program_id, "u_linear_to_srgb");
+                log!("QuadGLSL::init(): self.u_linear_to_srgb={:?}", self.u_linear_to_srgb);
+
+                self.u_uv_scale = gl.get_uniform_location(quad_program_id, "u_uv_scale");
+                log!("QuadGLSL::init(): self.u_uv_scale={:?}", self.u_uv_scale);
+
+                self.u_depth_texture = gl.get_uniform_location(quad_program_id, "u_depth_texture");
+                log!("QuadGLSL::init(): self.u_depth_texture={:?}", self.u_depth_texture);
+                self.u_dof_blur = gl.get_uniform_location(quad_program_id, "u_dof_blur");
+                log!("QuadGLSL::init(): self.u_dof_blur={:?}", self.u_dof_blur);
+
+                self.u_tone_map = gl.get_uniform_location(quad_program_id, "u_tone_map");
+                log!("QuadGLSL::init(): self.u_tone_map={:?}", self.u_tone_map);
+                self.u_exposure = gl.get_uniform_location(quad_program_id, "u_exposure");
+                log!("QuadGLSL::init(): self.u_exposure={:?}", self.u_exposure);
+                self.u_fxaa = gl.get_uniform_location(quad_program_id, "u_fxaa");
+                log!("QuadGLSL::init(): self.u_fxaa={:?}", self.u_fxaa);
+
+                self.u_background_mode = gl.get_uniform_location(quad_program_id, "u_background_mode");
+                log!("QuadGLSL::init(): self.u_background_mode={:?}", self.u_background_mode);
+                self.u_gradient_top = gl.get_uniform_location(quad_program_id, "u_gradient_top");
+                log!("QuadGLSL::init(): self.u_gradient_top={:?}", self.u_gradient_top);
+                self.u_gradient_bottom = gl.get_uniform_location(quad_program_id, "u_gradient_bottom");
+                log!("QuadGLSL::init(): self.u_gradient_bottom={:?}", self.u_gradient_bottom);
+                self.u_background_texture = gl.get_uniform_location(quad_program_id, "u_background_texture");
+                log!("QuadGLSL::init(): self.u_background_texture={:?}", self.u_background_texture);
+                self.u_background_aspect = gl.get_uniform_location(quad_program_id, "u_background_aspect");
+                log!("QuadGLSL::init(): self.u_background_aspect={:?}", self.u_background_aspect);
+
+                self.background_texture = Some(gl.create_texture().unwrap());
+                log!("QuadGLSL::init(): self.background_texture={:?}", self.background_texture);
             }
             gl.use_program(None);
             gl.bind_vertex_array(None);
@@ -618,14 +2213,128 @@ impl QuadGLSL {
     }
 
 
-    pub fn render(
-        &self,
-        gl: &Context,
-    ) {
+    /// Reallocates the render-to-texture targets for a new viewport size
+    /// Returns false if the size hasn't changed
+    pub fn resize(&mut self, gl: &Context, width: i32, height: i32) -> bool {
+        if width == self.width && height == self.height {
+            return false;
+        }
+        log!("QuadGLSL::resize(): {}x{} -> {}x{}", self.width, self.height, width, height);
+        unsafe {
+            // the framebuffer keeps the textures as its attachments, only the storage is replaced
+            gl.bind_texture(context::TEXTURE_2D, self.texture);
+            self.allocate_texture(gl, width, height);
+            gl.bind_texture(context::TEXTURE_2D, self.depth_texture);
+            self.allocate_texture(gl, width, height);
+            gl.bind_texture(context::TEXTURE_2D, None);
+        }
+        true
+    }
+
+
+    /// Specifies the storage of the bound texture
+    unsafe fn allocate_texture(&mut self, gl: &Context, width: i32, height: i32) {
+        gl.tex_image_2d(
+            context::TEXTURE_2D,
+            0,
+            context::RGBA as i32, // alpha for compositing over the page behind the canvas
+            width.max(1),
+            height.max(1),
+            0,
+            context::RGBA,
+            context::UNSIGNED_BYTE,
+            None
+        );
+        self.width = width;
+        self.height = height;
+    }
+
+
+    /// Enables or disables the depth attachment as a draw buffer of the bound framebuffer
+    /// Enabling also clears it, the splat pass then accumulates the depth like the color
+    pub fn draw_depth(&self, gl: &Context, enabled: bool) {
+        unsafe {
+            if enabled {
+                gl.draw_buffers(&[context::COLOR_ATTACHMENT0, context::COLOR_ATTACHMENT1]);
+                gl.clear_buffer_f32_slice(context::COLOR, 1, &[0.0; 4]);
+            } else {
+                gl.draw_buffers(&[context::COLOR_ATTACHMENT0]);
+            }
+        }
+    }
+
+
+    /// Uploads the background image (RGBA8, top row first)
+    pub fn upload_background(&self, gl: &Context, width: u32, height: u32, pixels: &[u8]) {
+        unsafe {
+            gl.bind_texture(context::TEXTURE_2D, self.background_texture);
+            gl.tex_image_2d(
+                context::TEXTURE_2D,
+                0,
+                context::RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                context::RGBA,
+                context::UNSIGNED_BYTE,
+                Some(pixels)
+            );
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MIN_FILTER, context::LINEAR as i32);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_MAG_FILTER, context::LINEAR as i32);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_WRAP_S, context::CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(context::TEXTURE_2D, context::TEXTURE_WRAP_T, context::CLAMP_TO_EDGE as i32);
+            gl.bind_texture(context::TEXTURE_2D, None);
+        }
+    }
+
+
+    /// background: composited under the texture if background.is_composited()
+    pub fn render(&self, gl: &Context, background: &Background, params: &QuadRenderParams) {
+        let QuadRenderParams { dither, blend, linear_to_srgb, uv_scale, aspect, dof_blur, tone_map, exposure, fxaa } = *params;
         unsafe {
             gl.use_program(self.program);
             {
+                if blend {
+                    gl.enable(context::BLEND);
+                    gl.blend_equation(context::FUNC_ADD);
+                    gl.blend_func(context::ONE, context::ONE_MINUS_SRC_ALPHA);
+                } else {
+                    gl.disable(context::BLEND);
+                }
+
                 gl.uniform_1_i32(self.u_screen_texture.as_ref(), 0);
+                gl.uniform_1_f32(self.u_dither.as_ref(), dither);
+                gl.uniform_1_i32(self.u_linear_to_srgb.as_ref(), linear_to_srgb as i32);
+                gl.uniform_2_f32_slice(self.u_uv_scale.as_ref(), &uv_scale);
+                gl.uniform_1_i32(self.u_depth_texture.as_ref(), 2);
+                gl.uniform_1_f32(self.u_dof_blur.as_ref(), dof_blur);
+                let tone_map = match tone_map {
+                    ToneMap::Linear => 0,
+                    ToneMap::Reinhard => 1,
+                    ToneMap::Aces => 2,
+                };
+                gl.uniform_1_i32(self.u_tone_map.as_ref(), tone_map);
+                gl.uniform_1_f32(self.u_exposure.as_ref(), exposure);
+                gl.uniform_1_i32(self.u_fxaa.as_ref(), fxaa as i32);
+
+                let mode = if !background.is_composited() {
+                    0
+                } else if background.mode == BackgroundMode::Gradient {
+                    1
+                } else {
+                    2
+                };
+                gl.uniform_1_i32(self.u_background_mode.as_ref(), mode);
+                gl.uniform_4_f32_slice(self.u_gradient_top.as_ref(), &background.top);
+                gl.uniform_4_f32_slice(self.u_gradient_bottom.as_ref(), &background.bottom);
+                gl.uniform_1_i32(self.u_background_texture.as_ref(), 1);
+                if let Some((width, height)) = background.image_size {
+                    gl.uniform_1_f32(self.u_background_aspect.as_ref(), (width as f32/height as f32)/aspect);
+                }
+                gl.active_texture(context::TEXTURE1);
+                gl.bind_texture(context::TEXTURE_2D, self.background_texture);
+                gl.active_texture(context::TEXTURE2);
+                gl.bind_texture(context::TEXTURE_2D, self.depth_texture);
 
                 gl.active_texture(context::TEXTURE0);
                 gl.bind_texture(context::TEXTURE_2D, self.texture);
@@ -634,6 +2343,9 @@ impl QuadGLSL {
                 gl.draw_arrays(context::TRIANGLES, 0, 6);
             }
             gl.use_program(None);
+            gl.active_texture(context::TEXTURE2);
+            gl.bind_texture(context::TEXTURE_2D, None);
+            gl.active_texture(context::TEXTURE0);
         }
     }
 }
@@ -661,54 +2373,201 @@ pub async fn main() {
     log!("main(): OpenGL version: {:?}", gl.version());
     let glsl_ver = unsafe { gl.get_parameter_string(context::SHADING_LANGUAGE_VERSION) };
     log!("main(): GLSL version: {}", glsl_ver);
-
-    let fovy = degrees(45.0);
-
+    // bounds the splat textures of all scenes generated from now on
+    let max_texture_size = unsafe { gl.get_parameter_i32(context::MAX_TEXTURE_SIZE) } as usize;
+    log!("main(): MAX_TEXTURE_SIZE={}", max_texture_size);
+    set_max_texture_size(max_texture_size);
+
+    let mut fovy = degrees(45.0);
+
+    // placement of the scene in world space, 3DGS captures are Y-down
+    let mut model = ModelTransform { invert_y: true, ..ModelTransform::default() };
+    let mut prev_model = model;
+    // the URL pose is relative to the scene, so that links keep their viewpoint
+    let (url_position, url_target, url_up) = model.apply_pose(get_position(), get_target(), get_up());
     let mut camera = Camera::new_perspective(
         window.viewport(),
-        get_position(),
-        get_target(),
-        get_up(),
+        url_position,
+        url_target,
+        url_up,
         fovy,
         0.1,//0.2,
         10.0,//200.0,
     );
     let mut orbit_control = OrbitControl2::new(*camera.target(), 1.0, 100.0);
-    let mut fly_control = FlyControl::new(0.005);
+    // orbit around the scene centroid unless the target is given explicitly as a URL param
+    let mut orbit_target_pending = !has_target_param();
+    // and without a position either, move the camera back until the whole scene is in frame
+    let frame_scene = !has_position_param();
+    let mut fly_speed: Option<f32> = None; // set in egui, None = default_fly_speed() of the current scene
     let mut egui_control = TdCameraControl::Orbit;
 
-    // lock-free bus for streamed scene buffer (single-send, multi-consumer)
+    // lock-free bus for streamed scene buffer (single-send, single-consumer)
     let mut bus_buffer = Bus::<Vec::<u8>>::new(1);
-    let rx_buffer_threaded = bus_buffer.add_rx();
     let mut rx_buffer = bus_buffer.add_rx();
     let bus_buffer_rc =  Rc::new(RefCell::new(bus_buffer));
 
+    // lock-free bus for handing a (re)loaded scene over to the sorter thread
+    let mut bus_scene = Bus::<Arc<Scene>>::new(2);
+    let rx_scene_threaded = bus_scene.add_rx();
+
     // lock-free bus for scene buffer (single-send, single-consumer)
     let mut bus_progress = Bus::<f64>::new(10);
     let mut rx_progress = bus_progress.add_rx();
     let bus_progress_rc =  Rc::new(RefCell::new(bus_progress));
 
+    // lock-free bus for a background image (width, height, RGBA8 pixels) loaded from a URL
+    let mut bus_background = Bus::<(u32, u32, Vec<u8>)>::new(1);
+    let mut rx_background = bus_background.add_rx();
+    let bus_background_rc = Rc::new(RefCell::new(bus_background));
+
+    // lock-free bus for a camera pose pasted from the clipboard
+    let mut bus_pose = Bus::<CameraPose>::new(1);
+    let mut rx_pose = bus_pose.add_rx();
+    let bus_pose_rc = Rc::new(RefCell::new(bus_pose));
+
+    // latest decoding progress of a picked file (None while no file is decoded)
+    let file_progress_rc: ProgressCell = Rc::new(Cell::new(None));
+
+    // lock-free bus for a scene picked from a local file via the "Open File" prompt
+    let mut bus_picked = Bus::<Arc<Scene>>::new(1);
+    let mut rx_picked = bus_picked.add_rx();
+    let bus_picked_rc = Rc::new(RefCell::new(bus_picked));
+
+    // lock-free bus for the octree of a .lod scene, whose nodes are then streamed as the camera needs them
+    let mut bus_lod = Bus::<Octree>::new(1);
+    let mut rx_lod = bus_lod.add_rx();
+    let bus_lod_rc = Rc::new(RefCell::new(bus_lod));
+
+    // without a url param, fall back to the asset baked in at build time (GAUZILLA_FALLBACK_URL), if any;
+    // otherwise nothing is fetched and the user is prompted to pick a local file
+    // scenes listed with ?scene=... params can be switched in place, the first one is shown if there is no url param
     let mut url = get_url_param();
+    let mut scene_list = get_scene_list();
     if url.is_empty() {
-        url = "https://huggingface.co/datasets/satyoshi/gauzilla-data/resolve/main/book_store.splat".to_string();
+        url = scene_list.first().cloned().unwrap_or_default();
+    }
+    if url.is_empty() {
+        url = option_env!("GAUZILLA_FALLBACK_URL").unwrap_or_default().to_string();
+    }
+    let has_url = !url.is_empty();
+    if has_url && !scene_list.contains(&url) {
+        scene_list.insert(0, url.clone());
+    }
+    let mut scene_index = scene_list.iter().position(|u| *u == url).unwrap_or(0);
+    log!("main(): url={}, scene_list.len()={}", url, scene_list.len());
+
+    // inline data (data:...;base64,...) is decoded in memory instead of being streamed
+    let mut is_data_url = url.starts_with("data:");
+
+    // one or more comma-separated .spz URLs are downloaded, decoded, and merged into one scene
+    let spz_urls = if is_data_url { None } else { parse_spz_urls(&url) };
+
+    // both are loaded before the render loop starts instead of being streamed
+    let mut loaded_up_front = is_data_url || spz_urls.is_some();
+
+    // .splatv (splat video) is always streamed from the URL and decoded frame by frame
+    let mut is_splatv = !loaded_up_front && SplatvStream::is_splatv_url(&url);
+    let mut splatv: Option<SplatvPlayback> = None;
+    let mut worker_handle: Option<web_sys::Worker> = None;
+    if is_splatv {
+        let stream = Rc::new(RefCell::new(SplatvStream::new()));
+        worker_handle = Some(stream_splatv_in_worker(
+            stream.clone(), bus_progress_rc.clone(), url.clone(), Arc::clone(&error_flag), Arc::clone(&error_msg)
+        ));
+        splatv = Some(SplatvPlayback::new(stream));
+    }
+
+    // .lod (octree index) is streamed node by node from the .splat file next to it
+    let mut is_lod = !loaded_up_front && LodStream::is_lod_url(&url);
+    let mut lod_stream: Option<LodStream> = None;
+    if is_lod {
+        open_lod_in_background(url.clone(), bus_lod_rc.clone(), Arc::clone(&error_flag), Arc::clone(&error_msg));
     }
-    log!("main(): url={}", url);
 
     #[cfg(feature = "async_splat_stream")]
-    let worker_handle = stream_splat_in_worker(bus_buffer_rc, bus_progress_rc, url);
+    if has_url && !is_splatv && !is_lod && !loaded_up_front {
+        worker_handle = Some(stream_splat_in_worker(bus_buffer_rc.clone(), bus_progress_rc.clone(), url.clone()));
+    }
+    // an empty scene still carries the mirror so that .splatv keyframes inherit it
+    let empty_scene = || {
+        let mut s = Scene::new();
+        s.mirror = get_mirror();
+        Arc::new(s)
+    };
+    let mut picked_scene = false; // the current scene came from the file picker (or layers), not from url
     #[cfg(feature = "async_splat_stream")]
     //let mut scene = Scene::new();
-    let mut scene = Arc::new(Scene::new());
+    let mut scene = empty_scene();
     #[cfg(not(feature = "async_splat_stream"))]
-    let scene = Arc::new(load_scene().await);
+    let mut scene = if is_splatv || is_lod || loaded_up_front {
+        empty_scene()
+    } else {
+        match load_scene(None).await {
+            Ok(s) => {
+                picked_scene = true; // not the scene at url, so there is nothing to reload
+                Arc::new(s)
+            },
+            Err(e) => {
+                log!("main(): ERROR: {}", e);
+                set_error_for_egui(&error_flag, &error_msg, format!("ERROR: could not open the selected file: {}", e));
+                empty_scene()
+            },
+        }
+    };
+
+    if is_data_url {
+        scene = match Scene::from_data_url(&url, &get_format_param()).await {
+            Ok(s) => Arc::new(s),
+            Err(e) => {
+                log!("main(): ERROR: {}", e);
+                set_error_for_egui(&error_flag, &error_msg, format!("ERROR: could not load the data URL: {}", e));
+                empty_scene()
+            },
+        };
+    }
+    if let Some(urls) = spz_urls.as_ref() {
+        scene = match Scene::from_spz_urls(urls).await {
+            Ok(s) => Arc::new(s),
+            Err(e) => {
+                log!("main(): ERROR: {}", e);
+                set_error_for_egui(&error_flag, &error_msg, format!("ERROR: could not load the .spz files: {}", e));
+                empty_scene()
+            },
+        };
+    }
 
     let mut splat_glsl = SplatGLSL::new();
     splat_glsl.init(&gl, &error_flag, &error_msg, &scene);
+    #[cfg(not(feature = "async_splat_stream"))]
+    if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+        set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+    }
+    #[cfg(feature = "async_splat_stream")]
+    if loaded_up_front {
+        if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+            set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+        }
+    }
+    if orbit_target_pending && scene.splat_count > 0 {
+        place_camera(&mut camera, &mut orbit_control, &scene, &model, fovy, frame_scene);
+        orbit_target_pending = false;
+    }
+    let mut fly_control_speed = default_fly_speed(&scene); // speed fly_control was built with
+    let mut fly_control = FlyControl::new(fly_control_speed);
 
     let mut quad_glsl = QuadGLSL::new();
     quad_glsl.init(&gl, &error_flag, &error_msg, canvas_w as i32, canvas_h as i32);
 
-    // TODO: implement resize() for change in window size
+    let mut point_glsl = PointGLSL::new();
+    point_glsl.init(&gl, &error_flag, &error_msg);
+    let mut splat_id_glsl = SplatIdGLSL::new();
+    splat_id_glsl.init(&gl, &error_flag, &error_msg);
+    let mut box_glsl = BoxGLSL::new();
+    box_glsl.init(&gl, &error_flag, &error_msg);
+
+    // mobile browsers may drop the WebGL context under memory pressure
+    register_webgl_context_handlers();
 
     // lock-free bus for depth_index
     let mut bus_depth_threaded = Bus::<Vec<u32>>::new(10);
@@ -718,57 +2577,386 @@ pub async fn main() {
     let mut bus_vp = Bus::<Mat4>::new(10);
     let rx_vp_threaded: BusReader<Matrix4<f32>> = bus_vp.add_rx();
 
+    // lock-free bus for sort settings
+    let mut bus_sort_settings = Bus::<SortSettings>::new(10);
+    let rx_sort_settings_threaded = bus_sort_settings.add_rx();
+
     // lock-free bus for sort_time
     let mut bus_time_threaded = Bus::<f64>::new(10);
     let mut rx_time = bus_time_threaded.add_rx();
 
+    // lock-free buses for the idle refinement sort (request: view_proj, reply: sort_time)
+    let mut bus_refine = Bus::<Mat4>::new(10);
+    let rx_refine_threaded = bus_refine.add_rx();
+    let mut bus_refined_threaded = Bus::<f64>::new(10);
+    let mut rx_refined = bus_refined_threaded.add_rx();
+
     let thread_handle = launch_sorter_thread(
         scene.clone(),
-        rx_buffer_threaded,
+        rx_scene_threaded,
         rx_vp_threaded,
+        rx_sort_settings_threaded,
+        rx_refine_threaded,
         bus_depth_threaded,
         cpu_cores,
         bus_time_threaded,
+        bus_refined_threaded,
+        SortAlgorithm::from_param(&get_sort_param()), // ?sort=radix
     );
 
+    // the views it can handle are sorted on the GPU instead of the sorter thread
+    #[cfg(feature = "webgpu_sort")]
+    let mut gpu_sorter = GpuSorter::new();
+
     /////////////////////////////////////////////////////////////////////////////////
 
     let mut gui = three_d::GUI::new(&gl);
     let mut pointer_over_gui = false;
-    let mut splat_scale = 1_f32;
+    let mut keyboard_over_gui = false;
+    let mut splat_scale = api::get_splat_scale();
+    let mut clear_color = [0.0_f32, 0.0, 0.0, 1.0];
+    let mut preserve_canvas = false;
     let mut cam_roll = 0_f32;
     let mut prev_cam_roll = 0_f32;
-    let mut flip_y = true;
-    let mut frame_prev = get_time_milliseconds();
-    let mut fps_ma = IncrementalMA::new(100);
+    let mut clock = FrameClock::new(get_time_milliseconds());
     let mut sort_time = 0_f64;
     let mut sort_time_ma = IncrementalMA::new(100);
     let mut send_view_proj: bool = true;
     let mut progress = 0_f64;
+    let mut rx_streamed: Option<BusReader<Scene>> = None; // scene built from the streamed buffer
     let mut s_temp = Scene::new();
+    let mut brush = OpacityBrush::new();
+    let mut undo_brush = false;
+    let mut reload_scene = false;
+    let mut switch_scene: Option<usize> = None; // index into scene_list
+    let mut prefetch_scenes = true; // download the other scenes of scene_list once the first one is shown
+    let mut scenes_prefetched = false;
+    let mut half_positions = false;
+    let mut full_covariance = false;
+    let mut max_sh_degree = 3_usize;
+    let mut view_dependent = true; // render the SH bands of the texture (false: flat DC color without repacking)
+    let mut scale_clamp: Option<f32> = None; // percentile of the splat scales to clamp outliers to
+    let mut scale_clamp_percentile = 99.0_f32; // as set in egui
+    let mut mirror = get_mirror();
+    let mut world_scale = 1_f32; // scene units per source file unit
+    let mut repack_scene = false;
+    let mut copy_stats = false;
+    let mut copy_camera = false;
+    let mut frame_camera = false; // orbit around the centroid with the whole scene in frame
+    let mut save_screenshot = false;
+    let mut paste_camera = false;
+    let mut export_json = false;
+    let mut export_spz = false;
+    let mut export_lod_files = false;
+    let mut recording = false; // the canvas is being captured by a MediaRecorder
+    let mut toggle_recording = false;
+    let mut record_fps = 30_u32;
+    let mut record_hide_gui = true; // keeps egui out of the video (Esc stops the recording)
+    let mut record_start = 0_f64;
+    let mut open_file = false;
+    let mut add_layer = false; // the picked file is added to the layers instead of replacing the scene
+    // the scenes merged into scene (empty while a single scene is shown), and the last merge
+    // loading, editing, or rebuilding the scene in any other way flattens the layers
+    let mut layers = Vec::<SceneLayer>::new();
+    let mut merged_scene: Option<Arc<Scene>> = None;
+    let mut merge_layers = false;
+    let mut validation: Option<Vec<String>> = None; // problems found by the last Scene::validate()
+    let mut background = Background::new();
+    let mut load_background = false;
+    let mut blend_mode = BlendMode::Over;
+    let mut quad_mode = QuadMode::ScreenAligned;
+    let mut color_space = ColorSpace::Srgb;
+    let mut tone_map = ToneMap::Linear;
+    let mut exposure = 0.0_f32; // in stops
+    let mut render_scale = 1_f32; // resolution of the splat pass relative to the canvas
+    let mut quality_preset: Option<QualityPreset> = None;
+    let mut apply_quality_preset = true; // pick the preset for the next loaded scene
+    let mut skip_additive_sort = true;
+    let mut context_lost = false;
+    let mut sort_settings = SortSettings::default();
+    let mut prev_sort_settings = sort_settings.clone();
+    let mut idle_sort = IdleDetector::new(500.0);
+    let mut draw_cap = DrawCap::new();
+    let mut gpu_timer = GpuTimer::new(&gl);
+    let mut near_fade = 0_f32; // fade distance in front of the near plane (0 = off)
+    let mut antialias = Antialias::default();
+    let mut explode = 0_f32; // offset of the splats from the scene center in scene radii
+    let mut explode_anim: Option<f64> = None; // milliseconds into the explode animation
+    let mut show_points = false; // draw the splat centers as points (for debugging alignment)
+    let mut points_only = false; // without the splats
+    let mut point_size = 2_f32; // in pixels
+    let mut crop = false; // sort and draw only the splats inside crop_box
+    let mut crop_box: Option<([f32; 3], [f32; 3])> = None; // (min, max) in scene coordinates (before the model transform), the scene bounds until edited
+    let mut show_crop_box = true; // draw crop_box as lines
+    let mut global_alpha = api::get_global_alpha(); // opacity multiplier for the whole cloud (e.g. for crossfades)
+    let mut fade_in = true; // ramp up the opacity after a new scene has been uploaded
+    let mut fade_in_pending = true; // a new scene (or the one loaded before the loop) waits for its first sort
+    let mut fade_in_elapsed: Option<f64> = None; // milliseconds since the first sort of a new scene was drawn
+    let mut hover_highlight = false;
+    let mut hovered: Option<u32> = None; // splat under the cursor
+    let mut double_click = DoubleClick::new(400.0, 6.0); // double-clicking a splat makes it the orbit target
+    let mut inspect_splat = false; // clicking picks a splat with the ID pass
+    let mut inspect_press: Option<(f32, f32)> = None; // where the left button went down
+    let mut pick_pixel: Option<(f32, f32)> = None; // cursor of a click to be picked after the splat pass
+    let mut inspected: Option<Option<u32>> = None; // result of the last pick (Some(None): no splat)
+    let mut dither = false;
+    let mut dither_strength = 1.0_f32; // in 8-bit color steps
+    let mut fxaa = false;
+    let mut depth_of_field = false;
+    let mut dof_focus = 5.0_f32; // view-space distance that stays sharp
+    let mut dof_blur = 8.0_f32; // blur radius in render pixels far from the focal plane
+    let mut refined_sort_time: Option<f64> = None; // set once the idle refinement has been rendered
+    let mut sorted_on_gpu = false; // the last view was sorted on the GPU (full precision, nothing to refine)
+
+    #[cfg(not(feature = "async_splat_stream"))]
+    let mut done_streaming = !is_splatv && !is_lod;
+    #[cfg(feature = "async_splat_stream")]
+    let mut done_streaming = loaded_up_front || !has_url;
+
+    window.render_loop(move |mut frame_input| {
+        let error_flag = Arc::clone(&error_flag);
+        let error_msg = Arc::clone(&error_msg);
+
+        clock.tick(get_time_milliseconds());
+        let now = clock.now();
+        let fps = clock.fps();
+
+        // no GL calls into a dead context
+        if is_webgl_context_lost() {
+            if !context_lost {
+                log!("main(): WebGL context lost");
+                context_lost = true;
+            }
+            return FrameOutput::default();
+        }
+        if take_webgl_context_restored() {
+            log!("main(): WebGL context restored, re-initializing GL resources");
+            splat_glsl = SplatGLSL::new();
+            splat_glsl.init(&gl, &error_flag, &error_msg, &scene);
+            if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+                set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+            }
+            if brush.has_edits() {
+                for row in 0..scene.tex_height {
+                    splat_glsl.upload_texture_row(&gl, scene.tex_width, row, &brush.row_data(&scene, row));
+                }
+            }
+
+            quad_glsl = QuadGLSL::new();
+            quad_glsl.init(
+                &gl, &error_flag, &error_msg,
+                frame_input.viewport.width as i32, frame_input.viewport.height as i32
+            );
+            if let Some((width, height)) = background.image_size {
+                quad_glsl.upload_background(&gl, width, height, &background.image_pixels);
+            }
+
+            point_glsl = PointGLSL::new();
+            point_glsl.init(&gl, &error_flag, &error_msg);
+            splat_id_glsl = SplatIdGLSL::new();
+            splat_id_glsl.init(&gl, &error_flag, &error_msg);
+            box_glsl = BoxGLSL::new();
+            box_glsl.init(&gl, &error_flag, &error_msg);
+
+            gui = three_d::GUI::new(&gl);
+            gpu_timer = GpuTimer::new(&gl); // the old queries died with the context
+
+            // the index buffer is gone as well, so force a new depth sort
+            scene.invalidate_sort();
+            send_view_proj = true;
+            context_lost = false;
+        }
+
+        // the window (and with it the canvas) has been resized
+        // focal, htan_fov and the viewport uniforms follow camera.viewport() below, only the screen-sized texture is reallocated
+        if quad_glsl.resize(&gl, frame_input.viewport.width as i32, frame_input.viewport.height as i32) {
+            send_view_proj = true; // the aspect ratio and with it the frustum have changed
+        }
+
+        // decoding progress of a picked file (None once it's done or failed)
+        let file_progress = file_progress_rc.get();
+
+        // the host page may have changed the splat scale/opacity/background/modes since the last frame
+        splat_scale = api::get_splat_scale();
+        global_alpha = api::get_global_alpha();
+        clear_color.copy_from_slice(&api::get_clear_color());
+        preserve_canvas = api::get_preserve_canvas();
+        blend_mode = BlendMode::ALL[api::get_blend_mode()];
+        egui_control = if api::get_control_mode() == 0 { TdCameraControl::Orbit } else { TdCameraControl::Fly };
+        fly_speed = api::get_fly_speed();
+        sort_settings.lod = api::get_lod();
+        cam_roll = api::get_camera_roll();
+        if let Some([position, target, up]) = api::take_camera_pose() {
+            look_at(&mut camera, &mut orbit_control, position.into(), target.into(), up.into());
+            // the up vector already has the orientation, so the roll starts over from it
+            cam_roll = 0.0;
+            prev_cam_roll = 0.0;
+            send_view_proj = true;
+        }
+
+        if !error_flag.load(Ordering::Relaxed) {
+            /////////////////////////////////////////////////////////////////////////////////////
+            // receive sort_time from the second thread
+            if let Ok(f) = rx_time.try_recv() {
+                sort_time = sort_time_ma.add(f);
+            }
+            #[cfg(feature = "webgpu_sort")]
+            if let Some(f) = gpu_sorter.take_sort_time() {
+                sort_time = sort_time_ma.add(f);
+            }
+            if let Ok(f) = rx_refined.try_recv() {
+                refined_sort_time = Some(f);
+            }
+
+            // switch to another scene of the list in place
+            if let Some(i) = switch_scene.take() {
+                url = scene_list[i].clone();
+                scene_index = i;
+                log!("main(): switching to url={}", url);
+                if let Some(w) = worker_handle.take() {
+                    w.terminate();
+                }
+                splatv = None;
+                lod_stream = None;
+                picked_scene = false;
+                is_data_url = url.starts_with("data:");
+                let spz_urls = if is_data_url { None } else { parse_spz_urls(&url) };
+                loaded_up_front = is_data_url || spz_urls.is_some();
+                is_splatv = !loaded_up_front && SplatvStream::is_splatv_url(&url);
+                is_lod = !loaded_up_front && LodStream::is_lod_url(&url);
+                if loaded_up_front {
+                    // decoded in memory and handed over like a picked file
+                    let bus_picked_rc = bus_picked_rc.clone();
+                    let error_flag = Arc::clone(&error_flag);
+                    let error_msg = Arc::clone(&error_msg);
+                    let url = url.clone();
+                    execute_future(async move {
+                        let result = match spz_urls {
+                            Some(urls) => Scene::from_spz_urls(&urls).await,
+                            None => Scene::from_data_url(&url, &get_format_param()).await,
+                        };
+                        match result {
+                            Ok(s) => {
+                                let _ = bus_picked_rc.borrow_mut().try_broadcast(Arc::new(s));
+                            },
+                            Err(e) => {
+                                log!("main(): ERROR: {}", e);
+                                set_error_for_egui(&error_flag, &error_msg, format!("ERROR: could not load the scene: {}", e));
+                            },
+                        }
+                    });
+                    done_streaming = true;
+                } else {
+                    reload_scene = true;
+                }
+            }
+
+            // re-run the streaming pipeline for the current URL in place
+            if reload_scene {
+                log!("main(): reloading url={}", url);
+                if let Some(w) = worker_handle.take() {
+                    w.terminate();
+                }
+                if is_splatv {
+                    let stream = Rc::new(RefCell::new(SplatvStream::new()));
+                    worker_handle = Some(stream_splatv_in_worker(
+                        stream.clone(), bus_progress_rc.clone(), url.clone(), Arc::clone(&error_flag), Arc::clone(&error_msg)
+                    ));
+                    splatv = Some(SplatvPlayback::new(stream));
+                } else if is_lod {
+                    lod_stream = None;
+                    open_lod_in_background(url.clone(), bus_lod_rc.clone(), Arc::clone(&error_flag), Arc::clone(&error_msg));
+                } else {
+                    worker_handle = Some(stream_splat_in_worker(bus_buffer_rc.clone(), bus_progress_rc.clone(), url.clone()));
+                }
+                done_streaming = false;
+                progress = 0.0;
+                reload_scene = false;
+            }
 
-    #[cfg(not(feature = "async_splat_stream"))]
-    let done_streaming = true;
-    #[cfg(feature = "async_splat_stream")]
-    let mut done_streaming = false;
+            // regenerate the texture in the newly selected position/covariance format/mirror/SH degree/world scale
+            if repack_scene {
+                let pack = PackSettings { half_positions, full_covariance, mirror, max_sh_degree, world_scale, scale_clamp };
+                if scene.pack_settings() != pack && !scene.buffer.is_empty() {
+                    scene = Arc::new(scene.rebuilt(&pack));
+                    crop_box = None; // mirroring and rescaling move the splats
+                    log!(
+                        "main(): rebuilt texture, half_positions={}, full_covariance={}, mirror={:?}, max_sh_degree={}, world_scale={}, tex_height={}",
+                        half_positions, full_covariance, mirror, max_sh_degree, world_scale, scene.tex_height
+                    );
+                    if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+                        set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+                    }
+                    if brush.has_edits() {
+                        for row in 0..scene.tex_height {
+                            splat_glsl.upload_texture_row(&gl, scene.tex_width, row, &brush.row_data(&scene, row));
+                        }
+                    }
 
-    window.render_loop(move |mut frame_input| {
-        let error_flag = Arc::clone(&error_flag);
-        let error_msg = Arc::clone(&error_msg);
+                    //////////////////////////////////
+                    // non-blocking (i.e., no atomic.wait)
+                    let _ = bus_scene.try_broadcast(scene.clone());
+                    //////////////////////////////////
+                    send_view_proj = true;
+                }
+                repack_scene = false;
+            }
+
+            // receive a background image
+            if let Ok((width, height, pixels)) = rx_background.try_recv() {
+                quad_glsl.upload_background(&gl, width, height, &pixels);
+                background.image_size = Some((width, height));
+                background.image_pixels = pixels;
+            }
 
-        let now =  get_time_milliseconds();
-        let fps =  1000.0 / (now - frame_prev);
-        frame_prev = now;
-        let fps = fps_ma.add(fps);
+            // receive a camera pose pasted from the clipboard
+            if let Ok(pose) = rx_pose.try_recv() {
+                log!("main(): pasted camera pose: {:?}", pose);
+                look_at(&mut camera, &mut orbit_control, pose.position.into(), pose.target.into(), pose.up.into());
+                fovy = degrees(pose.fovy_degrees);
+                camera.set_perspective_projection(fovy, camera.z_near(), camera.z_far());
+                // the pasted up vector is already rolled, so mark the roll as applied to keep it from being applied twice
+                cam_roll = pose.roll_degrees.clamp(-180.0, 180.0);
+                prev_cam_roll = cam_roll;
+                send_view_proj = true;
+            }
 
-        if !error_flag.load(Ordering::Relaxed) {
-            /////////////////////////////////////////////////////////////////////////////////////
-            // receive sort_time from the second thread
-            if let Ok(f) = rx_time.try_recv() {
-                sort_time = sort_time_ma.add(f);
+            // receive a scene picked from a local file
+            if let Ok(picked) = rx_picked.try_recv() {
+                let pack = PackSettings { half_positions, full_covariance, mirror, max_sh_degree, world_scale, scale_clamp };
+                let picked = Arc::new(picked.rebuilt(&pack));
+                if add_layer {
+                    if layers.is_empty() && scene.splat_count > 0 {
+                        layers.push(SceneLayer::new(scene.clone(), format!("1: {}", scene.source_format)));
+                    }
+                    let name = format!("{}: {}", layers.len() + 1, picked.source_format);
+                    layers.push(SceneLayer::new(picked, name));
+                    merge_layers = true;
+                    add_layer = false;
+                } else {
+                    scene = picked;
+                    picked_scene = true;
+                    if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+                        set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+                    }
+                    fade_in_pending = true;
+
+                    //////////////////////////////////
+                    // non-blocking (i.e., no atomic.wait)
+                    let _ = bus_scene.try_broadcast(scene.clone());
+                    //////////////////////////////////
+                    brush.reset();
+                    hovered = None;
+                    crop_box = None; // the bounds of the new scene
+                    apply_quality_preset = true;
+                    if orbit_target_pending && scene.splat_count > 0 {
+                        place_camera(&mut camera, &mut orbit_control, &scene, &model, fovy, frame_scene);
+                        orbit_target_pending = false;
+                    }
+                    send_view_proj = true;
+                }
             }
 
-            #[cfg(feature = "async_splat_stream")]
             if !done_streaming {
                 // receive progress from async JS worker callback
                 if let Ok(pct) = rx_progress.try_recv() {
@@ -777,25 +2965,51 @@ pub async fn main() {
 
                 // receive splat binary buffer from async JS worker callback
                 if let Ok(buffer) = rx_buffer.try_recv() {
-                    let mut s = Scene::new();
-                    s.buffer = buffer;
-                    s.splat_count = s.buffer.len() / 32; // 32bytes per splat
-                    s.generate_texture();
+                    // the scene is built on a thread that can wait for the texture workers (the main thread can't)
+                    let mut bus_streamed = Bus::<Scene>::new(1);
+                    rx_streamed = Some(bus_streamed.add_rx());
+                    thread::spawn(move || {
+                        let mut s = Scene::new();
+                        if is_splat_f32(&buffer) {
+                            s.load_splat_f32(&buffer);
+                        } else {
+                            s.buffer = buffer;
+                            s.splat_count = s.buffer.len() / 32; // 32bytes per splat
+                        }
+                        s.half_positions = half_positions;
+                        s.full_covariance = full_covariance;
+                        s.source_format = "splat";
+                        s.set_mirror(mirror);
+                        s.set_world_scale(world_scale);
+                        s.scale_clamp = scale_clamp;
+                        s.compute_bounding_box();
+                        s.compute_importance();
+                        s.generate_texture(cpu_cores);
+                        let _ = bus_streamed.try_broadcast(s);
+                    });
+                }
+                if let Some(Ok(s)) = rx_streamed.as_mut().map(|rx| rx.try_recv()) {
+                    rx_streamed = None;
                     scene = Arc::new(s);
-
-                    unsafe {
-                        gl.bind_texture(context::TEXTURE_2D, splat_glsl.texture);
-                        gl.tex_image_2d(
-                            context::TEXTURE_2D,
-                            0,
-                            context::RGBA32UI as i32,
-                            scene.tex_width as i32,
-                            scene.tex_height as i32,
-                            0,
-                            context::RGBA_INTEGER,
-                            context::UNSIGNED_INT,
-                            Some(transmute_slice::<_, u8>(scene.tex_data.as_slice()))
-                        );
+                    if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+                        set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+                    }
+                    fade_in_pending = true;
+
+                    //////////////////////////////////
+                    // non-blocking (i.e., no atomic.wait)
+                    let _ = bus_scene.try_broadcast(scene.clone());
+                    //////////////////////////////////
+                    if let Some(w) = worker_handle.take() {
+                        w.terminate(); // no longer need to receive buffer
+                    }
+                    brush.reset();
+                    hovered = None;
+                    crop_box = None; // the bounds of the new scene
+                    apply_quality_preset = true;
+                    if orbit_target_pending {
+                        place_camera(&mut camera, &mut orbit_control, &scene, &model, fovy, frame_scene);
+                        orbit_target_pending = false;
                     }
 
                     done_streaming = true;
@@ -814,7 +3028,7 @@ pub async fn main() {
                     log!("main(): done streaming");
                     worker_handle.terminate(); // no longer need to receive buffer
 
-                    scene.generate_texture();
+                    scene.generate_texture(1);
                     unsafe {
                         gl.bind_texture(context::TEXTURE_2D, splat_texture);
                         gl.tex_image_2d(
@@ -836,6 +3050,126 @@ pub async fn main() {
                 */
             }
 
+            // receive the octree of a .lod scene and start streaming into an empty scene
+            if let Ok(octree) = rx_lod.try_recv() {
+                let stream = LodStream::new(&url, octree);
+                let pack = PackSettings { half_positions, full_covariance, mirror, max_sh_degree, world_scale, scale_clamp };
+                scene = Arc::new(stream.empty_scene(&pack));
+                lod_stream = Some(stream);
+                if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+                    set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+                }
+                fade_in_pending = true;
+
+                //////////////////////////////////
+                // non-blocking (i.e., no atomic.wait)
+                let _ = bus_scene.try_broadcast(scene.clone());
+                //////////////////////////////////
+                brush.reset();
+                hovered = None;
+                crop_box = None;
+                apply_quality_preset = true;
+                done_streaming = true;
+                send_view_proj = true;
+            }
+
+            // the streamed scene has been replaced (e.g. by a picked file or merged layers)
+            if lod_stream.is_some() && scene.octree.is_none() {
+                lod_stream = None;
+            }
+
+            // append the .lod nodes downloaded since the last frame, uploading only the texture rows they were packed into
+            if let Some(stream) = lod_stream.as_mut() {
+                if let Some(e) = stream.take_error() {
+                    set_error_for_egui(&error_flag, &error_msg, format!("ERROR: .lod: {}", e));
+                }
+                if let Some((next, tex_rows)) = stream.append_arrived(&scene) {
+                    scene = Arc::new(next);
+                    match tex_rows {
+                        Some(tex_rows) => {
+                            let row_len = scene.tex_width*4;
+                            for row in tex_rows {
+                                splat_glsl.upload_texture_row(
+                                    &gl, scene.tex_width, row,
+                                    &scene.tex_data[row*row_len..(row + 1)*row_len]
+                                );
+                            }
+                        },
+                        None => {
+                            if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+                                set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+                            }
+                        },
+                    }
+                    // the painted alphas were for fewer splats
+                    brush.reset();
+                    hovered = None;
+
+                    //////////////////////////////////
+                    // non-blocking (i.e., no atomic.wait)
+                    let _ = bus_scene.try_broadcast(scene.clone());
+                    //////////////////////////////////
+                    if orbit_target_pending {
+                        place_camera(&mut camera, &mut orbit_control, &scene, &model, fovy, frame_scene);
+                        orbit_target_pending = false;
+                    }
+                    send_view_proj = true; // also requests the nodes that didn't fit into the last batch
+                }
+            }
+
+            // play back .splatv frames at the frame rate of the stream
+            if let Some(playback) = splatv.as_mut() {
+                match playback.advance(&scene, now) {
+                    Ok(Some(update)) => {
+                        match update {
+                            SplatvUpdate::Key(s) => {
+                                scene = s;
+                                if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+                                    set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+                                }
+                                fade_in_pending = true;
+                                // a keyframe may reorder the splats, so the painted alphas no longer apply
+                                brush.reset();
+                                hovered = None;
+                            },
+                            SplatvUpdate::Delta(s, tex_rows) => {
+                                scene = s;
+                                let row_len = scene.tex_width*4;
+                                for row in tex_rows {
+                                    if brush.has_edits() {
+                                        splat_glsl.upload_texture_row(&gl, scene.tex_width, row, &brush.row_data(&scene, row));
+                                    } else {
+                                        splat_glsl.upload_texture_row(
+                                            &gl, scene.tex_width, row,
+                                            &scene.tex_data[row*row_len..(row + 1)*row_len]
+                                        );
+                                    }
+                                }
+                            },
+                        }
+
+                        //////////////////////////////////
+                        // non-blocking (i.e., no atomic.wait)
+                        let _ = bus_scene.try_broadcast(scene.clone());
+                        //////////////////////////////////
+                        if orbit_target_pending {
+                            place_camera(&mut camera, &mut orbit_control, &scene, &model, fovy, frame_scene);
+                            orbit_target_pending = false;
+                        }
+                        done_streaming = true;
+                        send_view_proj = true;
+                    },
+                    Ok(None) => {}, // not due yet or waiting for more data
+                    Err(e) => {
+                        set_error_for_egui(&error_flag, &error_msg, format!("ERROR: .splatv: {}", e));
+                        if let Some(w) = worker_handle.take() {
+                            w.terminate();
+                        }
+                        splatv = None;
+                    },
+                }
+            }
+
             /////////////////////////////////////////////////////////////////////////////////////
 
             camera.set_viewport(frame_input.viewport);
@@ -868,6 +3202,151 @@ pub async fn main() {
                 */
             }
 
+            // opacity brush consumes left mouse drags so that the camera stays still while painting
+            if brush.enabled && !pointer_over_gui {
+                let view_proj = camera.projection() * camera.view() * model.matrix();
+                let viewport = camera.viewport();
+                for event in frame_input.events.iter_mut() {
+                    match event {
+                        Event::MousePress { button: MouseButton::Left, handled, .. } => {
+                            brush.begin_stroke();
+                            *handled = true;
+                        },
+                        Event::MouseMotion { button: Some(MouseButton::Left), position, handled, .. } => {
+                            let rows = brush.paint(&scene, &view_proj, viewport, (position.x, position.y));
+                            for row in rows {
+                                splat_glsl.upload_texture_row(&gl, scene.tex_width, row, &brush.row_data(&scene, row));
+                            }
+                            *handled = true;
+                        },
+                        Event::MouseRelease { button: MouseButton::Left, handled, .. } => {
+                            brush.end_stroke();
+                            *handled = true;
+                        },
+                        _ => {}
+                    }
+                }
+            }
+            // highlight the splat under the cursor
+            if hover_highlight && !pointer_over_gui {
+                let cursor = frame_input.events.iter().rev().find_map(|event| match event {
+                    Event::MouseMotion { position, .. } => Some((position.x, position.y)),
+                    _ => None,
+                });
+                if let Some(cursor) = cursor {
+                    let view_proj = camera.projection() * camera.view() * model.matrix();
+                    hovered = pick_splat(&scene, &view_proj, camera.viewport(), cursor, 8.0);
+                }
+            } else {
+                hovered = None;
+            }
+            // double-click a splat to orbit around it (a miss keeps the current target)
+            if !brush.enabled && !pointer_over_gui {
+                for event in frame_input.events.iter() {
+                    if let Event::MousePress { button: MouseButton::Left, position, .. } = event {
+                        let cursor = (position.x, position.y);
+                        if !double_click.press(now, cursor) {
+                            continue;
+                        }
+                        let view_proj = camera.projection() * camera.view() * model.matrix();
+                        if let Some(i) = pick_splat(&scene, &view_proj, camera.viewport(), cursor, 8.0) {
+                            let f_buffer: &[f32] = transmute_slice::<_, f32>(scene.buffer.as_slice());
+                            let index_f = 8*i as usize;
+                            let target = model.apply(vec3(f_buffer[index_f + 0], f_buffer[index_f + 1], f_buffer[index_f + 2]));
+                            let position = *camera.position();
+                            let up = *camera.up();
+                            look_at(&mut camera, &mut orbit_control, position, target, up);
+                            send_view_proj = true;
+                            log!("main(): orbit target set to splat {} at {:?}", i, target);
+                        }
+                    }
+                }
+            }
+            // a click (not an orbit drag) inspects the splat under the cursor
+            if inspect_splat && !brush.enabled && !pointer_over_gui {
+                for event in frame_input.events.iter() {
+                    match event {
+                        Event::MousePress { button: MouseButton::Left, position, .. } => {
+                            inspect_press = Some((position.x, position.y));
+                        },
+                        Event::MouseRelease { button: MouseButton::Left, position, .. } => {
+                            if let Some((x, y)) = inspect_press.take() {
+                                if (position.x - x).abs() <= 4.0 && (position.y - y).abs() <= 4.0 {
+                                    pick_pixel = Some((position.x, position.y));
+                                }
+                            }
+                        },
+                        _ => {}
+                    }
+                }
+            }
+            if undo_brush {
+                for row in brush.undo(&scene) {
+                    splat_glsl.upload_texture_row(&gl, scene.tex_width, row, &brush.row_data(&scene, row));
+                }
+                undo_brush = false;
+            }
+
+            // Esc stops a recording (the GUI may be hidden)
+            if recording {
+                for event in frame_input.events.iter_mut() {
+                    if let Event::KeyPress { kind: Key::Escape, handled, .. } = event {
+                        toggle_recording = true;
+                        *handled = true;
+                    }
+                }
+            }
+
+            // fine camera nudge: arrows move camera and target together (shift: forward/backward),
+            // brackets roll by 1 degree
+            // one step per key press (or key repeat), so unlike the animations it doesn't depend on the frame rate
+            if !keyboard_over_gui {
+                let distance = camera.target().distance(*camera.position());
+                let step = 0.01*distance.max(0.1);
+                for event in frame_input.events.iter_mut() {
+                    match event {
+                        Event::KeyPress { kind, modifiers, handled } => {
+                            let right = camera.right_direction().normalize();
+                            let up = right.cross(camera.view_direction()).normalize();
+                            let forward = camera.view_direction().normalize();
+                            let change = match kind {
+                                Key::ArrowLeft => Some(-right*step),
+                                Key::ArrowRight => Some(right*step),
+                                Key::ArrowUp if modifiers.shift => Some(forward*step),
+                                Key::ArrowDown if modifiers.shift => Some(-forward*step),
+                                Key::ArrowUp => Some(up*step),
+                                Key::ArrowDown => Some(-up*step),
+                                _ => None,
+                            };
+                            if let Some(change) = change {
+                                camera.translate(&change);
+                                orbit_control.set_target(*camera.target());
+                                send_view_proj = true;
+                                *handled = true;
+                            }
+                        },
+                        Event::Text(text) => {
+                            if text == "[" {
+                                cam_roll = (cam_roll - 1.0).max(-180.0);
+                                send_view_proj = true;
+                            } else if text == "]" {
+                                cam_roll = (cam_roll + 1.0).min(180.0);
+                                send_view_proj = true;
+                            }
+                        },
+                        _ => {}
+                    }
+                }
+            }
+
+            // FlyControl has no speed setter, so it's rebuilt when the speed (or the auto speed of a new scene) changes
+            // (it moves by the deltas of the mouse events, not per frame, so the speed needs no frame time)
+            let speed = fly_speed.unwrap_or_else(|| default_fly_speed(&scene));
+            if speed != fly_control_speed {
+                fly_control = FlyControl::new(speed);
+                fly_control_speed = speed;
+            }
+
             if !pointer_over_gui {
                 match egui_control {
                     TdCameraControl::Orbit => {
@@ -879,11 +3358,6 @@ pub async fn main() {
                 }
             }
 
-            if flip_y {
-                //camera.mirror_in_xz_plane(); // FIXME
-                camera.roll(degrees(180.0));
-                flip_y = false;
-            }
             if !are_floats_equal(cam_roll, prev_cam_roll, 0.00001) {
                 camera.roll(degrees(-prev_cam_roll));
                 camera.roll(degrees(cam_roll));
@@ -891,7 +3365,9 @@ pub async fn main() {
             }
         }
 
-        let view_matrix: &Mat4 = camera.view();
+        // the passes and the sort work in scene coordinates
+        let model_matrix = model.matrix();
+        let view_matrix: &Mat4 = &(camera.view() * model_matrix);
         let view_slice = &[
             view_matrix[0][0], view_matrix[0][1], view_matrix[0][2], view_matrix[0][3],
             view_matrix[1][0], view_matrix[1][1], view_matrix[1][2], view_matrix[1][3],
@@ -905,9 +3381,14 @@ pub async fn main() {
             projection_matrix[2][0], projection_matrix[2][1], projection_matrix[2][2], projection_matrix[2][3],
             projection_matrix[3][0], projection_matrix[3][1], projection_matrix[3][2], projection_matrix[3][3]
         ];
+        let view_proj_slice = &mat4_to_array(&(projection_matrix * view_matrix));
+        let scene_on_screen = scene.is_in_frustum(view_proj_slice);
         let w = camera.viewport().width as f32;
         let h = camera.viewport().height as f32;
         let cam_pos = camera.position();
+        let model_cam_pos = (model_matrix.invert().unwrap_or_else(Mat4::identity) * cam_pos.extend(1.0)).truncate();
+        let rw = (w*render_scale).round().max(1.0);
+        let rh = (h*render_scale).round().max(1.0);
         let fx = 0.5*projection_matrix[0][0]*w;
         let fy = -0.5*projection_matrix[1][1]*h;
         let htany = (fovy / 2.0).tan() as f32;
@@ -921,6 +3402,7 @@ pub async fn main() {
             frame_input.device_pixel_ratio,
             |gui_context| {
                 pointer_over_gui = gui_context.is_using_pointer();//.is_pointer_over_area();
+                keyboard_over_gui = gui_context.wants_keyboard_input();
 
                 if error_flag.load(Ordering::Relaxed) {
                     egui::Window::new("Error")
@@ -930,11 +3412,11 @@ pub async fn main() {
                                 let mutex = error_msg.lock().unwrap();
                                 ui.colored_label(egui::Color32::RED, &(*mutex))
                             }
-                            /*
+                            // load errors are recoverable: dismissing returns to the panel to pick another file
                             if ui.button("Ok").clicked() {
+                                error_msg.lock().unwrap().clear();
                                 error_flag.store(false, Ordering::Relaxed);
                             }
-                            */
                         });
                 } else {
                     if !done_streaming {
@@ -945,68 +3427,568 @@ pub async fn main() {
                                     .show_percentage()
                                     .animate(false);
                                 ui.add(progress_bar);
+                                if ui.button("Retry").clicked() {
+                                    reload_scene = true;
+                                }
+
+                            });
+                    } else if let Some(pct) = file_progress {
+                        egui::Window::new("Loading...")
+                            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                            .show(gui_context, |ui| {
+                                let progress_bar = egui::ProgressBar::new(pct as f32)
+                                    .show_percentage()
+                                    .animate(false);
+                                ui.add(progress_bar);
+                            });
+                    } else {
+                        if !has_url && scene.splat_count == 0 {
+                            egui::Window::new("No Scene")
+                                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                                .show(gui_context, |ui| {
+                                    ui.label("No scene URL was given (?url=...).");
+                                    if ui.button("Open File...").clicked() {
+                                        open_file = true;
+                                        add_layer = false;
+                                    }
+                                });
+                        }
+                        let mut close_validation = false;
+                        if let Some(problems) = validation.as_ref() {
+                            egui::Window::new("Validation")
+                                .show(gui_context, |ui| {
+                                    if problems.is_empty() {
+                                        ui.label("No problems found.");
+                                    }
+                                    for p in problems.iter() {
+                                        ui.colored_label(egui::Color32::YELLOW, p);
+                                    }
+                                    if ui.button("Close").clicked() {
+                                        close_validation = true;
+                                    }
+                                });
+                        }
+                        if close_validation {
+                            validation = None;
+                        }
+                        egui::Window::new("Gauzilla")
+                            //.vscroll(true)
+                            .show(gui_context, |ui| {
+                            /*
+                            // TODO: open a PLY file as bytes and process it
+                            if ui.button("Open PLY file").clicked() {
+                                let task = rfd::AsyncFileDialog::new()
+                                    .add_filter("ply", &["ply"])
+                                    .pick_file();
+                                execute_future(async move {
+                                    let file = task.await;
+                                    if let Some(f) = file {
+                                        let bytes = f.read().await;
+                                        match Scene::parse_file_header(bytes) {
+                                            Ok((file_header_size, splat_count, mut cursor)) => {
+
+                                            },
+                                            Err(s) => set_error_for_egui(
+                                                &error_flag, &error_msg, String::from("ERROR: could not open the selected file.\
+                                                Choose a correctly formatted PLY file for 3D Gaussian Splatting.")
+                                            ),
+                                        }
+                                    }
+                                });
+                                ui.close_menu();
+                            }
+                            */
+
+                            egui::Grid::new("my_grid")
+                                .num_columns(2)
+                                .spacing([40.0, 4.0])
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.add(egui::Label::new("FPS"));
+                                    ui.label(format!("{:.2}", fps));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("CPU Sort Time (ms)"));
+                                    ui.label(format!("{:.2}", sort_time));
+                                    ui.end_row();
+
+                                    if gpu_timer.is_available() {
+                                        let fmt = |ms: Option<f64>| ms.map_or("-".to_string(), |ms| format!("{:.2}", ms));
+                                        ui.add(egui::Label::new("GPU Time (ms)"))
+                                            .on_hover_text("Splat pass / quad pass / GUI pass, measured with EXT_disjoint_timer_query_webgl2");
+                                        ui.label(format!(
+                                            "{} / {} / {}",
+                                            fmt(gpu_timer.ms(GpuPass::Splat)),
+                                            fmt(gpu_timer.ms(GpuPass::Quad)),
+                                            fmt(gpu_timer.ms(GpuPass::Gui))
+                                        ));
+                                        ui.end_row();
+                                    }
+
+                                    ui.add(egui::Label::new("CPU Cores"));
+                                    ui.label(format!("{}", cpu_cores));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("GL Version"));
+                                    ui.label(format!("{:?}", gl.version()));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Sort Buckets"));
+                                    ui.horizontal(|ui| {
+                                        ui.add(egui::Slider::new(
+                                            &mut sort_settings.depth_bits,
+                                            SortSettings::MIN_DEPTH_BITS..=SortSettings::MAX_DEPTH_BITS
+                                        ).suffix(" bits"));
+                                        ui.label(format!("({} KB)", sort_settings.histogram_bytes() / 1024));
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Sort Axis"))
+                                        .on_hover_text("Debug: sorts along a fixed world direction instead of the camera depth");
+                                    let sort_axes = [
+                                        ("Camera", None),
+                                        ("+X", Some([1.0, 0.0, 0.0])),
+                                        ("-X", Some([-1.0, 0.0, 0.0])),
+                                        ("+Y", Some([0.0, 1.0, 0.0])),
+                                        ("-Y", Some([0.0, -1.0, 0.0])),
+                                        ("+Z", Some([0.0, 0.0, 1.0])),
+                                        ("-Z", Some([0.0, 0.0, -1.0])),
+                                    ];
+                                    let selected = sort_axes.iter()
+                                        .find(|(_, axis)| *axis == sort_settings.axis)
+                                        .map_or("Custom", |(name, _)| *name);
+                                    egui::ComboBox::from_id_source("sort_axis")
+                                        .selected_text(selected)
+                                        .show_ui(ui, |ui| {
+                                            for (name, axis) in sort_axes.iter() {
+                                                ui.selectable_value(&mut sort_settings.axis, *axis, *name);
+                                            }
+                                        });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Half-Float Positions"))
+                                        .on_hover_text(
+                                            "Stores splat positions as 16-bit floats, shrinking the texture by 25%. \
+                                            Precision is about 1/2048 of the distance from the origin, \
+                                            so large scenes far from the origin may look jittery."
+                                        );
+                                    if ui.checkbox(&mut half_positions, "").changed() {
+                                        repack_scene = true;
+                                    }
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Float Covariance"))
+                                        .on_hover_text(
+                                            "Stores the splat covariances as 32-bit instead of 16-bit floats, \
+                                            fixing the shape artifacts of very thin or very large splats. \
+                                            Takes one more texel per splat: the texture grows by 50% (by 67% with half-float positions)."
+                                        );
+                                    if ui.checkbox(&mut full_covariance, "").changed() {
+                                        repack_scene = true;
+                                    }
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Scale Clamp"))
+                                        .on_hover_text("Clamps the scales of outlier giant splats to a percentile of the scene's scales");
+                                    ui.horizontal(|ui| {
+                                        let mut enabled = scale_clamp.is_some();
+                                        let toggled = ui.checkbox(&mut enabled, "").changed();
+                                        let response = ui.add_enabled(
+                                            enabled,
+                                            egui::Slider::new(&mut scale_clamp_percentile, 90.0..=100.0).suffix(" %").step_by(0.1)
+                                        );
+                                        // rebuild once the value is settled, not on every drag step
+                                        if toggled || response.lost_focus() || response.drag_released() {
+                                            scale_clamp = if enabled { Some(scale_clamp_percentile) } else { None };
+                                            repack_scene = true;
+                                        }
+                                        if let Some(limit) = scene.scale_limit {
+                                            ui.label(format!(
+                                                "({} clamped to {:.3})",
+                                                scene.clamped_count.to_formatted_string(&Locale::en),
+                                                limit
+                                            ));
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("SH Degree"))
+                                        .on_hover_text("Highest degree of spherical harmonics used for view-dependent color");
+                                    ui.horizontal(|ui| {
+                                        if ui.add(egui::Slider::new(&mut max_sh_degree, 0..=3)).changed() {
+                                            repack_scene = true;
+                                        }
+                                        if scene.sh_degree == 0 {
+                                            ui.label("(DC only)");
+                                        } else {
+                                            ui.checkbox(&mut view_dependent, "View-Dependent")
+                                                .on_hover_text("Uncheck to compare with the flat DC color without rebuilding the texture");
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Dither"))
+                                        .on_hover_text("Adds ordered-dither noise to the final image to hide color banding");
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut dither, "");
+                                        ui.add_enabled(dither, egui::Slider::new(&mut dither_strength, 0.0..=4.0));
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("FXAA"))
+                                        .on_hover_text("Smooths aliased edges of the final image (splats, points, and the crop box)");
+                                    ui.checkbox(&mut fxaa, "");
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Depth of Field"))
+                                        .on_hover_text("Blurs the splats in front of and behind the focal distance (screen-space, after the splat pass)");
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut depth_of_field, "");
+                                        ui.add_enabled_ui(depth_of_field, |ui| {
+                                            ui.add(
+                                                egui::DragValue::new(&mut dof_focus)
+                                                    .speed(0.01*dof_focus.max(0.1))
+                                                    .clamp_range(0.01..=1e4)
+                                                    .prefix("focus: ")
+                                            );
+                                            if ui.button("Target").on_hover_text("Focuses on the orbit target").clicked() {
+                                                dof_focus = camera.target().distance(*camera.position()).max(0.01);
+                                            }
+                                        });
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Blur"));
+                                    ui.add_enabled(depth_of_field, egui::Slider::new(&mut dof_blur, 1.0..=32.0).suffix("px"));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("World Scale"))
+                                        .on_hover_text("Scene units per unit of the source file (e.g. 100 to turn meters into centimeters)");
+                                    let response = ui.add(
+                                        egui::DragValue::new(&mut world_scale)
+                                            .speed(0.01)
+                                            .clamp_range(0.001..=1000.0)
+                                    );
+                                    // rebuild once the value is settled, not on every drag step
+                                    if response.lost_focus() || response.drag_released() {
+                                        repack_scene = true;
+                                    }
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Mirror"));
+                                    ui.horizontal(|ui| {
+                                        let x = ui.checkbox(&mut mirror.x, "X").changed();
+                                        let y = ui.checkbox(&mut mirror.y, "Y").changed();
+                                        let z = ui.checkbox(&mut mirror.z, "Z").changed();
+                                        if x || y || z {
+                                            repack_scene = true;
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Idle Refine Sort"))
+                                        .on_hover_text(format!(
+                                            "Re-sorts once with {} bits when the camera has been still for a while",
+                                            SortSettings::MAX_DEPTH_BITS
+                                        ));
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut idle_sort.enabled, "");
+                                        ui.add(egui::Slider::new(&mut idle_sort.idle_ms, 100.0..=5000.0).suffix(" ms"));
+                                        match refined_sort_time {
+                                            Some(t) => ui.label(format!("refined ({:.0} ms)", t)),
+                                            None => ui.label(""),
+                                        };
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Draw Cap"))
+                                        .on_hover_text("Draws only the nearest splats each frame (for slow GPUs)");
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut draw_cap.enabled, "");
+                                        ui.checkbox(&mut draw_cap.auto, "Auto");
+                                        if draw_cap.auto {
+                                            ui.add(egui::Slider::new(&mut draw_cap.target_fps, 10.0..=120.0).suffix(" fps"));
+                                        } else {
+                                            ui.add(egui::Slider::new(&mut draw_cap.max_splats, 0..=scene.splat_count));
+                                        }
+                                        let count = draw_cap.count(scene.splat_count);
+                                        ui.label(format!("({})", count.to_formatted_string(&Locale::en)));
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Splat Count"));
+                                    ui.label(format!(
+                                        "{} ({} active)",
+                                        scene.splat_count.to_formatted_string(&Locale::en),
+                                        splat_glsl.index_count.to_formatted_string(&Locale::en)
+                                    ));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Min Opacity"))
+                                        .on_hover_text("Skips the near-transparent splats");
+                                    ui.add(egui::Slider::new(&mut sort_settings.min_opacity, 0.0..=1.0));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Splat Limit"))
+                                        .on_hover_text("Renders (and sorts) only the most important splats (by size and opacity)");
+                                    ui.horizontal(|ui| {
+                                        ui.add(egui::Slider::new(&mut sort_settings.splat_percent, 0.0..=100.0).suffix("%"));
+                                        // the same budget as a splat count
+                                        let mut count = sort_settings.limited_count(scene.splat_count);
+                                        let response = ui.add(egui::DragValue::new(&mut count)
+                                            .clamp_range(0..=scene.splat_count)
+                                            .speed(scene.splat_count as f64/1000.0)
+                                            .suffix(" splats"))
+                                            .on_hover_text("Max splats");
+                                        if response.changed() && scene.splat_count > 0 {
+                                            sort_settings.splat_percent = ((count as f64)/(scene.splat_count as f64)*100.0) as f32;
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Frustum Culling"))
+                                        .on_hover_text("Sorts and draws only the splats around the view (resorts whenever the camera moves)");
+                                    ui.checkbox(&mut sort_settings.cull, "");
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Octree LOD"))
+                                        .on_hover_text("Draws coarser octree nodes for distant and off-screen regions");
+                                    ui.horizontal(|ui| {
+                                        let mut lod_enabled = sort_settings.lod.is_some();
+                                        let mut lod_threshold = sort_settings.lod.unwrap_or(0.1);
+                                        ui.checkbox(&mut lod_enabled, "");
+                                        ui.add_enabled(
+                                            lod_enabled,
+                                            egui::Slider::new(&mut lod_threshold, 0.01..=1.0).text("refine above")
+                                        );
+                                        sort_settings.lod = if lod_enabled { Some(lod_threshold) } else { None };
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Quality Preset"))
+                                        .on_hover_text("Defaults picked from the splat count and CPU cores when the scene was loaded");
+                                    ui.label(quality_preset.map_or("-".to_string(), |p| format!("{:?}", p)));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Render Scale"))
+                                        .on_hover_text("Resolution of the splat pass relative to the canvas");
+                                    ui.add(egui::Slider::new(&mut render_scale, 0.25..=1.0));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Splat Scale"));
+                                    ui.add(egui::Slider::new(&mut splat_scale, 0.1..=1.0));
+                                    ui.end_row();
 
-                            });
-                    } else {
-                        egui::Window::new("Gauzilla")
-                            //.vscroll(true)
-                            .show(gui_context, |ui| {
-                            /*
-                            // TODO: open a PLY file as bytes and process it
-                            if ui.button("Open PLY file").clicked() {
-                                let task = rfd::AsyncFileDialog::new()
-                                    .add_filter("ply", &["ply"])
-                                    .pick_file();
-                                execute_future(async move {
-                                    let file = task.await;
-                                    if let Some(f) = file {
-                                        let bytes = f.read().await;
-                                        match Scene::parse_file_header(bytes) {
-                                            Ok((file_header_size, splat_count, mut cursor)) => {
+                                    ui.add(egui::Label::new("Near Fade"))
+                                        .on_hover_text("Fades out splats within this distance of the near plane to avoid pop-in");
+                                    ui.add(egui::Slider::new(&mut near_fade, 0.0..=5.0));
+                                    ui.end_row();
 
-                                            },
-                                            Err(s) => set_error_for_egui(
-                                                &error_flag, &error_msg, String::from("ERROR: could not open the selected file.\
-                                                Choose a correctly formatted PLY file for 3D Gaussian Splatting.")
-                                            ),
+                                    ui.add(egui::Label::new("Antialiasing"))
+                                        .on_hover_text(
+                                            "Screen-space dilation of the splats in pixels^2 against shimmering of thin structures \
+                                            (Oriented quads aren't filtered). \
+                                            Compensate: keep the opacity-weighted area, as in Mip-Splatting."
+                                        );
+                                    ui.horizontal(|ui| {
+                                        ui.add(egui::Slider::new(&mut antialias.dilation, 0.0..=2.0));
+                                        ui.checkbox(&mut antialias.compensate, "Compensate")
+                                            .on_hover_text("On by default for antialiased SPZ captures");
+                                        if scene.antialiased {
+                                            ui.label("(antialiased capture)");
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Explode"))
+                                        .on_hover_text("Pushes the splats away from the scene center (in scene radii) to reveal the inside");
+                                    ui.horizontal(|ui| {
+                                        ui.add_enabled(explode_anim.is_none(), egui::Slider::new(&mut explode, 0.0..=1.0));
+                                        if ui.add_enabled(explode_anim.is_none(), egui::Button::new("Animate")).clicked() {
+                                            explode_anim = Some(0.0);
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Splat Centers"))
+                                        .on_hover_text(
+                                            "Draws the center of every splat as a point in its color (size in pixels), \
+                                            e.g. to check the alignment of external geometry. Only: hides the splats."
+                                        );
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut show_points, "Points");
+                                        ui.add_enabled_ui(show_points, |ui| {
+                                            ui.add(egui::Slider::new(&mut point_size, 1.0..=4.0));
+                                            ui.checkbox(&mut points_only, "Only");
+                                        });
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Model Transform"))
+                                        .on_hover_text("Moves, rotates (XYZ Euler angles), and uniformly scales the scene, e.g. to fix sideways captures");
+                                    ui.vertical(|ui| {
+                                        ui.horizontal(|ui| {
+                                            ui.label("move");
+                                            for v in model.translation.iter_mut() {
+                                                ui.add(egui::DragValue::new(v).speed(0.01));
+                                            }
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("rotate");
+                                            for v in model.rotation.iter_mut() {
+                                                ui.add(egui::DragValue::new(v).speed(0.5).clamp_range(-180.0..=180.0).suffix("°"));
+                                            }
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("scale");
+                                            ui.add(egui::DragValue::new(&mut model.scale).speed(0.01).clamp_range(ModelTransform::MIN_SCALE..=1000.0));
+                                            if ui.button("Reset").clicked() {
+                                                model = ModelTransform { invert_y: model.invert_y, ..ModelTransform::default() };
+                                            }
+                                        });
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Crop Box"))
+                                        .on_hover_text("Sorts and draws only the splats centered inside the box (min and max in scene coordinates, before the model transform)");
+                                    ui.vertical(|ui| {
+                                        ui.horizontal(|ui| {
+                                            ui.checkbox(&mut crop, "Crop");
+                                            ui.add_enabled_ui(crop, |ui| {
+                                                ui.checkbox(&mut show_crop_box, "Show");
+                                                if ui.button("Reset").clicked() {
+                                                    crop_box = None;
+                                                }
+                                            });
+                                        });
+                                        if let Some((min, max)) = crop_box.as_mut() {
+                                            let speed = (0..3).map(|k| max[k] - min[k]).fold(0_f32, f32::max).max(0.01) as f64/200.0;
+                                            for (label, bound) in [("min", min), ("max", max)] {
+                                                ui.add_enabled_ui(crop, |ui| {
+                                                    ui.horizontal(|ui| {
+                                                        ui.label(label);
+                                                        for v in bound.iter_mut() {
+                                                            ui.add(egui::DragValue::new(v).speed(speed));
+                                                        }
+                                                    });
+                                                });
+                                            }
                                         }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Global Opacity"))
+                                        .on_hover_text("Multiplies the opacity of every splat");
+                                    ui.add(egui::Slider::new(&mut global_alpha, 0.0..=1.0));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Fade In"))
+                                        .on_hover_text("Ramps up the opacity over half a second when a new scene or keyframe appears");
+                                    ui.checkbox(&mut fade_in, "");
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Background"))
+                                        .on_hover_text(
+                                            "Clear color (alpha < 1 shows the page behind the canvas). \
+                                            Transparent clears to alpha 0 so that the splats overlay the page. \
+                                            Preserve draws over the previous frame instead of clearing \
+                                            (needs preserveDrawingBuffer)."
+                                        );
+                                    ui.horizontal(|ui| {
+                                        ui.add_enabled_ui(!preserve_canvas, |ui| {
+                                            ui.color_edit_button_rgba_unmultiplied(&mut clear_color);
+                                            let mut transparent = clear_color[3] == 0.0;
+                                            if ui.checkbox(&mut transparent, "Transparent").changed() {
+                                                clear_color[3] = if transparent { 0.0 } else { 1.0 };
+                                            }
+                                        });
+                                        ui.checkbox(&mut preserve_canvas, "Preserve");
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Background Mode"));
+                                    ui.horizontal(|ui| {
+                                        ui.radio_value(&mut background.mode, BackgroundMode::Solid, "Solid");
+                                        ui.radio_value(&mut background.mode, BackgroundMode::Gradient, "Gradient");
+                                        ui.radio_value(&mut background.mode, BackgroundMode::Image, "Image");
+                                    });
+                                    ui.end_row();
+
+                                    match background.mode {
+                                        BackgroundMode::Solid => {},
+                                        BackgroundMode::Gradient => {
+                                            ui.add(egui::Label::new("Gradient"))
+                                                .on_hover_text("Top and bottom colors");
+                                            ui.horizontal(|ui| {
+                                                ui.color_edit_button_rgba_unmultiplied(&mut background.top);
+                                                ui.color_edit_button_rgba_unmultiplied(&mut background.bottom);
+                                            });
+                                            ui.end_row();
+                                        },
+                                        BackgroundMode::Image => {
+                                            ui.add(egui::Label::new("Image URL"))
+                                                .on_hover_text("Needs CORS headers if served from another origin");
+                                            ui.horizontal(|ui| {
+                                                ui.add(egui::TextEdit::singleline(&mut background.url).desired_width(160.0));
+                                                if ui.add_enabled(!background.url.trim().is_empty(), egui::Button::new("Load")).clicked() {
+                                                    load_background = true;
+                                                }
+                                            });
+                                            ui.end_row();
+                                        },
                                     }
-                                });
-                                ui.close_menu();
-                            }
-                            */
 
-                            egui::Grid::new("my_grid")
-                                .num_columns(2)
-                                .spacing([40.0, 4.0])
-                                .striped(true)
-                                .show(ui, |ui| {
-                                    ui.add(egui::Label::new("FPS"));
-                                    ui.label(format!("{:.2}", fps));
+                                    ui.add(egui::Label::new("Blend Mode"));
+                                    ui.horizontal(|ui| {
+                                        ui.radio_value(&mut blend_mode, BlendMode::Over, "Over");
+                                        ui.radio_value(&mut blend_mode, BlendMode::Additive, "Additive");
+                                        ui.radio_value(&mut blend_mode, BlendMode::Premultiplied, "Premultiplied");
+                                        ui.radio_value(&mut blend_mode, BlendMode::Under, "Under")
+                                            .on_hover_text("Front-to-back compositing");
+                                    });
                                     ui.end_row();
 
-                                    ui.add(egui::Label::new("CPU Sort Time (ms)"));
-                                    ui.label(format!("{:.2}", sort_time));
+                                    ui.add(egui::Label::new("Color Space"))
+                                        .on_hover_text(
+                                            "sRGB: blend the stored colors as is. \
+                                            Linear Blend: decode sRGB colors to linear for blending. \
+                                            Linear: the file stores linear colors."
+                                        );
+                                    ui.horizontal(|ui| {
+                                        ui.radio_value(&mut color_space, ColorSpace::Srgb, "sRGB");
+                                        ui.radio_value(&mut color_space, ColorSpace::LinearBlend, "Linear Blend");
+                                        ui.radio_value(&mut color_space, ColorSpace::Linear, "Linear");
+                                    });
                                     ui.end_row();
 
-                                    ui.add(egui::Label::new("CPU Cores"));
-                                    ui.label(format!("{}", cpu_cores));
+                                    ui.add(egui::Label::new("Tone Mapping"))
+                                        .on_hover_text("Curve applied to the exposed splat colors (Linear leaves them as is)");
+                                    ui.horizontal(|ui| {
+                                        ui.radio_value(&mut tone_map, ToneMap::Linear, "Linear");
+                                        ui.radio_value(&mut tone_map, ToneMap::Reinhard, "Reinhard");
+                                        ui.radio_value(&mut tone_map, ToneMap::Aces, "ACES");
+                                    });
                                     ui.end_row();
 
-                                    ui.add(egui::Label::new("GL Version"));
-                                    ui.label(format!("{:?}", gl.version()));
+                                    ui.add(egui::Label::new("Exposure"));
+                                    ui.add(egui::Slider::new(&mut exposure, -4.0..=4.0).suffix(" EV"));
                                     ui.end_row();
 
-                                    ui.add(egui::Label::new("Splat Count"));
-                                    ui.label(format!("{}", scene.splat_count.to_formatted_string(&Locale::en)));
+                                    ui.add(egui::Label::new("Quad Geometry"))
+                                        .on_hover_text("Oriented lays each quad in the plane of the Gaussian's two largest 3D axes");
+                                    ui.horizontal(|ui| {
+                                        ui.radio_value(&mut quad_mode, QuadMode::ScreenAligned, "Screen-Aligned");
+                                        ui.radio_value(&mut quad_mode, QuadMode::Oriented, "Oriented");
+                                    });
                                     ui.end_row();
 
-                                    ui.add(egui::Label::new("Splat Scale"));
-                                    ui.add(egui::Slider::new(&mut splat_scale, 0.1..=1.0));
+                                    ui.add(egui::Label::new("Skip Sort (Additive)"));
+                                    ui.checkbox(&mut skip_additive_sort, "");
                                     ui.end_row();
 
                                     ui.add(egui::Label::new("Invert Y"));
-                                    ui.checkbox(&mut flip_y, "");
+                                    ui.checkbox(&mut model.invert_y, "")
+                                        .on_hover_text("The scene is Y-down (part of the model transform, independent of the camera roll)");
                                     ui.end_row();
 
                                     ui.add(egui::Label::new("Window Size"));
@@ -1032,10 +4014,224 @@ pub async fn main() {
                                     });
                                     ui.end_row();
 
+                                    ui.add(egui::Label::new("Fly Speed"))
+                                        .on_hover_text("Movement speed of the fly control, Auto: scaled to the scene size");
+                                    ui.horizontal(|ui| {
+                                        let mut auto = fly_speed.is_none();
+                                        let mut speed = fly_speed.unwrap_or(fly_control_speed);
+                                        ui.checkbox(&mut auto, "Auto");
+                                        ui.add_enabled(
+                                            !auto,
+                                            egui::Slider::new(&mut speed, 1e-5..=10.0).logarithmic(true)
+                                        );
+                                        fly_speed = if auto { None } else { Some(speed) };
+                                    });
+                                    ui.end_row();
+
                                     ui.add(egui::Label::new("Camera Roll"));
                                     ui.add(egui::Slider::new(&mut cam_roll, -180.0..=180.0).suffix("°"));
                                     ui.end_row();
 
+                                    ui.add(egui::Label::new("Camera Pose"))
+                                        .on_hover_text("Position, target, up, field of view and roll as JSON");
+                                    ui.horizontal(|ui| {
+                                        if ui.button("Frame Scene").on_hover_text("Fits the whole scene into view and resets the roll").clicked() {
+                                            frame_camera = true;
+                                        }
+                                        if ui.button("Copy Camera").clicked() {
+                                            copy_camera = true;
+                                        }
+                                        if ui.button("Paste Camera").clicked() {
+                                            paste_camera = true;
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    if scene_list.len() > 1 {
+                                        ui.add(egui::Label::new("Scenes"))
+                                            .on_hover_text("Switches between the scenes listed with ?scene=... without reloading the page");
+                                        ui.horizontal(|ui| {
+                                            let name = |u: &String| if u.starts_with("data:") {
+                                                "inline data".to_string()
+                                            } else {
+                                                u.split('?').next().unwrap_or("").rsplit('/').next().unwrap_or("").to_string()
+                                            };
+                                            egui::ComboBox::from_id_source("scene_list")
+                                                .selected_text(name(&scene_list[scene_index]))
+                                                .show_ui(ui, |ui| {
+                                                    for (i, u) in scene_list.iter().enumerate() {
+                                                        if ui.selectable_label(i == scene_index, name(u)).clicked() && i != scene_index {
+                                                            switch_scene = Some(i);
+                                                        }
+                                                    }
+                                                });
+                                            ui.checkbox(&mut prefetch_scenes, "Prefetch")
+                                                .on_hover_text("Downloads the other scenes in the background so that switching is faster");
+                                        });
+                                        ui.end_row();
+                                    }
+
+                                    ui.add(egui::Label::new("Layers"))
+                                        .on_hover_text(
+                                            "Shows more scenes (from local files) in the same space, each moved by an offset. \
+                                            They're merged and sorted as one, loading or editing a scene flattens them."
+                                        );
+                                    ui.vertical(|ui| {
+                                        let mut remove: Option<usize> = None;
+                                        for (i, layer) in layers.iter_mut().enumerate() {
+                                            ui.horizontal(|ui| {
+                                                merge_layers |= ui.checkbox(&mut layer.visible, &layer.name).changed();
+                                                for v in layer.offset.iter_mut() {
+                                                    // merging is done once the drag is over
+                                                    let response = ui.add(egui::DragValue::new(v).speed(0.01));
+                                                    merge_layers |= response.drag_released() || (response.changed() && !response.dragged());
+                                                }
+                                                if ui.small_button("Remove").clicked() {
+                                                    remove = Some(i);
+                                                }
+                                            });
+                                        }
+                                        if let Some(i) = remove {
+                                            layers.remove(i);
+                                            merge_layers = true;
+                                        }
+                                        if ui.button("Add File...").clicked() {
+                                            open_file = true;
+                                            add_layer = true;
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Scene"));
+                                    ui.horizontal(|ui| {
+                                        // scenes loaded up front aren't streamed, there is nothing to re-fetch,
+                                        // and reloading a picked scene would replace it with the one at url
+                                        if ui.add_enabled(has_url && !loaded_up_front && !picked_scene, egui::Button::new("Reload Scene")).clicked() {
+                                            reload_scene = true;
+                                        }
+                                        if ui.button("Copy Stats").clicked() {
+                                            copy_stats = true;
+                                        }
+                                        if ui.button("Export JSON").clicked() {
+                                            export_json = true;
+                                        }
+                                        if ui.add_enabled(scene.splat_count > 0, egui::Button::new("Export SPZ")).clicked() {
+                                            export_spz = true;
+                                        }
+                                        if ui.add_enabled(scene.splat_count > 0, egui::Button::new("Export LOD"))
+                                            .on_hover_text("Saves the scene as scene.lod and scene.splat to be streamed node by node with ?url=scene.lod")
+                                            .clicked() {
+                                            export_lod_files = true;
+                                        }
+                                        if ui.button("Validate").clicked() {
+                                            let problems = scene.validate();
+                                            for p in problems.iter() {
+                                                log!("main(): validate: {}", p);
+                                            }
+                                            validation = Some(problems);
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Screenshot"))
+                                        .on_hover_text("Saves the current view (without the GUI) as a PNG at the canvas resolution");
+                                    if ui.button("Save Screenshot").clicked() {
+                                        save_screenshot = true;
+                                    }
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Record"))
+                                        .on_hover_text("Captures the canvas to a WebM video, downloaded when stopped");
+                                    ui.horizontal(|ui| {
+                                        let text = if recording {
+                                            format!("Stop ({:.0} s)", (now - record_start)/1000.0)
+                                        } else {
+                                            "Record".to_string()
+                                        };
+                                        if ui.button(text).clicked() {
+                                            toggle_recording = true;
+                                        }
+                                        ui.add_enabled(!recording, egui::Slider::new(&mut record_fps, 15..=60).suffix(" fps"));
+                                        ui.add_enabled(!recording, egui::Checkbox::new(&mut record_hide_gui, "Hide GUI"))
+                                            .on_hover_text("Press Esc to stop");
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Hover Highlight"))
+                                        .on_hover_text("Highlights the splat under the cursor");
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut hover_highlight, "");
+                                        if let Some(i) = hovered {
+                                            ui.label(format!("#{}", i));
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Inspect Splat"))
+                                        .on_hover_text("Click a splat to show its attributes (the index is the row of Scene::buffer)");
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut inspect_splat, "");
+                                        match inspected {
+                                            Some(Some(i)) if (i as usize) < scene.splat_count => {
+                                                ui.label(format!("#{}", i));
+                                            },
+                                            Some(_) => {
+                                                ui.label("no splat");
+                                            },
+                                            None => {},
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    if let (true, Some(Some(i))) = (inspect_splat, inspected) {
+                                        let i = i as usize;
+                                        if i < scene.splat_count {
+                                            let f_buffer: &[f32] = transmute_slice::<_, f32>(scene.buffer.as_slice());
+                                            let row = &scene.buffer[32*i..32*(i + 1)];
+                                            ui.add(egui::Label::new("Position"));
+                                            ui.label(format!("{:.4}, {:.4}, {:.4}", f_buffer[8*i], f_buffer[8*i + 1], f_buffer[8*i + 2]));
+                                            ui.end_row();
+
+                                            ui.add(egui::Label::new("Scale"));
+                                            ui.label(format!("{:.4}, {:.4}, {:.4}", f_buffer[8*i + 3], f_buffer[8*i + 4], f_buffer[8*i + 5]));
+                                            ui.end_row();
+
+                                            ui.add(egui::Label::new("Rotation"))
+                                                .on_hover_text("Quaternion (w, x, y, z)");
+                                            let q: Vec<String> = row[28..32].iter().map(|&b| format!("{:.3}", unpack_quat_byte(b))).collect();
+                                            ui.label(q.join(", "));
+                                            ui.end_row();
+
+                                            ui.add(egui::Label::new("Opacity"));
+                                            ui.label(format!("{:.3}", row[27] as f32/255.0));
+                                            ui.end_row();
+
+                                            ui.add(egui::Label::new("Color"));
+                                            ui.colored_label(
+                                                egui::Color32::from_rgb(row[24], row[25], row[26]),
+                                                format!("#{:02x}{:02x}{:02x}", row[24], row[25], row[26])
+                                            );
+                                            ui.end_row();
+                                        }
+                                    }
+
+                                    ui.add(egui::Label::new("Opacity Brush"));
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut brush.enabled, "");
+                                        if ui.add_enabled(brush.can_undo(), egui::Button::new("Undo Stroke")).clicked() {
+                                            undo_brush = true;
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Brush Radius"));
+                                    ui.add(egui::Slider::new(&mut brush.radius, 1.0..=200.0).suffix("px"));
+                                    ui.end_row();
+
+                                    ui.add(egui::Label::new("Brush Strength"));
+                                    ui.add(egui::Slider::new(&mut brush.strength, 0.01..=1.0));
+                                    ui.end_row();
+
                                     ui.add(egui::Label::new("GitHub"));
                                     use egui::special_emojis::GITHUB;
                                     ui.hyperlink_to(
@@ -1050,48 +4246,513 @@ pub async fn main() {
             },
         );
 
+        // quality defaults for a newly loaded scene, the user may override them afterwards
+        if apply_quality_preset && scene.splat_count > 0 {
+            let preset = QualityPreset::for_scene(scene.splat_count, cpu_cores);
+            log!("main(): quality preset {:?} for {} splats on {} cores", preset, scene.splat_count, cpu_cores);
+            splat_scale = preset.splat_scale();
+            sort_settings.splat_percent = preset.splat_percent();
+            render_scale = preset.render_scale();
+            // antialiased captures were trained with a compensated 2D filter, so they'd look too sharp without one
+            antialias.compensate = scene.antialiased;
+            if scene.antialiased && antialias.dilation == 0.0 {
+                antialias.dilation = Antialias::DEFAULT_DILATION;
+            }
+            quality_preset = Some(preset);
+            apply_quality_preset = false;
+        }
+
+        // keep the host-facing values in sync with egui
+        api::set_splat_scale(splat_scale);
+        api::set_global_alpha(global_alpha);
+        api::set_clear_color(clear_color[0], clear_color[1], clear_color[2], clear_color[3]);
+        api::set_blend_mode(BlendMode::ALL.iter().position(|&m| m == blend_mode).unwrap_or(0));
+        api::set_control_mode((egui_control == TdCameraControl::Fly) as usize);
+        api::set_fly_speed(fly_speed);
+        api::set_lod(sort_settings.lod);
+        api::set_camera_roll(cam_roll);
+        {
+            let (p, t, u) = (camera.position(), camera.target(), camera.up());
+            api::set_current_camera_pose([p.x, p.y, p.z], [t.x, t.y, t.z], [u.x, u.y, u.z]);
+        }
+        if preserve_canvas != api::get_preserve_canvas() {
+            api::set_preserve_canvas(preserve_canvas);
+        }
+
+        if copy_stats {
+            copy_to_clipboard(&stats_summary(&gl, &scene, &url, cpu_cores));
+            copy_stats = false;
+        }
+
+        if frame_camera {
+            if let Some(centroid) = scene.centroid() {
+                // unroll first, so that the framed view starts over from the unrolled up vector
+                camera.roll(degrees(-prev_cam_roll));
+                cam_roll = 0.0;
+                prev_cam_roll = 0.0;
+                api::set_camera_roll(cam_roll); // already synced for this frame
+                frame_bounding_box(&mut camera, &mut orbit_control, &scene, &model, fovy, centroid);
+                send_view_proj = true;
+            }
+            frame_camera = false;
+        }
+
+        if copy_camera {
+            copy_to_clipboard(&CameraPose::new(&camera, fovy, cam_roll).to_json());
+            copy_camera = false;
+        }
+
+        if paste_camera {
+            let bus_pose_rc = bus_pose_rc.clone();
+            let error_flag = Arc::clone(&error_flag);
+            let error_msg = Arc::clone(&error_msg);
+            execute_future(async move {
+                let pose = read_from_clipboard().await
+                    .map_err(GauzillaError::from)
+                    .and_then(|text| CameraPose::from_json(&text.as_string().unwrap_or_default()));
+                match pose {
+                    Ok(pose) => {
+                        let _ = bus_pose_rc.borrow_mut().try_broadcast(pose);
+                    },
+                    Err(e) => {
+                        log!("main(): ERROR: {}", e);
+                        set_error_for_egui(&error_flag, &error_msg, format!("ERROR: could not paste the camera: {}", e));
+                    },
+                }
+            });
+            paste_camera = false;
+        }
+
+        if export_json {
+            let snapshot = RenderSnapshot {
+                camera: CameraSnapshot::new(&camera, fovy),
+                camera_control: format!("{:?}", egui_control),
+                splat_scale,
+                quality_preset: quality_preset.map(|p| format!("{:?}", p)),
+                render_scale,
+                blend_mode: format!("{:?}", blend_mode),
+                color_space: format!("{:?}", color_space),
+                tone_map: format!("{:?}", tone_map),
+                exposure,
+                quad_mode: format!("{:?}", quad_mode),
+                near_fade,
+                global_alpha,
+                aa_dilation: antialias.dilation,
+                fxaa,
+                splat_percent: sort_settings.splat_percent,
+                depth_bits: sort_settings.depth_bits,
+                draw_cap: draw_cap.enabled.then(|| draw_cap.count(scene.splat_count)),
+                half_positions: scene.half_positions,
+                full_covariance: scene.full_covariance,
+                mirror: [scene.mirror.x, scene.mirror.y, scene.mirror.z],
+                world_scale: scene.world_scale,
+                sh_degree: if view_dependent { scene.sh_tex_degree } else { 0 },
+                fps,
+                sort_time_ms: sort_time,
+                splat_count: scene.splat_count,
+                source_format: scene.source_format.to_string(),
+                url: if is_data_url { "data:".to_string() } else { url.clone() }, // don't dump inline payloads
+            };
+            match serde_json::to_string_pretty(&snapshot) {
+                Ok(json) => download_bytes(json.as_bytes(), "gauzilla-stats.json"),
+                Err(e) => log!("main(): ERROR: serde_json::to_string_pretty(): {}", e),
+            }
+            export_json = false;
+        }
+
+        if load_background {
+            let bus_background_rc = bus_background_rc.clone();
+            let error_flag = Arc::clone(&error_flag);
+            let error_msg = Arc::clone(&error_msg);
+            let url = background.url.trim().to_string();
+            execute_future(async move {
+                match load_background_image(&url).await {
+                    Ok(image) => {
+                        let _ = bus_background_rc.borrow_mut().try_broadcast(image);
+                    },
+                    Err(e) => {
+                        log!("main(): ERROR: {}", e);
+                        set_error_for_egui(&error_flag, &error_msg, format!("ERROR: could not load the background image: {}", e));
+                    },
+                }
+            });
+            load_background = false;
+        }
+
+        // a scene loaded or rebuilt since the last merge replaces the layers
+        if !layers.is_empty() && !merge_layers && !merged_scene.as_ref().map_or(false, |m| Arc::ptr_eq(m, &scene)) {
+            layers.clear();
+            merged_scene = None;
+        }
+        if merge_layers {
+            let parts: Vec<(&Scene, [f32; 3])> = layers.iter()
+                .filter(|layer| layer.visible)
+                .map(|layer| (layer.scene.as_ref(), layer.offset))
+                .collect();
+            scene = Arc::new(Scene::merged(&parts));
+            if let Err(e) = splat_glsl.upload_texture(&gl, &scene) {
+                set_error_for_egui(&error_flag, &error_msg, format!("ERROR: {}", e));
+            }
+
+            //////////////////////////////////
+            // non-blocking (i.e., no atomic.wait)
+            let _ = bus_scene.try_broadcast(scene.clone());
+            //////////////////////////////////
+            brush.reset();
+            hovered = None;
+            crop_box = None; // the bounds of the merged scene
+            picked_scene = true;
+            merged_scene = if layers.is_empty() { None } else { Some(scene.clone()) };
+            send_view_proj = true;
+            merge_layers = false;
+        }
+
+        if open_file {
+            let bus_picked_rc = bus_picked_rc.clone();
+            let file_progress_rc = file_progress_rc.clone();
+            let error_flag = Arc::clone(&error_flag);
+            let error_msg = Arc::clone(&error_msg);
+            execute_future(async move {
+                let result = load_scene(Some(&file_progress_rc)).await;
+                file_progress_rc.set(None); // done either way, hides the progress bar
+                match result {
+                    Ok(s) => {
+                        if s.splat_count > 0 {
+                            let _ = bus_picked_rc.borrow_mut().try_broadcast(Arc::new(s));
+                        }
+                    },
+                    Err(e) => {
+                        log!("main(): ERROR: {}", e);
+                        set_error_for_egui(&error_flag, &error_msg, format!("ERROR: could not open the selected file: {}", e));
+                    },
+                }
+            });
+            open_file = false;
+        }
+
+        if export_spz {
+            let scene = scene.clone();
+            execute_future(async move {
+                match spz::export_spz(&scene).await {
+                    Ok(bytes) => download_bytes(&bytes, "scene.spz"),
+                    Err(e) => log!("main(): ERROR: export_spz(): {}", e),
+                }
+            });
+            export_spz = false;
+        }
+
+        if export_lod_files {
+            let (lod, splat) = export_lod(&scene, NODE_SPLATS);
+            download_bytes(&lod, "scene.lod");
+            download_bytes(&splat, "scene.splat");
+            export_lod_files = false;
+        }
+
+        if toggle_recording {
+            if recording {
+                stop_recording("gauzilla.webm");
+                recording = false;
+                log!("main(): recorded {:.1} s", (now - record_start)/1000.0);
+            } else if start_recording(record_fps) {
+                recording = true;
+                record_start = now;
+            } else {
+                show_warning("Recording is not supported by this browser", 3000);
+            }
+            toggle_recording = false;
+        }
+
+        // warm the HTTP cache with the other scenes once the current one is shown
+        if prefetch_scenes && !scenes_prefetched && done_streaming && scene_list.len() > 1 {
+            let urls: Vec<String> = scene_list.iter()
+                .filter(|u| **u != url && !u.starts_with("data:"))
+                .flat_map(|u| parse_spz_urls(u).unwrap_or_else(|| vec![u.clone()]))
+                .collect();
+            execute_future(async move {
+                for u in urls.iter() {
+                    prefetch_url(u).await;
+                }
+                log!("main(): prefetched {} scene files", urls.len());
+            });
+            scenes_prefetched = true;
+        }
+
+        draw_cap.update(now, fps, scene.splat_count);
+
+        // opt-in telemetry for the host page (no-op unless a callback is registered)
+        if done_streaming {
+            api::report_stats(now, fps, sort_time, scene.splat_count, cpu_cores);
+        }
+
         if !error_flag.load(Ordering::Relaxed) {
+            // the draw order follows the blend mode
+            sort_settings.order = blend_mode.draw_order();
+
+            // the animations advance by the clamped frame time, so that they don't jump after a stall (e.g. a background tab)
+            let dt_ms = 1000.0*clock.dt() as f64;
+
+            // explode animation: out and back in, the sort follows the exploded positions
+            if let Some(elapsed) = explode_anim.as_mut() {
+                *elapsed += dt_ms;
+                let t = *elapsed/EXPLODE_ANIM_MS;
+                if t >= 1.0 {
+                    explode = 0.0;
+                    explode_anim = None;
+                } else {
+                    explode = (std::f64::consts::PI*t).sin() as f32;
+                }
+            }
+            // fade-in after a new scene upload, on top of the global opacity
+            let mut fade = 1.0_f32;
+            if fade_in_pending && fade_in {
+                fade = 0.0; // hidden until the splat pass gets the first order sorted for it
+            } else if let Some(elapsed) = fade_in_elapsed.as_mut() {
+                *elapsed += dt_ms;
+                let t = (*elapsed/FADE_IN_MS) as f32;
+                if t >= 1.0 || !fade_in {
+                    fade_in_elapsed = None;
+                } else {
+                    let t = t.max(0.0);
+                    fade = t*t*(3.0 - 2.0*t); // smoothstep
+                }
+            }
+
+            let explode_params = if explode > 0.0 {
+                scene.bbox.map(|(min, max)| {
+                    let c = 0.5*(min + max);
+                    ([c.x, c.y, c.z], explode*0.5*(max - min).magnitude())
+                })
+            } else {
+                None
+            };
+            sort_settings.explode = explode_params;
+            if crop && crop_box.is_none() {
+                crop_box = scene.bbox.map(|(min, max)| (min.into(), max.into())); // also after the scene was replaced
+            }
+            sort_settings.crop = if crop { crop_box } else { None };
+
+            // the sort's view_proj includes the model transform
+            if model != prev_model {
+                prev_model = model;
+                send_view_proj = true;
+            }
+
+            // send sort settings to thread only when they've been changed in egui
+            let settings_changed = sort_settings != prev_sort_settings;
+            if settings_changed {
+                //////////////////////////////////
+                // non-blocking (i.e., no atomic.wait)
+                let _ = bus_sort_settings.try_broadcast(sort_settings.clone());
+                //////////////////////////////////
+                prev_sort_settings = sort_settings.clone();
+                send_view_proj = true;
+            }
+
             // send view_proj to thread only when it's changed by user input
-            if done_streaming && send_view_proj  {
+            // additive blending is order-independent, so the sort can be skipped
+            // (unless the settings changed, e.g. the splat count limiter filters the depth_index)
+            let sort_needed = !(blend_mode == BlendMode::Additive && skip_additive_sort) || settings_changed;
+
+            // download the .lod nodes the new view needs (all nodes in the frustum while the LOD is off)
+            if let Some(stream) = lod_stream.as_mut() {
+                if send_view_proj {
+                    let view_proj = projection_matrix * view_matrix;
+                    stream.request(&mat4_to_array(&view_proj), sort_settings.lod.unwrap_or(0.0), scene.mirror, scene.world_scale);
+                }
+            }
+
+            // a view that came in while the GPU sort was busy (or that it failed on)
+            #[cfg(feature = "webgpu_sort")]
+            if gpu_sorter.take_pending() {
+                send_view_proj = true;
+            }
+
+            if done_streaming && send_view_proj && sort_needed {
+                let view_proj = projection_matrix * view_matrix;
+                #[cfg(feature = "webgpu_sort")]
+                let on_gpu = gpu_sorter.sort(&scene, &mat4_to_array(&view_proj), &sort_settings);
+                #[cfg(not(feature = "webgpu_sort"))]
+                let on_gpu = false;
+                if !on_gpu {
+                    //////////////////////////////////
+                    // non-blocking (i.e., no atomic.wait)
+                    let _ = bus_vp.try_broadcast(view_proj);
+                    //////////////////////////////////
+                }
+                sorted_on_gpu = on_gpu;
+                send_view_proj = false;
+                idle_sort.touch(now);
+                refined_sort_time = None;
+            }
+
+            // use the spare CPU time of a still camera for a finer sort
+            if done_streaming && sort_needed && !sorted_on_gpu && idle_sort.poll(now) {
                 let view_proj = projection_matrix * view_matrix;
                 //////////////////////////////////
                 // non-blocking (i.e., no atomic.wait)
-                let _ = bus_vp.try_broadcast(view_proj);
+                let _ = bus_refine.try_broadcast(view_proj);
                 //////////////////////////////////
-                send_view_proj = false;
             }
 
+            // under front-to-back blending the clear color is composited under the splats by the quad pass
+            let under = blend_mode.draw_order() == DrawOrder::FrontToBack;
+            let clear_background = Background::solid(clear_color);
+            let quad_background = if under && !preserve_canvas && !background.is_composited() {
+                &clear_background
+            } else {
+                &background
+            };
+
+            // the splat pass and the picking pass draw the same splats
+            let splat_view = SplatView {
+                projection: *projection_slice,
+                view: *view_slice,
+                focal: [fx.abs()*rw/w, fy.abs()*rh/h],
+                viewport: [rw, rh],
+                htan_fov: [htanx, htany],
+                cam_pos: [model_cam_pos.x, model_cam_pos.y, model_cam_pos.z],
+            };
+            let splat_params = SplatRenderParams {
+                splat_scale,
+                near_fade: [camera.z_near(), near_fade],
+                global_alpha: global_alpha*fade,
+                depth_focus: dof_focus,
+                explode: explode_params,
+                view_dependent,
+                antialias,
+                hovered,
+                blend_mode,
+                color_space,
+                quad_mode,
+                max_splats: draw_cap.count(scene.splat_count) as i32,
+            };
+
             unsafe {
                 // render to texture
                 gl.bind_framebuffer(context::FRAMEBUFFER, quad_glsl.framebuffer);
                 {
-                    gl.viewport(0, 0, w as i32, h as i32);
+                    // the splats are drawn into the lower-left rw x rh of the texture and stretched by the quad pass
+                    gl.viewport(0, 0, rw as i32, rh as i32);
+                    // the canvas expects premultiplied alpha, and a preserved canvas
+                    // gets the splats composited over it, so the texture starts out transparent
+                    // so does a background that the quad pass composites the splats over,
+                    // and front-to-back blending, which needs the destination alpha
+                    let transparent = preserve_canvas || background.is_composited() || under;
+                    let [mut r, mut g, mut b, a] = if transparent { [0.0; 4] } else { clear_color };
+                    if color_space.encode_output() {
+                        // the picked clear color is sRGB, the offscreen texture linear
+                        r = srgb_to_linear(r);
+                        g = srgb_to_linear(g);
+                        b = srgb_to_linear(b);
+                    }
+                    gl.clear_color(r*a, g*a, b*a, a);
                     gl.clear(context::COLOR_BUFFER_BIT);
 
-                    splat_glsl.render(
-                        &gl,
-                        projection_slice,
-                        view_slice,
-                        &[fx.abs(), fy.abs()],
-                        &[w, h],
-                        &[htanx, htany],
-                        &[cam_pos.x, cam_pos.y, cam_pos.z],
-                        splat_scale,
-                        &mut rx_depth,
-                        scene.splat_count as i32
-                    );
+                    // skip the whole splat pass while the scene is out of frame
+                    if scene_on_screen && !(show_points && points_only) {
+                        gpu_timer.begin(&gl, GpuPass::Splat);
+                        quad_glsl.draw_depth(&gl, depth_of_field);
+                        // drain the sorter thread's results either way, a GPU result is newer
+                        #[cfg(feature = "webgpu_sort")]
+                        let depth_index = {
+                            let cpu_index = rx_depth.try_take();
+                            gpu_sorter.take_result().or(cpu_index)
+                        };
+                        #[cfg(not(feature = "webgpu_sort"))]
+                        let depth_index = rx_depth.try_take();
+                        let mut depth = DepthArray { depth_index };
+                        if fade_in_pending && depth.depth_index.is_some() {
+                            // drawn at fade 0 this frame, the ramp starts with the next
+                            fade_in_pending = false;
+                            fade_in_elapsed = Some(0.0);
+                        }
+                        splat_glsl.render(&gl, &splat_view, &splat_params, &mut depth);
+                        quad_glsl.draw_depth(&gl, false);
+                        gpu_timer.end(&gl);
+                    }
+                    if scene_on_screen && show_points {
+                        point_glsl.render(
+                            &gl,
+                            &splat_glsl,
+                            projection_slice,
+                            view_slice,
+                            point_size,
+                            explode_params,
+                            color_space,
+                            scene.splat_count as i32
+                        );
+                    }
+                    if let (true, Some((min, max))) = (crop && show_crop_box, sort_settings.crop) {
+                        box_glsl.render(&gl, projection_slice, view_slice, &min, &max, &[1.0, 0.8, 0.0, 1.0]);
+                    }
                 }
                 gl.bind_framebuffer(context::FRAMEBUFFER, None);
 
+                if let Some((x, y)) = pick_pixel.take() {
+                    // the cursor is in canvas pixels, the splats were drawn at the render scale
+                    let pixel = ((x*rw/w) as i32, (y*rh/h) as i32);
+                    let picked = if scene_on_screen {
+                        splat_id_glsl.pick(&gl, &splat_glsl, pixel, &splat_view, &splat_params)
+                    } else {
+                        None
+                    };
+                    log!("main(): picked {:?} at {:?}", picked, pixel);
+                    inspected = Some(picked);
+                }
+
                 { // render the textured quad
                     gl.viewport(0, 0, w as i32, h as i32);
-                    gl.clear(context::COLOR_BUFFER_BIT);
+                    if !preserve_canvas {
+                        gl.clear_color(0.0, 0.0, 0.0, 0.0);
+                        gl.clear(context::COLOR_BUFFER_BIT);
+                    }
+
+                    gpu_timer.begin(&gl, GpuPass::Quad);
+                    quad_glsl.render(&gl, quad_background, &QuadRenderParams {
+                        dither: if dither { dither_strength } else { 0.0 },
+                        blend: preserve_canvas,
+                        linear_to_srgb: color_space.encode_output(),
+                        uv_scale: [rw/w, rh/h],
+                        aspect: w/h,
+                        dof_blur: if depth_of_field && scene_on_screen && !(show_points && points_only) { dof_blur } else { 0.0 },
+                        tone_map,
+                        exposure,
+                        fxaa,
+                    });
+                    gpu_timer.end(&gl);
+                }
 
-                    quad_glsl.render(&gl);
+                if save_screenshot {
+                    // the canvas holds the final image (background, output encoding, full resolution) until the GUI is drawn over it;
+                    // the read-back stalls this frame only, encoding and the download happen asynchronously
+                    let (width, height) = (w as u32, h as u32);
+                    let mut pixels = vec![0_u8; 4*(width as usize)*(height as usize)];
+                    gl.read_pixels(
+                        0,
+                        0,
+                        width as i32,
+                        height as i32,
+                        context::RGBA,
+                        context::UNSIGNED_BYTE,
+                        context::PixelPackData::Slice(&mut pixels)
+                    );
+                    let pixels = gl_pixels_to_image(&pixels, width as usize);
+                    execute_future(async move {
+                        match encode_png(&pixels, width, height).await.dyn_into::<Uint8Array>() {
+                            Ok(png) => download_bytes(&png.to_vec(), "gauzilla-screenshot.png"),
+                            Err(e) => log!("main(): ERROR: encode_png(): {:?}", e),
+                        }
+                    });
+                    save_screenshot = false;
                 }
 
-                gui.render();
+                gpu_timer.begin(&gl, GpuPass::Gui);
+                if !(recording && record_hide_gui) {
+                    gui.render();
+                }
+                gpu_timer.end(&gl);
                 gl.flush();
+                gpu_timer.poll(&gl);
             }
         } else {
             gui.render();
@@ -1108,3 +4769,39 @@ pub async fn main() {
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mat4_near(a: Mat4, b: Mat4) {
+        for c in 0..4 {
+            for r in 0..4 {
+                assert!((a[c][r] - b[c][r]).abs() < 1e-4, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_model_transform_url_pose() {
+        let viewport = Viewport::new_at_origo(640, 480);
+        let model = ModelTransform { invert_y: true, ..ModelTransform::default() };
+        // default URL params and an off-axis link
+        for (position, target, up) in [
+            (vec3(0.0, 0.0, 5.0), vec3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)),
+            (vec3(1.5, -2.0, 3.0), vec3(0.5, 0.25, -1.0), vec3(0.0, 1.0, 0.0)),
+        ] {
+            // baseline: the scene as is, viewed from the URL pose rolled by 180°
+            let mut baseline = Camera::new_perspective(viewport, position, target, up, degrees(45.0), 0.1, 10.0);
+            baseline.roll(degrees(180.0));
+
+            let (p, t, u) = model.apply_pose(position, target, up);
+            let camera = Camera::new_perspective(viewport, p, t, u, degrees(45.0), 0.1, 10.0);
+            assert_mat4_near(*camera.view() * model.matrix(), *baseline.view());
+        }
+
+        // the default pose isn't moved at all
+        let (p, t, u) = model.apply_pose(vec3(0.0, 0.0, 5.0), vec3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0));
+        assert_eq!((p, t), (vec3(0.0, 0.0, 5.0), vec3(0.0, 0.0, 0.0)));
+        assert!((u - vec3(0.0, 1.0, 0.0)).magnitude() < 1e-6);
+    }
+}
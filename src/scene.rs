@@ -1,19 +1,37 @@
 use std::{
-    io::{BufRead, Cursor, BufReader, Read, Seek, SeekFrom},
+    io::{BufRead, Cursor, BufReader, Seek, SeekFrom},
     cmp::Ordering,
-    sync::{Arc, Mutex},
+    sync::{Arc, Weak, Mutex, atomic::{AtomicUsize, Ordering as AtomicOrdering}},
 };
 use three_d::prelude::*;
-use bus::Bus;
-//use wasm_thread as thread;
+use bus::{Bus, BusReader};
+use wasm_thread as thread;
 
 use crate::log; // macro import
 use crate::utils::*;
 use crate::spz::{Spz, load_spz};
+use crate::pool::WorkerPool;
+use crate::lod::Octree;
+use crate::error::GauzillaError;
 
 
 const MAX_HEADER_LINES: usize = 65;
-const SH_C0: f32 = 0.28209479177387814;
+pub(crate) const SH_C0: f32 = 0.28209479177387814;
+const SH_TEX_WIDTH: usize = 4096; // texels per row of the SH texture
+const TEX_WIDTH: usize = 2048; // texels per row of the splat texture, widened for scenes that don't fit
+// smaller scenes are sorted on the calling thread, as spawning the workers would cost more than it saves
+const PARALLEL_SORT_MIN_SPLATS: usize = 1 << 16;
+const PROGRESS_SPLATS: usize = 1 << 16; // splats decoded between two updates of the loading progress bar
+// share of the clip volume kept by the culling before the sort, wider than the 1.2 gsplat.vert discards at,
+// so that splats entering the view while the order is stale are already sorted
+const CULL_MARGIN: f32 = 1.5;
+// full-precision .splat variant: "SPF4", splat count (u32 LE), then 44-byte rows with the quaternion as 4x f32
+const SPLAT_F32_MAGIC: &[u8; 4] = b"SPF4";
+const SPLAT_F32_ROW: usize = 3*4 + 3*4 + 4 + 4*4;
+// .ksplat (GaussianSplats3D's SplatBuffer 0.1): a header, max_section_count section headers, then the sections
+const KSPLAT_HEADER_BYTES: usize = 4096;
+const KSPLAT_SECTION_HEADER_BYTES: usize = 1024;
+const KSPLAT_SH_COUNTS: [usize; 4] = [0, 9, 24, 45]; // SH components per splat by degree
 
 
 #[derive(Clone)]
@@ -33,6 +51,249 @@ impl Default for SerializedSplat {
 }
 
 
+/// Encoding of the vertex data of a PLY file
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlyFormat {
+    BinaryLittleEndian,
+    Ascii, // one whitespace-separated line per vertex (slow)
+}
+
+
+/// What [Scene::parse_file_header] found in the header of a PLY file
+#[derive(Clone, Debug)]
+pub struct PlyHeader {
+    pub size: u16, // header length in bytes
+    pub splat_count: usize,
+    pub format: PlyFormat,
+    pub properties: Vec<String>, // names of the vertex properties in file order
+    pub property_types: Vec<String>, // PLY types of the vertex properties (e.g. "float")
+}
+impl PlyHeader {
+    /// SH degree implied by the number of f_rest_* properties (3*((degree + 1)^2 - 1) of them)
+    pub fn sh_degree(&self) -> usize {
+        let rest = self.properties.iter().filter(|p| p.starts_with("f_rest_")).count();
+        (0..=3).rev().find(|&d| 3*sh_rest_count(d) <= rest).unwrap_or(0)
+    }
+
+    /// Index of the named vertex property in file order (i.e. of its value in each vertex)
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|p| p == name)
+    }
+
+    /// True if the vertices carry normals (Scaniverse and some other exporters leave out nx/ny/nz)
+    pub fn has_normals(&self) -> bool {
+        ["nx", "ny", "nz"].iter().all(|n| self.column(n).is_some())
+    }
+
+    /// Types of the vertex properties in file order
+    fn parsed_types(&self) -> Result<Vec<PlyType>, GauzillaError> {
+        self.property_types.iter().map(|ty| PlyType::parse(ty)).collect()
+    }
+
+    /// Sizes in bytes of the vertex properties in file order
+    pub fn property_sizes(&self) -> Result<Vec<usize>, GauzillaError> {
+        Ok(self.parsed_types()?.iter().map(|ty| ty.size()).collect())
+    }
+
+    /// Bytes per vertex of a binary file
+    pub fn vertex_stride(&self) -> Result<usize, GauzillaError> {
+        Ok(self.property_sizes()?.iter().sum())
+    }
+}
+
+
+/// Scalar type of a PLY vertex property, parsed once from the header
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum PlyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+impl PlyType {
+    fn parse(ty: &str) -> Result<Self, GauzillaError> {
+        match ty {
+            "char" | "int8" => Ok(PlyType::Int8),
+            "uchar" | "uint8" => Ok(PlyType::UInt8),
+            "short" | "int16" => Ok(PlyType::Int16),
+            "ushort" | "uint16" => Ok(PlyType::UInt16),
+            "int" | "int32" => Ok(PlyType::Int32),
+            "uint" | "uint32" => Ok(PlyType::UInt32),
+            "float" | "float32" => Ok(PlyType::Float32),
+            "double" | "float64" => Ok(PlyType::Float64),
+            _ => Err(GauzillaError::UnsupportedFormat(format!("PLY property type {}", ty))),
+        }
+    }
+
+    /// Size in bytes
+    fn size(&self) -> usize {
+        match self {
+            PlyType::Int8 | PlyType::UInt8 => 1,
+            PlyType::Int16 | PlyType::UInt16 => 2,
+            PlyType::Int32 | PlyType::UInt32 | PlyType::Float32 => 4,
+            PlyType::Float64 => 8,
+        }
+    }
+
+    /// Reads a little-endian scalar of this type as f32
+    #[inline(always)]
+    fn read(&self, bytes: &[u8]) -> f32 {
+        match self {
+            PlyType::Int8 => bytes[0] as i8 as f32,
+            PlyType::UInt8 => bytes[0] as f32,
+            PlyType::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            PlyType::UInt16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            PlyType::Int32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+            PlyType::UInt32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+            PlyType::Float32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            PlyType::Float64 => f64::from_le_bytes(bytes[0..8].try_into().unwrap()) as f32,
+        }
+    }
+}
+
+
+/// Columns of the vertex properties that make up a splat, looked up by name
+/// so the order of the properties and the number of f_rest_* ones don't matter
+struct PlyColumns {
+    position: [usize; 3],
+    normal: [Option<usize>; 3],
+    f_dc: [usize; 3],
+    f_rest: Vec<Option<usize>>, // f_rest_k for k in 0..3*rest (None if absent)
+    rest: usize, // SH coefficients per channel
+    opacity: usize,
+    scale: [usize; 3],
+    rotation: [usize; 4],
+}
+impl PlyColumns {
+    fn new(header: &PlyHeader) -> Result<Self, GauzillaError> {
+        let column = |name: &str| header.column(name);
+        let required = |name: &str| column(name)
+            .ok_or_else(|| GauzillaError::BadHeader(format!("missing vertex property {}", name)));
+
+        let rest = sh_rest_count(header.sh_degree());
+        Ok(Self {
+            position: [required("x")?, required("y")?, required("z")?],
+            normal: [column("nx"), column("ny"), column("nz")],
+            f_dc: [required("f_dc_0")?, required("f_dc_1")?, required("f_dc_2")?],
+            f_rest: (0..3*rest).map(|k| column(&format!("f_rest_{}", k))).collect(),
+            rest,
+            opacity: required("opacity")?,
+            scale: [required("scale_0")?, required("scale_1")?, required("scale_2")?],
+            rotation: [required("rot_0")?, required("rot_1")?, required("rot_2")?, required("rot_3")?],
+        })
+    }
+
+
+    /// Assembles a splat from the property values of one vertex
+    fn fill(&self, values: &[f32], s: &mut SerializedSplat) {
+        for k in 0..3 {
+            s.position[k] = values[self.position[k]];
+            s.n[k] = self.normal[k].map_or(0.0, |c| values[c]);
+            s.color[k] = values[self.f_dc[k]];
+            s.scale[k] = values[self.scale[k]];
+        }
+        for (k, c) in self.f_rest.iter().enumerate() {
+            // same channel-major layout as a binary degree-3 file (15 coefficients per channel)
+            s.color[3 + 15*(k/self.rest) + k%self.rest] = c.map_or(0.0, |c| values[c]);
+        }
+        s.alpha = values[self.opacity];
+        for k in 0..4 {
+            s.rotation[k] = values[self.rotation[k]];
+        }
+    }
+}
+
+
+/// Reads the vertices of a binary little-endian PLY file
+struct BinaryPlyReader<'a> {
+    columns: PlyColumns,
+    types: Vec<PlyType>, // per property, parsed once from the header
+    all_float: bool, // every property is a float, so a vertex is copied as is
+    stride: usize, // bytes per vertex
+    start: usize, // offset of the first vertex
+    bytes: &'a [u8],
+}
+impl<'a> BinaryPlyReader<'a> {
+    /// cursor is positioned at the first vertex
+    fn new(cursor: &'a Cursor<Vec<u8>>, header: &'a PlyHeader) -> Result<Self, GauzillaError> {
+        let columns = PlyColumns::new(header)?;
+        let types = header.parsed_types()?;
+        let all_float = types.iter().all(|&ty| ty == PlyType::Float32);
+        let stride = types.iter().map(|ty| ty.size()).sum::<usize>();
+        let start = cursor.position() as usize;
+        let bytes = cursor.get_ref().as_slice();
+        if bytes.len() < start + stride*header.splat_count {
+            return Err(GauzillaError::Truncated(format!("expected {} splats after the PLY header", header.splat_count)));
+        }
+        Ok(Self { columns, types, all_float, stride, start, bytes })
+    }
+
+
+    /// Parses the vertices first..first + out.len() into out
+    fn read(&self, first: usize, out: &mut [SerializedSplat]) {
+        let mut values = vec![0_f32; self.types.len()];
+        for (j, s) in out.iter_mut().enumerate() {
+            let offset = self.start + (first + j)*self.stride;
+            let vertex = &self.bytes[offset..offset + self.stride];
+            if self.all_float {
+                // single memcpy per vertex (WASM and x86 are little-endian like the file)
+                transmute_slice_mut::<_, u8>(values.as_mut_slice()).copy_from_slice(vertex);
+            } else {
+                let mut offset = 0;
+                for (value, ty) in values.iter_mut().zip(self.types.iter()) {
+                    *value = ty.read(&vertex[offset..]);
+                    offset += ty.size();
+                }
+            }
+            self.columns.fill(&values, s);
+        }
+    }
+}
+
+
+/// Progress bar of the "Loading..." window, fed while a file is decoded (None once it's done)
+/// Only the latest share is kept, so unlike a bounded bus no update (in particular the last one) can be dropped.
+pub type ProgressCell = Rc<Cell<Option<f64>>>;
+
+
+/// Sets the decoded share [0, 1] of the progress bar (if any)
+/// and yields to the browser so that the bar gets painted
+pub async fn report_progress(progress: Option<&ProgressCell>, fraction: f64) {
+    if let Some(cell) = progress {
+        cell.set(Some(fraction));
+        sleep_js(0).await;
+    }
+}
+
+
+/// Decodes a quaternion component packed into a byte of the splat buffer ([0, 255] -> [-1, 1])
+pub fn unpack_quat_byte(b: u8) -> f32 {
+    b as f32/127.5 - 1.0
+}
+
+
+/// Number of higher-order (non-DC) SH coefficients per color channel for the given degree
+pub fn sh_rest_count(degree: usize) -> usize {
+    (degree + 1)*(degree + 1) - 1
+}
+
+
+/// Returns true if bytes hold the full-precision .splat variant:
+/// the magic "SPF4", the splat count as u32 (little-endian), then per splat
+/// position (3x f32), scale (3x f32), RGBA (4x u8), and quaternion (w, x, y, z) (4x f32)
+pub fn is_splat_f32(bytes: &[u8]) -> bool {
+    if bytes.len() < 8 || &bytes[0..4] != SPLAT_F32_MAGIC {
+        return false;
+    }
+    let n = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    bytes.len() == 8 + n*SPLAT_F32_ROW
+}
+
+
 #[derive(Clone)]
 #[repr(C)]
 pub struct SerializedSplat2 { // Scaniverse PLY format (no normals) / SPZ format
@@ -49,45 +310,467 @@ impl Default for SerializedSplat2 {
 }
 
 
+/// Order in which the sorted splats are drawn (has to match the blend func)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DrawOrder {
+    BackToFront, // for "over" compositing
+    FrontToBack, // for "under" compositing
+}
+
+
+/// Depth sort implementation, picked when the sorter thread is launched
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortAlgorithm {
+    Counting, // single pass over 2^depth_bits buckets (Scene::sort)
+    Radix, // 8-bit LSD passes over 256 buckets with reused scratch buffers (Scene::sort_radix)
+}
+impl SortAlgorithm {
+    /// Parses the ?sort= URL parameter ("radix", anything else is counting)
+    pub fn from_param(param: &str) -> Self {
+        if param.eq_ignore_ascii_case("radix") { SortAlgorithm::Radix } else { SortAlgorithm::Counting }
+    }
+}
+
+
+/// Buffers kept between radix sorts, so that large scenes don't reallocate them every frame
+#[derive(Default)]
+struct RadixScratch {
+    keys: Vec<u32>, // quantized depths
+    keys_tmp: Vec<u32>,
+    index_tmp: Vec<u32>,
+}
+
+
+/// Parameters of the depth sort that can be changed at runtime (sent to the sorter thread)
+#[derive(Clone, PartialEq, Debug)]
+pub struct SortSettings {
+    pub depth_bits: u32, // the counting sort uses 2^depth_bits buckets
+    pub splat_percent: f32, // only the top splat_percent% most important splats are rendered
+    pub axis: Option<[f32; 3]>, // debug: sort as if looking along this world direction instead of the camera's
+    pub order: DrawOrder,
+    pub lod: Option<f32>, // octree LOD: refine nodes covering more than this share of the viewport height
+    pub explode: Option<([f32; 3], f32)>, // (center, offset): sort as if each splat was pushed away from center (cf. gsplat.vert)
+    pub cull: bool, // leave out the splats well outside the view, cf. CULL_MARGIN (resorts on camera moves, not only turns)
+    pub min_opacity: f32, // leave out the splats whose alpha (0 to 1) is below this
+    pub crop: Option<([f32; 3], [f32; 3])>, // (min, max): leave out the splats centered outside this box (scene coordinates, before the model transform)
+}
+impl SortSettings {
+    pub const MIN_DEPTH_BITS: u32 = 12;
+    pub const MAX_DEPTH_BITS: u32 = 20;
+
+    /// Settings for a one-shot higher-quality sort (e.g. while the camera is idle)
+    pub fn refined(&self) -> Self {
+        Self {
+            depth_bits: Self::MAX_DEPTH_BITS,
+            ..self.clone()
+        }
+    }
+
+
+    /// Number of splats that are rendered out of splat_count
+    pub fn limited_count(&self, splat_count: usize) -> usize {
+        let count = ((self.splat_percent.clamp(0.0, 100.0) as f64)/100.0*(splat_count as f64)).round() as usize;
+        count.min(splat_count)
+    }
+
+    /// Alpha byte below which splats are left out (0 keeps all of them)
+    pub fn min_alpha(&self) -> u8 {
+        (self.min_opacity.clamp(0.0, 1.0)*255.0).round() as u8
+    }
+
+    /// Memory used by the counts/starts histograms in bytes
+    pub fn histogram_bytes(&self) -> usize {
+        2*(1_usize << self.depth_bits)*std::mem::size_of::<u32>()
+    }
+}
+impl Default for SortSettings {
+    fn default() -> Self {
+        Self {
+            depth_bits: 16,
+            splat_percent: 100.0,
+            axis: None,
+            order: DrawOrder::BackToFront,
+            lod: None,
+            explode: None,
+            cull: false,
+            min_opacity: 0.0,
+            crop: None,
+        }
+    }
+}
+
+
+/// Returns false if the axis-aligned box is outside of the view frustum
+/// (i.e., all 8 corners lie outside the same clip plane of view_proj)
+pub fn aabb_in_frustum(min: [f32; 3], max: [f32; 3], view_proj: &[f32]) -> bool {
+    let mut outside = [0_usize; 6]; // -x, +x, -y, +y, -z (near), +z (far)
+    for i in 0..8 {
+        let x = if i & 1 == 0 { min[0] } else { max[0] };
+        let y = if i & 2 == 0 { min[1] } else { max[1] };
+        let z = if i & 4 == 0 { min[2] } else { max[2] };
+        // column-major view_proj
+        let clip = |r: usize| view_proj[r]*x + view_proj[4 + r]*y + view_proj[8 + r]*z + view_proj[12 + r];
+        let (cx, cy, cz, cw) = (clip(0), clip(1), clip(2), clip(3));
+        if cx < -cw { outside[0] += 1; }
+        if cx > cw { outside[1] += 1; }
+        if cy < -cw { outside[2] += 1; }
+        if cy > cw { outside[3] += 1; }
+        if cz < -cw { outside[4] += 1; }
+        if cz > cw { outside[5] += 1; }
+    }
+
+    !outside.iter().any(|&n| n == 8)
+}
+
+
+/// Destination of the depth-sorted splat indices produced by [Scene::sort]
+pub trait DepthSink {
+    fn submit(&mut self, depth_index: Vec<u32>);
+}
+
+
+/// Source of the depth-sorted splat indices consumed by the renderer
+pub trait DepthSource {
+    /// Returns the latest sort result, if a new one is available
+    fn try_take(&mut self) -> Option<Vec<u32>>;
+}
+
+
+/// Hands the sort result over to another thread (sorter thread -> render loop)
+impl DepthSink for Bus<Vec<u32>> {
+    fn submit(&mut self, depth_index: Vec<u32>) {
+        //////////////////////////////////
+        // no cloning is happening for the single-consumer case
+        let _ = self.try_broadcast(depth_index);
+        //////////////////////////////////
+    }
+}
+impl DepthSource for BusReader<Vec<u32>> {
+    fn try_take(&mut self) -> Option<Vec<u32>> {
+        //////////////////////////////////
+        // non-blocking (i.e., no atomic.wait)
+        self.try_recv().ok()
+        //////////////////////////////////
+    }
+}
+
+
+/// Keeps the sort result in memory on the same thread (e.g. for profiling or single-frame rendering)
+#[derive(Default)]
+pub struct DepthArray {
+    pub depth_index: Option<Vec<u32>>,
+}
+impl DepthSink for DepthArray {
+    fn submit(&mut self, depth_index: Vec<u32>) {
+        self.depth_index = Some(depth_index);
+    }
+}
+impl DepthSource for DepthArray {
+    fn try_take(&mut self) -> Option<Vec<u32>> {
+        self.depth_index.take()
+    }
+}
+
+
+/// Axes along which the splats are mirrored (e.g. to fix the handedness of a capture)
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct MirrorAxes {
+    pub x: bool,
+    pub y: bool,
+    pub z: bool,
+}
+impl MirrorAxes {
+    /// Parses a URL parameter such as "x" or "xz" (case-insensitive, unknown characters are ignored)
+    pub fn from_param(param: &str) -> Self {
+        let param = param.to_lowercase();
+        Self {
+            x: param.contains('x'),
+            y: param.contains('y'),
+            z: param.contains('z'),
+        }
+    }
+
+
+    /// Axes that have to be flipped to go from self to other
+    pub fn diff(&self, other: &MirrorAxes) -> MirrorAxes {
+        MirrorAxes {
+            x: self.x != other.x,
+            y: self.y != other.y,
+            z: self.z != other.z,
+        }
+    }
+
+
+    pub fn is_identity(&self) -> bool {
+        !(self.x || self.y || self.z)
+    }
+}
+
+
+/// Texture formats and source transforms a scene is packed with, cf. Scene::rebuilt()
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PackSettings {
+    pub half_positions: bool, // positions packed as half-floats
+    pub full_covariance: bool, // covariance packed as f32 instead of half-floats
+    pub mirror: MirrorAxes, // relative to the source file
+    pub max_sh_degree: usize, // highest SH degree put into the SH texture
+    pub world_scale: f32, // scene units per source file unit
+    pub scale_clamp: Option<f32>, // percentile of the largest splat scales above which scales are clamped
+}
+impl Default for PackSettings {
+    fn default() -> Self {
+        Self {
+            half_positions: false,
+            full_covariance: false,
+            mirror: MirrorAxes::default(),
+            max_sh_degree: 3,
+            world_scale: 1.0,
+            scale_clamp: None,
+        }
+    }
+}
+
+
+// MAX_TEXTURE_SIZE of the GPU, the WebGL2 minimum until the renderer has queried it
+static MAX_TEXTURE_SIZE: AtomicUsize = AtomicUsize::new(2048);
+
+
+/// Identifies the packed splat texture of a scene (cf. TEXTURE_CACHE)
+#[derive(PartialEq, Clone, Copy, Debug)]
+struct TextureKey {
+    buffer_hash: u64, // of buffer and rotations
+    splat_count: usize,
+    half_positions: bool,
+    full_covariance: bool,
+    max_scale: u32, // f32 bits of the scale clamp
+    width: usize,
+}
+
+
+// splat textures of the scenes still alive, reused while the geometry stays the same (e.g. reloading a URL)
+// only weak references, so a texture is freed with the last scene that uses it
+static TEXTURE_CACHE: Mutex<Vec<(TextureKey, Weak<Vec<u32>>)>> = Mutex::new(Vec::new());
+
+
+/// Records the GPU's MAX_TEXTURE_SIZE, which bounds the splat textures generated from then on (also in workers)
+pub(crate) fn set_max_texture_size(size: usize) {
+    MAX_TEXTURE_SIZE.store(size.max(1), AtomicOrdering::Relaxed);
+}
+
+
+pub(crate) fn max_texture_size() -> usize {
+    MAX_TEXTURE_SIZE.load(AtomicOrdering::Relaxed)
+}
+
+
 /// A point cloud of Gaussian splats
 pub struct Scene {
     pub splat_count: usize,
     pub(crate) buffer: Vec<u8>,
-    pub(crate) tex_data: Vec<u32>,
+    pub(crate) tex_data: Arc<Vec<u32>>, // shared with TEXTURE_CACHE and the scenes packed from the same geometry
     pub(crate) tex_width: usize,
     pub(crate) tex_height: usize,
+    pub(crate) bbox: Option<(Vec3, Vec3)>, // axis-aligned bounding box (min, max) of splat centers
+    pub(crate) half_positions: bool, // positions packed as half-floats (3 texels per 2 splats)
+    pub(crate) full_covariance: bool, // covariance packed as f32 (one more texel per splat) instead of half-floats
+    pub(crate) source_format: &'static str, // file format the splats were loaded from ("" if unknown)
+    pub(crate) mirror: MirrorAxes, // mirror applied to buffer relative to the source file
+    pub(crate) world_scale: f32, // scene units per source file unit, applied to positions and scales in buffer
+    pub(crate) unscaled: Option<Arc<Vec<f32>>>, // source positions (unmirrored) and scales, 6 per splat, kept while world_scale != 1
+    pub(crate) importance_rank: Vec<u32>, // rank of each splat by size*opacity (0 = most important)
+    pub(crate) rotations: Vec<[f32; 4]>, // unit quaternions (w, x, y, z) in buffer order, empty if only the u8 ones in buffer are known
+    pub(crate) sh_degree: usize, // SH degree of sh_coeffs (0 = DC only)
+    pub(crate) antialiased: bool, // trained with the 2D antialiasing filter (SPZ flag), rendered with opacity compensation
+    pub(crate) sh_coeffs: Vec<f32>, // per splat sh_rest_count(sh_degree) RGB triplets in buffer order
+    pub(crate) max_sh_degree: usize, // highest SH degree put into the SH texture
+    pub(crate) sh_tex_data: Vec<f32>, // RGB32F, one texel per coefficient (empty for DC-only scenes)
+    pub(crate) sh_tex_width: usize,
+    pub(crate) sh_tex_height: usize,
+    pub(crate) sh_tex_degree: usize, // SH degree stored in sh_tex_data
+    pub(crate) scale_clamp: Option<f32>, // percentile of the largest splat scales above which scales are clamped in the texture
+    pub(crate) scale_limit: Option<f32>, // scale the percentile corresponded to in the last generate_texture()
+    pub(crate) clamped_count: usize, // number of splats clamped in the last generate_texture()
+    pub(crate) octree: Option<Arc<Octree>>, // resident nodes of a streamed .lod scene in source file coordinates (None: built by the sorter)
     prev_vp: Mutex<Vec<f32>>,
+    radix_scratch: Mutex<RadixScratch>,
 }
 impl Scene {
     pub fn new() -> Self {
         Self {
             splat_count: 0,
             buffer: Vec::<u8>::new(),
-            tex_data: Vec::<u32>::new(),
+            tex_data: Arc::new(Vec::<u32>::new()),
             tex_width: 0,
             tex_height: 0,
+            bbox: None,
+            half_positions: false,
+            full_covariance: false,
+            source_format: "",
+            mirror: MirrorAxes::default(),
+            world_scale: 1.0,
+            unscaled: None,
+            importance_rank: Vec::<u32>::new(),
+            rotations: Vec::<[f32; 4]>::new(),
+            sh_degree: 0,
+            antialiased: false,
+            sh_coeffs: Vec::<f32>::new(),
+            max_sh_degree: 3,
+            sh_tex_data: Vec::<f32>::new(),
+            sh_tex_width: 0,
+            sh_tex_height: 0,
+            sh_tex_degree: 0,
+            scale_clamp: None,
+            scale_limit: None,
+            clamped_count: 0,
+            octree: None,
             prev_vp: Mutex::new(Vec::<f32>::new()),
+            radix_scratch: Mutex::new(RadixScratch::default()),
+        }
+    }
+
+
+    /// Scans the splat centers in buffer and caches their axis-aligned bounding box
+    pub fn compute_bounding_box(&mut self) {
+        if self.buffer.is_empty() || self.splat_count == 0 {
+            self.bbox = None;
+            return;
+        }
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(self.buffer.as_slice());
+
+        let mut min = vec3(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = vec3(f32::MIN, f32::MIN, f32::MIN);
+        for i in 0..self.splat_count {
+            let index_f = 8*i;
+            let p = vec3(f_buffer[index_f + 0], f_buffer[index_f + 1], f_buffer[index_f + 2]);
+            min = vec3(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = vec3(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+        log!("Scene::compute_bounding_box(): min={:?}, max={:?}", min, max);
+
+        self.bbox = Some((min, max));
+    }
+
+
+    /// Axis-aligned bounding box (min, max) of the splat centers, as cached by compute_bounding_box() on load
+    /// An empty scene has a zero-size box at the origin.
+    pub fn bounding_box(&self) -> (Vec3, Vec3) {
+        self.bbox.unwrap_or((vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0)))
+    }
+
+
+    /// Ranks the splats by importance (size*opacity, same measure as the load-time ordering)
+    /// PLY/SPZ buffers are already stored in this order, but .splat files need not be
+    pub fn compute_importance(&mut self) {
+        if self.buffer.is_empty() || self.splat_count == 0 {
+            self.importance_rank.clear();
+            return;
+        }
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(self.buffer.as_slice());
+        let u_buffer: &[u8] = self.buffer.as_slice();
+
+        let size_list: Vec<f32> = (0..self.splat_count)
+            .map(|i| {
+                let index_f = 8*i;
+                let size = f_buffer[index_f + 3]*f_buffer[index_f + 4]*f_buffer[index_f + 5];
+                let opacity = (u_buffer[32*i + 3*4 + 3*4 + 3] as f32)/255.0;
+                size*opacity
+            })
+            .collect();
+
+        // stable sort keeps the stored order for ties
+        let mut size_index: Vec<u32> = (0..self.splat_count as u32).collect();
+        size_index.sort_by(
+            |&a, &b| size_list[b as usize]
+                .partial_cmp(&size_list[a as usize])
+                .unwrap_or(Ordering::Equal)
+        );
+
+        let mut rank = vec![0_u32; self.splat_count];
+        for (k, &i) in size_index.iter().enumerate() {
+            rank[i as usize] = k as u32;
+        }
+        self.importance_rank = rank;
+    }
+
+
+    /// Returns the mean position of the splat centers
+    pub fn centroid(&self) -> Option<Vec3> {
+        if self.buffer.is_empty() || self.splat_count == 0 {
+            return None;
+        }
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(self.buffer.as_slice());
+
+        // accumulate in f64 to avoid losing precision on large clouds
+        let mut sum = [0_f64; 3];
+        for i in 0..self.splat_count {
+            let index_f = 8*i;
+            sum[0] += f_buffer[index_f + 0] as f64;
+            sum[1] += f_buffer[index_f + 1] as f64;
+            sum[2] += f_buffer[index_f + 2] as f64;
+        }
+        let n = self.splat_count as f64;
+
+        Some(vec3((sum[0]/n) as f32, (sum[1]/n) as f32, (sum[2]/n) as f32))
+    }
+
+
+    /// Returns false if the whole bounding box is outside of the view frustum
+    /// (i.e., all 8 corners lie outside the same clip plane of view_proj)
+    pub fn is_in_frustum(&self, view_proj: &[f32]) -> bool {
+        match self.bbox {
+            Some((min, max)) => aabb_in_frustum([min.x, min.y, min.z], [max.x, max.y, max.z], view_proj),
+            None => true, // unknown extent, can't tell
         }
     }
 
 
     /// Parses the header of a PLY file
-    /// Returns the header length in bytes, the number of splats in the file, and the file cursor
-    pub fn parse_file_header(bytes: Vec<u8>) -> Result<(u16, usize, Cursor<Vec<u8>>), String> {
+    /// Returns the header (length in bytes, number of splats, format, vertex properties) and the file cursor
+    pub fn parse_file_header(bytes: Vec<u8>) -> Result<(PlyHeader, Cursor<Vec<u8>>), GauzillaError> {
         let mut reader = BufReader::new(Cursor::new(bytes));
         let mut line = String::new();
         let mut splat_count: usize = 0;
+        let mut format = PlyFormat::BinaryLittleEndian;
+        let mut properties = Vec::<String>::new();
+        let mut property_types = Vec::<String>::new();
+        let mut in_vertex_element = false;
         let mut success = false;
         let mut i = 0;
 
         loop {
-            reader.read_line(&mut line).unwrap();
-            if line == "end_header\n" {
-                success = true;
-                break;
+            if reader.read_line(&mut line).map_err(|e| GauzillaError::BadHeader(e.to_string()))? == 0 {
+                break; // EOF before end_header
             }
-            if line.starts_with("element vertex ") {
-                splat_count = line[15..line.len() - 1].parse().unwrap();
+            // tokenized, so that \r\n line endings and extra whitespace don't matter
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.as_slice() {
+                ["end_header"] => {
+                    success = true;
+                    break;
+                },
+                ["format", f, ..] => {
+                    format = match *f {
+                        "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                        "ascii" => PlyFormat::Ascii,
+                        _ => return Err(GauzillaError::UnsupportedFormat(line.trim_end().to_string())),
+                    };
+                },
+                ["element", name, count, ..] => {
+                    in_vertex_element = *name == "vertex";
+                    if in_vertex_element {
+                        splat_count = count.parse()
+                            .map_err(|_| GauzillaError::BadHeader(format!("invalid vertex count: {}", line.trim_end())))?;
+                    }
+                },
+                ["element", ..] => in_vertex_element = false,
+                ["property", "list", ..] if in_vertex_element => {
+                    return Err(GauzillaError::UnsupportedFormat(format!("PLY list property {}", line.trim_end())));
+                },
+                ["property", ty, name] if in_vertex_element => {
+                    property_types.push(ty.to_string());
+                    properties.push(name.to_string());
+                },
+                _ => {}, // ply, comment, obj_info, properties of other elements
             }
             line.clear();
 
@@ -100,27 +783,164 @@ impl Scene {
         if !success {
             let error = "Scene::parse_file_header(): ERROR: the file is not correctly formatted.";
             log!("{}, i={}", error, i);
-            return Err(error.to_string());
+            return Err(GauzillaError::BadHeader(error.to_string()));
         }
 
         let file_header_size = reader.stream_position().unwrap() as u16;
         let cursor = reader.into_inner();
+        let header = PlyHeader {
+            size: file_header_size,
+            splat_count,
+            format,
+            properties,
+            property_types,
+        };
         log!(
-            "Scene::parse_file_header(): i={}, file_header_size={}, splat_count={}",
+            "Scene::parse_file_header(): i={}, file_header_size={}, splat_count={}, format={:?}, normals={}, vertex_stride={:?}",
             i,
             file_header_size,
-            splat_count
+            splat_count,
+            format,
+            header.has_normals(),
+            header.vertex_stride().ok()
         );
+        Ok((header, cursor))
+    }
+
+
+    /// Parses the vertex lines of an ascii PLY file into the same layout as the binary one
+    fn read_ascii_splats(cursor: &mut Cursor<Vec<u8>>, header: &PlyHeader) -> Result<Vec<SerializedSplat>, GauzillaError> {
+        let columns = PlyColumns::new(header)?;
+
+        let mut serialized_splats = vec![SerializedSplat::default(); header.splat_count];
+        let mut lines = cursor.lines();
+        let mut values = Vec::<f32>::with_capacity(header.properties.len());
+        for (i, s) in serialized_splats.iter_mut().enumerate() {
+            Self::read_ascii_splat(&mut lines, header, &columns, i, &mut values, s)?;
+        }
+
+        Ok(serialized_splats)
+    }
+
+
+    /// Same as read_ascii_splats(), reporting progress (up to max_progress) every PROGRESS_SPLATS lines
+    async fn read_ascii_splats_with_progress(
+        cursor: &mut Cursor<Vec<u8>>,
+        header: &PlyHeader,
+        progress: &ProgressCell,
+        max_progress: f64
+    ) -> Result<Vec<SerializedSplat>, GauzillaError> {
+        let columns = PlyColumns::new(header)?;
+
+        let mut serialized_splats = vec![SerializedSplat::default(); header.splat_count];
+        let mut lines = cursor.lines();
+        let mut values = Vec::<f32>::with_capacity(header.properties.len());
+        for (k, chunk) in serialized_splats.chunks_mut(PROGRESS_SPLATS).enumerate() {
+            for (j, s) in chunk.iter_mut().enumerate() {
+                Self::read_ascii_splat(&mut lines, header, &columns, k*PROGRESS_SPLATS + j, &mut values, s)?;
+            }
+            let done = k*PROGRESS_SPLATS + chunk.len();
+            report_progress(Some(progress), max_progress*(done as f64)/(header.splat_count as f64)).await;
+        }
+
+        Ok(serialized_splats)
+    }
+
+
+    /// Parses the next vertex line, vertex i, into s (values is scratch space)
+    fn read_ascii_splat(
+        lines: &mut impl Iterator<Item = std::io::Result<String>>,
+        header: &PlyHeader,
+        columns: &PlyColumns,
+        i: usize,
+        values: &mut Vec<f32>,
+        s: &mut SerializedSplat
+    ) -> Result<(), GauzillaError> {
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return Err(GauzillaError::Truncated(format!("expected {} vertices, found {}", header.splat_count, i))),
+        };
+        values.clear();
+        for token in line.split_whitespace() {
+            values.push(token.parse::<f32>()
+                .map_err(|_| GauzillaError::DecodeFailed(format!("vertex {}: invalid value {}", i, token)))?);
+        }
+        if values.len() < header.properties.len() {
+            return Err(GauzillaError::DecodeFailed(format!(
+                "vertex {}: {} values for {} properties", i, values.len(), header.properties.len()
+            )));
+        }
+        columns.fill(values, s);
+
+        Ok(())
+    }
+
+
+    /// Parses the vertices of a binary little-endian PLY file by property name
+    /// (e.g. INRIA 3DGS outputs with f_dc_0..2 and any number of f_rest_* properties)
+    fn read_binary_splats(cursor: &mut Cursor<Vec<u8>>, header: &PlyHeader) -> Result<Vec<SerializedSplat>, GauzillaError> {
+        let reader = BinaryPlyReader::new(cursor, header)?;
+        let mut serialized_splats = vec![SerializedSplat::default(); header.splat_count];
+        reader.read(0, &mut serialized_splats);
+        Ok(serialized_splats)
+    }
+
 
-        Ok((file_header_size, splat_count, cursor))
+    /// Same as read_binary_splats(), reporting progress (up to max_progress) every PROGRESS_SPLATS splats
+    async fn read_binary_splats_with_progress(
+        cursor: &mut Cursor<Vec<u8>>,
+        header: &PlyHeader,
+        progress: &ProgressCell,
+        max_progress: f64
+    ) -> Result<Vec<SerializedSplat>, GauzillaError> {
+        let reader = BinaryPlyReader::new(cursor, header)?;
+        let mut serialized_splats = vec![SerializedSplat::default(); header.splat_count];
+        for (k, chunk) in serialized_splats.chunks_mut(PROGRESS_SPLATS).enumerate() {
+            reader.read(k*PROGRESS_SPLATS, chunk);
+            let done = k*PROGRESS_SPLATS + chunk.len();
+            report_progress(Some(progress), max_progress*(done as f64)/(header.splat_count as f64)).await;
+        }
+        Ok(serialized_splats)
     }
 
 
     /// Loads an entire PLY file into WASM memory
-    pub fn load(&mut self, cursor: &mut Cursor<Vec<u8>>, file_header_size: u16) {
-        let mut serialized_splats = vec![SerializedSplat::default(); self.splat_count];
-        cursor.seek(SeekFrom::Start(file_header_size as u64)).unwrap();
-        cursor.read_exact(transmute_slice_mut::<_, u8>(serialized_splats.as_mut_slice())).unwrap();
+    pub fn load(&mut self, cursor: &mut Cursor<Vec<u8>>, header: &PlyHeader) -> Result<(), GauzillaError> {
+        cursor.seek(SeekFrom::Start(header.size as u64))
+            .map_err(|e| GauzillaError::Truncated(e.to_string()))?;
+        let serialized_splats = match header.format {
+            PlyFormat::BinaryLittleEndian => Self::read_binary_splats(cursor, header)?,
+            PlyFormat::Ascii => Self::read_ascii_splats(cursor, header)?,
+        };
+        self.load_serialized(serialized_splats, header.sh_degree());
+
+        Ok(())
+    }
+
+
+    /// Same as load(), updating the progress bar while the vertices are parsed
+    pub async fn load_with_progress(
+        &mut self,
+        cursor: &mut Cursor<Vec<u8>>,
+        header: &PlyHeader,
+        progress: &ProgressCell
+    ) -> Result<(), GauzillaError> {
+        cursor.seek(SeekFrom::Start(header.size as u64))
+            .map_err(|e| GauzillaError::Truncated(e.to_string()))?;
+        let serialized_splats = match header.format {
+            PlyFormat::BinaryLittleEndian => Self::read_binary_splats_with_progress(cursor, header, progress, 0.8).await?,
+            PlyFormat::Ascii => Self::read_ascii_splats_with_progress(cursor, header, progress, 0.8).await?,
+        };
+        report_progress(Some(progress), 0.8).await;
+        self.load_serialized(serialized_splats, header.sh_degree());
+
+        Ok(())
+    }
+
+
+    /// Builds the buffer from the parsed PLY vertices, ordered by importance
+    fn load_serialized(&mut self, serialized_splats: Vec<SerializedSplat>, sh_degree: usize) {
+        self.sh_degree = sh_degree;
 
         // calculate importance of each splat
         let mut size_list = vec![0_f32; self.splat_count];
@@ -152,6 +972,8 @@ impl Scene {
         // IJKL - quaternion (u8)
         let row_length = 3*4 + 3*4 + 4 + 4; // 32bytes
         let mut buffer = vec![0_u8; row_length*self.splat_count];
+        let mut rotations = Vec::<[f32; 4]>::with_capacity(self.splat_count);
+        let mut sh_coeffs = Vec::<f32>::with_capacity(3*sh_rest_count(self.sh_degree)*self.splat_count);
         for i in 0..self.splat_count {
             let row = size_index[i] as usize;
             let s = &serialized_splats[row];
@@ -197,14 +1019,23 @@ impl Scene {
                 rot[1] = (((s.rotation[1]/qlen) + 1.0)*0.5 * 255.0) as u8;
                 rot[2] = (((s.rotation[2]/qlen) + 1.0)*0.5 * 255.0) as u8;
                 rot[3] = (((s.rotation[3]/qlen) + 1.0)*0.5 * 255.0) as u8;
+                rotations.push(s.rotation.map(|q| q/qlen));
             }
+
+            Self::push_sh(&s.color, self.sh_degree, &mut sh_coeffs);
         }
+        Self::mirror_buffer(&mut buffer, self.mirror);
+        Self::mirror_rotations(&mut rotations, self.mirror);
+        Self::mirror_sh(&mut sh_coeffs, self.sh_degree, self.mirror);
         self.buffer = buffer;
+        self.rotations = rotations;
+        self.sh_coeffs = sh_coeffs;
     }
 
 
     /// Loads an entire PLY file (w/o normals) into WASM memory
-    pub fn load_no_normal(&mut self, serialized_splats: Vec<SerializedSplat2>) { // TODO: remove code redundancy w/ load()
+    pub fn load_no_normal(&mut self, serialized_splats: Vec<SerializedSplat2>, sh_degree: usize) { // TODO: remove code redundancy w/ load()
+        self.sh_degree = sh_degree.min(3);
         // calculate importance of each splat
         let mut size_list = vec![0_f32; self.splat_count];
         let mut size_index = vec![0_u32; self.splat_count];
@@ -235,6 +1066,8 @@ impl Scene {
         // IJKL - quaternion (u8)
         let row_length = 3*4 + 3*4 + 4 + 4; // 32bytes
         let mut buffer = vec![0_u8; row_length*self.splat_count];
+        let mut rotations = Vec::<[f32; 4]>::with_capacity(self.splat_count);
+        let mut sh_coeffs = Vec::<f32>::with_capacity(3*sh_rest_count(self.sh_degree)*self.splat_count);
         for i in 0..self.splat_count {
             let row = size_index[i] as usize;
             let s = &serialized_splats[row];
@@ -280,146 +1113,1361 @@ impl Scene {
                 rot[1] = (((s.rotation[1]/qlen) + 1.0)*0.5 * 255.0) as u8;
                 rot[2] = (((s.rotation[2]/qlen) + 1.0)*0.5 * 255.0) as u8;
                 rot[3] = (((s.rotation[3]/qlen) + 1.0)*0.5 * 255.0) as u8;
+                rotations.push(s.rotation.map(|q| q/qlen));
             }
+
+            Self::push_sh(&s.color, self.sh_degree, &mut sh_coeffs);
         }
+        Self::mirror_buffer(&mut buffer, self.mirror);
+        Self::mirror_rotations(&mut rotations, self.mirror);
+        Self::mirror_sh(&mut sh_coeffs, self.sh_degree, self.mirror);
         self.buffer = buffer;
+        self.rotations = rotations;
+        self.sh_coeffs = sh_coeffs;
     }
 
 
-    /// Generates a 2D texture from the splats
-    pub fn generate_texture(&mut self) { // TODO: parallelize
-        if self.buffer.is_empty() {
-            return;
+    /// Loads a full-precision .splat file (see is_splat_f32())
+    /// The quaternions are kept as f32 for the covariance and quantized to u8 only in buffer.
+    pub fn load_splat_f32(&mut self, bytes: &[u8]) {
+        let n = (bytes.len() - 8) / SPLAT_F32_ROW;
+        let mut buffer = vec![0_u8; 32*n];
+        let mut rotations = Vec::<[f32; 4]>::with_capacity(n);
+        for i in 0..n {
+            let src = &bytes[8 + SPLAT_F32_ROW*i..8 + SPLAT_F32_ROW*(i + 1)];
+            let row = &mut buffer[32*i..32*(i + 1)];
+            row[0..3*4 + 3*4 + 4].copy_from_slice(&src[0..3*4 + 3*4 + 4]); // position, scale, RGBA
+
+            let q: [f32; 4] = std::array::from_fn(|k| {
+                let start = 3*4 + 3*4 + 4 + 4*k;
+                f32::from_le_bytes([src[start], src[start + 1], src[start + 2], src[start + 3]])
+            });
+            let qlen = (q[0].powi(2) + q[1].powi(2) + q[2].powi(2) + q[3].powi(2)).sqrt();
+            let q = q.map(|v| v/qlen);
+            for k in 0..4 {
+                // [-1, 1] -> [0, 255]
+                row[3*4 + 3*4 + 4 + k] = ((q[k] + 1.0)*0.5 * 255.0) as u8;
+            }
+            rotations.push(q);
+        }
+        Self::mirror_buffer(&mut buffer, self.mirror);
+        Self::mirror_rotations(&mut rotations, self.mirror);
+        self.splat_count = n;
+        self.buffer = buffer;
+        self.rotations = rotations;
+    }
+
+
+    /// Loads a bucketed .ksplat file (version 0.1 of the GaussianSplats3D viewer) at any compression level
+    /// Level 0 stores f32 rows, levels 1 and 2 u16 centers relative to the center of a bucket of splats
+    /// and f16 scales/rotations. The SH coefficients are skipped, only the base color is kept.
+    pub fn load_ksplat(&mut self, bytes: &[u8]) -> Result<(), GauzillaError> {
+        let u16_at = |o: usize| u16::from_le_bytes([bytes[o], bytes[o + 1]]);
+        let u32_at = |o: usize| u32::from_le_bytes([bytes[o], bytes[o + 1], bytes[o + 2], bytes[o + 3]]);
+        let f32_at = |o: usize| f32::from_bits(u32_at(o));
+        let f16_at = |o: usize| half::f16::from_bits(u16_at(o)).to_f32();
+
+        if bytes.len() < KSPLAT_HEADER_BYTES {
+            return Err(GauzillaError::Truncated(format!("{} bytes is shorter than the .ksplat header", bytes.len())));
+        }
+        let (version_major, version_minor) = (bytes[0], bytes[1]);
+        if version_major != 0 || version_minor < 1 {
+            return Err(GauzillaError::UnsupportedFormat(format!(".ksplat version {}.{}", version_major, version_minor)));
+        }
+        let max_section_count = u32_at(4) as usize;
+        let section_count = u32_at(8) as usize;
+        let compression_level = u16_at(20);
+        if section_count > max_section_count {
+            return Err(GauzillaError::BadHeader(format!("{} of at most {} sections", section_count, max_section_count)));
+        }
+        let sections_start = max_section_count
+            .checked_mul(KSPLAT_SECTION_HEADER_BYTES)
+            .and_then(|n| n.checked_add(KSPLAT_HEADER_BYTES))
+            .filter(|&n| n <= bytes.len())
+            .ok_or_else(|| GauzillaError::Truncated(format!("{} section headers", max_section_count)))?;
+        // bytes of (center, scale, rotation, SH component) and the default quantization range of the centers
+        let (center_bytes, scale_bytes, rotation_bytes, sh_bytes, default_range) = match compression_level {
+            0 => (12, 12, 16, 4, 1),
+            1 => (6, 6, 8, 2, 32767),
+            2 => (6, 6, 8, 1, 32767),
+            _ => return Err(GauzillaError::UnsupportedFormat(format!(".ksplat compression level {}", compression_level))),
+        };
+        log!(
+            "Scene::load_ksplat(): version={}.{}, sections={}/{}, splat_count={}, compression_level={}",
+            version_major, version_minor, section_count, max_section_count, u32_at(16), compression_level
+        );
+
+        let mut buffer = Vec::<u8>::new();
+        let mut rotations = Vec::<[f32; 4]>::new();
+        let mut base = sections_start;
+        for s in 0..section_count {
+            let h = KSPLAT_HEADER_BYTES + s*KSPLAT_SECTION_HEADER_BYTES;
+            let splat_count = u32_at(h) as usize;
+            let max_splat_count = u32_at(h + 4) as usize;
+            let bucket_size = u32_at(h + 8) as usize;
+            let bucket_count = u32_at(h + 12) as usize;
+            let half_block_size = f32_at(h + 16)/2.0;
+            let bucket_bytes = u16_at(h + 20) as usize;
+            let range = match u32_at(h + 24) {
+                0 => default_range,
+                r => r,
+            } as f32;
+            let full_bucket_count = u32_at(h + 32) as usize;
+            let partial_bucket_count = u32_at(h + 36) as usize;
+            let sh_count = *KSPLAT_SH_COUNTS.get(u16_at(h + 40) as usize)
+                .ok_or_else(|| GauzillaError::UnsupportedFormat(format!("SH degree {} in section {}", u16_at(h + 40), s)))?;
+            let bytes_per_splat = center_bytes + scale_bytes + rotation_bytes + 4 + sh_count*sh_bytes;
+
+            // lengths of the partially filled buckets, the bucket centers, then the splats
+            let buckets = base + 4*partial_bucket_count;
+            let data = buckets + bucket_bytes*bucket_count;
+            let end = data + bytes_per_splat*max_splat_count;
+            if splat_count > max_splat_count || end > bytes.len() {
+                return Err(GauzillaError::Truncated(format!("section {} ends at {} of {} bytes", s, end, bytes.len())));
+            }
+            if compression_level > 0 && (bucket_bytes < 12 || full_bucket_count + partial_bucket_count > bucket_count) {
+                return Err(GauzillaError::BadHeader(format!("{} buckets in section {}", bucket_count, s)));
+            }
+
+            // the full buckets hold bucket_size splats each, the partially filled ones follow
+            let mut partial = 0;
+            let mut partial_start = full_bucket_count*bucket_size;
+            for i in 0..splat_count {
+                let src = data + bytes_per_splat*i;
+                let position: [f32; 3] = if compression_level == 0 {
+                    std::array::from_fn(|k| f32_at(src + 4*k))
+                } else {
+                    let bucket = if i < partial_start {
+                        i/bucket_size
+                    } else {
+                        while partial < partial_bucket_count && i >= partial_start + u32_at(base + 4*partial) as usize {
+                            partial_start += u32_at(base + 4*partial) as usize;
+                            partial += 1;
+                        }
+                        full_bucket_count + partial
+                    };
+                    if bucket >= bucket_count {
+                        return Err(GauzillaError::BadHeader(format!("splat {} outside the buckets of section {}", i, s)));
+                    }
+                    let center = buckets + bucket_bytes*bucket;
+                    std::array::from_fn(|k| (u16_at(src + 2*k) as f32 - range)*half_block_size/range + f32_at(center + 4*k))
+                };
+                let src = src + center_bytes;
+                let scale: [f32; 3] = std::array::from_fn(|k| if compression_level == 0 { f32_at(src + 4*k) } else { f16_at(src + 2*k) });
+                let src = src + scale_bytes;
+                let q: [f32; 4] = std::array::from_fn(|k| if compression_level == 0 { f32_at(src + 4*k) } else { f16_at(src + 2*k) });
+                let src = src + rotation_bytes;
+
+                let mut row = [0_u8; 32];
+                for k in 0..3 {
+                    row[4*k..4*(k + 1)].copy_from_slice(&position[k].to_le_bytes());
+                    row[3*4 + 4*k..3*4 + 4*(k + 1)].copy_from_slice(&scale[k].to_le_bytes());
+                }
+                row[3*4 + 3*4..3*4 + 3*4 + 4].copy_from_slice(&bytes[src..src + 4]); // RGBA
+                // (w, x, y, z) as in .splat files
+                let qlen = (q[0].powi(2) + q[1].powi(2) + q[2].powi(2) + q[3].powi(2)).sqrt().max(f32::EPSILON);
+                let q = q.map(|v| v/qlen);
+                for k in 0..4 {
+                    // [-1, 1] -> [0, 255]
+                    row[3*4 + 3*4 + 4 + k] = ((q[k] + 1.0)*0.5 * 255.0) as u8;
+                }
+                buffer.extend_from_slice(&row);
+                rotations.push(q);
+            }
+            base = end;
+        }
+        Self::mirror_buffer(&mut buffer, self.mirror);
+        Self::mirror_rotations(&mut rotations, self.mirror);
+        self.splat_count = rotations.len();
+        self.buffer = buffer;
+        self.rotations = rotations;
+        Ok(())
+    }
+
+
+    /// Appends the higher-order SH coefficients of a splat as RGB triplets
+    /// color holds the DC term followed by 15 coefficients per channel (R, then G, then B) as in PLY files
+    fn push_sh(color: &[f32; 3*16], sh_degree: usize, sh_coeffs: &mut Vec<f32>) {
+        for j in 0..sh_rest_count(sh_degree) {
+            sh_coeffs.push(color[3 + j]);
+            sh_coeffs.push(color[3 + 15 + j]);
+            sh_coeffs.push(color[3 + 2*15 + j]);
+        }
+    }
+
+
+    /// Mirrors the SH coefficients of all splats along the given axes
+    /// Negates the coefficients whose basis function is odd in a mirrored axis
+    fn mirror_sh(sh_coeffs: &mut [f32], sh_degree: usize, axes: MirrorAxes) {
+        // parity of the basis functions 1..15 in (x, y, z), cf. the SH evaluation in gsplat.frag
+        const ODD: [[bool; 3]; 15] = [
+            [false, true, false], [false, false, true], [true, false, false], // y, z, x
+            [true, true, false], [false, true, true], [false, false, false], // xy, yz, 2zz-xx-yy
+            [true, false, true], [false, false, false], // xz, xx-yy
+            [false, true, false], [true, true, true], [false, true, false], // y(3xx-yy), xyz, y(4zz-xx-yy)
+            [false, false, true], [true, false, false], [false, false, true], // z(2zz-3xx-3yy), x(4zz-xx-yy), z(xx-yy)
+            [true, false, false], // x(xx-3yy)
+        ];
+        let rest = sh_rest_count(sh_degree);
+        if axes.is_identity() || rest == 0 {
+            return;
+        }
+        let mirrored = [axes.x, axes.y, axes.z];
+        let negate: Vec<bool> = ODD[..rest].iter()
+            .map(|odd| (0..3).filter(|&a| odd[a] && mirrored[a]).count() % 2 == 1)
+            .collect();
+        for splat in sh_coeffs.chunks_exact_mut(3*rest) {
+            for (j, rgb) in splat.chunks_exact_mut(3).enumerate() {
+                if negate[j] {
+                    rgb.iter_mut().for_each(|c| *c = -*c);
+                }
+            }
+        }
+    }
+
+
+    /// Mirrors every 32-byte row of a splat buffer along the given axes
+    ///
+    /// Mirroring along X maps the rotation R to M*R*M with M = diag(-1, 1, 1), which keeps R a proper
+    /// rotation and the covariance R*S*S^T*R^T equal to M*Sigma*M, so splats don't turn inside-out.
+    /// For the quaternion (w, x, y, z) this means negating the two components other than x.
+    /// Scales are magnitudes along the local axes and stay unchanged.
+    fn mirror_buffer(buffer: &mut [u8], axes: MirrorAxes) {
+        if axes.is_identity() {
+            return;
+        }
+        let flips = [
+            (axes.x, [false, false, true, true]), // (w, x, -y, -z)
+            (axes.y, [false, true, false, true]), // (w, -x, y, -z)
+            (axes.z, [false, true, true, false]), // (w, -x, -y, z)
+        ];
+        for row in buffer.chunks_exact_mut(32) {
+            {
+                let position: &mut [f32] = transmute_slice_mut::<_, f32>(&mut row[0..3*4]);
+                if axes.x { position[0] = -position[0]; }
+                if axes.y { position[1] = -position[1]; }
+                if axes.z { position[2] = -position[2]; }
+            }
+
+            // [0, 255] encodes [-1, 1] linearly, so 255 - v is the exact negation of v
+            let rot = &mut row[3*4 + 3*4 + 4..32];
+            for (enabled, negate) in flips.iter() {
+                if !*enabled {
+                    continue;
+                }
+                for k in 0..4 {
+                    if negate[k] {
+                        rot[k] = 255 - rot[k];
+                    }
+                }
+            }
+        }
+    }
+
+
+    /// Mirrors full-precision quaternions (w, x, y, z) the same way as mirror_buffer()
+    fn mirror_rotations(rotations: &mut [[f32; 4]], axes: MirrorAxes) {
+        if axes.is_identity() {
+            return;
+        }
+        let negate = [
+            false,
+            axes.y != axes.z,
+            axes.x != axes.z,
+            axes.x != axes.y,
+        ];
+        for q in rotations.iter_mut() {
+            for k in 1..4 {
+                if negate[k] {
+                    q[k] = -q[k];
+                }
+            }
+        }
+    }
+
+
+    /// Mirrors the splats in place so that the buffer matches the given mirror relative to the source file
+    /// The texture and bounding box have to be regenerated afterwards
+    pub fn set_mirror(&mut self, mirror: MirrorAxes) {
+        let diff = self.mirror.diff(&mirror);
+        Self::mirror_buffer(&mut self.buffer, diff);
+        Self::mirror_rotations(&mut self.rotations, diff);
+        Self::mirror_sh(&mut self.sh_coeffs, self.sh_degree, diff);
+        self.mirror = mirror;
+    }
+
+
+    /// Multiplies the positions and scales of every 32-byte row of a splat buffer by ratio
+    fn scale_buffer(buffer: &mut [u8], ratio: f32) {
+        if ratio == 1.0 {
+            return;
+        }
+        for row in buffer.chunks_exact_mut(32) {
+            let f: &mut [f32] = transmute_slice_mut::<_, f32>(&mut row[0..3*4 + 3*4]);
+            f.iter_mut().for_each(|v| *v *= ratio);
+        }
+    }
+
+
+    /// Rescales the splats in place so that one source file unit maps to world_scale scene units
+    /// The positions and scales are recomputed from the source values, so that no rounding error builds up
+    /// while the scale is changed back and forth.
+    /// The texture and bounding box have to be regenerated afterwards
+    pub fn set_world_scale(&mut self, world_scale: f32) {
+        if world_scale == self.world_scale {
+            return;
+        }
+        let sign = [self.mirror.x, self.mirror.y, self.mirror.z].map(|m| if m { -1.0_f32 } else { 1.0 });
+        let unscaled = match self.unscaled.take() {
+            Some(unscaled) if unscaled.len() == 6*self.splat_count => unscaled,
+            _ => {
+                // the buffer holds the source values times world_scale (exactly while that is 1)
+                let inv = 1.0/self.world_scale;
+                let f_buffer: &[f32] = transmute_slice::<_, f32>(self.buffer.as_slice());
+                let mut unscaled = Vec::<f32>::with_capacity(6*self.splat_count);
+                for i in 0..self.splat_count {
+                    let f = &f_buffer[8*i..8*i + 6];
+                    unscaled.extend_from_slice(&[
+                        f[0]*sign[0]*inv, f[1]*sign[1]*inv, f[2]*sign[2]*inv,
+                        f[3]*inv, f[4]*inv, f[5]*inv,
+                    ]);
+                }
+                Arc::new(unscaled)
+            },
+        };
+        for (row, source) in self.buffer.chunks_exact_mut(32).zip(unscaled.chunks_exact(6)) {
+            let f: &mut [f32] = transmute_slice_mut::<_, f32>(&mut row[0..3*4 + 3*4]);
+            for k in 0..3 {
+                f[k] = source[k]*sign[k]*world_scale;
+                f[3 + k] = source[3 + k]*world_scale;
+            }
+        }
+        self.world_scale = world_scale;
+        // at scale 1 the buffer holds the source values again
+        self.unscaled = if world_scale == 1.0 { None } else { Some(unscaled) };
+    }
+
+
+    /// Generates a 2D texture from the splats
+    /// Large scenes are packed by n_threads workers, which blocks, so call it with n_threads > 1 from a worker only
+    pub fn generate_texture(&mut self, n_threads: usize) {
+        if self.buffer.is_empty() {
+            return;
         }
         let f_buffer: &[f32] = transmute_slice::<_, f32>(self.buffer.as_slice());
         let u_buffer: &[u8] = transmute_slice::<_, u8>(self.buffer.as_slice());
 
-        let texwidth = 1024*2 as usize;
-        let texheight = ((2*self.splat_count) as f64 / texwidth as f64).ceil() as usize;
+        let max_size = max_texture_size();
+        let texels = Self::texel_count(self.splat_count, self.half_positions, self.full_covariance);
+        let texwidth = Self::texture_width(texels, max_size);
+        let texheight = (texels as f64 / texwidth as f64).ceil() as usize;
         let len_texdata = texwidth*texheight*4 as usize; // 4 components per pixel (RGBA)
-        log!("Scene::generate_texture(): texheight={}, len_texdata={}", texheight, len_texdata);
-        let mut texdata = vec![0_u32; len_texdata];
+        log!("Scene::generate_texture(): texwidth={}, texheight={}, len_texdata={}", texwidth, texheight, len_texdata);
+        if texheight > max_size {
+            // generated anyway, check_texture_size() reports it before the upload
+            log!("Scene::generate_texture(): WARNING: {} rows exceed MAX_TEXTURE_SIZE={}", texheight, max_size);
+        }
+
+        // outlier giant splats are clamped in the texture only, the buffer keeps the loaded scales
+        self.scale_limit = self.scale_clamp.and_then(|p| self.scale_percentile(p));
+        let max_scale = self.scale_limit.unwrap_or(f32::INFINITY);
+        self.clamped_count = match self.scale_limit {
+            Some(limit) => (0..self.splat_count)
+                .filter(|&i| f_buffer[8*i + 3].max(f_buffer[8*i + 4]).max(f_buffer[8*i + 5]) > limit)
+                .count(),
+            None => 0,
+        };
+        if let Some(limit) = self.scale_limit {
+            log!("Scene::generate_texture(): clamped {} splats to scale {}", self.clamped_count, limit);
+        }
 
+        let (half_positions, full_covariance) = (self.half_positions, self.full_covariance);
+        let rotations = &self.rotations;
+        let key = TextureKey {
+            buffer_hash: hash_bytes(&self.buffer[..32*self.splat_count]) ^ hash_bytes(transmute_slice::<_, u8>(rotations.as_slice())).rotate_left(1),
+            splat_count: self.splat_count,
+            half_positions,
+            full_covariance,
+            max_scale: max_scale.to_bits(),
+            width: texwidth,
+        };
+        let cached = TEXTURE_CACHE.lock().unwrap().iter()
+            .find(|(k, _)| *k == key)
+            .and_then(|(_, tex_data)| tex_data.upgrade());
+        if let Some(tex_data) = cached {
+            log!("Scene::generate_texture(): reusing the texture packed last time");
+            self.tex_data = tex_data;
+            self.tex_width = texwidth;
+            self.tex_height = texheight;
+            self.generate_sh_texture();
+            return;
+        }
+
+        let mut texdata = vec![0_u32; len_texdata];
+        match Self::parallel_chunk(self.splat_count, n_threads) {
+            Some(chunk) => {
+                // even ranges, so that the splats sharing a half-float position texel are packed by the same worker
+                let chunk = chunk.next_multiple_of(2);
+                let chunk_len = 4*Self::texel_count(chunk, half_positions, full_covariance);
+                thread::scope(|s| {
+                    for (t, texels) in texdata.chunks_mut(chunk_len).take(self.splat_count.div_ceil(chunk)).enumerate() {
+                        let first = t*chunk;
+                        let last = (first + chunk).min(self.splat_count);
+                        s.spawn(move || {
+                            // offsets relative to the range, which starts on a texel group
+                            let (f_buffer, u_buffer) = (&f_buffer[8*first..], &u_buffer[32*first..]);
+                            for i in first..last {
+                                Self::pack_splat(
+                                    f_buffer, u_buffer, rotations.get(i), i - first, half_positions, full_covariance, max_scale, texels
+                                );
+                            }
+                        });
+                    }
+                });
+            },
+            None => {
+                for i in 0..self.splat_count {
+                    Self::pack_splat(
+                        f_buffer, u_buffer, rotations.get(i), i, half_positions, full_covariance, max_scale, &mut texdata
+                    );
+                }
+            },
+        }
+        self.tex_data = Arc::new(texdata);
         {
-            let texdata_f = transmute_slice_mut::<_, f32>(texdata.as_mut_slice());
-            for i in 0..self.splat_count {
-                // x, y, z components of the i-th splat in f_buffer
-                let index_f: usize = 8*i;
-                texdata_f[index_f + 0] = f_buffer[index_f + 0];
-                texdata_f[index_f + 1] = f_buffer[index_f + 1];
-                texdata_f[index_f + 2] = f_buffer[index_f + 2];
+            let mut cache = TEXTURE_CACHE.lock().unwrap();
+            cache.retain(|(k, tex_data)| *k != key && tex_data.strong_count() > 0);
+            cache.push((key, Arc::downgrade(&self.tex_data)));
+        }
+
+        self.tex_width = texwidth;
+        self.tex_height = texheight;
+
+        self.generate_sh_texture();
+    }
+
+
+    /// Generates the RGB32F texture of the higher-order SH coefficients up to max_sh_degree
+    /// Splat i occupies the texels [i*n, (i + 1)*n) with n = sh_rest_count(sh_tex_degree).
+    /// Skipped (left empty) for DC-only scenes.
+    fn generate_sh_texture(&mut self) {
+        self.sh_tex_degree = self.sh_degree.min(self.max_sh_degree);
+        let n = sh_rest_count(self.sh_tex_degree);
+        if n == 0 || self.splat_count == 0 {
+            self.sh_tex_degree = 0;
+            self.sh_tex_data = Vec::<f32>::new();
+            self.sh_tex_width = 0;
+            self.sh_tex_height = 0;
+            return;
+        }
+
+        let stride = 3*sh_rest_count(self.sh_degree);
+        let texheight = ((n*self.splat_count) as f64 / SH_TEX_WIDTH as f64).ceil() as usize;
+        let mut texdata = vec![0_f32; 3*SH_TEX_WIDTH*texheight];
+        for i in 0..self.splat_count {
+            texdata[3*n*i..3*n*(i + 1)].copy_from_slice(&self.sh_coeffs[stride*i..stride*i + 3*n]);
+        }
+        log!("Scene::generate_sh_texture(): degree={}, texheight={}", self.sh_tex_degree, texheight);
+
+        self.sh_tex_data = texdata;
+        self.sh_tex_width = SH_TEX_WIDTH;
+        self.sh_tex_height = texheight;
+    }
+
+
+    /// Returns the p-th percentile (0-100) of the largest scale of each splat
+    pub fn scale_percentile(&self, p: f32) -> Option<f32> {
+        if self.splat_count == 0 || self.buffer.len() < 32*self.splat_count {
+            return None;
+        }
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(self.buffer.as_slice());
+        let mut max_scales: Vec<f32> = (0..self.splat_count)
+            .map(|i| f_buffer[8*i + 3].max(f_buffer[8*i + 4]).max(f_buffer[8*i + 5]))
+            .collect();
+        let k = ((p.clamp(0.0, 100.0)/100.0)*((self.splat_count - 1) as f32)).round() as usize;
+        let (_, value, _) = max_scales.select_nth_unstable_by(k, |a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        Some(*value)
+    }
+
+
+    /// Settings this scene was packed with
+    pub fn pack_settings(&self) -> PackSettings {
+        PackSettings {
+            half_positions: self.half_positions,
+            full_covariance: self.full_covariance,
+            mirror: self.mirror,
+            max_sh_degree: self.max_sh_degree,
+            world_scale: self.world_scale,
+            scale_clamp: self.scale_clamp,
+        }
+    }
+
+
+    /// Returns a copy of this scene with its texture regenerated for the given position and covariance formats, mirror,
+    /// SH degree, world scale, and scale clamp percentile
+    pub fn rebuilt(&self, pack: &PackSettings) -> Scene {
+        let mut scene = Scene::new();
+        scene.splat_count = self.splat_count;
+        scene.buffer = self.buffer.clone();
+        scene.bbox = self.bbox;
+        scene.half_positions = pack.half_positions;
+        scene.full_covariance = pack.full_covariance;
+        scene.source_format = self.source_format;
+        scene.mirror = self.mirror;
+        scene.world_scale = self.world_scale;
+        scene.unscaled = self.unscaled.clone();
+        scene.importance_rank = self.importance_rank.clone();
+        scene.rotations = self.rotations.clone();
+        scene.sh_degree = self.sh_degree;
+        scene.antialiased = self.antialiased;
+        scene.sh_coeffs = self.sh_coeffs.clone();
+        scene.max_sh_degree = pack.max_sh_degree;
+        scene.scale_clamp = pack.scale_clamp;
+        scene.octree = self.octree.clone(); // same buffer order, and the octree is unaffected by mirror and world scale
+        if scene.mirror != pack.mirror || scene.world_scale != pack.world_scale {
+            scene.set_mirror(pack.mirror);
+            scene.set_world_scale(pack.world_scale);
+            scene.compute_bounding_box();
+        }
+        scene.generate_texture(1);
+        scene
+    }
+
+
+    /// Concatenates scenes, each translated by its offset, into one that is sorted (and blended) as a whole
+    /// The texture formats, mirror, world scale, and scale clamp are taken from the first scene.
+    /// The merged scene gets the highest SH degree, the others contribute zeros for the missing coefficients.
+    pub fn merged(parts: &[(&Scene, [f32; 3])]) -> Scene {
+        let mut scene = Scene::new();
+        let first = match parts.first() {
+            Some((first, _)) => *first,
+            None => return scene,
+        };
+        scene.half_positions = first.half_positions;
+        scene.full_covariance = first.full_covariance;
+        scene.source_format = first.source_format;
+        scene.mirror = first.mirror;
+        scene.world_scale = first.world_scale;
+        scene.max_sh_degree = first.max_sh_degree;
+        scene.scale_clamp = first.scale_clamp;
+        scene.sh_degree = parts.iter().map(|(s, _)| s.sh_degree).max().unwrap_or(0);
+        scene.antialiased = parts.iter().any(|(s, _)| s.antialiased);
+        // full-precision rotations are kept if any scene has them, the others are recovered from the u8 ones
+        let keep_rotations = parts.iter().any(|(s, _)| !s.rotations.is_empty());
+        let sh_len = 3*sh_rest_count(scene.sh_degree);
+
+        for (s, offset) in parts.iter() {
+            let start = scene.buffer.len();
+            scene.buffer.extend_from_slice(&s.buffer[..32*s.splat_count]);
+            for row in scene.buffer[start..].chunks_exact_mut(32) {
+                let p: &mut [f32] = transmute_slice_mut::<_, f32>(&mut row[0..3*4]);
+                for k in 0..3 {
+                    p[k] += offset[k];
+                }
+            }
+            if keep_rotations {
+                if s.rotations.len() == s.splat_count {
+                    scene.rotations.extend_from_slice(&s.rotations);
+                } else {
+                    scene.rotations.extend(s.buffer.chunks_exact(32).take(s.splat_count).map(|row| {
+                        let q: [f32; 4] = std::array::from_fn(|k| unpack_quat_byte(row[3*4 + 3*4 + 4 + k]));
+                        let qlen = (q[0].powi(2) + q[1].powi(2) + q[2].powi(2) + q[3].powi(2)).sqrt().max(f32::EPSILON);
+                        q.map(|v| v/qlen)
+                    }));
+                }
             }
+            let n = 3*sh_rest_count(s.sh_degree);
+            for i in 0..s.splat_count {
+                if let Some(coeffs) = s.sh_coeffs.get(n*i..n*(i + 1)) {
+                    scene.sh_coeffs.extend_from_slice(coeffs);
+                } else {
+                    scene.sh_coeffs.resize(scene.sh_coeffs.len() + n, 0.0);
+                }
+                scene.sh_coeffs.resize(scene.sh_coeffs.len() + sh_len - n, 0.0);
+            }
+            scene.splat_count += s.splat_count;
+        }
+        if scene.sh_degree == 0 {
+            scene.sh_coeffs.clear();
         }
 
-        {
-            let texdata_c = transmute_slice_mut::<_, u8>(texdata.as_mut_slice());
-            for i in 0..self.splat_count {
-                // r, g, b, a components of the i-th splat in u_buffer
-                let index_c: usize = 4*(8*i + 7);
-                let index_u: usize = 32*i + 3*4 + 3*4;
-                texdata_c[index_c + 0] = u_buffer[index_u + 0];
-                texdata_c[index_c + 1] = u_buffer[index_u + 1];
-                texdata_c[index_c + 2] = u_buffer[index_u + 2];
-                texdata_c[index_c + 3] = u_buffer[index_u + 3];
+        scene.compute_bounding_box();
+        scene.compute_importance();
+        scene.generate_texture(1);
+        log!("Scene::merged(): {} scenes, splat_count={}, sh_degree={}", parts.len(), scene.splat_count, scene.sh_degree);
+
+        scene
+    }
+
+
+    /// Checks the invariants of the 32-byte splat rows
+    /// Returns one line per kind of problem with the number of affected splats (empty if none)
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::<String>::new();
+        if self.buffer.len() % 32 != 0 {
+            problems.push(format!("buffer length {} is not a multiple of 32 bytes", self.buffer.len()));
+        }
+        let rows = self.buffer.len() / 32;
+        if rows != self.splat_count {
+            problems.push(format!("splat count {} doesn't match the {} rows in the buffer", self.splat_count, rows));
+        }
+        if !self.rotations.is_empty() && self.rotations.len() != rows {
+            problems.push(format!("{} full-precision rotations for {} rows", self.rotations.len(), rows));
+        }
+        if rows == 0 {
+            problems.push("the scene is empty".to_string());
+            return problems;
+        }
+
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(&self.buffer[..32*rows]);
+        let mut bad_position = Vec::<usize>::new();
+        let mut bad_scale = Vec::<usize>::new();
+        let mut bad_rotation = Vec::<usize>::new();
+        let mut transparent = 0_usize;
+        for i in 0..rows {
+            let index_f = 8*i;
+            if !f_buffer[index_f..index_f + 3].iter().all(|v| v.is_finite()) {
+                bad_position.push(i);
+            }
+            if !f_buffer[index_f + 3..index_f + 6].iter().all(|v| v.is_finite() && *v > 0.0) {
+                bad_scale.push(i);
+            }
+
+            let q = match self.rotations.get(i) {
+                Some(q) => *q,
+                None => {
+                    let rot = &self.buffer[32*i + 28..32*i + 32];
+                    [0, 1, 2, 3].map(|k| unpack_quat_byte(rot[k]))
+                },
+            };
+            let qlen = (q[0]*q[0] + q[1]*q[1] + q[2]*q[2] + q[3]*q[3]).sqrt();
+            if !((qlen - 1.0).abs() < 0.05) { // u8 quantization alone stays well below 0.05
+                bad_rotation.push(i);
+            }
+
+            if self.buffer[32*i + 27] == 0 {
+                transparent += 1;
             }
         }
 
-        for i in 0..self.splat_count {
-            let index_f: usize = 8*i;
-            let scale = [
-                f_buffer[index_f + 3],
-                f_buffer[index_f + 4],
-                f_buffer[index_f + 5],
-            ];
-
-            let index_u: usize = 32*i + 3*4 + 3*4 + 4;
-            let rot = [
+        let mut report = |what: &str, splats: &[usize]| {
+            if let Some(first) = splats.first() {
+                problems.push(format!("{} splats with {} (first: #{})", splats.len(), what, first));
+            }
+        };
+        report("NaN/inf positions", &bad_position);
+        report("NaN/inf or non-positive scales", &bad_scale);
+        report("non-unit quaternions", &bad_rotation);
+        if transparent > 0 {
+            problems.push(format!("{} fully transparent splats", transparent));
+        }
+
+        problems
+    }
+
+
+    /// Plain-text summary of the scene for bug reports
+    pub fn stats_summary(&self) -> String {
+        let mut text = String::new();
+        text.push_str(&format!("Splat Count: {}\n", self.splat_count));
+        match self.bbox {
+            Some((min, max)) => text.push_str(&format!(
+                "AABB: min=({:.3}, {:.3}, {:.3}), max=({:.3}, {:.3}, {:.3})\n",
+                min.x, min.y, min.z, max.x, max.y, max.z
+            )),
+            None => text.push_str("AABB: n/a\n"),
+        }
+        text.push_str(&format!("World Scale: {}\n", self.world_scale));
+        text.push_str(&format!("SH Degree: {} ({} rendered)\n", self.sh_degree, self.sh_tex_degree));
+        text.push_str(&format!(
+            "Source Format: {}\n",
+            if self.source_format.is_empty() { "unknown" } else { self.source_format }
+        ));
+        text.push_str(&format!(
+            "Texture: {}x{} RGBA32UI ({} positions, {} covariance)\n",
+            self.tex_width, self.tex_height,
+            if self.half_positions { "half-float" } else { "float" },
+            if self.full_covariance { "float" } else { "half-float" }
+        ));
+        text
+    }
+
+
+    /// Narrowest power-of-two row length from TEX_WIDTH up to max_size that keeps the height of texels within max_size
+    /// (max_size if none does, cf. check_texture_size())
+    fn texture_width(texels: usize, max_size: usize) -> usize {
+        let mut width = TEX_WIDTH.min(max_size);
+        while width < max_size && texels.div_ceil(width) > max_size {
+            width = (2*width).min(max_size);
+        }
+        width
+    }
+
+
+    /// Returns an error if the splat texture exceeds the 2D texture size of the GPU (cf. set_max_texture_size())
+    pub fn check_texture_size(&self) -> Result<(), GauzillaError> {
+        let max_size = max_texture_size();
+        if self.tex_width > max_size || self.tex_height > max_size {
+            return Err(GauzillaError::TooLarge(format!(
+                "{} splats need a {}x{} texture, MAX_TEXTURE_SIZE={}",
+                self.splat_count, self.tex_width, self.tex_height, max_size
+            )));
+        }
+        Ok(())
+    }
+
+
+    /// Number of RGBA32UI texels needed for splat_count splats
+    /// Full-float positions take 2 texels per splat, half-float positions 3 texels per 2 splats,
+    /// and a full-float covariance one more texel per splat
+    /// (i.e. 32 bytes per splat by default, 24 with half-float positions, 48 and 40 with a full-float covariance)
+    fn texel_count(splat_count: usize, half_positions: bool, full_covariance: bool) -> usize {
+        let c = Self::cov_texels(full_covariance);
+        if half_positions {
+            (1 + 2*c)*((splat_count + 1)/2)
+        } else {
+            (1 + c)*splat_count
+        }
+    }
+
+
+    /// Number of texels of a covariance: half-floats fit into the xyz of one (w holds the color), floats need two
+    fn cov_texels(full_covariance: bool) -> usize {
+        if full_covariance { 2 } else { 1 }
+    }
+
+
+    /// Index into tex_data of the first u32 holding the position of the i-th splat
+    fn pos_offset(i: usize, half_positions: bool, full_covariance: bool) -> usize {
+        let c = Self::cov_texels(full_covariance);
+        if half_positions {
+            4*(1 + 2*c)*(i/2) // shared by splats 2k and 2k+1
+        } else {
+            4*(1 + c)*i
+        }
+    }
+
+
+    /// Index into tex_data of the texel holding the covariance (xyz) and color (w) of the i-th splat
+    /// A full-float covariance continues in the xyz of the next texel.
+    pub(crate) fn cov_offset(&self, i: usize) -> usize {
+        Self::cov_offset_of(i, self.half_positions, self.full_covariance)
+    }
+
+
+    fn cov_offset_of(i: usize, half_positions: bool, full_covariance: bool) -> usize {
+        let c = Self::cov_texels(full_covariance);
+        if half_positions {
+            Self::pos_offset(i, half_positions, full_covariance) + 4*(1 + c*(i%2))
+        } else {
+            Self::pos_offset(i, half_positions, full_covariance) + 4
+        }
+    }
+
+
+    /// Index into tex_data of the u32 holding the RGBA color of the i-th splat
+    pub(crate) fn color_offset(&self, i: usize) -> usize {
+        self.cov_offset(i) + 3
+    }
+
+
+    /// Texture rows spanned by the texels of the i-th splat (first, last)
+    pub(crate) fn texture_rows(&self, i: usize) -> (usize, usize) {
+        let row_len = self.tex_width*4; // 4 components per pixel (RGBA)
+        let first = Self::pos_offset(i, self.half_positions, self.full_covariance) / row_len;
+        let last = (self.cov_offset(i) + 4*Self::cov_texels(self.full_covariance) - 1) / row_len;
+        (first, last)
+    }
+
+
+    /// Range of splats that may have texels in the given texture row (conservative at the edges)
+    pub(crate) fn splats_in_row(&self, row: usize) -> std::ops::Range<usize> {
+        let (t0, t1) = (row*self.tex_width, (row + 1)*self.tex_width); // texel range of the row
+        let c = Self::cov_texels(self.full_covariance);
+        let (first, last) = if self.half_positions {
+            let n = 1 + 2*c; // texels per 2 splats
+            (2*(t0/n), 2*((t1 + n - 1)/n))
+        } else {
+            let n = 1 + c; // texels per splat
+            (t0/n, (t1 + n - 1)/n)
+        };
+        first.min(self.splat_count)..last.min(self.splat_count)
+    }
+
+
+    /// Rotation matrix of a (w, x, y, z) quaternion
+    fn rotation_matrix(rot: &[f32; 4]) -> Mat3 {
+        Mat3::new( // column-major
+            1.0 - 2.0*(rot[2]*rot[2] + rot[3]*rot[3]),
+            2.0*(rot[1]*rot[2] + rot[0]*rot[3]),
+            2.0*(rot[1]*rot[3] - rot[0]*rot[2]),
+
+            2.0*(rot[1]*rot[2] - rot[0]*rot[3]),
+            1.0 - 2.0*(rot[1]*rot[1] + rot[3]*rot[3]),
+            2.0*(rot[2]*rot[3] + rot[0]*rot[1]),
+
+            2.0*(rot[1]*rot[3] + rot[0]*rot[2]),
+            2.0*(rot[2]*rot[3] - rot[0]*rot[1]),
+            1.0 - 2.0*(rot[1]*rot[1] + rot[2]*rot[2]),
+        )
+    }
+
+
+    /// Packs the i-th splat of the buffer into its RGBA32UI texels in tex_data
+    /// rotation is the full-precision quaternion of the splat if known, otherwise the u8 one in u_buffer is used
+    fn pack_splat(
+        f_buffer: &[f32],
+        u_buffer: &[u8],
+        rotation: Option<&[f32; 4]>,
+        i: usize,
+        half_positions: bool,
+        full_covariance: bool,
+        max_scale: f32, // scales are clamped to this (f32::INFINITY for none)
+        tex_data: &mut [u32]
+    ) {
+        let index_f: usize = 8*i;
+
+        let pos = Self::pos_offset(i, half_positions, full_covariance);
+        let (x, y, z) = (f_buffer[index_f + 0], f_buffer[index_f + 1], f_buffer[index_f + 2]);
+        if half_positions {
+            // texel 3k: half2(x0, y0), half2(z0, x1), half2(y1, z1)
+            // precision is ~1/2048 of the magnitude, i.e. it gets coarser the farther from the origin
+            if i % 2 == 0 {
+                tex_data[pos + 0] = pack_half_2x16(x, y);
+                tex_data[pos + 1] = (tex_data[pos + 1] & 0xffff0000) | (pack_half_2x16(z, 0.0) & 0x0000ffff);
+            } else {
+                tex_data[pos + 1] = (tex_data[pos + 1] & 0x0000ffff) | (pack_half_2x16(0.0, x) & 0xffff0000);
+                tex_data[pos + 2] = pack_half_2x16(y, z);
+            }
+        } else {
+            // x, y, z components of the i-th splat in f_buffer
+            let texels_f = transmute_slice_mut::<_, f32>(&mut tex_data[pos..pos + 3]);
+            texels_f[0] = x;
+            texels_f[1] = y;
+            texels_f[2] = z;
+        }
+
+        let cov = Self::cov_offset_of(i, half_positions, full_covariance);
+        let texels = &mut tex_data[cov..cov + 4*Self::cov_texels(full_covariance)];
+
+        {
+            // r, g, b, a components of the i-th splat in u_buffer
+            let texels_c = transmute_slice_mut::<_, u8>(texels);
+            let index_c: usize = 4*3;
+            let index_u: usize = 32*i + 3*4 + 3*4;
+            texels_c[index_c + 0] = u_buffer[index_u + 0];
+            texels_c[index_c + 1] = u_buffer[index_u + 1];
+            texels_c[index_c + 2] = u_buffer[index_u + 2];
+            texels_c[index_c + 3] = u_buffer[index_u + 3];
+        }
+
+        let scale = [
+            f_buffer[index_f + 3].min(max_scale),
+            f_buffer[index_f + 4].min(max_scale),
+            f_buffer[index_f + 5].min(max_scale),
+        ];
+
+        let index_u: usize = 32*i + 3*4 + 3*4 + 4;
+        let rot = match rotation {
+            Some(q) => *q,
+            None => [
                 // [0, 255] -> [-1, 1]
                 ((u_buffer[index_u + 0] as f32)/255.0)*2.0 - 1.0, // qw
                 ((u_buffer[index_u + 1] as f32)/255.0)*2.0 - 1.0, // qx
                 ((u_buffer[index_u + 2] as f32)/255.0)*2.0 - 1.0, // qy
                 ((u_buffer[index_u + 3] as f32)/255.0)*2.0 - 1.0, // qz
-            ];
+            ],
+        };
+
+        let r = Self::rotation_matrix(&rot);
 
-            let r = Mat3::new( // column-major
-                1.0 - 2.0*(rot[2]*rot[2] + rot[3]*rot[3]),
-                2.0*(rot[1]*rot[2] + rot[0]*rot[3]),
-                2.0*(rot[1]*rot[3] - rot[0]*rot[2]),
+        let s = Mat3::new(
+            scale[0], 0.0, 0.0,
+            0.0, scale[1], 0.0,
+            0.0, 0.0, scale[2]
+        );
 
-                2.0*(rot[1]*rot[2] - rot[0]*rot[3]),
-                1.0 - 2.0*(rot[1]*rot[1] + rot[3]*rot[3]),
-                2.0*(rot[2]*rot[3] + rot[0]*rot[1]),
+        let m = r*s;
+        let m = &[ // column-major: [col][row]
+            m[0][0], m[0][1], m[0][2],
+            m[1][0], m[1][1], m[1][2],
+            m[2][0], m[2][1], m[2][2],
+        ];
 
-                2.0*(rot[1]*rot[3] + rot[0]*rot[2]),
-                2.0*(rot[2]*rot[3] - rot[0]*rot[1]),
-                1.0 - 2.0*(rot[1]*rot[1] + rot[2]*rot[2]),
-            );
+        // M * M^T = R * S * S^T * R^T
+        let sigma = [
+            m[0]*m[0] + m[3]*m[3] + m[6]*m[6],
+            m[0]*m[1] + m[3]*m[4] + m[6]*m[7],
+            m[0]*m[2] + m[3]*m[5] + m[6]*m[8],
+            m[1]*m[1] + m[4]*m[4] + m[7]*m[7],
+            m[1]*m[2] + m[4]*m[5] + m[7]*m[8],
+            m[2]*m[2] + m[5]*m[5] + m[8]*m[8],
+        ];
 
-            let s = Mat3::new(
-                scale[0], 0.0, 0.0,
-                0.0, scale[1], 0.0,
-                0.0, 0.0, scale[2]
+        // JavaScript typically uses the host system's endianness
+        // (x86-64 and Apple CPUs are little-endian).
+        // WASM's linear memory is always little-endian.
+        if full_covariance {
+            // a, b, c in the first texel, d, e, f in the next
+            let texels_f = transmute_slice_mut::<_, f32>(texels);
+            for (k, index) in [0, 1, 2, 4, 5, 6].iter().enumerate() {
+                texels_f[*index] = 4.0*sigma[k];
+            }
+        } else {
+            texels[0] = pack_half_2x16(4.0*sigma[0], 4.0*sigma[1]); // a, b
+            texels[1] = pack_half_2x16(4.0*sigma[2], 4.0*sigma[3]); // c, d
+            texels[2] = pack_half_2x16(4.0*sigma[4], 4.0*sigma[5]); // e, f
+        }
+    }
+
+
+    /// Builds the scene of a .splatv keyframe in the position/covariance format, mirror, world scale, and clamp of self
+    pub fn splatv_keyframe(&self, buffer: Vec<u8>) -> Scene {
+        let mut scene = Scene::new();
+        scene.splat_count = buffer.len() / 32; // 32bytes per splat
+        scene.buffer = buffer;
+        scene.half_positions = self.half_positions;
+        scene.full_covariance = self.full_covariance;
+        scene.source_format = "splatv";
+        scene.set_mirror(self.mirror);
+        scene.set_world_scale(self.world_scale);
+        scene.scale_clamp = self.scale_clamp;
+        scene.compute_bounding_box();
+        scene.compute_importance();
+        scene.generate_texture(1);
+        scene
+    }
+
+
+    /// Patches the rows of a .splatv delta frame into buffer and tex_data in place
+    /// The keyframe's importance ranking and scale clamp are kept, and the bounding box only grows.
+    /// Returns the (sorted, unique) texture rows that need to be re-uploaded
+    pub fn apply_splatv_delta(&mut self, rows: &[(u32, [u8; 32])]) -> Result<Vec<usize>, GauzillaError> {
+        if self.tex_data.is_empty() {
+            return Err(GauzillaError::DecodeFailed(
+                "Scene::apply_splatv_delta(): delta frame before the first keyframe".to_string()
+            ));
+        }
+        let max_scale = self.scale_limit.unwrap_or(f32::INFINITY);
+
+        // the source values kept for set_world_scale() are patched as well, or dropped if shared
+        let mut unscaled = self.unscaled.take();
+        let mut source = unscaled.as_mut().and_then(Arc::get_mut);
+        for (i, row) in rows.iter() {
+            let i = *i as usize;
+            self.buffer[32*i..32*(i + 1)].copy_from_slice(row);
+            if let Some(source) = source.as_mut() {
+                for k in 0..6 {
+                    source[6*i + k] = f32::from_le_bytes(row[4*k..4*k + 4].try_into().unwrap());
+                }
+            }
+            Self::mirror_buffer(&mut self.buffer[32*i..32*(i + 1)], self.mirror);
+            Self::scale_buffer(&mut self.buffer[32*i..32*(i + 1)], self.world_scale);
+        }
+        let patched = source.is_some();
+        self.unscaled = if patched { unscaled } else { None };
+
+        let mut tex_rows = Vec::<usize>::with_capacity(rows.len());
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(self.buffer.as_slice());
+        let u_buffer: &[u8] = transmute_slice::<_, u8>(self.buffer.as_slice());
+        let (mut min, mut max) = self.bbox.unwrap_or((vec3(f32::MAX, f32::MAX, f32::MAX), vec3(f32::MIN, f32::MIN, f32::MIN)));
+        for (i, _) in rows.iter() {
+            let i = *i as usize;
+            Self::pack_splat(
+                f_buffer, u_buffer, None, i, self.half_positions, self.full_covariance, max_scale, Arc::make_mut(&mut self.tex_data)
             );
+            let p = vec3(f_buffer[8*i + 0], f_buffer[8*i + 1], f_buffer[8*i + 2]);
+            min = vec3(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = vec3(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+            let (first, last) = self.texture_rows(i);
+            tex_rows.extend(first..=last);
+        }
+        if !rows.is_empty() {
+            self.bbox = Some((min, max));
+        }
+        tex_rows.sort_unstable();
+        tex_rows.dedup();
 
-            let m = r*s;
-            let m = &[ // column-major: [col][row]
-                m[0][0], m[0][1], m[0][2],
-                m[1][0], m[1][1], m[1][2],
-                m[2][0], m[2][1], m[2][2],
-            ];
-
-            // M * M^T = R * S * S^T * R^T
-            let sigma = [
-                m[0]*m[0] + m[3]*m[3] + m[6]*m[6],
-                m[0]*m[1] + m[3]*m[4] + m[6]*m[7],
-                m[0]*m[2] + m[3]*m[5] + m[6]*m[8],
-                m[1]*m[1] + m[4]*m[4] + m[7]*m[7],
-                m[1]*m[2] + m[4]*m[5] + m[7]*m[8],
-                m[2]*m[2] + m[5]*m[5] + m[8]*m[8],
-            ];
-
-            // JavaScript typically uses the host system's endianness
-            // (x86-64 and Apple CPUs are little-endian).
-            // WASM's linear memory is always little-endian.
-            texdata[index_f + 4] = pack_half_2x16(4.0*sigma[0], 4.0*sigma[1]); // a, b
-            texdata[index_f + 5] = pack_half_2x16(4.0*sigma[2], 4.0*sigma[3]); // c, d
-            texdata[index_f + 6] = pack_half_2x16(4.0*sigma[4], 4.0*sigma[5]); // e, f
-        }
-
-        self.tex_data = texdata;
-        self.tex_width = texwidth;
-        self.tex_height = texheight;
+        Ok(tex_rows)
     }
 
 
-    /// Sorts the splats based on their depth using 16-bit single-pass counting sort
-    pub fn sort(scene: &Arc<Self>, view_proj: &[f32], bus: &mut Bus<Vec<u32>>, n_threads: usize) {
+    /// Returns a copy of a .splatv scene (buffer, texture, and the keyframe's ranking and clamp)
+    /// to patch the next delta frame into while this one is being sorted
+    pub fn splatv_copy(&self) -> Scene {
+        let mut scene = Scene::new();
+        scene.splat_count = self.splat_count;
+        scene.buffer = self.buffer.clone();
+        scene.tex_data = Arc::new(self.tex_data.to_vec()); // not shared, so that the deltas are patched in place
+        scene.tex_width = self.tex_width;
+        scene.tex_height = self.tex_height;
+        scene.bbox = self.bbox;
+        scene.half_positions = self.half_positions;
+        scene.full_covariance = self.full_covariance;
+        scene.source_format = self.source_format;
+        scene.mirror = self.mirror;
+        scene.world_scale = self.world_scale;
+        scene.importance_rank = self.importance_rank.clone();
+        scene.scale_clamp = self.scale_clamp;
+        scene.scale_limit = self.scale_limit;
+        scene.clamped_count = self.clamped_count;
+        scene
+    }
+
+
+    /// Returns a copy of self with the 32-byte rows of a .splat file appended, mirrored and scaled like self
+    /// The new splats are packed into the spare rows of the texture if they fit, otherwise the texture is
+    /// regenerated with twice the rows needed, so that the following appends fit.
+    /// Returns the texture rows to re-upload, or None if the whole texture has to be uploaded
+    pub fn appended(&self, rows: &[u8]) -> (Scene, Option<Vec<usize>>) {
+        let mut scene = self.splatv_copy();
+        let first = scene.splat_count;
+        let mut rows = rows[..rows.len()/32*32].to_vec();
+        Self::mirror_buffer(&mut rows, self.mirror);
+        Self::scale_buffer(&mut rows, self.world_scale);
+        scene.buffer.extend_from_slice(&rows);
+        scene.splat_count = scene.buffer.len() / 32; // 32bytes per splat
+        if scene.splat_count == first {
+            return (scene, Some(Vec::<usize>::new()));
+        }
+        // streamed coarse to fine, so the later splats are the less important ones
+        scene.importance_rank.extend(first as u32..scene.splat_count as u32);
+
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(scene.buffer.as_slice());
+        let u_buffer: &[u8] = transmute_slice::<_, u8>(scene.buffer.as_slice());
+        let (mut min, mut max) = scene.bbox.unwrap_or((vec3(f32::MAX, f32::MAX, f32::MAX), vec3(f32::MIN, f32::MIN, f32::MIN)));
+        for i in first..scene.splat_count {
+            let p = vec3(f_buffer[8*i + 0], f_buffer[8*i + 1], f_buffer[8*i + 2]);
+            min = vec3(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = vec3(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+        scene.bbox = Some((min, max));
+
+        let texels = Self::texel_count(scene.splat_count, scene.half_positions, scene.full_covariance);
+        if scene.tex_data.is_empty() || texels > scene.tex_width*scene.tex_height {
+            scene.generate_texture(1);
+            let tex_height = (2*scene.tex_height).min(max_texture_size().max(scene.tex_height));
+            Arc::make_mut(&mut scene.tex_data).resize(scene.tex_width*tex_height*4, 0);
+            scene.tex_height = tex_height;
+            return (scene, None);
+        }
+
+        let max_scale = scene.scale_limit.unwrap_or(f32::INFINITY);
+        let tex_data = Arc::make_mut(&mut scene.tex_data);
+        for i in first..scene.splat_count {
+            Self::pack_splat(f_buffer, u_buffer, None, i, scene.half_positions, scene.full_covariance, max_scale, tex_data);
+        }
+        let (first_row, _) = scene.texture_rows(first);
+        let (_, last_row) = scene.texture_rows(scene.splat_count - 1);
+
+        (scene, Some((first_row..=last_row).collect()))
+    }
+
+
+    /// Returns true if other's buffer and texture are laid out like self's, so that rows can be patched from one into the other
+    pub fn same_layout(&self, other: &Scene) -> bool {
+        self.splat_count == other.splat_count
+            && self.half_positions == other.half_positions
+            && self.full_covariance == other.full_covariance
+            && self.mirror == other.mirror
+            && self.world_scale == other.world_scale
+            && self.scale_limit == other.scale_limit
+            && self.tex_width == other.tex_width
+    }
+
+
+    /// Forces the next sort to run even if the view direction hasn't changed
+    pub fn invalidate_sort(&self) {
+        let mut mutex = self.prev_vp.lock().unwrap();
+        (*mutex).clear();
+    }
+
+
+    /// Position of splat i, pushed away from the explode center (cf. gsplat.vert)
+    fn exploded_position(f_buffer: &[f32], i: usize, explode: Option<([f32; 3], f32)>) -> [f32; 3] {
+        let index_f = 8*i;
+        let mut p = [f_buffer[index_f + 0], f_buffer[index_f + 1], f_buffer[index_f + 2]];
+        if let Some((c, offset)) = explode {
+            let d = [p[0] - c[0], p[1] - c[1], p[2] - c[2]];
+            let len = (d[0]*d[0] + d[1]*d[1] + d[2]*d[2]).sqrt();
+            if len > 1e-6 {
+                for k in 0..3 {
+                    p[k] += offset*d[k]/len;
+                }
+            }
+        }
+        p
+    }
+
+
+    /// Test for the splats within settings.splat_percent, at least settings.min_opacity opaque,
+    /// inside settings.crop and, if settings.cull, near the view frustum (cf. cull_planes()),
+    /// which are the only ones sorted and drawn, or None if all of them are
+    fn active_filter<'a>(&'a self, view_proj: &[f32], settings: &'a SortSettings) -> Option<impl Fn(usize) -> bool + Sync + 'a> {
+        let limit = settings.limited_count(self.splat_count);
+        let limited = limit < self.splat_count && self.importance_rank.len() == self.splat_count;
+        let min_alpha = settings.min_alpha();
+        if !limited && !settings.cull && min_alpha == 0 && settings.crop.is_none() {
+            return None;
+        }
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(self.buffer.as_slice());
+        let planes = if settings.cull { Some(Self::cull_planes(view_proj)) } else { None };
+        Some(move |i: usize| {
+            if limited && self.importance_rank[i] >= limit as u32 {
+                return false;
+            }
+            if self.buffer[32*i + 24 + 3] < min_alpha {
+                return false;
+            }
+            if let Some((min, max)) = settings.crop {
+                if !(0..3).all(|k| (min[k]..=max[k]).contains(&f_buffer[8*i + k])) {
+                    return false;
+                }
+            }
+            match &planes {
+                Some(planes) => {
+                    // padded by the splat's extent (3 sigma along its largest axis), so a splat
+                    // whose center is just off screen but that reaches into the view is kept
+                    let p = Self::exploded_position(f_buffer, i, settings.explode);
+                    let radius = 3.0*f_buffer[8*i + 3].max(f_buffer[8*i + 4]).max(f_buffer[8*i + 5]);
+                    planes.iter().all(|plane| plane[0]*p[0] + plane[1]*p[1] + plane[2]*p[2] + plane[3] >= -radius)
+                },
+                None => true,
+            }
+        })
+    }
+
+
+    /// Near, left, right, bottom, and top planes of the clip volume widened by CULL_MARGIN,
+    /// as (a, b, c, d) in scene coordinates with unit normals pointing inwards,
+    /// so that a*x + b*y + c*z + d is the distance of (x, y, z) from the plane
+    fn cull_planes(view_proj: &[f32]) -> [[f32; 4]; 5] {
+        let row = |r: usize| [view_proj[r], view_proj[4 + r], view_proj[8 + r], view_proj[12 + r]];
+        let w = row(3);
+        let plane = |r: usize, sign: f32| {
+            let v = row(r);
+            let mut plane = [0_f32; 4];
+            for k in 0..4 {
+                plane[k] = CULL_MARGIN*w[k] + sign*v[k];
+            }
+            let len = (plane[0]*plane[0] + plane[1]*plane[1] + plane[2]*plane[2]).sqrt();
+            if len > f32::EPSILON {
+                plane.iter_mut().for_each(|c| *c /= len);
+            }
+            plane
+        };
+        [plane(2, 1.0), plane(0, 1.0), plane(0, -1.0), plane(1, 1.0), plane(1, -1.0)]
+    }
+
+
+    /// Sorts the splats based on their depth using single-pass counting sort
+    /// with 2^settings.depth_bits buckets (16 bits by default)
+    pub fn sort(
+        scene: &Arc<Self>,
+        view_proj: &[f32],
+        settings: &SortSettings,
+        sink: &mut impl DepthSink,
+        pool: Option<&WorkerPool>
+    ) {
+        let mut active = None;
+        let mut size_list = Vec::<u32>::new();
+        let view_key = match Self::quantized_depths(scene, view_proj, settings, &mut active, &mut size_list, pool) {
+            Some(view_key) => view_key,
+            None => return,
+        };
+        let n = size_list.len();
+
+        let bucket_count: usize = 1 << settings.depth_bits; // 65,536 for 16 bits
+        // count the occurrences of each depth
+        let counts0 = match Self::pool_chunk(n, pool) {
+            Some((pool, chunk)) => {
+                // local histograms of contiguous ranges, merged in range order
+                let locals = pool.map(size_list.chunks(chunk).collect(), |depths| {
+                    let mut counts = vec![0_u32; bucket_count];
+                    for &depth in depths.iter() {
+                        counts[depth as usize] += 1;
+                    }
+                    counts
+                });
+                let mut counts0 = vec![0_u32; bucket_count];
+                for local in locals {
+                    for (count, local) in counts0.iter_mut().zip(local) {
+                        *count += local;
+                    }
+                }
+                counts0
+            },
+            None => {
+                let mut counts0 = vec![0_u32; bucket_count];
+                for &depth in size_list.iter() {
+                    counts0[depth as usize] += 1;
+                }
+                counts0
+            },
+        };
+        let mut starts0 = vec![0_u32; bucket_count];
+        // store the cumulative count of elements
+        for i in 1..bucket_count {
+            starts0[i] = starts0[i-1] + counts0[i-1];
+        }
+
+        let mut depth_index = vec![0_u32; n];
+        for i in 0..n {
+            let depth = size_list[i] as usize;
+            let j = starts0[depth] as usize;
+            depth_index[j] = match &active {
+                Some(active) => active[i],
+                None => i as u32,
+            };
+            starts0[depth] += 1;
+        }
+
+        Self::submit_sorted(scene, settings, view_key, depth_index, sink);
+    }
+
+
+    /// Same result as sort(), using 8-bit LSD radix passes (two for 16 depth bits)
+    /// The 256-entry histograms live on the stack and the key/index scratch buffers are kept on the scene,
+    /// so unlike sort() nothing proportional to 2^depth_bits is allocated per frame.
+    pub fn sort_radix(
+        scene: &Arc<Self>,
+        view_proj: &[f32],
+        settings: &SortSettings,
+        sink: &mut impl DepthSink,
+        pool: Option<&WorkerPool>
+    ) {
+        let mut active = None;
+        let mut scratch = scene.radix_scratch.lock().unwrap();
+        let scratch = &mut *scratch;
+        let view_key = match Self::quantized_depths(scene, view_proj, settings, &mut active, &mut scratch.keys, pool) {
+            Some(view_key) => view_key,
+            None => return,
+        };
+
+        let n = scratch.keys.len();
+        scratch.keys_tmp.resize(n, 0);
+        scratch.index_tmp.resize(n, 0);
+        let mut depth_index: Vec<u32> = active.unwrap_or_else(|| (0..n as u32).collect());
+        for pass in 0..(settings.depth_bits as usize + 7)/8 {
+            let shift = 8*pass;
+            let mut counts = [0_u32; 256];
+            for &key in scratch.keys.iter() {
+                counts[((key >> shift) & 0xff) as usize] += 1;
+            }
+            let mut start = 0_u32;
+            for count in counts.iter_mut() {
+                let c = *count;
+                *count = start;
+                start += c;
+            }
+            // stable scatter, so the lower digits sorted by the previous passes stay in order
+            for j in 0..n {
+                let key = scratch.keys[j];
+                let bucket = ((key >> shift) & 0xff) as usize;
+                let k = counts[bucket] as usize;
+                scratch.keys_tmp[k] = key;
+                scratch.index_tmp[k] = depth_index[j];
+                counts[bucket] += 1;
+            }
+            std::mem::swap(&mut scratch.keys, &mut scratch.keys_tmp);
+            std::mem::swap(&mut depth_index, &mut scratch.index_tmp);
+        }
+
+        Self::submit_sorted(scene, settings, view_key, depth_index, sink);
+    }
+
+
+    /// Sorts with the given algorithm
+    pub fn sort_with(
+        algorithm: SortAlgorithm,
+        scene: &Arc<Self>,
+        view_proj: &[f32],
+        settings: &SortSettings,
+        sink: &mut impl DepthSink,
+        pool: Option<&WorkerPool>
+    ) {
+        match algorithm {
+            SortAlgorithm::Counting => Self::sort(scene, view_proj, settings, sink, pool),
+            SortAlgorithm::Radix => Self::sort_radix(scene, view_proj, settings, sink, pool),
+        }
+    }
+
+
+    /// Size of the contiguous splat ranges handled by each of n_threads workers,
+    /// or None if the scene is sorted on the calling thread (n_threads <= 1 or a small scene)
+    fn parallel_chunk(splat_count: usize, n_threads: usize) -> Option<usize> {
+        if n_threads <= 1 || splat_count < PARALLEL_SORT_MIN_SPLATS {
+            return None;
+        }
+        Some((splat_count + n_threads - 1)/n_threads)
+    }
+
+
+    /// The pool and the size of the contiguous splat ranges handed to each of its workers,
+    /// or None if the scene is sorted on the calling thread (no pool or a small scene)
+    fn pool_chunk(splat_count: usize, pool: Option<&WorkerPool>) -> Option<(&WorkerPool, usize)> {
+        let pool = pool?;
+        Some((pool, Self::parallel_chunk(splat_count, pool.len())?))
+    }
+
+
+    /// Computes the depth of each splat quantized to [0, 2^settings.depth_bits) into keys (nearest = 0)
+    /// If only some of the splats are sorted (cf. active_filter()), their indices go into active,
+    /// keys[j] being the depth of splat active[j].
+    /// The depths of large scenes are computed by the workers of pool.
+    /// Returns the view the order is valid for (to be stored in prev_vp), or None if the previous order can be kept
+    /// (empty scene, nothing on screen, or the view direction hardly changed since the last sort)
+    fn quantized_depths(
+        scene: &Self,
+        view_proj: &[f32],
+        settings: &SortSettings,
+        active: &mut Option<Vec<u32>>,
+        keys: &mut Vec<u32>,
+        pool: Option<&WorkerPool>
+    ) -> Option<Vec<f32>> {
         if scene.buffer.is_empty() {
-            return;
+            return None;
+        }
+        if !scene.is_in_frustum(view_proj) {
+            return None; // nothing on screen, keep the previous order
         }
         let f_buffer: &[f32] = transmute_slice::<_, f32>(scene.buffer.as_slice());
 
+        // depth row of view_proj, or the debug sort axis (normalized, farther along it is drawn first)
+        let depth_row = match settings.axis {
+            Some(a) => {
+                let len = (a[0]*a[0] + a[1]*a[1] + a[2]*a[2]).sqrt().max(f32::EPSILON);
+                [a[0]/len, a[1]/len, a[2]/len]
+            },
+            None => [view_proj[2], view_proj[6], view_proj[10]],
+        };
+
+        // the culled set also changes when the camera only moves, so it's compared with the whole view_proj
+        let mut view_key = depth_row.to_vec();
+        if settings.cull {
+            view_key.extend_from_slice(view_proj);
+        }
         {
             let mut mutex = scene.prev_vp.lock().unwrap();
             if (*mutex).is_empty() {
-                (*mutex).push(view_proj[2]);
-                (*mutex).push(view_proj[6]);
-                (*mutex).push(view_proj[10]);
-            } else {
+                *mutex = view_key.clone();
+            } else if (*mutex).len() == view_key.len() {
                 let dot =
-                    (*mutex)[0]*view_proj[2] +
-                    (*mutex)[1]*view_proj[6] +
-                    (*mutex)[2]*view_proj[10];
-                if (dot - 1.0).abs() < 0.01 {
-                    return;
+                    (*mutex)[0]*depth_row[0] +
+                    (*mutex)[1]*depth_row[1] +
+                    (*mutex)[2]*depth_row[2];
+                let same_view = (*mutex)[3..].iter()
+                    .zip(view_key[3..].iter())
+                    .all(|(a, b)| (a - b).abs() <= 1e-4*(1.0 + b.abs()));
+                if (dot - 1.0).abs() < 0.01 && same_view {
+                    return None;
                 }
             }
         }
+        let filter = scene.active_filter(view_proj, settings);
 
         // calculates the depth for each splat based on the view projection matrix
         // and updates sizeList with the calculated depths.
-        let mut max_depth = i32::MIN;
-        let mut min_depth = i32::MAX;
         /*
         let mut size_list = vec![0_i32; scene.splat_count];
         for i in 0..scene.splat_count {
@@ -436,66 +2484,115 @@ impl Scene {
             if depth < min_depth { min_depth = depth; }
         }
         */
-        let size_list: Vec<i32> = (0..scene.splat_count)
-            .map(|i| {
-                let index_f = 8*i as usize;
-                let depth = (
-                    (
-                        view_proj[2] * f_buffer[index_f + 0] +
-                        view_proj[6] * f_buffer[index_f + 1] +
-                        view_proj[10] * f_buffer[index_f + 2]
-                    ) * 4096.0
-                ) as i32;
-                if depth > max_depth { max_depth = depth; }
-                if depth < min_depth { min_depth = depth; }
-                depth
-            })
-            .collect();
-        let mut size_list = size_list;
+        let explode = settings.explode;
+        let depth_of = |i: usize| -> i32 {
+            let p = Self::exploded_position(f_buffer, i, explode);
+            (
+                (
+                    depth_row[0] * p[0] +
+                    depth_row[1] * p[1] +
+                    depth_row[2] * p[2]
+                ) * 4096.0
+            ) as i32
+        };
+
+        keys.clear();
+        let (min_depth, max_depth) = match filter {
+            None => {
+                *active = None;
+                // the signed depths of splats [first, first + keys.len()) go into keys (as bit patterns),
+                // returns their (min, max)
+                let depth_range = |keys: &mut [u32], first: usize| -> (i32, i32) {
+                    let mut min_depth = i32::MAX;
+                    let mut max_depth = i32::MIN;
+                    for (j, key) in keys.iter_mut().enumerate() {
+                        let depth = depth_of(first + j);
+                        if depth > max_depth { max_depth = depth; }
+                        if depth < min_depth { min_depth = depth; }
+                        *key = depth as u32;
+                    }
+                    (min_depth, max_depth)
+                };
+                keys.resize(scene.splat_count, 0);
+                match Self::pool_chunk(scene.splat_count, pool) {
+                    Some((pool, chunk)) => {
+                        pool.map(keys.chunks_mut(chunk).enumerate().collect(), |(t, keys)| depth_range(keys, t*chunk))
+                            .into_iter()
+                            .fold((i32::MAX, i32::MIN), |(min, max), (a, b)| (min.min(a), max.max(b)))
+                    },
+                    None => depth_range(keys.as_mut_slice(), 0),
+                }
+            },
+            Some(filter) => {
+                // the indices and signed depths (as bit patterns) of the active splats in [first, last),
+                // filtered and projected in the same pass
+                let filtered_range = |(first, last): (usize, usize)| -> (Vec<u32>, Vec<u32>, i32, i32) {
+                    let mut indices = Vec::new();
+                    let mut depths = Vec::new();
+                    let mut min_depth = i32::MAX;
+                    let mut max_depth = i32::MIN;
+                    for i in (first..last).filter(|&i| filter(i)) {
+                        let depth = depth_of(i);
+                        if depth > max_depth { max_depth = depth; }
+                        if depth < min_depth { min_depth = depth; }
+                        indices.push(i as u32);
+                        depths.push(depth as u32);
+                    }
+                    (indices, depths, min_depth, max_depth)
+                };
+                let n = scene.splat_count;
+                let ranges = match Self::pool_chunk(n, pool) {
+                    Some((pool, chunk)) => {
+                        pool.map((0..n).step_by(chunk).map(|first| (first, (first + chunk).min(n))).collect(), filtered_range)
+                    },
+                    None => vec![filtered_range((0, n))],
+                };
+                let mut indices = Vec::with_capacity(ranges.iter().map(|r| r.0.len()).sum());
+                let mut range = (i32::MAX, i32::MIN);
+                for (range_indices, range_depths, min, max) in ranges {
+                    indices.extend_from_slice(&range_indices);
+                    keys.extend_from_slice(&range_depths);
+                    range = (range.0.min(min), range.1.max(max));
+                }
+                *active = Some(indices);
+                range
+            },
+        };
+        if keys.is_empty() {
+            return Some(view_key); // nothing within the splat limit or on screen
+        }
         //log!("Scene::sort(): max_depth={:?}, min_depth={:?}", max_depth, min_depth);
 
-        let size16: usize = 256*256; // 65,536
-        let depth_inv = (size16 - 1) as f32 / (max_depth - min_depth) as f32;
+        let bucket_count: usize = 1 << settings.depth_bits; // 65,536 for 16 bits
+        let depth_inv = (bucket_count - 1) as f32 / (max_depth - min_depth).max(1) as f32; // all splats at one depth (e.g. a single splat) go to bucket 0
 
-        let mut counts0 = vec![0_u32; size16];
-        // count the occurrences of each depth
-        for i in 0..scene.splat_count {
-            let depth = ((size_list[i] - min_depth) as f32 * depth_inv).floor() as i32;
-            let depth = depth.clamp(0, size16 as i32 - 1);
-            size_list[i] = depth;
-            counts0[depth as usize] += 1;
-        }
-        let mut starts0 = vec![0_u32; size16];
-        // store the cumulative count of elements
-        for i in 1..size16 {
-            starts0[i] = starts0[i-1] + counts0[i-1];
+        for key in keys.iter_mut() {
+            let depth = ((*key as i32 - min_depth) as f32 * depth_inv).floor() as i32;
+            *key = depth.clamp(0, bucket_count as i32 - 1) as u32;
         }
 
-        let mut depth_index = vec![0_u32; scene.splat_count];
-        for i in 0..scene.splat_count {
-            let depth = size_list[i] as usize;
-            let j = starts0[depth] as usize;
-            depth_index[j] = i as u32;
-            starts0[depth] += 1;
+        Some(view_key)
+    }
+
+
+    /// Puts depth_index (nearest first) into draw order and hands it to the sink
+    fn submit_sorted(scene: &Self, settings: &SortSettings, view_key: Vec<f32>, mut depth_index: Vec<u32>, sink: &mut impl DepthSink) {
+        // the sorts leave the nearest splats first
+        if settings.order == DrawOrder::BackToFront {
+            depth_index.reverse();
         }
-        depth_index.reverse();// FIXME
 
-        //////////////////////////////////
-        // no cloning is happening for the single-consumer case
-        let _ = bus.try_broadcast(depth_index);
-        //////////////////////////////////
+        sink.submit(depth_index);
 
         {
             let mut mutex = scene.prev_vp.lock().unwrap();
-            (*mutex)[0] = view_proj[2];
-            (*mutex)[1] = view_proj[6];
-            (*mutex)[2] = view_proj[10];
+            *mutex = view_key; // may have been invalidated meanwhile
         }
     }
 
 
     /// Sorts the splats based on their depth using 16-bit single-pass counting sort
-    pub fn sort2(scene: &Self, view_proj: &[f32], bus: &mut Bus<Vec<u32>>, n_threads: usize) {
+    pub fn sort2(scene: &Self, view_proj: &[f32], sink: &mut impl DepthSink, n_threads: usize) {
         if scene.buffer.is_empty() {
             return;
         }
@@ -557,7 +2654,7 @@ impl Scene {
         //log!("Scene::sort(): max_depth={:?}, min_depth={:?}", max_depth, min_depth);
 
         let size16: usize = 256*256; // 65,536
-        let depth_inv = (size16 - 1) as f32 / (max_depth - min_depth) as f32;
+        let depth_inv = (size16 - 1) as f32 / (max_depth - min_depth).max(1) as f32;
 
         let mut counts0 = vec![0_u32; size16];
         // count the occurrences of each depth
@@ -582,78 +2679,250 @@ impl Scene {
         }
         depth_index.reverse();// FIXME
 
-        //////////////////////////////////
-        // no cloning is happening for the single-consumer case
-        let _ = bus.try_broadcast(depth_index);
-        //////////////////////////////////
+        sink.submit(depth_index);
 
         {
             let mut mutex = scene.prev_vp.lock().unwrap();
-            (*mutex)[0] = view_proj[2];
-            (*mutex)[1] = view_proj[6];
-            (*mutex)[2] = view_proj[10];
+            *mutex = vec![view_proj[2], view_proj[6], view_proj[10]]; // may have been invalidated meanwhile
         }
     }
 
 }
 
 
-/// Loads a .ply or .splat file and returns a [Scene]
-pub async fn load_scene() -> Scene {
-    /*
-    A WebAssembly page has a constant size of 65,536 bytes (or 64KB).
-    Therefore, the maximum range that a WASM module can address,
-    as WASM currently only allows 32-bit addressing, is 2^16 * 64KB = 4GB.
-    */
-    let mut scene = Scene::new();
-
-    let file = rfd::AsyncFileDialog::new()
-        .add_filter("3DGS model", &["ply", "splat", "spz"])
-        .pick_file().await;
-    if let Some(f) = file.as_ref() {
-        if f.file_name().contains(".ply") {
-            let mut file_header_size = 0_u16;
-            let mut splat_count = 0_usize;
-            let mut cursor = Cursor::new(Vec::<u8>::new());
-            let bytes = f.read().await;
-            match Scene::parse_file_header(bytes) {
-                Ok((fhs, sc, c)) => {
-                    file_header_size = fhs;
-                    splat_count = sc;
-                    cursor = c;
-                },
-                Err(e) => {
-                    log!("load_scene(): ERROR: {}", e);
-                    unreachable!();
-                },
+#[cfg(test)]
+impl Scene {
+    /// Builds a small deterministic scene of n splats (no RNG) for tests:
+    /// splat i is at (i, 2i, 3i) with scale (0.1, 0.2, 0.3), an identity rotation,
+    /// and color (i % 256, 255 - i % 256, 128, 255)
+    pub fn test_scene(n: usize) -> Self {
+        let mut scene = Scene::new();
+        scene.splat_count = n;
+        scene.buffer = vec![0_u8; 32*n];
+        for i in 0..n {
+            let row = &mut scene.buffer[32*i..32*(i + 1)];
+            {
+                let f: &mut [f32] = transmute_slice_mut::<_, f32>(&mut row[0..24]);
+                f.copy_from_slice(&[i as f32, 2.0*i as f32, 3.0*i as f32, 0.1, 0.2, 0.3]);
             }
-            scene.splat_count = splat_count;
-            scene.load(&mut cursor, file_header_size);
+            let c = (i % 256) as u8;
+            row[24..28].copy_from_slice(&[c, 255 - c, 128, 255]);
+            row[28..32].copy_from_slice(&[255, 128, 128, 128]); // (qw, qx, qy, qz) = (1, 0, 0, 0)
+        }
+        scene.compute_bounding_box();
+        scene.compute_importance();
+        scene.generate_texture(1);
+        scene
+    }
+}
 
-        } else if f.file_name().contains(".splat") {
-            scene.buffer = f.read().await;
-            scene.splat_count = scene.buffer.len() / 32; // 32bytes per splat
 
-        } else if f.file_name().contains(".spz") {
-            let mut spz = Spz::new();
-            spz.init();
+/// Builds an ascii PLY with one float property per name and one vertex per row
+#[cfg(test)]
+pub fn ascii_ply(names: &[&str], rows: &[&[f32]]) -> Vec<u8> {
+    let mut text = format!("ply\nformat ascii 1.0\nelement vertex {}\n", rows.len());
+    for name in names.iter() {
+        text.push_str(&format!("property float {}\n", name));
+    }
+    text.push_str("end_header\n");
+    for row in rows.iter() {
+        let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+        text.push_str(&values.join(" "));
+        text.push('\n');
+    }
+    text.into_bytes()
+}
+
+
+impl Scene {
+    /// Loads a .ply, .splat, .ksplat, or .spz file from memory
+    /// format is the file extension without the dot (e.g. "ply")
+    /// PLY parsing and SPZ decoding report their progress to progress, if given.
+    pub async fn from_bytes(bytes: Vec<u8>, format: &str, progress: Option<&ProgressCell>) -> Result<Scene, GauzillaError> {
+        /*
+        A WebAssembly page has a constant size of 65,536 bytes (or 64KB).
+        Therefore, the maximum range that a WASM module can address,
+        as WASM currently only allows 32-bit addressing, is 2^16 * 64KB = 4GB.
+        */
+        let mut scene = Scene::new();
+        scene.mirror = get_mirror();
+
+        match format {
+            "ply" => {
+                let (header, mut cursor) = Scene::parse_file_header(bytes)
+                    .map_err(|e| { log!("Scene::from_bytes(): ERROR: {}", e); e })?;
+                if header.format == PlyFormat::Ascii {
+                    let warning = format!("Loading an ascii PLY file ({} splats), this may take a while...", header.splat_count);
+                    log!("Scene::from_bytes(): WARNING: {}", warning);
+                    show_warning(&warning, 5000);
+                }
+                scene.splat_count = header.splat_count;
+                match progress {
+                    Some(progress) => scene.load_with_progress(&mut cursor, &header, progress).await?,
+                    None => scene.load(&mut cursor, &header)?,
+                }
+                scene.source_format = "ply";
+            },
+            "splat" if is_splat_f32(&bytes) => {
+                scene.load_splat_f32(&bytes);
+                scene.source_format = "splat";
+            },
+            "splat" => {
+                scene.buffer = bytes;
+                if scene.buffer.len() % 32 != 0 {
+                    return Err(GauzillaError::Truncated(format!("{} bytes is not a multiple of 32", scene.buffer.len())));
+                }
+                scene.splat_count = scene.buffer.len() / 32; // 32bytes per splat
+                scene.source_format = "splat";
+                Scene::mirror_buffer(&mut scene.buffer, scene.mirror);
+            },
+            "ksplat" => {
+                scene.load_ksplat(&bytes)?;
+                scene.source_format = "ksplat";
+            },
+            "spz" => {
+                let mut spz = Spz::new();
+                spz.init();
+
+                // the decoder worker doesn't report its own progress, only the stages are shown
+                report_progress(progress, 0.1).await;
+                let (serialized_splats, sh_degree, antialiased) = load_spz(&mut spz, bytes).await?;
+                report_progress(progress, 0.7).await;
+
+                scene.splat_count = serialized_splats.len();
+                scene.load_no_normal(serialized_splats, sh_degree);
+                scene.antialiased = antialiased;
+                scene.source_format = "spz";
+            },
+            _ => return Err(GauzillaError::UnsupportedFormat(format.to_string())),
+        }
+
+        report_progress(progress, 0.9).await;
+        scene.compute_bounding_box();
+        scene.compute_importance();
+        scene.generate_texture(1);
+
+        log!("Scene::from_bytes(): format={}, scene.splat_count={}", format, scene.splat_count);
+
+        Ok(scene)
+    }
+
+
+    /// Loads a scene inlined as a base64 data URL (e.g. "data:application/x-ply;base64,...")
+    /// The format is taken from format_hint if given, otherwise from the media type.
+    pub async fn from_data_url(url: &str, format_hint: &str) -> Result<Scene, GauzillaError> {
+        let (media_type, payload) = parse_data_url(url)
+            .ok_or_else(|| GauzillaError::BadHeader("not a base64 data URL".to_string()))?;
+        let format = if format_hint.is_empty() {
+            // "application/x-ply" -> "ply"
+            let subtype = media_type.rsplit('/').next().unwrap_or("");
+            subtype.strip_prefix("x-").unwrap_or(subtype).to_lowercase()
+        } else {
+            format_hint.to_lowercase()
+        };
+        let bytes = decode_base64(payload)
+            .ok_or_else(|| GauzillaError::DecodeFailed("invalid base64 payload".to_string()))?;
+        log!("Scene::from_data_url(): media_type={}, format={}, {} bytes", media_type, format, bytes.len());
+
+        Scene::from_bytes(bytes, &format, None).await
+    }
+
+
+    /// Downloads and decodes several .spz files and merges them into one scene
+    pub async fn from_spz_urls(urls: &[String]) -> Result<Scene, GauzillaError> {
+        let mut spz = Spz::new();
+        spz.init();
+
+        let mut parts = Vec::<(Vec<SerializedSplat2>, usize, bool)>::with_capacity(urls.len());
+        for url in urls.iter() {
+            let bytes = fetch_bytes(url).await?;
+            let (serialized_splats, sh_degree, antialiased) = load_spz(&mut spz, bytes).await?;
+            log!(
+                "Scene::from_spz_urls(): url={}, splat_count={}, sh_degree={}, antialiased={}",
+                url, serialized_splats.len(), sh_degree, antialiased
+            );
+            parts.push((serialized_splats, sh_degree, antialiased));
+        }
+
+        Ok(Scene::from_spz_parts(parts, get_mirror()))
+    }
+
+
+    /// Merges decoded .spz files (splats, SH degree, and antialiased flag of each) into one scene
+    /// The scene gets the highest SH degree, files with a lower one contribute zeros for the missing coefficients.
+    /// It's antialiased if any of the files is (one filter setting applies to all splats).
+    pub fn from_spz_parts(parts: Vec<(Vec<SerializedSplat2>, usize, bool)>, mirror: MirrorAxes) -> Scene {
+        let sh_degree = parts.iter().map(|(_, d, _)| *d).max().unwrap_or(0);
+        let antialiased = parts.iter().any(|(_, _, a)| *a);
+        let mut serialized_splats = Vec::<SerializedSplat2>::with_capacity(parts.iter().map(|(s, _, _)| s.len()).sum());
+        for (splats, _, _) in parts {
+            serialized_splats.extend(splats); // coefficients above a file's degree are already zero
+        }
+
+        let mut scene = Scene::new();
+        scene.mirror = mirror;
+        scene.splat_count = serialized_splats.len();
+        if scene.splat_count > 0 {
+            scene.load_no_normal(serialized_splats, sh_degree);
+        }
+        scene.antialiased = antialiased;
+        scene.source_format = "spz";
+        scene.compute_bounding_box();
+        scene.compute_importance();
+        scene.generate_texture(1);
+        log!("Scene::from_spz_parts(): splat_count={}, sh_degree={}", scene.splat_count, scene.sh_degree);
 
-            let buffer = f.read().await;
-            let serialized_splats = load_spz(&mut spz, buffer).await;
+        scene
+    }
+}
 
-            scene.splat_count = serialized_splats.len();
-            scene.load_no_normal(serialized_splats);
 
-        } else {
-            unreachable!();
-        }
+/// Splits a url param listing one or more .spz files separated by commas
+/// Returns None unless every entry is a .spz URL.
+pub fn parse_spz_urls(url: &str) -> Option<Vec<String>> {
+    let urls: Vec<String> = url.split(',')
+        .map(|u| u.trim())
+        .filter(|u| !u.is_empty())
+        .map(|u| u.to_string())
+        .collect();
+    let is_spz = |u: &String| u.split('?').next().unwrap_or("").to_lowercase().ends_with(".spz");
+    if urls.is_empty() || !urls.iter().all(is_spz) {
+        return None;
     }
+    Some(urls)
+}
 
-    scene.generate_texture();
 
-    log!("load_scene(): scene.splat_count={}", scene.splat_count);
+/// Loads a .ply, .splat, .ksplat, or .spz file picked by the user and returns a [Scene]
+/// Returns an empty scene if no file was picked
+pub async fn load_scene(progress: Option<&ProgressCell>) -> Result<Scene, GauzillaError> {
+    let file = rfd::AsyncFileDialog::new()
+        .add_filter("3DGS model", &["ply", "splat", "ksplat", "spz"])
+        .pick_file().await;
+    let f = match file.as_ref() {
+        Some(f) => f,
+        None => {
+            let mut scene = Scene::new();
+            scene.mirror = get_mirror();
+            return Ok(scene);
+        },
+    };
+
+    let name = f.file_name();
+    let format = if name.contains(".ply") {
+        "ply"
+    } else if name.contains(".ksplat") {
+        "ksplat"
+    } else if name.contains(".splat") {
+        "splat"
+    } else if name.contains(".spz") {
+        "spz"
+    } else {
+        return Err(GauzillaError::UnsupportedFormat(name));
+    };
 
-    scene
+    report_progress(progress, 0.0).await;
+    Scene::from_bytes(f.read().await, format, progress).await
 }
 
 
@@ -666,8 +2935,77 @@ use js_sys::{Uint8Array, Object, Boolean};
 use web_sys::{Headers, Request, RequestInit, RequestMode, RequestCredentials, Response, ReadableStream, ReadableStreamDefaultReader};
 
 
+/// Downloads a whole file via HTTP (only works in main thread)
+pub async fn fetch_bytes(url: &str) -> Result<Vec<u8>, GauzillaError> {
+    let mut opts = RequestInit::new();
+    opts.method("GET");
+    opts.mode(RequestMode::Cors); // cross-origin
+    opts.credentials(RequestCredentials::Omit);
+
+    let request = Request::new_with_str_and_init(url, &opts)?;
+    let window = web_sys::window().unwrap();
+
+    let res = JsFuture::from(window.fetch_with_request(&request)).await?; // JavaScript Promise execution
+    let res: Response = res.dyn_into().unwrap();
+
+    let status = res.status();
+    if status != 200 {
+        log!("fetch_bytes(): ERROR: HTTP status={}, url={}", status, url);
+        return Err(GauzillaError::Network(status));
+    }
+
+    let array_buffer = JsFuture::from(res.array_buffer()?).await?; // download byte array
+    let bytes = Uint8Array::new(&array_buffer).to_vec();
+    if bytes.is_empty() {
+        return Err(GauzillaError::Truncated(format!("empty response from {}", url)));
+    }
+
+    Ok(bytes)
+}
+
+
+/// Downloads the bytes [start, end) of a file via HTTP with a range request (only works in main thread)
+/// Servers that ignore the range and send the whole file are handled as well.
+pub async fn fetch_range(url: &str, start: usize, end: usize) -> Result<Vec<u8>, GauzillaError> {
+    let headers = Headers::new()?;
+    headers.set("Range", &format!("bytes={}-{}", start, end - 1))?;
+
+    let mut opts = RequestInit::new();
+    opts.method("GET");
+    opts.mode(RequestMode::Cors); // cross-origin
+    opts.credentials(RequestCredentials::Omit);
+    opts.headers(&headers);
+
+    let request = Request::new_with_str_and_init(url, &opts)?;
+    let window = web_sys::window().unwrap();
+
+    let res = JsFuture::from(window.fetch_with_request(&request)).await?; // JavaScript Promise execution
+    let res: Response = res.dyn_into().unwrap();
+
+    let status = res.status();
+    if status != 206 && status != 200 {
+        log!("fetch_range(): ERROR: HTTP status={}, url={}", status, url);
+        return Err(GauzillaError::Network(status));
+    }
+
+    let array_buffer = JsFuture::from(res.array_buffer()?).await?; // download byte array
+    let mut bytes = Uint8Array::new(&array_buffer).to_vec();
+    if status == 200 {
+        if bytes.len() < end {
+            return Err(GauzillaError::Truncated(format!("{} bytes from {}, expected at least {}", bytes.len(), url, end)));
+        }
+        bytes = bytes[start..end].to_vec();
+    }
+    if bytes.len() != end - start {
+        return Err(GauzillaError::Truncated(format!("{} bytes from {}, expected {}", bytes.len(), url, end - start)));
+    }
+
+    Ok(bytes)
+}
+
+
 /// Streams a .splat file via HTTP and returns a [Scene] (blocking, only works in main thread)
-pub async fn stream_splat(url: &str) -> Result<Scene, JsValue> {
+pub async fn stream_splat(url: &str) -> Result<Scene, GauzillaError> {
     let mut scene = Scene::new();
 
     let mut opts = RequestInit::new();
@@ -683,16 +3021,17 @@ pub async fn stream_splat(url: &str) -> Result<Scene, JsValue> {
 
     let status = res.status();
     if status != 200 {
-        let err = format!("load_splat(): ERROR: HTTP status={}", status);
-        log!("{}", err.as_str());
-        return Err(JsValue::from_str(err.as_str()));
+        log!("stream_splat(): ERROR: HTTP status={}", status);
+        return Err(GauzillaError::Network(status));
     }
 
-    let cl = res.headers().get("content-length")?;
-    let cl: Result<usize, _> = cl.unwrap().parse();
-    let byte_len = cl.unwrap();
+    let cl = res.headers().get("content-length")?
+        .ok_or_else(|| GauzillaError::BadHeader("missing content-length".to_string()))?;
+    let byte_len: usize = cl.parse()
+        .map_err(|_| GauzillaError::BadHeader(format!("invalid content-length: {}", cl)))?;
     let splat_count = byte_len / 32;
     scene.splat_count = splat_count;
+    scene.source_format = "splat";
     scene.buffer.resize(byte_len, 0_u8);
     log!("stream_splat(): byte_len={}", byte_len);
     log!("stream_splat(): splat_count={}", splat_count);
@@ -725,7 +3064,7 @@ pub async fn stream_splat(url: &str) -> Result<Scene, JsValue> {
         if bytes_read + chunk.len() <= byte_len {
             scene.buffer[bytes_read..bytes_read+chunk.len()].copy_from_slice(chunk.as_slice());
         } else {
-            unreachable!();
+            return Err(GauzillaError::DecodeFailed(format!("received more than content-length={} bytes", byte_len)));
         }
 
         bytes_read += chunk.len();
@@ -735,14 +3074,19 @@ pub async fn stream_splat(url: &str) -> Result<Scene, JsValue> {
     }
     let elapsed = 0.001*(get_time_milliseconds() - start);
     log!("stream_splat(): bytes_read={}, byte_len={}, elapsed={:.2}s", bytes_read, byte_len, elapsed);
+    if bytes_read < byte_len {
+        return Err(GauzillaError::Truncated(format!("received {} of {} bytes", bytes_read, byte_len)));
+    }
 
-    scene.generate_texture();
+    scene.compute_bounding_box();
+    scene.compute_importance();
+    scene.generate_texture(1);
 
     Ok(scene)
 }
 
 
-use std::{rc::Rc, cell::RefCell};
+use std::{rc::Rc, cell::{Cell, RefCell}};
 use web_sys::{Worker, MessageEvent};
 use js_sys::Number;
 
@@ -859,3 +3203,985 @@ fn onmessage2(
     callback
 }
 */
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use half::f16;
+
+    fn unpack_half_2x16(x: u32) -> (f32, f32) {
+        (f16::from_bits(x as u16).to_f32(), f16::from_bits((x >> 16) as u16).to_f32())
+    }
+
+    /// view_proj that leaves the splats where they are, so the depth is z
+    fn identity() -> [f32; 16] {
+        [
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ]
+    }
+
+    /// the properties of a DC-only 3DGS PLY, in the usual order
+    const SPLAT_PROPERTIES: [&str; 14] = ["x", "y", "z", "f_dc_0", "f_dc_1", "f_dc_2", "opacity",
+        "scale_0", "scale_1", "scale_2", "rot_0", "rot_1", "rot_2", "rot_3"];
+
+    #[test]
+    fn test_scene_bounding_box() {
+        let scene = Scene::test_scene(10);
+        let (min, max) = scene.bbox.unwrap();
+        assert_eq!(min, vec3(0.0, 0.0, 0.0));
+        assert_eq!(max, vec3(9.0, 18.0, 27.0));
+        assert_eq!(scene.bounding_box(), (min, max));
+        assert_eq!(Scene::new().bounding_box(), (vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_scene_appended() {
+        let n = 2000;
+        let full = Scene::test_scene(n);
+        let (first, tex_rows) = Scene::new().appended(&full.buffer[..32*n/2]);
+        assert!(tex_rows.is_none()); // no texture to pack into yet
+        assert_eq!(first.splat_count, n/2);
+        let (both, tex_rows) = first.appended(&full.buffer[32*n/2..]);
+        assert!(!tex_rows.unwrap().is_empty()); // fits into the spare rows
+        assert_eq!(both.splat_count, n);
+        assert_eq!(both.bbox, full.bbox);
+        assert_eq!(both.tex_data[..full.tex_data.len()], full.tex_data[..]);
+    }
+
+    #[test]
+    fn test_generate_texture_parallel() {
+        let n = PARALLEL_SORT_MIN_SPLATS + 4321; // odd, so the last half-float position texel is shared by one splat only
+        let mut scene = Scene::test_scene(n);
+        for (half_positions, full_covariance) in [(false, false), (true, false), (false, true), (true, true)] {
+            scene.half_positions = half_positions;
+            scene.full_covariance = full_covariance;
+            scene.tex_data = Arc::default(); // nothing to reuse from the cache
+            scene.generate_texture(1);
+            let single = scene.tex_data.to_vec();
+            for n_threads in [2, 3, 4] {
+                scene.tex_data = Arc::default();
+                scene.generate_texture(n_threads);
+                assert_eq!(*scene.tex_data, single, "{} threads, half_positions={}, full_covariance={}", n_threads, half_positions, full_covariance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_texture_cache() {
+        let mut scene = Scene::test_scene(100);
+        // geometry of this test only, so that the other tests don't share its cache entry
+        transmute_slice_mut::<_, f32>(&mut scene.buffer[0..4])[0] = 4242.5;
+        scene.generate_texture(1);
+        let packed = scene.tex_data.clone();
+        scene.tex_data = Arc::default();
+        scene.generate_texture(1); // same buffer: taken from the cache
+        assert!(Arc::ptr_eq(&scene.tex_data, &packed));
+
+        // other geometry or layout: packed again
+        scene.buffer[0] ^= 1;
+        scene.generate_texture(1);
+        assert!(!Arc::ptr_eq(&scene.tex_data, &packed));
+        assert_ne!(scene.tex_data, packed);
+        scene.buffer[0] ^= 1;
+        scene.half_positions = true;
+        scene.generate_texture(1);
+        assert!(!Arc::ptr_eq(&scene.tex_data, &packed));
+        scene.half_positions = false;
+        scene.generate_texture(1);
+        assert!(Arc::ptr_eq(&scene.tex_data, &packed));
+
+        // dropped with the last scene using it: packed again
+        let weak = Arc::downgrade(&packed);
+        drop(packed);
+        scene.tex_data = Arc::default();
+        assert!(weak.upgrade().is_none());
+        scene.generate_texture(1);
+        assert_eq!(scene.tex_data.len(), 4*scene.tex_width*scene.tex_height);
+    }
+
+    #[test]
+    fn test_texture_width() {
+        assert_eq!(Scene::texture_width(1000, 4096), 2048);
+        assert_eq!(Scene::texture_width(2048*4096, 4096), 2048);
+        assert_eq!(Scene::texture_width(2048*4096 + 1, 4096), 4096);
+        assert_eq!(Scene::texture_width(12_000_000, 16384), 2048);
+        assert_eq!(Scene::texture_width(12_000_000, 2048), 2048); // doesn't fit at all
+        assert_eq!(Scene::texture_width(1000, 1024), 1024);
+
+        let mut scene = Scene::test_scene(10);
+        assert!(scene.check_texture_size().is_ok());
+        scene.tex_height = max_texture_size() + 1;
+        assert_eq!(scene.check_texture_size().unwrap_err().kind(), "TooLarge");
+    }
+
+    #[test]
+    fn test_scene_texture_layout() {
+        let scene = Scene::test_scene(1500); // more than one texture row (1024 splats per row)
+        assert_eq!(scene.tex_width, 2048);
+        assert_eq!(scene.tex_height, 2);
+        assert_eq!(scene.tex_data.len(), 2048*2*4);
+
+        let texdata_f = transmute_slice::<_, f32>(scene.tex_data.as_slice());
+        for i in [0_usize, 1, 1023, 1024, 1499] {
+            assert_eq!(&texdata_f[8*i..8*i + 3], &[i as f32, 2.0*i as f32, 3.0*i as f32]);
+            let c = (i % 256) as u32;
+            assert_eq!(scene.tex_data[8*i + 7], c | ((255 - c) << 8) | (128 << 16) | (255 << 24));
+        }
+    }
+
+    #[test]
+    fn test_scene_half_positions_layout() {
+        let full = Scene::test_scene(1500);
+        let scene = full.rebuilt(&PackSettings { half_positions: true, mirror: full.mirror, ..PackSettings::default() });
+        assert_eq!(scene.tex_height, 2); // 2250 texels
+        assert_eq!(scene.tex_data.len(), 2048*2*4);
+
+        for i in [0_usize, 1, 2, 3, 1364, 1365, 1498, 1499] {
+            let p = 12*(i/2);
+            let (x, y, z) = if i % 2 == 0 {
+                let (x, y) = unpack_half_2x16(scene.tex_data[p]);
+                (x, y, unpack_half_2x16(scene.tex_data[p + 1]).0)
+            } else {
+                let (y, z) = unpack_half_2x16(scene.tex_data[p + 2]);
+                (unpack_half_2x16(scene.tex_data[p + 1]).1, y, z)
+            };
+            let expected = [i as f32, 2.0*i as f32, 3.0*i as f32];
+            for (v, e) in [x, y, z].iter().zip(expected.iter()) {
+                assert!((v - e).abs() <= e.abs()/1024.0, "splat {}: {} vs {}", i, v, e);
+            }
+
+            // covariance and color are stored as in the full-float layout
+            let c = scene.cov_offset(i);
+            assert_eq!(&scene.tex_data[c..c + 4], &full.tex_data[8*i + 4..8*i + 8]);
+            let (first, last) = scene.texture_rows(i);
+            assert!(first <= last && last < scene.tex_height);
+            assert!(scene.splats_in_row(c / (scene.tex_width*4)).contains(&i));
+        }
+    }
+
+    #[test]
+    fn test_scene_full_covariance_layout() {
+        let half = Scene::test_scene(1500);
+        for half_positions in [false, true] {
+            let scene = half.rebuilt(&PackSettings { half_positions, full_covariance: true, mirror: half.mirror, ..PackSettings::default() });
+            let texels = if half_positions { 5*750 } else { 3*1500 };
+            assert_eq!(scene.tex_height, (texels + 2047)/2048);
+
+            for i in [0_usize, 1, 2, 3, 682, 683, 1364, 1365, 1498, 1499] {
+                let c = scene.cov_offset(i);
+                // same color, covariance as f32 in (c, c + 1, c + 2) and (c + 4, c + 5, c + 6)
+                assert_eq!(scene.tex_data[c + 3], half.tex_data[8*i + 7]);
+                let (a, b) = unpack_half_2x16(half.tex_data[8*i + 4]);
+                let (cc, d) = unpack_half_2x16(half.tex_data[8*i + 5]);
+                let (e, f) = unpack_half_2x16(half.tex_data[8*i + 6]);
+                for (k, expected) in [(0, a), (1, b), (2, cc), (4, d), (5, e), (6, f)] {
+                    let v = f32::from_bits(scene.tex_data[c + k]);
+                    assert!((v - expected).abs() <= 1e-3, "splat {}: {} vs {}", i, v, expected);
+                }
+                if !half_positions {
+                    let p = 12*i;
+                    assert_eq!(f32::from_bits(scene.tex_data[p]), i as f32);
+                }
+
+                let (first, last) = scene.texture_rows(i);
+                assert!(first <= last && last < scene.tex_height);
+                assert!(scene.splats_in_row(c / (scene.tex_width*4)).contains(&i));
+                assert!(scene.splats_in_row((c + 4) / (scene.tex_width*4)).contains(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn test_scene_covariance_packing() {
+        let scene = Scene::test_scene(4);
+        // (nearly) identity rotation: sigma = diag(scale^2), packed as 4*sigma
+        let (a, b) = unpack_half_2x16(scene.tex_data[4]);
+        let (c, d) = unpack_half_2x16(scene.tex_data[5]);
+        let (e, f) = unpack_half_2x16(scene.tex_data[6]);
+        let eps = 5e-3; // the u8 quaternion is only approximately (1, 0, 0, 0)
+        assert!((a - 4.0*0.01).abs() < eps);
+        assert!(b.abs() < eps);
+        assert!(c.abs() < eps);
+        assert!((d - 4.0*0.04).abs() < eps);
+        assert!(e.abs() < eps);
+        assert!((f - 4.0*0.09).abs() < eps);
+    }
+
+    #[test]
+    fn test_scene_sort_back_to_front() {
+        let n = 100;
+        let scene = Arc::new(Scene::test_scene(n));
+        let identity = identity();
+
+        let mut bus = Bus::<Vec<u32>>::new(1);
+        let mut rx = bus.add_rx();
+        Scene::sort(&scene, &identity, &SortSettings::default(), &mut bus, None);
+        let depth_index = rx.try_recv().unwrap();
+
+        // depth is z = 3i, farthest first
+        let expected: Vec<u32> = (0..n as u32).rev().collect();
+        assert_eq!(depth_index, expected);
+    }
+
+    #[test]
+    fn test_scene_sort_front_to_back() {
+        let n = 100;
+        let scene = Arc::new(Scene::test_scene(n));
+        let identity = identity();
+
+        let settings = SortSettings {
+            order: DrawOrder::FrontToBack,
+            ..SortSettings::default()
+        };
+        let mut bus = Bus::<Vec<u32>>::new(1);
+        let mut rx = bus.add_rx();
+        Scene::sort(&scene, &identity, &settings, &mut bus, None);
+        let depth_index = rx.try_recv().unwrap();
+
+        // depth is z = 3i, nearest first
+        let expected: Vec<u32> = (0..n as u32).collect();
+        assert_eq!(depth_index, expected);
+    }
+
+    #[test]
+    fn test_scene_sort_radix() {
+        let n = 5000;
+        let mut scene = Scene::test_scene(n);
+        {
+            // scrambled depths with many ties, so that stability matters
+            let f: &mut [f32] = transmute_slice_mut::<_, f32>(&mut scene.buffer);
+            let mut x = 12345_u32;
+            for i in 0..n {
+                x = x.wrapping_mul(1664525).wrapping_add(1013904223);
+                f[8*i + 2] = ((x >> 8) % 1000) as f32 - 500.0;
+            }
+        }
+        let scene = Arc::new(scene);
+        let identity = identity();
+
+        for depth_bits in [SortSettings::MIN_DEPTH_BITS, 16, SortSettings::MAX_DEPTH_BITS] {
+            for order in [DrawOrder::BackToFront, DrawOrder::FrontToBack] {
+                let settings = SortSettings { depth_bits, order, ..SortSettings::default() };
+                let mut counting = DepthArray::default();
+                scene.invalidate_sort();
+                Scene::sort(&scene, &identity, &settings, &mut counting, None);
+                let mut radix = DepthArray::default();
+                scene.invalidate_sort();
+                Scene::sort_with(SortAlgorithm::Radix, &scene, &identity, &settings, &mut radix, None);
+                assert_eq!(radix.depth_index.take().unwrap(), counting.depth_index.take().unwrap(), "{} bits", depth_bits);
+
+                // same early-out for an unchanged view direction
+                Scene::sort_radix(&scene, &identity, &settings, &mut radix, None);
+                assert!(radix.depth_index.is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_scene_sort_parallel() {
+        let n = PARALLEL_SORT_MIN_SPLATS + 4321; // not a multiple of the thread count
+        let mut scene = Scene::test_scene(n);
+        {
+            let f: &mut [f32] = transmute_slice_mut::<_, f32>(&mut scene.buffer);
+            let mut x = 54321_u32;
+            for i in 0..n {
+                x = x.wrapping_mul(1664525).wrapping_add(1013904223);
+                f[8*i + 2] = ((x >> 8) % 5000) as f32 - 2500.0;
+            }
+        }
+        let scene = Arc::new(scene);
+        let identity = identity();
+
+        let pools: Vec<WorkerPool> = [2, 3, 4].into_iter().map(WorkerPool::new).collect();
+        for algorithm in [SortAlgorithm::Counting, SortAlgorithm::Radix] {
+            for depth_bits in [SortSettings::MIN_DEPTH_BITS, SortSettings::MAX_DEPTH_BITS] {
+                let settings = SortSettings { depth_bits, ..SortSettings::default() };
+                let mut single = DepthArray::default();
+                scene.invalidate_sort();
+                Scene::sort_with(algorithm, &scene, &identity, &settings, &mut single, None);
+                let single = single.depth_index.take().unwrap();
+                for pool in pools.iter() {
+                    let n_threads = pool.len();
+                    let mut parallel = DepthArray::default();
+                    scene.invalidate_sort();
+                    Scene::sort_with(algorithm, &scene, &identity, &settings, &mut parallel, Some(pool));
+                    assert_eq!(parallel.depth_index.take().unwrap(), single, "{} threads, {} bits", n_threads, depth_bits);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_scene_sort_explode() {
+        let mut scene = Scene::test_scene(2);
+        {
+            let f: &mut [f32] = transmute_slice_mut::<_, f32>(&mut scene.buffer);
+            f[0..3].copy_from_slice(&[0.0, 0.0, 1.0]);
+            f[8..11].copy_from_slice(&[10.0, 0.0, 2.0]);
+        }
+        scene.compute_bounding_box();
+        let scene = Arc::new(scene);
+        let mut view_proj = [0_f32; 16];
+        view_proj[0] = 0.05;
+        view_proj[5] = 1.0;
+        view_proj[10] = 0.1;
+        view_proj[15] = 1.0;
+
+        let mut depth = DepthArray::default();
+        Scene::sort(&scene, &view_proj, &SortSettings::default(), &mut depth, None);
+        assert_eq!(depth.depth_index.take().unwrap(), vec![1, 0]);
+
+        // pushed 5 away from the origin, splat 0 ends up at z = 6 and splat 1 at z ~ 3
+        let settings = SortSettings {
+            explode: Some(([0.0, 0.0, 0.0], 5.0)),
+            ..SortSettings::default()
+        };
+        scene.invalidate_sort();
+        Scene::sort(&scene, &view_proj, &settings, &mut depth, None);
+        assert_eq!(depth.depth_index.take().unwrap(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_scene_mirror_x_rotation() {
+        let mut scene = Scene::test_scene(2);
+        // a rotation that isn't symmetric about any axis
+        let q = [0.9_f32, 0.3, 0.2, 0.1];
+        let qlen = q.iter().map(|v| v*v).sum::<f32>().sqrt();
+        for k in 0..4 {
+            scene.buffer[32 + 28 + k] = (((q[k]/qlen) + 1.0)*0.5*255.0) as u8;
+        }
+        let scene = scene.rebuilt(&PackSettings { mirror: scene.mirror, ..PackSettings::default() });
+
+        let decode = |scene: &Scene| -> [f32; 4] {
+            let rot = &scene.buffer[32 + 28..64];
+            [0, 1, 2, 3].map(|k| ((rot[k] as f32)/255.0)*2.0 - 1.0)
+        };
+        let r = Scene::rotation_matrix(&decode(&scene));
+
+        let mirrored = scene.rebuilt(&PackSettings { mirror: MirrorAxes { x: true, y: false, z: false }, ..PackSettings::default() });
+        let r_m = Scene::rotation_matrix(&decode(&mirrored));
+
+        // M*R*M with M = diag(-1, 1, 1) flips the sign of the entries in the first row xor column
+        let m = Mat3::from_diagonal(vec3(-1.0, 1.0, 1.0));
+        let expected = m*r*m;
+        for c in 0..3 {
+            for row in 0..3 {
+                assert!((r_m[c][row] - expected[c][row]).abs() < 1e-5, "R'[{}][{}]", c, row);
+            }
+        }
+        let det = r_m[0][0]*(r_m[1][1]*r_m[2][2] - r_m[2][1]*r_m[1][2])
+            - r_m[1][0]*(r_m[0][1]*r_m[2][2] - r_m[2][1]*r_m[0][2])
+            + r_m[2][0]*(r_m[0][1]*r_m[1][2] - r_m[1][1]*r_m[0][2]);
+        assert!(det > 0.0); // still a proper rotation, not a reflection
+
+        let f_buffer = transmute_slice::<_, f32>(mirrored.buffer.as_slice());
+        assert_eq!(&f_buffer[8..11], &[-1.0, 2.0, 3.0]);
+        assert_eq!(mirrored.bbox.unwrap().0.x, -1.0);
+
+        // the covariance stays consistent: off-diagonal terms involving x change sign
+        let (a, b) = unpack_half_2x16(scene.tex_data[8 + 4]);
+        let (c, d) = unpack_half_2x16(scene.tex_data[8 + 5]);
+        let (e, f) = unpack_half_2x16(scene.tex_data[8 + 6]);
+        let (a_m, b_m) = unpack_half_2x16(mirrored.tex_data[8 + 4]);
+        let (c_m, d_m) = unpack_half_2x16(mirrored.tex_data[8 + 5]);
+        let (e_m, f_m) = unpack_half_2x16(mirrored.tex_data[8 + 6]);
+        let eps = 1e-3;
+        assert!((a_m - a).abs() < eps && (b_m + b).abs() < eps && (c_m + c).abs() < eps);
+        assert!((d_m - d).abs() < eps && (e_m - e).abs() < eps && (f_m - f).abs() < eps);
+
+        // mirroring twice restores the original buffer
+        assert_eq!(mirrored.rebuilt(&PackSettings::default()).buffer, scene.buffer);
+    }
+
+    #[test]
+    fn test_scene_world_scale() {
+        let scene = Scene::test_scene(4);
+        let scaled = scene.rebuilt(&PackSettings { mirror: scene.mirror, world_scale: 2.0, ..PackSettings::default() });
+        let f_buffer = transmute_slice::<_, f32>(scaled.buffer.as_slice());
+        assert_eq!(&f_buffer[8..14], &[2.0, 4.0, 6.0, 0.2, 0.4, 0.6]);
+        assert_eq!(scaled.bbox.unwrap().1, vec3(6.0, 12.0, 18.0));
+        assert_eq!(scaled.buffer[32 + 24..64], scene.buffer[32 + 24..64]); // color and rotation untouched
+
+        // rescaling is relative to the source file, not to the current scale
+        let halved = scaled.rebuilt(&PackSettings { mirror: scene.mirror, world_scale: 0.5, ..PackSettings::default() });
+        let f_buffer = transmute_slice::<_, f32>(halved.buffer.as_slice());
+        assert_eq!(&f_buffer[8..11], &[0.5, 1.0, 1.5]);
+        assert_eq!(halved.rebuilt(&PackSettings { mirror: scene.mirror, ..PackSettings::default() }).buffer, scene.buffer);
+
+        // dragging the slider back and forth, also mirrored, ends up at the source values
+        let mut dragged = Scene::test_scene(4);
+        dragged.set_mirror(MirrorAxes { x: true, y: false, z: false });
+        for world_scale in [0.3, 1.7, 0.01, 3.3, 0.7] {
+            dragged.set_world_scale(world_scale);
+        }
+        dragged.set_mirror(MirrorAxes::default());
+        dragged.set_world_scale(1.0);
+        assert_eq!(dragged.buffer, scene.buffer);
+        assert!(dragged.unscaled.is_none());
+    }
+
+    #[test]
+    fn test_scene_sort_splat_limit() {
+        let n = 100;
+        let mut scene = Scene::test_scene(n);
+        // make the farthest half of the splats the least important
+        for i in n/2..n {
+            scene.buffer[32*i + 3*4 + 3*4 + 3] = 10; // alpha
+        }
+        scene.compute_importance();
+        let scene = Arc::new(scene);
+        let identity = identity();
+
+        let settings = SortSettings { splat_percent: 50.0, ..SortSettings::default() };
+        assert_eq!(settings.limited_count(n), n/2);
+        let mut bus = Bus::<Vec<u32>>::new(1);
+        let mut rx = bus.add_rx();
+        Scene::sort(&scene, &identity, &settings, &mut bus, None);
+        let depth_index = rx.try_recv().unwrap();
+
+        let expected: Vec<u32> = (0..(n/2) as u32).rev().collect();
+        assert_eq!(depth_index, expected);
+
+        // only the active subset is sorted, by either algorithm
+        let mut radix = DepthArray::default();
+        scene.invalidate_sort();
+        Scene::sort_radix(&scene, &identity, &settings, &mut radix, None);
+        assert_eq!(radix.depth_index.take().unwrap(), expected);
+
+        let settings = SortSettings { splat_percent: 0.0, ..SortSettings::default() };
+        let mut depth = DepthArray::default();
+        scene.invalidate_sort();
+        Scene::sort(&scene, &identity, &settings, &mut depth, None);
+        assert!(depth.depth_index.take().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_scene_sort_cull() {
+        let n = 20;
+        let scene = Arc::new(Scene::test_scene(n));
+        // splat i is at clip (0.1i, 0.2i, 0.3i, 1) with a radius of 0.9 (3*0.3), so it's off screen
+        // once its world y = 2i is above 1.5/0.1 + 0.9, i.e. after splat 7
+        let mut view_proj = [0_f32; 16];
+        view_proj[0] = 0.1;
+        view_proj[5] = 0.1;
+        view_proj[10] = 0.1;
+        view_proj[15] = 1.0;
+
+        let settings = SortSettings { cull: true, ..SortSettings::default() };
+        let mut depth = DepthArray::default();
+        Scene::sort(&scene, &view_proj, &settings, &mut depth, None);
+        let expected: Vec<u32> = (0..8).rev().collect();
+        assert_eq!(depth.depth_index.take().unwrap(), expected);
+
+        let mut radix = DepthArray::default();
+        scene.invalidate_sort();
+        Scene::sort_radix(&scene, &view_proj, &settings, &mut radix, None);
+        assert_eq!(radix.depth_index.take().unwrap(), expected);
+
+        // moving the camera without turning it changes the culled set, so it resorts
+        view_proj[13] = -0.6; // y - 0.6, so splats up to world y = 2.1/0.1 + 0.9 are kept
+        Scene::sort(&scene, &view_proj, &settings, &mut depth, None);
+        let expected: Vec<u32> = (0..11).rev().collect();
+        assert_eq!(depth.depth_index.take().unwrap(), expected);
+
+        // without culling all splats are sorted
+        scene.invalidate_sort();
+        Scene::sort(&scene, &view_proj, &SortSettings::default(), &mut depth, None);
+        assert_eq!(depth.depth_index.take().unwrap().len(), n);
+    }
+
+    #[test]
+    fn test_scene_sort_min_opacity() {
+        let n = 10;
+        let mut scene = Scene::test_scene(n);
+        for i in 0..n {
+            scene.buffer[32*i + 24 + 3] = (25*i) as u8; // alpha
+        }
+        let scene = Arc::new(scene);
+        let identity = identity();
+
+        let settings = SortSettings { min_opacity: 0.5, ..SortSettings::default() };
+        assert_eq!(settings.min_alpha(), 128);
+        let mut depth = DepthArray::default();
+        Scene::sort(&scene, &identity, &settings, &mut depth, None);
+        let expected: Vec<u32> = (6..n as u32).rev().collect();
+        assert_eq!(depth.depth_index.take().unwrap(), expected);
+
+        // the buffer itself is kept, so lowering the threshold brings the splats back
+        let settings = SortSettings { min_opacity: 0.0, ..SortSettings::default() };
+        scene.invalidate_sort();
+        Scene::sort(&scene, &identity, &settings, &mut depth, None);
+        assert_eq!(depth.depth_index.take().unwrap().len(), n);
+    }
+
+    #[test]
+    fn test_scene_sort_crop() {
+        let n = 10;
+        let scene = Arc::new(Scene::test_scene(n));
+        let identity = identity();
+
+        // splat i is at (i, 2i, 3i), the box keeps 2 to 4 (bounds included)
+        let settings = SortSettings { crop: Some(([1.5, 0.0, 0.0], [4.0, 100.0, 100.0])), ..SortSettings::default() };
+        let mut depth = DepthArray::default();
+        Scene::sort(&scene, &identity, &settings, &mut depth, None);
+        assert_eq!(depth.depth_index.take().unwrap(), vec![4, 3, 2]);
+
+        let mut radix = DepthArray::default();
+        scene.invalidate_sort();
+        Scene::sort_radix(&scene, &identity, &settings, &mut radix, None);
+        assert_eq!(radix.depth_index.take().unwrap(), vec![4, 3, 2]);
+
+        // an empty box leaves nothing to draw
+        let settings = SortSettings { crop: Some(([1.0; 3], [0.0; 3])), ..SortSettings::default() };
+        scene.invalidate_sort();
+        Scene::sort(&scene, &identity, &settings, &mut depth, None);
+        assert!(depth.depth_index.take().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_scene_sort_axis() {
+        let n = 10;
+        let scene = Arc::new(Scene::test_scene(n));
+        let identity = identity();
+
+        // splat i is at y = 2i, so looking along +Y draws the last splat first
+        let settings = SortSettings { axis: Some([0.0, 2.0, 0.0]), ..SortSettings::default() };
+        let mut depth = DepthArray::default();
+        Scene::sort(&scene, &identity, &settings, &mut depth, None);
+        let expected: Vec<u32> = (0..n as u32).rev().collect();
+        assert_eq!(depth.depth_index.take().unwrap(), expected);
+
+        let settings = SortSettings { axis: Some([0.0, -1.0, 0.0]), ..SortSettings::default() };
+        scene.invalidate_sort();
+        Scene::sort(&scene, &identity, &settings, &mut depth, None);
+        let expected: Vec<u32> = (0..n as u32).collect();
+        assert_eq!(depth.depth_index.take().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_scene_single_splat() {
+        let ply = ascii_ply(&SPLAT_PROPERTIES, &[&[1.0, 2.0, -3.0, 0.0, 0.0, 0.0, 0.0, -2.3, -1.6, -1.2, 1.0, 0.0, 0.0, 0.0]]);
+        let (header, mut cursor) = Scene::parse_file_header(ply).unwrap();
+        let mut scene = Scene::new();
+        scene.splat_count = header.splat_count;
+        scene.load(&mut cursor, &header).unwrap();
+        scene.compute_bounding_box();
+        scene.compute_importance();
+        scene.generate_texture(1);
+
+        // point AABB
+        assert_eq!(scene.bbox, Some((vec3(1.0, 2.0, -3.0), vec3(1.0, 2.0, -3.0))));
+        assert_eq!(scene.centroid(), Some(vec3(1.0, 2.0, -3.0)));
+
+        // one texture row, for both position formats
+        assert_eq!((scene.tex_width, scene.tex_height), (2048, 1));
+        assert_eq!(scene.tex_data.len(), 2048*4);
+        let half = scene.rebuilt(&PackSettings { half_positions: true, full_covariance: false, scale_clamp: None, ..scene.pack_settings() });
+        assert_eq!((half.tex_width, half.tex_height), (2048, 1));
+
+        // a single depth value must not divide by zero
+        let scene = Arc::new(scene);
+        let mut view_proj = identity();
+        view_proj[12] = -1.0; // moves the splat to the origin of the clip volume
+        view_proj[13] = -2.0;
+        view_proj[14] = 3.0;
+        assert!(scene.is_in_frustum(&view_proj));
+        let mut depth = DepthArray::default();
+        Scene::sort(&scene, &view_proj, &SortSettings::default(), &mut depth, None);
+        assert_eq!(depth.depth_index.take().unwrap(), vec![0]);
+
+        let settings = SortSettings { axis: Some([1.0, 0.0, 0.0]), ..SortSettings::default() };
+        scene.invalidate_sort();
+        Scene::sort(&scene, &view_proj, &settings, &mut depth, None);
+        assert_eq!(depth.depth_index.take().unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn test_parse_file_header_errors() {
+        let ok = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nend_header\n".to_vec();
+        let (header, _) = Scene::parse_file_header(ok.clone()).unwrap();
+        assert_eq!((header.size as usize, header.splat_count), (ok.len(), 3));
+        assert_eq!(header.format, PlyFormat::BinaryLittleEndian);
+
+        let big_endian = b"ply\nformat binary_big_endian 1.0\nelement vertex 3\nend_header\n".to_vec();
+        assert!(matches!(Scene::parse_file_header(big_endian), Err(GauzillaError::UnsupportedFormat(_))));
+
+        let missing_end = b"ply\nelement vertex 3\n".to_vec();
+        assert!(matches!(Scene::parse_file_header(missing_end), Err(GauzillaError::BadHeader(_))));
+
+        let bad_count = b"ply\nelement vertex three\nend_header\n".to_vec();
+        assert!(matches!(Scene::parse_file_header(bad_count), Err(GauzillaError::BadHeader(_))));
+    }
+
+    #[test]
+    fn test_load_ascii_ply() {
+        let ply = ascii_ply(&SPLAT_PROPERTIES, &[
+            &[1.0, 2.0, 3.0, 0.0, 0.0, 0.0, 0.0, -2.3, -1.6, -1.2, 1.0, 0.0, 0.0, 0.0],
+            &[-1.0, -2.0, -3.0, 0.0, 0.0, 0.0, 10.0, -2.3, -1.6, -1.2, 1.0, 0.0, 0.0, 0.0],
+        ]);
+        let text = String::from_utf8(ply).unwrap()
+            .replace("end_header\n", "element face 0\nproperty list uchar int vertex_indices\nend_header\n");
+
+        let (header, mut cursor) = Scene::parse_file_header(text.into_bytes()).unwrap();
+        assert_eq!(header.format, PlyFormat::Ascii);
+        assert_eq!(header.properties.len(), SPLAT_PROPERTIES.len()); // face properties are not vertex properties
+
+        let mut scene = Scene::new();
+        scene.splat_count = header.splat_count;
+        scene.load(&mut cursor, &header).unwrap();
+        let f_buffer = transmute_slice::<_, f32>(scene.buffer.as_slice());
+        // the more opaque second splat comes first in the importance order
+        assert_eq!(&f_buffer[0..3], &[-1.0, -2.0, -3.0]);
+        assert_eq!(&f_buffer[8..11], &[1.0, 2.0, 3.0]);
+        assert!((f_buffer[3] - (-2.3_f32).exp()).abs() < 1e-6);
+        assert_eq!(scene.buffer[24..28], [127, 127, 127, 254]); // 0.5 gray, sigmoid(10) opacity
+
+        let missing_y = ascii_ply(&["x"], &[&[1.0]]);
+        let (header, mut cursor) = Scene::parse_file_header(missing_y).unwrap();
+        let mut scene = Scene::new();
+        scene.splat_count = header.splat_count;
+        assert!(matches!(scene.load(&mut cursor, &header), Err(GauzillaError::BadHeader(_)))); // missing y
+    }
+
+    #[test]
+    fn test_load_sh_degree_1() {
+        let f_rest: Vec<String> = (0..9).map(|k| format!("f_rest_{}", k)).collect();
+        let mut names = SPLAT_PROPERTIES[..6].to_vec();
+        names.extend(f_rest.iter().map(|s| s.as_str()));
+        names.extend_from_slice(&SPLAT_PROPERTIES[6..]);
+        // f_rest: R = (1, 2, 3), G = (4, 5, 6), B = (7, 8, 9)
+        let ply = ascii_ply(&names, &[
+            &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 0.0, -2.3, -1.6, -1.2, 1.0, 0.0, 0.0, 0.0],
+        ]);
+
+        let (header, mut cursor) = Scene::parse_file_header(ply).unwrap();
+        assert_eq!(header.sh_degree(), 1);
+
+        let mut scene = Scene::new();
+        scene.splat_count = header.splat_count;
+        scene.load(&mut cursor, &header).unwrap();
+        assert_eq!(scene.sh_degree, 1);
+        // RGB triplets per coefficient
+        assert_eq!(scene.sh_coeffs, vec![1.0, 4.0, 7.0, 2.0, 5.0, 8.0, 3.0, 6.0, 9.0]);
+
+        scene.generate_texture(1);
+        assert_eq!(scene.sh_tex_degree, 1);
+        assert_eq!((scene.sh_tex_width, scene.sh_tex_height), (SH_TEX_WIDTH, 1));
+        assert_eq!(&scene.sh_tex_data[0..9], scene.sh_coeffs.as_slice());
+
+        // basis functions 1..3 are y, z, x: mirroring X only negates the third coefficient
+        scene.set_mirror(MirrorAxes { x: true, y: false, z: false });
+        assert_eq!(scene.sh_coeffs, vec![1.0, 4.0, 7.0, 2.0, 5.0, 8.0, -3.0, -6.0, -9.0]);
+
+        // DC only: no SH texture
+        let scene = scene.rebuilt(&PackSettings { half_positions: false, full_covariance: false, max_sh_degree: 0, scale_clamp: None, ..scene.pack_settings() });
+        assert_eq!(scene.sh_tex_degree, 0);
+        assert!(scene.sh_tex_data.is_empty());
+    }
+
+    #[test]
+    fn test_load_binary_ply_by_name() {
+        // INRIA-style names in a shuffled order, f_rest before f_dc, and a double position
+        let mut names: Vec<String> = ["rot_0", "rot_1", "rot_2", "rot_3", "opacity"].iter().map(|s| s.to_string()).collect();
+        names.extend((0..9).rev().map(|k| format!("f_rest_{}", k)));
+        names.extend(["f_dc_0", "f_dc_1", "f_dc_2", "scale_0", "scale_1", "scale_2", "x", "y"].iter().map(|s| s.to_string()));
+        let mut bytes = b"ply\nformat binary_little_endian 1.0\nelement vertex 1\n".to_vec();
+        for name in names.iter() {
+            bytes.extend_from_slice(format!("property float {}\n", name).as_bytes());
+        }
+        bytes.extend_from_slice(b"property double z\nend_header\n");
+
+        let mut values = vec![1.0_f32, 0.0, 0.0, 0.0, 10.0];
+        values.extend((1..=9).rev().map(|k| k as f32)); // f_rest_k = k + 1
+        values.extend([0.0, 0.0, 0.0, -2.3, -1.6, -1.2, 1.0, 2.0]);
+        for v in values.iter() {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes.extend_from_slice(&3.0_f64.to_le_bytes());
+
+        let (header, mut cursor) = Scene::parse_file_header(bytes.clone()).unwrap();
+        assert_eq!(header.property_types.last().map(|t| t.as_str()), Some("double"));
+        let mut scene = Scene::new();
+        scene.splat_count = header.splat_count;
+        scene.load(&mut cursor, &header).unwrap();
+        let f_buffer = transmute_slice::<_, f32>(scene.buffer.as_slice());
+        assert_eq!(&f_buffer[0..3], &[1.0, 2.0, 3.0]);
+        assert!((f_buffer[3] - (-2.3_f32).exp()).abs() < 1e-6);
+        assert_eq!(scene.buffer[24..28], [127, 127, 127, 254]);
+        assert_eq!(scene.sh_degree, 1);
+        assert_eq!(scene.sh_coeffs, vec![1.0, 4.0, 7.0, 2.0, 5.0, 8.0, 3.0, 6.0, 9.0]);
+
+        let (header, mut cursor) = Scene::parse_file_header(bytes[..bytes.len() - 1].to_vec()).unwrap();
+        let mut scene = Scene::new();
+        scene.splat_count = header.splat_count;
+        assert!(matches!(scene.load(&mut cursor, &header), Err(GauzillaError::Truncated(_))));
+    }
+
+    #[test]
+    fn test_load_binary_ply_without_normals() {
+        // Scaniverse-style: no nx/ny/nz and no f_rest_*
+        let names = SPLAT_PROPERTIES;
+        let mut bytes = b"ply\nformat binary_little_endian 1.0\nelement vertex 2\n".to_vec();
+        for name in names.iter() {
+            bytes.extend_from_slice(format!("property float {}\n", name).as_bytes());
+        }
+        bytes.extend_from_slice(b"end_header\n");
+        for i in 0..2 {
+            for v in [i as f32, 2.0, 3.0, 0.0, 0.0, 0.0, 10.0, -2.3, -1.6, -1.2, 1.0, 0.0, 0.0, 0.0].iter() {
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+
+        let (header, mut cursor) = Scene::parse_file_header(bytes).unwrap();
+        assert!(!header.has_normals());
+        assert_eq!(header.vertex_stride().unwrap(), 4*names.len());
+        let mut scene = Scene::new();
+        scene.splat_count = header.splat_count;
+        scene.load(&mut cursor, &header).unwrap();
+        assert_eq!(scene.sh_degree, 0);
+        let f_buffer = transmute_slice::<_, f32>(scene.buffer.as_slice());
+        let mut xs = vec![f_buffer[0], f_buffer[8]];
+        xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(xs, vec![0.0, 1.0]);
+
+        let (header, _) = Scene::parse_file_header(
+            b"ply\nformat ascii 1.0\nelement vertex 0\nproperty float nx\nproperty float ny\nproperty float nz\nend_header\n".to_vec()
+        ).unwrap();
+        assert!(header.has_normals());
+    }
+
+    #[test]
+    fn test_parse_ply_header_crlf() {
+        let mut text = String::from("ply\r\nformat ascii 1.0\r\ncomment exported on Windows\r\nelement vertex  1 \r\n");
+        for name in ["rot_0", "rot_1", "rot_2", "rot_3", "x", "y", "z", "f_dc_0", "f_dc_1", "f_dc_2", "opacity",
+            "scale_0", "scale_1", "scale_2"].iter() {
+            text.push_str(&format!("property float {}\r\n", name));
+        }
+        text.push_str("element face 0\r\nproperty list uchar int vertex_indices\r\nend_header\r\n");
+        let header_len = text.len();
+        text.push_str("1 0 0 0 4 5 6 0 0 0 0 -1 -1 -1\r\n");
+
+        let (header, mut cursor) = Scene::parse_file_header(text.into_bytes()).unwrap();
+        assert_eq!(header.size as usize, header_len);
+        assert_eq!(header.splat_count, 1);
+        assert_eq!(header.properties.len(), 14); // the face list isn't a vertex property
+        assert_eq!(header.column("x"), Some(4));
+        assert_eq!(header.vertex_stride().unwrap(), 56);
+
+        let mut scene = Scene::new();
+        scene.splat_count = header.splat_count;
+        scene.load(&mut cursor, &header).unwrap();
+        let f_buffer = transmute_slice::<_, f32>(scene.buffer.as_slice());
+        assert_eq!(&f_buffer[0..3], &[4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn test_load_splat_f32() {
+        let rotation = [0.9_f32, 0.3, -0.2, 0.1]; // unnormalized (w, x, y, z)
+        let log_scale = [-1.0_f32, -5.0, -5.0]; // thin splat, sensitive to rotation error
+
+        let mut row = vec![1.0, 2.0, 3.0, 0.0, 0.0, 0.0, 0.0];
+        row.extend_from_slice(&log_scale);
+        row.extend_from_slice(&rotation);
+        let (header, mut cursor) = Scene::parse_file_header(ascii_ply(&SPLAT_PROPERTIES, &[row.as_slice()])).unwrap();
+        let mut ply = Scene::new();
+        ply.splat_count = header.splat_count;
+        ply.load(&mut cursor, &header).unwrap();
+        ply.generate_texture(1);
+
+        let mut bytes = b"SPF4".to_vec();
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        for v in [1.0_f32, 2.0, 3.0].iter().chain(log_scale.map(|v| v.exp()).iter()) {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes.extend_from_slice(&[127, 127, 127, 127]);
+        for v in rotation.iter() {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        assert!(is_splat_f32(&bytes));
+        assert!(!is_splat_f32(&bytes[..bytes.len() - 1]));
+
+        let mut splat = Scene::new();
+        splat.load_splat_f32(&bytes);
+        splat.generate_texture(1);
+        assert_eq!(splat.splat_count, 1);
+        assert_eq!(&splat.tex_data[0..3], &ply.tex_data[0..3]); // position
+        assert_eq!(&splat.tex_data[4..7], &ply.tex_data[4..7]); // covariance
+
+        // the u8 quaternion alone loses precision
+        let mut quantized = Scene::new();
+        quantized.splat_count = 1;
+        quantized.buffer = splat.buffer.clone();
+        quantized.generate_texture(1);
+        assert_ne!(&quantized.tex_data[4..7], &ply.tex_data[4..7]);
+    }
+
+    #[test]
+    fn test_load_ksplat() {
+        // level 1: one section of 3 splats in a full bucket of 2 and a partially filled one
+        let mut bytes = vec![0_u8; KSPLAT_HEADER_BYTES + KSPLAT_SECTION_HEADER_BYTES];
+        bytes[1] = 1; // version 0.1
+        let put_u32 = |bytes: &mut Vec<u8>, o: usize, v: u32| bytes[o..o + 4].copy_from_slice(&v.to_le_bytes());
+        put_u32(&mut bytes, 4, 1); // max sections
+        put_u32(&mut bytes, 8, 1); // sections
+        put_u32(&mut bytes, 12, 3);
+        put_u32(&mut bytes, 16, 3);
+        bytes[20] = 1; // compression level
+        let h = KSPLAT_HEADER_BYTES;
+        put_u32(&mut bytes, h, 3); // splats
+        put_u32(&mut bytes, h + 4, 3); // max splats
+        put_u32(&mut bytes, h + 8, 2); // bucket size
+        put_u32(&mut bytes, h + 12, 2); // buckets
+        bytes[h + 16..h + 20].copy_from_slice(&2.0_f32.to_le_bytes()); // bucket block size
+        bytes[h + 20] = 12; // bytes per bucket center
+        put_u32(&mut bytes, h + 32, 1); // full buckets
+        put_u32(&mut bytes, h + 36, 1); // partially filled buckets
+
+        bytes.extend_from_slice(&1_u32.to_le_bytes()); // length of the partially filled bucket
+        for v in [10.0_f32, 0.0, 0.0, -5.0, 5.0, 5.0] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        for center in [[32767_u16, 32767, 32767], [65534, 32767, 0], [32767, 32767, 32767]] {
+            for v in center {
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+            for v in [0.5_f32, 0.5, 0.5, 1.0, 0.0, 0.0, 0.0] {
+                bytes.extend_from_slice(&f16::from_f32(v).to_bits().to_le_bytes());
+            }
+            bytes.extend_from_slice(&[10, 20, 30, 40]);
+        }
+
+        let mut scene = Scene::new();
+        scene.load_ksplat(&bytes).unwrap();
+        assert_eq!(scene.splat_count, 3);
+        let f_buffer = transmute_slice::<_, f32>(scene.buffer.as_slice());
+        assert_eq!(&f_buffer[0..6], &[10.0, 0.0, 0.0, 0.5, 0.5, 0.5]);
+        assert_eq!(&f_buffer[8..11], &[11.0, 0.0, -1.0]);
+        assert_eq!(&f_buffer[16..19], &[-5.0, 5.0, 5.0]); // in the partially filled bucket
+        assert_eq!(&scene.buffer[24..32], &[10, 20, 30, 40, 255, 127, 127, 127]);
+
+        assert!(matches!(scene.load_ksplat(&bytes[..bytes.len() - 1]), Err(GauzillaError::Truncated(_))));
+        bytes[1] = 0;
+        assert!(matches!(scene.load_ksplat(&bytes), Err(GauzillaError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn test_parse_spz_urls() {
+        assert_eq!(
+            parse_spz_urls("https://a.com/0.spz, https://a.com/1.SPZ?v=2"),
+            Some(vec!["https://a.com/0.spz".to_string(), "https://a.com/1.SPZ?v=2".to_string()])
+        );
+        assert_eq!(parse_spz_urls("a.spz"), Some(vec!["a.spz".to_string()]));
+        assert_eq!(parse_spz_urls("a.spz,b.splat"), None);
+        assert_eq!(parse_spz_urls(""), None);
+    }
+
+    #[test]
+    fn test_scene_from_spz_parts() {
+        let splat = |x: f32, sh: f32| {
+            let mut s = SerializedSplat2::default();
+            s.position = [x, 0.0, 0.0];
+            s.rotation = [1.0, 0.0, 0.0, 0.0];
+            s.alpha = 10.0 - x; // keeps the importance order equal to the input order
+            s.color[3] = sh; // first red coefficient of degree 1
+            s
+        };
+        let parts = vec![
+            (vec![splat(0.0, 0.0), splat(1.0, 0.0)], 0, false),
+            (vec![splat(2.0, 0.5)], 1, true),
+        ];
+        let scene = Scene::from_spz_parts(parts, MirrorAxes::default());
+        assert_eq!(scene.splat_count, 3);
+        assert_eq!(scene.sh_degree, 1);
+        assert!(scene.antialiased);
+        assert!(scene.rebuilt(&PackSettings { mirror: scene.mirror, ..PackSettings::default() }).antialiased);
+        assert_eq!(scene.sh_coeffs.len(), 3*3*3);
+        assert!(scene.sh_coeffs[..2*9].iter().all(|&c| c == 0.0)); // zero-filled degree-0 splats
+        assert_eq!(scene.sh_coeffs[2*9], 0.5);
+        assert_eq!(scene.bbox.unwrap().1.x, 2.0);
+    }
+
+    #[test]
+    fn test_unpack_quat_byte() {
+        assert_eq!(unpack_quat_byte(0), -1.0);
+        assert_eq!(unpack_quat_byte(255), 1.0);
+        // inverse of the packing, (q + 1)*127.5
+        for q in [-0.5_f32, 0.0, 0.3, 0.9] {
+            assert!((unpack_quat_byte(((q + 1.0)*127.5).round() as u8) - q).abs() <= 0.5/127.5);
+        }
+    }
+
+    #[test]
+    fn test_scene_merged() {
+        let a = Scene::test_scene(2);
+        let mut b = Scene::test_scene(3);
+        b.rotations = vec![[1.0, 0.0, 0.0, 0.0]; 3];
+        let merged = Scene::merged(&[(&a, [0.0; 3]), (&b, [10.0, 0.0, -1.0])]);
+        assert_eq!(merged.splat_count, 5);
+        assert_eq!(merged.buffer.len(), 32*5);
+        let f_buffer = transmute_slice::<_, f32>(merged.buffer.as_slice());
+        assert_eq!(&f_buffer[8..11], &[1.0, 2.0, 3.0]);
+        assert_eq!(&f_buffer[2*8..2*8 + 3], &[10.0, 0.0, -1.0]); // first splat of b
+        assert_eq!(&f_buffer[4*8..4*8 + 6], &[12.0, 4.0, 5.0, 0.1, 0.2, 0.3]);
+        assert_eq!(merged.buffer[2*32 + 24..], b.buffer[24..]); // colors and rotations untouched
+        assert_eq!(merged.bbox.unwrap(), (vec3(0.0, 0.0, -1.0), vec3(12.0, 4.0, 5.0)));
+
+        // the u8 quaternions of a stand in for its missing full-precision ones
+        assert_eq!(merged.rotations.len(), 5);
+        assert!((merged.rotations[0][0] - 1.0).abs() < 1e-4);
+        assert!(merged.validate().is_empty());
+
+        assert_eq!(Scene::merged(&[]).splat_count, 0);
+    }
+
+    #[test]
+    fn test_scene_scale_clamp() {
+        let mut scene = Scene::test_scene(10);
+        {
+            let f: &mut [f32] = transmute_slice_mut::<_, f32>(&mut scene.buffer);
+            f[8*5 + 3..8*5 + 6].copy_from_slice(&[10.0, 10.0, 10.0]);
+        }
+        assert_eq!(scene.scale_percentile(100.0), Some(10.0));
+        assert_eq!(scene.scale_percentile(90.0), Some(0.3));
+
+        let unclamped = scene.rebuilt(&PackSettings { mirror: scene.mirror, ..PackSettings::default() });
+        assert_eq!(unclamped.clamped_count, 0);
+        let clamped = scene.rebuilt(&PackSettings { mirror: scene.mirror, scale_clamp: Some(90.0), ..PackSettings::default() });
+        assert_eq!(clamped.scale_limit, Some(0.3));
+        assert_eq!(clamped.clamped_count, 1);
+        assert_eq!(clamped.buffer, unclamped.buffer);
+
+        // only the covariance of the outlier changes
+        let cov = |s: &Scene, i: usize| s.tex_data[s.cov_offset(i)..s.cov_offset(i) + 3].to_vec();
+        assert_eq!(cov(&clamped, 4), cov(&unclamped, 4));
+        assert_ne!(cov(&clamped, 5), cov(&unclamped, 5));
+    }
+
+    #[test]
+    fn test_scene_validate() {
+        let mut scene = Scene::test_scene(10);
+        assert!(scene.validate().is_empty());
+
+        {
+            let f: &mut [f32] = transmute_slice_mut::<_, f32>(&mut scene.buffer);
+            f[8*2 + 1] = f32::NAN; // y of splat 2
+            f[8*3 + 4] = 0.0; // scale of splat 3
+            f[8*4 + 5] = f32::INFINITY; // scale of splat 4
+        }
+        scene.buffer[32*5 + 28..32*5 + 32].copy_from_slice(&[128, 128, 128, 128]); // zero quaternion
+        scene.buffer[32*6 + 27] = 0;
+        assert_eq!(scene.validate(), vec![
+            "1 splats with NaN/inf positions (first: #2)".to_string(),
+            "2 splats with NaN/inf or non-positive scales (first: #3)".to_string(),
+            "1 splats with non-unit quaternions (first: #5)".to_string(),
+            "1 fully transparent splats".to_string(),
+        ]);
+
+        scene.buffer.push(0);
+        assert_eq!(scene.validate()[0], "buffer length 321 is not a multiple of 32 bytes");
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    rc::Rc,
+    cell::RefCell,
+    sync::{Arc, Mutex, atomic::AtomicBool},
+};
+use wasm_bindgen::prelude::*;
+use js_sys::{Object, Reflect, Number, Uint8Array};
+use web_sys::{Worker, MessageEvent};
+use bus::Bus;
+
+use crate::log; // macro import
+use crate::utils::set_error_for_egui;
+use crate::scene::Scene;
+use crate::error::GauzillaError;
+
+
+const MAGIC: &[u8; 6] = b"SPLATV";
+const HEADER_SIZE: usize = 16; // magic(6) + version(u16) + splat_count(u32) + fps(f32)
+const FRAME_HEADER_SIZE: usize = 8; // type(u8) + padding(3) + payload_len(u32)
+const ROW_LENGTH: usize = 32; // same row layout as .splat
+const FRAME_TYPE_KEY: u8 = 0;
+const FRAME_TYPE_DELTA: u8 = 1;
+
+
+/// A decoded frame of a .splatv stream
+#[derive(Clone, Debug)]
+pub enum SplatvFrame {
+    Key(Vec<u8>), // full buffer (splat_count rows)
+    Delta(Vec<(u32, [u8; ROW_LENGTH])>), // (splat index, new row) pairs
+}
+
+
+/// Incremental decoder of a .splatv (splat video) byte stream
+///
+/// All values are little-endian:
+/// ```text
+/// header:  "SPLATV" | version: u16 | splat_count: u32 | fps: f32
+/// frames:  type: u8 (0 = keyframe, 1 = delta) | padding: 3 bytes | payload_len: u32 | payload
+///   keyframe payload: splat_count 32-byte rows (same layout as .splat)
+///   delta payload:    n: u32 | n x (splat index: u32 | 32-byte row)
+/// ```
+pub struct SplatvStream {
+    pub splat_count: usize,
+    pub fps: f32,
+    pending: Vec<u8>, // received but not yet decoded bytes
+    header_parsed: bool,
+    pub frames_decoded: usize,
+}
+impl SplatvStream {
+    pub fn new() -> Self {
+        Self {
+            splat_count: 0,
+            fps: 30.0,
+            pending: Vec::<u8>::new(),
+            header_parsed: false,
+            frames_decoded: 0,
+        }
+    }
+
+
+    /// Returns true if the URL (ignoring any query string) points to a .splatv file
+    pub fn is_splatv_url(url: &str) -> bool {
+        url.split('?').next().unwrap_or("").to_lowercase().ends_with(".splatv")
+    }
+
+
+    /// Appends a downloaded chunk and parses the header once it's complete
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), GauzillaError> {
+        self.pending.extend_from_slice(chunk);
+
+        if !self.header_parsed && self.pending.len() >= HEADER_SIZE {
+            if &self.pending[0..6] != MAGIC {
+                return Err(GauzillaError::UnsupportedFormat("SplatvStream::push(): ERROR: not a .splatv stream".to_string()));
+            }
+            let version = u16::from_le_bytes([self.pending[6], self.pending[7]]);
+            self.splat_count = u32::from_le_bytes(self.pending[8..12].try_into().unwrap()) as usize;
+            self.fps = f32::from_le_bytes(self.pending[12..16].try_into().unwrap());
+            if !(self.fps > 0.0) {
+                self.fps = 30.0;
+            }
+            log!("SplatvStream::push(): version={}, splat_count={}, fps={}", version, self.splat_count, self.fps);
+
+            self.pending.drain(0..HEADER_SIZE);
+            self.header_parsed = true;
+        }
+
+        Ok(())
+    }
+
+
+    /// Decodes the next complete frame, if one has been received yet
+    pub fn next_frame(&mut self) -> Result<Option<SplatvFrame>, GauzillaError> {
+        if !self.header_parsed || self.pending.len() < FRAME_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let frame_type = self.pending[0];
+        let payload_len = u32::from_le_bytes(self.pending[4..8].try_into().unwrap()) as usize;
+        if self.pending.len() < FRAME_HEADER_SIZE + payload_len {
+            return Ok(None); // wait for the rest of the frame
+        }
+        let payload: Vec<u8> = self.pending.drain(0..FRAME_HEADER_SIZE + payload_len)
+            .skip(FRAME_HEADER_SIZE)
+            .collect();
+
+        let frame = match frame_type {
+            FRAME_TYPE_KEY => {
+                if payload.len() != self.splat_count*ROW_LENGTH {
+                    return Err(GauzillaError::DecodeFailed(format!(
+                        "SplatvStream::next_frame(): ERROR: keyframe has {} bytes, expected {}",
+                        payload.len(), self.splat_count*ROW_LENGTH
+                    )));
+                }
+                SplatvFrame::Key(payload)
+            },
+            FRAME_TYPE_DELTA => {
+                if payload.len() < 4 {
+                    return Err(GauzillaError::Truncated("SplatvStream::next_frame(): ERROR: truncated delta frame".to_string()));
+                }
+                let n = u32::from_le_bytes(payload[0..4].try_into().unwrap()) as usize;
+                let record_len = 4 + ROW_LENGTH;
+                if payload.len() != 4 + n*record_len {
+                    return Err(GauzillaError::DecodeFailed(format!(
+                        "SplatvStream::next_frame(): ERROR: delta frame has {} bytes, expected {}",
+                        payload.len(), 4 + n*record_len
+                    )));
+                }
+                let mut rows = Vec::<(u32, [u8; ROW_LENGTH])>::with_capacity(n);
+                for record in payload[4..].chunks_exact(record_len) {
+                    let index = u32::from_le_bytes(record[0..4].try_into().unwrap());
+                    if index as usize >= self.splat_count {
+                        return Err(GauzillaError::DecodeFailed(
+                            format!("SplatvStream::next_frame(): ERROR: splat index {} out of range", index)
+                        ));
+                    }
+                    rows.push((index, record[4..].try_into().unwrap()));
+                }
+                SplatvFrame::Delta(rows)
+            },
+            _ => {
+                return Err(GauzillaError::UnsupportedFormat(
+                    format!("SplatvStream::next_frame(): ERROR: unknown frame type {}", frame_type)
+                ));
+            },
+        };
+        self.frames_decoded += 1;
+
+        Ok(Some(frame))
+    }
+}
+
+
+/// What the renderer has to do after [SplatvPlayback::advance] decoded a frame
+pub enum SplatvUpdate {
+    Key(Arc<Scene>), // new scene, upload the whole texture
+    Delta(Arc<Scene>, Vec<usize>), // same scene with some splats moved, upload these texture rows
+}
+
+
+/// Plays back a .splatv stream on two scenes that take turns: the one shown (and sorted)
+/// and a spare that the next delta frame is patched into in place once the sorter has let go of it
+pub struct SplatvPlayback {
+    pub stream: Rc<RefCell<SplatvStream>>,
+    spare: Option<Arc<Scene>>,
+    spare_missing: Vec<(u32, [u8; ROW_LENGTH])>, // rows of the last delta frame, already in the shown scene but not in spare
+    next_frame: f64, // time the next frame is due in milliseconds
+}
+impl SplatvPlayback {
+    pub fn new(stream: Rc<RefCell<SplatvStream>>) -> Self {
+        Self {
+            stream,
+            spare: None,
+            spare_missing: Vec::<(u32, [u8; ROW_LENGTH])>::new(),
+            next_frame: 0.0,
+        }
+    }
+
+
+    fn frame_ms(&self) -> f64 {
+        1000.0 / (self.stream.borrow().fps as f64)
+    }
+
+
+    /// Decodes the next frame once it's due and applies it to (a copy of) shown
+    /// Returns None if no frame is due or received yet
+    pub fn advance(&mut self, shown: &Arc<Scene>, now: f64) -> Result<Option<SplatvUpdate>, GauzillaError> {
+        if now < self.next_frame {
+            return Ok(None);
+        }
+        if let Some(spare) = self.spare.as_mut() {
+            if !spare.same_layout(shown) {
+                // shown has been rebuilt in another format in the meantime
+                self.spare = Some(Arc::new(shown.splatv_copy()));
+                self.spare_missing.clear();
+            } else if Arc::get_mut(spare).is_none() {
+                // the sorter keeps the last scene it was sent until the next one arrives, give it a frame to get there
+                if now < self.next_frame + self.frame_ms() {
+                    return Ok(None);
+                }
+                // held elsewhere (e.g. by a layer)
+                self.spare = Some(Arc::new(shown.splatv_copy()));
+                self.spare_missing.clear();
+            }
+        }
+
+        let frame = match self.stream.borrow_mut().next_frame()? {
+            Some(frame) => frame,
+            None => return Ok(None), // waiting for more data
+        };
+        self.next_frame = now + self.frame_ms();
+
+        match frame {
+            SplatvFrame::Key(buffer) => {
+                let key = shown.splatv_keyframe(buffer);
+                self.spare = Some(Arc::new(key.splatv_copy()));
+                self.spare_missing.clear();
+                Ok(Some(SplatvUpdate::Key(Arc::new(key))))
+            },
+            SplatvFrame::Delta(rows) => {
+                let mut next = self.spare.take().ok_or_else(|| GauzillaError::DecodeFailed(
+                    "SplatvPlayback::advance(): delta frame before the first keyframe".to_string()
+                ))?;
+                let scene = Arc::get_mut(&mut next).expect("SplatvPlayback::advance(): spare scene is shared");
+                scene.apply_splatv_delta(&self.spare_missing)?;
+                let tex_rows = scene.apply_splatv_delta(&rows)?;
+                self.spare = Some(shown.clone());
+                self.spare_missing = rows;
+                Ok(Some(SplatvUpdate::Delta(next, tex_rows)))
+            },
+        }
+    }
+}
+
+
+/// Streams a .splatv file via HTTP in Worker (non-blocking)
+/// Downloaded chunks are appended to the [SplatvStream] as they arrive
+/// A stream that can't be decoded (e.g. bad magic number) is reported via the error window and the download stopped
+pub fn stream_splatv_in_worker(
+    stream: Rc<RefCell<SplatvStream>>,
+    bus_progress: Rc<RefCell<Bus<f64>>>,
+    url: String,
+    error_flag: Arc<AtomicBool>,
+    error_msg: Arc<Mutex<String>>
+) -> Worker {
+    let worker_handle = Worker::new("/downloader.js").unwrap();
+
+    let worker = worker_handle.clone();
+    let mut failed = false;
+    let callback = Closure::wrap(Box::new(move |event: MessageEvent| {
+        if failed {
+            return; // chunks posted before the worker was terminated
+        }
+        let data: Object = event.data().dyn_into().unwrap();
+
+        // the download failed (e.g. an HTTP error status)
+        if let Some(error) = Reflect::get(&data, &JsValue::from_str("error")).ok().and_then(|e| e.as_string()) {
+            log!("stream_splatv_in_worker(): {}", error);
+            set_error_for_egui(&error_flag, &error_msg, format!("ERROR: .splatv: {}", error));
+            worker.terminate();
+            failed = true;
+            return;
+        }
+
+        let chunk = Reflect::get(&data, &JsValue::from_str("chunk")).unwrap();
+        let chunk: Uint8Array = chunk.dyn_into().unwrap();
+        if let Err(e) = stream.borrow_mut().push(&chunk.to_vec()) {
+            log!("stream_splatv_in_worker(): {}", e);
+            set_error_for_egui(&error_flag, &error_msg, format!("ERROR: .splatv: {}", e));
+            worker.terminate();
+            failed = true;
+            return;
+        }
+
+        let bytes = Reflect::get(&data, &JsValue::from_str("bytes")).unwrap();
+        let bytes = bytes.dyn_into::<Number>().unwrap().value_of();
+        let total = Reflect::get(&data, &JsValue::from_str("total")).unwrap();
+        let total = total.dyn_into::<Number>().unwrap().value_of();
+        if total > 0.0 {
+            //////////////////////////////////
+            // non-blocking (i.e., no atomic.wait)
+            let mut bus_progress = bus_progress.as_ref().borrow_mut();
+            let _ = bus_progress.try_broadcast(bytes/total);
+            //////////////////////////////////
+        }
+    }) as Box<dyn FnMut(_)>);
+    worker_handle.set_onmessage(Some(callback.as_ref().unchecked_ref()));
+
+    let msg = Object::new();
+    Reflect::set(&msg, &"url".into(), &JsValue::from_str(url.as_str())).unwrap();
+    Reflect::set(&msg, &"chunked".into(), &JsValue::TRUE).unwrap();
+    worker_handle.post_message(&msg)
+        .expect("stream_splatv_in_worker(): ERROR: Failed to post message to worker.");
+
+    callback.forget(); // avoid being dropped prematurely
+
+    worker_handle
+}
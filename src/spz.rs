@@ -11,12 +11,19 @@ use web_sys::{
     MessageEvent,
 };
 use wasm_bindgen::prelude::*;
-use js_sys::{Object, JsString, Number, Reflect, Float32Array, Boolean};
+use js_sys::{Object, JsString, Number, Reflect, Float32Array, Boolean, Uint8Array};
 use bus::{Bus, BusReader};
 
 use crate::log; // macro import
-use crate::scene::SerializedSplat2;
+use crate::scene::{Scene, SerializedSplat2, sh_rest_count, unpack_quat_byte, SH_C0};
 use crate::utils::*;
+use crate::error::GauzillaError;
+
+
+const SPZ_MAGIC: u32 = 0x5053474e; // "NGSP"
+const SPZ_VERSION: u32 = 2;
+const SPZ_FRACTIONAL_BITS: u8 = 12; // ~0.25mm resolution, positions within +-2048
+const SPZ_COLOR_SCALE: f32 = 0.15;
 
 
 #[derive(Debug, Clone)]
@@ -134,15 +141,126 @@ impl GaussianCloud {
                 self.colors[i*3 + 1],
                 self.colors[i*3 + 2],
             ];
-            let sh = &self.sh[(i*45)+0..(i*45)+45];
-            let mut concatenated = Vec::<f32>::with_capacity(color.len() + sh.len());
-            concatenated.extend_from_slice(&color);
-            concatenated.extend_from_slice(&sh);
-            splat.color = *concatenated.as_array().unwrap();
+            // spz stores the SH coefficients as RGB triplets, PLY one channel after another
+            // SerializedSplat2 holds up to degree 3, missing coefficients stay zero
+            let degree = self.sh_degree.max(0) as usize;
+            let stride = 3*sh_rest_count(degree);
+            let sh = self.sh.get(stride*i..stride*(i + 1)).unwrap_or(&[]);
+            let rest = sh_rest_count(degree.min(3)).min(sh.len()/3);
+            splat.color[0..3].copy_from_slice(&color);
+            for c in 0..3 {
+                let mut channel = [0_f32; 15]; // SH coefficients of one color channel
+                for (j, v) in channel.iter_mut().take(rest).enumerate() {
+                    *v = sh[3*j + c];
+                }
+                splat.color[3 + 15*c..3 + 15*(c + 1)].copy_from_slice(&channel);
+            }
         }
 
         serialized_splats
     }
+
+
+    /// Converts the splats of a scene (as displayed, i.e., mirrored) back into SPZ's raw attributes
+    /// Colors and opacities are recovered from their 8-bit values, rotations are stored as xyzw.
+    pub fn from_scene(scene: &Scene) -> Self {
+        let n = scene.splat_count;
+        let f_buffer: &[f32] = transmute_slice::<_, f32>(scene.buffer.as_slice());
+        let u_buffer: &[u8] = scene.buffer.as_slice();
+
+        let mut cloud = GaussianCloud {
+            num_points: n as i32,
+            sh_degree: scene.sh_degree as i32,
+            antialiased: scene.antialiased,
+            positions: Vec::<f32>::with_capacity(3*n),
+            scales: Vec::<f32>::with_capacity(3*n),
+            rotations: Vec::<f32>::with_capacity(4*n),
+            alphas: Vec::<f32>::with_capacity(n),
+            colors: Vec::<f32>::with_capacity(3*n),
+            sh: scene.sh_coeffs.clone(), // same [coefficient][channel] order
+        };
+        for i in 0..n {
+            cloud.positions.extend_from_slice(&f_buffer[8*i..8*i + 3]);
+            cloud.scales.extend(f_buffer[8*i + 3..8*i + 6].iter().map(|s| s.ln()));
+
+            let rgba = &u_buffer[32*i + 24..32*i + 28];
+            cloud.colors.extend(rgba[0..3].iter().map(|&c| (c as f32/255.0 - 0.5) / SH_C0));
+            let a = (rgba[3] as f32/255.0).clamp(0.5/255.0, 1.0 - 0.5/255.0);
+            cloud.alphas.push((a / (1.0 - a)).ln()); // inverse sigmoid
+
+            // (w, x, y, z) in [0, 255] -> (x, y, z, w) in [-1, 1]
+            let rot = &u_buffer[32*i + 28..32*i + 32];
+            let q: Vec<f32> = rot.iter().map(|&v| unpack_quat_byte(v)).collect();
+            cloud.rotations.extend_from_slice(&[q[1], q[2], q[3], q[0]]);
+        }
+
+        cloud
+    }
+}
+
+
+/// Packs a cloud into the uncompressed SPZ (version 2) stream: a 16-byte header followed by the
+/// fixed-point positions, alphas, colors, scales, rotations, and SH coefficients of all points
+/// A .spz file is this stream gzipped (see [export_spz]).
+pub fn encode_spz(cloud: &GaussianCloud) -> Vec<u8> {
+    let n = cloud.num_points.max(0) as usize;
+    let sh_degree = cloud.sh_degree.clamp(0, 3) as usize;
+    let sh_per_point = 3*sh_rest_count(sh_degree);
+    let to_u8 = |x: f32| x.round().clamp(0.0, 255.0) as u8;
+
+    let mut bytes = Vec::<u8>::with_capacity(16 + n*(9 + 1 + 3 + 3 + 3 + sh_per_point));
+    bytes.extend_from_slice(&SPZ_MAGIC.to_le_bytes());
+    bytes.extend_from_slice(&SPZ_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(n as u32).to_le_bytes());
+    bytes.extend_from_slice(&[sh_degree as u8, SPZ_FRACTIONAL_BITS, cloud.antialiased as u8, 0]);
+
+    // 24-bit signed fixed point
+    let scale = (1 << SPZ_FRACTIONAL_BITS) as f32;
+    let limit = ((1 << 23) - 1) as f32;
+    for &p in cloud.positions[..3*n].iter() {
+        let fixed = (p*scale).round().clamp(-limit, limit) as i32;
+        bytes.extend_from_slice(&fixed.to_le_bytes()[0..3]);
+    }
+    for &a in cloud.alphas[..n].iter() {
+        bytes.push(to_u8(255.0 / (1.0 + (-a).exp())));
+    }
+    for &c in cloud.colors[..3*n].iter() {
+        bytes.push(to_u8(c*(SPZ_COLOR_SCALE*255.0) + 0.5*255.0));
+    }
+    for &s in cloud.scales[..3*n].iter() {
+        bytes.push(to_u8((s + 10.0)*16.0));
+    }
+    for q in cloud.rotations[..4*n].chunks_exact(4) {
+        // normalized with w >= 0, so that w can be derived from xyz
+        let len = (q[0]*q[0] + q[1]*q[1] + q[2]*q[2] + q[3]*q[3]).sqrt().max(f32::EPSILON);
+        let sign = if q[3] < 0.0 { -1.0 } else { 1.0 };
+        for k in 0..3 {
+            bytes.push(to_u8(sign*q[k]/len*127.5 + 127.5));
+        }
+    }
+    if sh_per_point > 0 {
+        // 5 bits for degree 1, 4 bits for the higher degrees
+        let quantize = |x: f32, bucket: i32| {
+            let q = (x*128.0).round() as i32 + 128;
+            ((q + bucket/2) / bucket * bucket).clamp(0, 255) as u8
+        };
+        for sh in cloud.sh[..n*sh_per_point].chunks_exact(sh_per_point) {
+            for (j, &x) in sh.iter().enumerate() {
+                bytes.push(quantize(x, if j < 9 { 1 << (8 - 5) } else { 1 << (8 - 4) }));
+            }
+        }
+    }
+
+    bytes
+}
+
+
+/// Encodes the scene as a .spz file (gzipped by the browser)
+pub async fn export_spz(scene: &Scene) -> Result<Vec<u8>, GauzillaError> {
+    let packed = encode_spz(&GaussianCloud::from_scene(scene));
+    let gzipped: Uint8Array = gzip_bytes(&packed).await?.dyn_into()?;
+    log!("export_spz(): {} splats, {} -> {} bytes", scene.splat_count, packed.len(), gzipped.length());
+    Ok(gzipped.to_vec())
 }
 
 
@@ -275,39 +393,100 @@ impl Spz {
 
 
 /// Loads spz. Blocks until spz is loaded.
-pub async fn load_spz(spz: &mut Spz, buffer: Vec<u8>) -> Vec<SerializedSplat2> {
+/// Returns the splats, their SH degree, and whether they were trained with the antialiasing filter
+pub async fn load_spz(spz: &mut Spz, buffer: Vec<u8>) -> Result<(Vec<SerializedSplat2>, usize, bool), GauzillaError> {
     log!("load_spz(): buffer.len()={}", buffer.len());
 
     if spz.rx_loaded.is_none() {
-        unreachable!("load_spz(): ERROR: spz.rx_loaded is None");
+        return Err(GauzillaError::Js("load_spz(): the decoder worker isn't initialized".to_string()));
     }
     if buffer.is_empty() {
-        unreachable!("load_spz(): ERROR: buffer is empty");
+        return Err(GauzillaError::Truncated("load_spz(): the buffer is empty".to_string()));
     }
 
-    let mut serialized_splats = Vec::<SerializedSplat2>::new();
-    if let Ok(url) = create_url_byte_array(buffer) {
-        spz.post2worker("load", Some(url));
-        if let Some(rx_loaded) = spz.rx_loaded.as_mut() {
-
-            // no direct blocking available in wasm (ie. rx_loaded.recv())
-            let mut i = 0;
-            loop {
-                if let Ok(gc) = rx_loaded.try_recv() {
-                    serialized_splats = gc.create_serialized_splat_vec();
-                    return serialized_splats;
-                }
+    let url = create_url_byte_array(buffer)?;
+    spz.post2worker("load", Some(url));
+    if let Some(rx_loaded) = spz.rx_loaded.as_mut() {
 
-                sleep_js(1000).await;
-                i += 1;
-                if i > 33 {
-                    unreachable!("load_spz(): ERROR: timed out");
-                }
+        // no direct blocking available in wasm (ie. rx_loaded.recv())
+        let mut i = 0;
+        loop {
+            if let Ok(gc) = rx_loaded.try_recv() {
+                let serialized_splats = gc.create_serialized_splat_vec();
+                return Ok((serialized_splats, gc.sh_degree.clamp(0, 3) as usize, gc.antialiased));
+            }
+
+            sleep_js(1000).await;
+            i += 1;
+            if i > 33 {
+                // the worker doesn't report failures, so a file it can't decode ends up here as well
+                return Err(GauzillaError::DecodeFailed("load_spz(): timed out waiting for the decoder".to_string()));
             }
         }
-    } else {
-        unreachable!("load_spz(): ERROR: create_url_byte_array() failed");
     }
 
-    serialized_splats
+    Ok((Vec::<SerializedSplat2>::new(), 0, false))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spz_color_sh_layout() {
+        let mut cloud = GaussianCloud {
+            num_points: 2,
+            sh_degree: 1,
+            antialiased: false,
+            positions: vec![0.0; 6],
+            scales: vec![0.0; 6],
+            rotations: vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+            alphas: vec![0.0; 2],
+            colors: vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6],
+            sh: (0..18).map(|k| k as f32).collect(), // 3 RGB triplets per splat
+        };
+        let splats = cloud.create_serialized_splat_vec();
+        let color = &splats[1].color;
+        assert_eq!(color[0..3], [0.4, 0.5, 0.6]);
+        // channel-major: R of coefficient j at 3 + j, G at 18 + j, B at 33 + j
+        assert_eq!([color[3], color[18], color[33]], [9.0, 10.0, 11.0]);
+        assert_eq!([color[5], color[20], color[35]], [15.0, 16.0, 17.0]);
+        assert!(color[6..18].iter().chain(&color[21..33]).chain(&color[36..48]).all(|&c| c == 0.0));
+
+        // missing SH data is zero-filled instead of panicking
+        cloud.sh.truncate(9);
+        let splats = cloud.create_serialized_splat_vec();
+        assert_eq!(splats[0].color[3], 0.0);
+        assert_eq!(splats[0].color[4], 3.0);
+        assert!(splats[1].color[3..48].iter().all(|&c| c == 0.0));
+    }
+
+
+    #[test]
+    fn test_encode_spz() {
+        let scene = Scene::test_scene(3);
+        let cloud = GaussianCloud::from_scene(&scene);
+        assert!((cloud.rotations[3] - 1.0).abs() < 1e-6); // xyzw, 128/255*2 - 1 ~ 0 for xyz
+        assert!(cloud.rotations[0..3].iter().all(|q| q.abs() < 0.01));
+        let bytes = encode_spz(&cloud);
+        assert_eq!(bytes.len(), 16 + 3*(9 + 1 + 3 + 3 + 3));
+        assert_eq!(&bytes[0..4], b"NGSP");
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 2);
+        assert_eq!(u32::from_le_bytes(bytes[8..12].try_into().unwrap()), 3);
+        assert_eq!(bytes[12..16], [0, 12, 0, 0]);
+
+        // splat 1 is at (1, 2, 3): 24-bit fixed point with 12 fractional bits
+        let p = &bytes[16 + 9..16 + 18];
+        assert_eq!(p, &[0x00, 0x10, 0x00, 0x00, 0x20, 0x00, 0x00, 0x30, 0x00]);
+
+        let alphas = &bytes[16 + 27..16 + 30];
+        assert!(alphas.iter().all(|&a| a == 255));
+        // color 128 -> 0.5 gray -> SH DC ~0 -> 127.5
+        let colors = &bytes[16 + 30..16 + 39];
+        assert!((colors[2] as i32 - 128).abs() <= 1);
+        // scale 0.1 -> (ln(0.1) + 10)*16
+        let scales = &bytes[16 + 39..16 + 48];
+        assert_eq!(scales[0], ((0.1_f32.ln() + 10.0)*16.0).round() as u8);
+    }
 }
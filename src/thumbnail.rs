@@ -0,0 +1,128 @@
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext};
+use js_sys::Uint8Array;
+use three_d::*;
+
+use crate::log; // macro import
+use crate::utils::*;
+use crate::scene::*;
+use crate::renderer::{SplatGLSL, SplatView, SplatRenderParams};
+use crate::error::GauzillaError;
+
+
+/// Renders the .splat file at url into a width x height PNG (e.g. for asset browsers)
+/// The camera looks at the center of the bounding box from the same side as the viewer's default view.
+/// Runs a single sorted frame on a detached canvas, so the interactive viewer isn't needed.
+#[wasm_bindgen]
+pub async fn render_thumbnail(url: String, width: u32, height: u32) -> Result<Vec<u8>, JsValue> {
+    let scene = Arc::new(stream_splat(&url).await?);
+    let pixels = render_pixels(&scene, width, height)?;
+
+    let png: Uint8Array = encode_png(&pixels, width, height).await.dyn_into()?;
+    log!("render_thumbnail(): {}x{}, {} bytes", width, height, png.length());
+
+    Ok(png.to_vec())
+}
+
+
+/// Renders one frame of the scene framed by its bounding box
+/// Returns the RGBA pixels, top row first
+fn render_pixels(scene: &Arc<Scene>, width: u32, height: u32) -> Result<Vec<u8>, GauzillaError> {
+    let (min, max) = scene.bbox
+        .ok_or_else(|| GauzillaError::DecodeFailed("render_pixels(): the scene is empty".to_string()))?;
+    if width == 0 || height == 0 {
+        return Err(GauzillaError::DecodeFailed(format!("render_pixels(): invalid size {}x{}", width, height)));
+    }
+
+    let document = web_sys::window().unwrap().document().unwrap();
+    let canvas: HtmlCanvasElement = document.create_element("canvas")?
+        .dyn_into()
+        .map_err(|_| GauzillaError::Js("render_pixels(): not a canvas".to_string()))?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let webgl2: WebGl2RenderingContext = canvas.get_context("webgl2")?
+        .ok_or_else(|| GauzillaError::Js("render_pixels(): WebGL2 is not available".to_string()))?
+        .dyn_into()
+        .map_err(|_| GauzillaError::Js("render_pixels(): not a WebGL2 context".to_string()))?;
+    let gl = Context::from_gl_context(Arc::new(context::Context::from_webgl2_context(webgl2)))
+        .map_err(|e| GauzillaError::Js(format!("render_pixels(): {:?}", e)))?;
+
+    // fit the bounding sphere of the AABB into the vertical field of view
+    let fovy = degrees(45.0);
+    let center = 0.5*(min + max);
+    let radius = (0.5*(max - min).magnitude()).max(0.001);
+    let distance = radius / (fovy / 2.0).sin();
+    let camera = Camera::new_perspective(
+        Viewport::new_at_origo(width, height),
+        center + vec3(0.0, 0.0, distance),
+        center,
+        vec3(0.0, -1.0, 0.0), // same view as the viewer's default invert_y model transform
+        fovy,
+        (distance - radius).max(0.01*distance),
+        distance + radius,
+    );
+
+    let view_matrix = camera.view();
+    let projection_matrix = camera.projection();
+    let w = width as f32;
+    let h = height as f32;
+    let cam_pos = camera.position();
+    let fx = 0.5*projection_matrix[0][0]*w;
+    let fy = -0.5*projection_matrix[1][1]*h;
+    let htany = (fovy / 2.0).tan() as f32;
+    let htanx = (htany/h)*w;
+
+    // sort synchronously on this thread
+    let mut depth = DepthArray::default();
+    let view_proj = projection_matrix * view_matrix;
+    Scene::sort(scene, &mat4_to_array(&view_proj), &SortSettings::default(), &mut depth, None);
+
+    let error_flag = Arc::new(AtomicBool::new(false));
+    let error_msg = Arc::new(Mutex::new(String::new()));
+    let mut splat_glsl = SplatGLSL::new();
+    splat_glsl.init(&gl, &error_flag, &error_msg, scene);
+    if error_flag.load(Ordering::Relaxed) {
+        return Err(GauzillaError::Js(error_msg.lock().unwrap().clone()));
+    }
+    splat_glsl.upload_texture(&gl, scene)?;
+
+    let mut pixels = vec![0_u8; 4*(width as usize)*(height as usize)];
+    unsafe {
+        gl.viewport(0, 0, width as i32, height as i32);
+        gl.clear_color(0.0, 0.0, 0.0, 1.0);
+        gl.clear(context::COLOR_BUFFER_BIT);
+
+        let splat_view = SplatView {
+            projection: mat4_to_array(projection_matrix),
+            view: mat4_to_array(view_matrix),
+            focal: [fx.abs(), fy.abs()],
+            viewport: [w, h],
+            htan_fov: [htanx, htany],
+            cam_pos: [cam_pos.x, cam_pos.y, cam_pos.z],
+        };
+        let splat_params = SplatRenderParams {
+            max_splats: scene.splat_count as i32,
+            ..SplatRenderParams::default()
+        };
+        splat_glsl.render(&gl, &splat_view, &splat_params, &mut depth);
+
+        // read back before returning to the event loop (the drawing buffer isn't preserved)
+        gl.read_pixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            context::RGBA,
+            context::UNSIGNED_BYTE,
+            context::PixelPackData::Slice(&mut pixels)
+        );
+    }
+
+    // GL rows start at the bottom, images at the top
+    Ok(gl_pixels_to_image(&pixels, width as usize))
+}
@@ -11,12 +11,17 @@ use three_d::*;
 use web_sys::{ Blob, Url };
 use js_sys::Array;
 
+use crate::scene::MirrorAxes;
+
 
 #[macro_export]
 macro_rules! log {
-    ( $( $t:tt )* ) => {
+    ( $( $t:tt )* ) => {{
+        #[cfg(target_arch = "wasm32")]
         web_sys::console::log_1(&format!( $( $t )* ).into());
-    }
+        #[cfg(not(target_arch = "wasm32"))]
+        println!( $( $t )* ); // native test builds
+    }}
 }
 
 
@@ -29,10 +34,41 @@ extern "C" {
     pub fn get_webgl1_version() -> String;
     pub fn get_webgl2_version() -> String;
     pub fn get_url_param() -> String;
+    pub fn get_scene_params() -> JsValue;
+    pub async fn prefetch_url(url: &str);
     pub fn get_position_param() -> JsValue;
     pub fn get_target_param() -> JsValue;
+    pub fn has_position_param() -> bool;
+    pub fn has_target_param() -> bool;
     pub fn get_up_param() -> JsValue;
+    pub fn get_mirror_param() -> String;
+    pub fn get_format_param() -> String;
+    pub fn get_sort_param() -> String;
     pub async fn sleep_js(ms: u32);
+    pub fn register_webgl_context_handlers();
+    pub fn is_webgl_context_lost() -> bool;
+    pub fn take_webgl_context_restored() -> bool;
+    pub fn copy_to_clipboard(text: &str);
+    #[wasm_bindgen(catch)]
+    pub async fn read_from_clipboard() -> Result<JsValue, JsValue>;
+    pub fn show_warning(msg: &str, ms: u32);
+    pub fn is_drawing_buffer_preserved() -> bool;
+    pub async fn encode_png(pixels: &[u8], width: u32, height: u32) -> JsValue;
+    #[wasm_bindgen(catch)]
+    pub async fn gzip_bytes(bytes: &[u8]) -> Result<JsValue, JsValue>;
+    pub fn download_bytes(bytes: &[u8], filename: &str);
+    #[wasm_bindgen(catch)]
+    pub async fn load_image_rgba(url: &str) -> Result<JsValue, JsValue>;
+    pub fn start_recording(fps: u32) -> bool;
+    pub fn stop_recording(filename: &str);
+}
+
+
+// Get the scene URLs to switch between
+#[inline(always)]
+pub fn get_scene_list() -> Vec<String> {
+    let array: Array = js_sys::Array::from(&get_scene_params());
+    array.iter().filter_map(|v| v.as_string()).collect()
 }
 
 
@@ -71,6 +107,25 @@ pub fn get_up() -> Vec3 {
 }
 
 
+// Get the mirror param (e.g. "xz") as MirrorAxes
+#[inline(always)]
+pub fn get_mirror() -> MirrorAxes {
+    MirrorAxes::from_param(&get_mirror_param())
+}
+
+
+/// Flattens a (column-major) matrix into an array to be passed as a slice
+#[inline(always)]
+pub fn mat4_to_array(m: &Mat4) -> [f32; 16] {
+    [
+        m[0][0], m[0][1], m[0][2], m[0][3],
+        m[1][0], m[1][1], m[1][2], m[1][3],
+        m[2][0], m[2][1], m[2][2], m[2][3],
+        m[3][0], m[3][1], m[3][2], m[3][3]
+    ]
+}
+
+
 /// Enable better error messages if our code ever panics
 pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
@@ -96,6 +151,68 @@ pub fn execute_future<F: Future<Output = ()> + 'static>(f: F) {
 }
 
 
+/// Converts RGBA8 pixels read back from GL (bottom row first, premultiplied alpha)
+/// into image order (top row first, straight alpha as expected by PNG)
+pub fn gl_pixels_to_image(pixels: &[u8], width: usize) -> Vec<u8> {
+    let row_len = 4*width;
+    let mut image = Vec::<u8>::with_capacity(pixels.len());
+    for row in pixels.chunks_exact(row_len).rev() {
+        image.extend_from_slice(row);
+    }
+    for p in image.chunks_exact_mut(4) {
+        let a = p[3] as u32;
+        if a > 0 && a < 255 {
+            for c in p[0..3].iter_mut() {
+                *c = ((*c as u32*255 + a/2)/a).min(255) as u8;
+            }
+        }
+    }
+    image
+}
+
+
+/// Splits a base64 data URL ("data:[<media type>][;<param>...];base64,<payload>") into its media type and payload
+pub fn parse_data_url(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix("data:")?;
+    let (meta, payload) = rest.split_once(',')?;
+    let mut params = meta.split(';');
+    let media_type = params.next().unwrap_or("");
+    if !params.any(|p| p == "base64") {
+        return None;
+    }
+    Some((media_type, payload))
+}
+
+
+/// Decodes base64 in either the standard or the URL-safe alphabet of RFC 4648
+/// ("+/" or "-_", also mixed), ignoring whitespace and padding
+pub fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+
+    let mut bytes = Vec::<u8>::with_capacity(s.len()*3/4);
+    let mut acc = 0_u32;
+    let mut bits = 0;
+    for c in s.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        acc = (acc << 6) | value(c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    Some(bytes)
+}
+
+
 /// Transmutes a slice
 #[inline(always)]
 pub fn transmute_slice<S, T>(slice: &[S]) -> &[T] {
@@ -124,6 +241,21 @@ pub fn pack_half_2x16(x: f32, y: f32) -> u32 {
 }
 
 
+/// Non-cryptographic 64-bit hash of a byte slice (FxHash-style over 8-byte words), e.g. to detect unchanged buffers
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    const K: u64 = 0x517cc1b727220a95;
+    let mix = |h: u64, w: u64| (h.rotate_left(5) ^ w).wrapping_mul(K);
+    let mut words = bytes.chunks_exact(8);
+    let mut h = mix(0, bytes.len() as u64);
+    for word in words.by_ref() {
+        h = mix(h, u64::from_le_bytes(word.try_into().unwrap()));
+    }
+    let mut tail = [0_u8; 8];
+    tail[..words.remainder().len()].copy_from_slice(words.remainder());
+    mix(h, u64::from_le_bytes(tail))
+}
+
+
 /// Check if a float is zero
 #[inline(always)]
 pub fn is_float_zero(x: f32, threshold: f32) -> bool {
@@ -184,16 +316,101 @@ impl IncrementalMA {
 }
 
 
-/*
-// TODO
+/// Frame time source for time-based motion: tick() once at the start of each frame,
+/// then read the same timestamp and delta times everywhere within that frame
+pub struct FrameClock {
+    now: f64, // timestamp of the current frame in milliseconds
+    raw_dt: f64, // milliseconds since the previous frame
+    smoothed_dt: f64, // moving average of raw_dt
+    dt_ma: IncrementalMA,
+}
+impl FrameClock {
+    /// Longest step handed to animations, so that a stall (e.g. a background tab) doesn't make them jump
+    pub const MAX_DT_MS: f64 = 100.0;
+
+    pub fn new(now: f64) -> Self {
+        Self {
+            now,
+            raw_dt: 0.0,
+            smoothed_dt: 0.0,
+            dt_ma: IncrementalMA::new(100),
+        }
+    }
+
+    pub fn tick(&mut self, now: f64) {
+        self.raw_dt = (now - self.now).max(0.0);
+        self.now = now;
+        self.smoothed_dt = self.dt_ma.add(self.raw_dt);
+    }
+
+    pub fn now(&self) -> f64 {
+        self.now
+    }
+
+    /// Unclamped frame time in milliseconds
+    pub fn raw_dt(&self) -> f64 {
+        self.raw_dt
+    }
+
+    /// Frame time in seconds for advancing animations, clamped to MAX_DT_MS
+    pub fn dt(&self) -> f32 {
+        (0.001*self.raw_dt.min(Self::MAX_DT_MS)) as f32
+    }
+
+    /// Average frame rate over the moving average window
+    pub fn fps(&self) -> f64 {
+        if self.smoothed_dt > 0.0 { 1000.0 / self.smoothed_dt } else { 0.0 }
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn test_parse_data_url() {
+        let (media_type, payload) = parse_data_url("data:application/x-ply;base64,cGx5Cg==").unwrap();
+        assert_eq!(media_type, "application/x-ply");
+        assert_eq!(decode_base64(payload).unwrap(), b"ply\n");
+        assert!(parse_data_url("data:text/plain,ply").is_none()); // not base64
+        assert!(parse_data_url("https://example.com/a.ply").is_none());
+
+        assert_eq!(decode_base64("AAECAwQF/w==").unwrap(), vec![0, 1, 2, 3, 4, 5, 255]);
+        assert_eq!(decode_base64("AAEC\nAwQF").unwrap(), vec![0, 1, 2, 3, 4, 5]);
+        assert!(decode_base64("AA*=").is_none());
+
+        // URL-safe alphabet
+        assert_eq!(decode_base64("-_8=").unwrap(), decode_base64("+/8=").unwrap());
+        assert_eq!(decode_base64("-_8").unwrap(), vec![251, 255]);
+    }
+
+    #[test]
+    fn test_frame_clock() {
+        let mut clock = FrameClock::new(1000.0);
+        assert_eq!(clock.fps(), 0.0);
+        clock.tick(1020.0);
+        clock.tick(1030.0);
+        assert_eq!(clock.now(), 1030.0);
+        assert_eq!(clock.raw_dt(), 10.0);
+        assert!((clock.fps() - 1000.0/15.0).abs() < 1e-9); // averaged over both frames
+
+        // a stall is reported as is but clamped for animations
+        clock.tick(3030.0);
+        assert_eq!(clock.raw_dt(), 2000.0);
+        assert!((clock.dt() - 0.1).abs() < 1e-6);
+
+        // a clock going backwards doesn't produce a negative step
+        clock.tick(3000.0);
+        assert_eq!(clock.raw_dt(), 0.0);
+    }
+
+    #[test]
+    fn test_gl_pixels_to_image() {
+        // 1x2: bottom row opaque red, top row half-transparent premultiplied gray
+        let pixels = [255, 0, 0, 255, 64, 64, 64, 128];
+        assert_eq!(gl_pixels_to_image(&pixels, 1), vec![128, 128, 128, 128, 255, 0, 0, 255]);
+        // fully transparent pixels stay black
+        assert_eq!(gl_pixels_to_image(&[0, 0, 0, 0], 1), vec![0, 0, 0, 0]);
     }
 }
-*/